@@ -0,0 +1,227 @@
+//! Animations render one frame after another, each at its own time on a timeline measured in
+//! seconds. The camera and animated geometries move between keyframes on the timeline, and every
+//! ray is traced at a time while the shutter of its frame is open so motion within a frame blurs.
+
+use crate::data::vector::Vector;
+use crate::float::Float;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AnimationError {
+    #[error("an animation needs at least one keyframe")]
+    NoKeyframes(),
+    #[error("the keyframe at time {time} must come after the keyframe before it")]
+    UnorderedKeyframes { time: Float },
+    #[error("the shutter must be open for between 0 and 1 of a frame, not {shutter}")]
+    InvalidShutter { shutter: Float },
+    #[error("the rotation axis of the keyframe at time {time} can not be zero")]
+    ZeroRotationAxis { time: Float },
+}
+
+/// Which frames of the timeline are rendered, chosen on the command line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationSettings {
+    pub frames: u32,
+    pub fps: Float,
+}
+
+impl AnimationSettings {
+    /// The time on the timeline that `frame` starts at
+    pub fn frame_time(&self, frame: u32) -> Float {
+        frame as Float / self.fps
+    }
+}
+
+/// How the camera moves, saved with the scene
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Animation {
+    // Where the camera looks from and at over the timeline, replacing the camera's own. The
+    // camera stays put when there are none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    camera: Vec<CameraKeyframe>,
+    // The part of every frame the shutter is open for, a half is the classic 180 degree shutter
+    #[serde(default = "default_shutter")]
+    shutter: Float,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    time: Float,
+    look_from: Vector,
+    look_at: Vector,
+}
+
+fn default_shutter() -> Float {
+    0.5
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Animation {
+            camera: vec![],
+            shutter: default_shutter(),
+        }
+    }
+}
+
+impl Animation {
+    pub fn shutter(&self) -> Float {
+        self.shutter
+    }
+
+    pub fn validate(&self) -> Result<(), AnimationError> {
+        if !(0.0..=1.0).contains(&self.shutter) {
+            return Err(AnimationError::InvalidShutter {
+                shutter: self.shutter,
+            });
+        }
+        if self.camera.is_empty() {
+            return Ok(());
+        }
+        check_times(self.camera.iter().map(|keyframe| keyframe.time))
+    }
+
+    /// Where the camera looks from and at, at `time`. The camera follows a Catmull-Rom spline
+    /// through its keyframes so a handful of them around a circle make a smooth orbit
+    pub fn camera_at(&self, time: Float) -> Option<(Vector, Vector)> {
+        if self.camera.is_empty() {
+            return None;
+        }
+        let (before, after, t) = segment(self.camera.iter().map(|keyframe| keyframe.time), time);
+        // The keyframes either side of the segment shape the curve, the end ones are repeated
+        let first = &self.camera[before.saturating_sub(1)];
+        let last = &self.camera[(after + 1).min(self.camera.len() - 1)];
+        let (before, after) = (&self.camera[before], &self.camera[after]);
+        Some((
+            catmull_rom(
+                &first.look_from,
+                &before.look_from,
+                &after.look_from,
+                &last.look_from,
+                t,
+            ),
+            catmull_rom(
+                &first.look_at,
+                &before.look_at,
+                &after.look_at,
+                &last.look_at,
+                t,
+            ),
+        ))
+    }
+
+    pub fn camera_keyframes(&self) -> impl Iterator<Item = (&Vector, &Vector)> {
+        self.camera
+            .iter()
+            .map(|keyframe| (&keyframe.look_from, &keyframe.look_at))
+    }
+}
+
+/// Keyframes must be given in order of time, with no two at the same time
+pub fn check_times<I: Iterator<Item = Float>>(times: I) -> Result<(), AnimationError> {
+    let mut previous = None;
+    let mut any = false;
+    for time in times {
+        if previous.is_some_and(|previous| time <= previous) || !time.is_finite() {
+            return Err(AnimationError::UnorderedKeyframes { time });
+        }
+        previous = Some(time);
+        any = true;
+    }
+    if any {
+        Ok(())
+    } else {
+        Err(AnimationError::NoKeyframes())
+    }
+}
+
+/// The indices of the keyframes either side of `time` and how far it is from the first to the
+/// second, from the keyframe times in order. Before the first keyframe and after the last the
+/// nearest keyframe is held
+pub fn segment<I: Iterator<Item = Float>>(times: I, time: Float) -> (usize, usize, Float) {
+    let times: Vec<Float> = times.collect();
+    let after = times.partition_point(|&keyframe| keyframe <= time);
+    if after == 0 {
+        return (0, 0, 0.0);
+    }
+    if after == times.len() {
+        return (after - 1, after - 1, 0.0);
+    }
+    let before = after - 1;
+    let t = (time - times[before]) / (times[after] - times[before]);
+    (before, after, t)
+}
+
+/// The point `t` of the way from `p1` to `p2` on the uniform Catmull-Rom spline through all four
+fn catmull_rom(p0: &Vector, p1: &Vector, p2: &Vector, p3: &Vector, t: Float) -> Vector {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * ((2.0 * p1)
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn keyframe(time: Float, x: Float, z: Float) -> CameraKeyframe {
+        CameraKeyframe {
+            time,
+            look_from: Vector::new(x, 1.0, z),
+            look_at: Vector::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    #[test]
+    fn test_segment() {
+        let times = [0.0, 1.0, 3.0];
+        assert_eq!(segment(times.iter().cloned(), -1.0), (0, 0, 0.0));
+        assert_eq!(segment(times.iter().cloned(), 0.5), (0, 1, 0.5));
+        assert_eq!(segment(times.iter().cloned(), 2.5), (1, 2, 0.75));
+        assert_eq!(segment(times.iter().cloned(), 3.0), (2, 2, 0.0));
+    }
+
+    #[test]
+    fn test_check_times() {
+        assert!(check_times([0.0, 0.5, 2.0].iter().cloned()).is_ok());
+        assert!(check_times([0.0, 0.0].iter().cloned()).is_err());
+        assert!(check_times([1.0, 0.5].iter().cloned()).is_err());
+        assert!(check_times([].iter().cloned()).is_err());
+    }
+
+    #[test]
+    fn test_camera_orbits_through_its_keyframes() {
+        // Four keyframes a quarter of the way round a circle of radius 2 from each other
+        let animation = Animation {
+            camera: vec![
+                keyframe(0.0, 2.0, 0.0),
+                keyframe(1.0, 0.0, 2.0),
+                keyframe(2.0, -2.0, 0.0),
+                keyframe(3.0, 0.0, -2.0),
+            ],
+            shutter: 0.5,
+        };
+        assert!(animation.validate().is_ok());
+
+        let (look_from, look_at) = animation.camera_at(1.0).unwrap();
+        assert_approx_eq!(look_from.x(), 0.0);
+        assert_approx_eq!(look_from.z(), 2.0);
+        assert_approx_eq!(look_at.len(), 0.0);
+
+        // Half way between keyframes the spline bulges out towards the circle, where a straight
+        // line cuts across at a radius of √2
+        let (look_from, _) = animation.camera_at(1.5).unwrap();
+        let radius = (look_from.x().powi(2) + look_from.z().powi(2)).sqrt();
+        assert!(radius > 1.6);
+
+        assert!(Animation::default().camera_at(1.0).is_none());
+        let open_too_long = Animation {
+            shutter: 1.5,
+            ..Animation::default()
+        };
+        assert!(open_too_long.validate().is_err());
+    }
+}
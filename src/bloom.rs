@@ -0,0 +1,148 @@
+use crate::data::colour::Colour;
+use crate::data::image::{Image, Pixel};
+use crate::float::Float;
+
+/// Makes bright parts of a render glow by blurring everything above a luminance threshold and
+/// adding it back on top of the image. Runs on the linear colours before tone mapping
+#[derive(Debug, Clone)]
+pub struct Bloom {
+    // Only the luminance above this spreads out
+    pub threshold: Float,
+    // Pixels, how far the glow reaches. The gaussian has a standard deviation of half of this
+    pub radius: Float,
+}
+
+impl Bloom {
+    pub fn apply(&self, image: &Image) -> Image {
+        let width = image.width() as usize;
+        let height = image.height() as usize;
+        let bright: Vec<Vec<Colour>> = (0..height)
+            .map(|row| {
+                (0..width)
+                    .map(|col| self.bright_part(image.get_pixel(row as u32, col as u32)))
+                    .collect()
+            })
+            .collect();
+
+        // A 2D gaussian is the same as blurring the rows and then the columns
+        let kernel = gaussian_kernel(self.radius);
+        let rows: Vec<Vec<Colour>> = (0..height)
+            .map(|row| blur(&kernel, width, |col| bright[row][col]))
+            .collect();
+        let columns: Vec<Vec<Colour>> = (0..width)
+            .map(|col| blur(&kernel, height, |row| rows[row][col]))
+            .collect();
+
+        let pixels: Vec<Pixel> = iproduct!(0..height, 0..width)
+            .map(|(row, col)| {
+                let colour = image.get_pixel(row as u32, col as u32) + columns[col][row];
+                Pixel::new(row as u32, col as u32, colour)
+            })
+            .collect();
+        Image::from(&pixels)
+    }
+
+    fn bright_part(&self, colour: &Colour) -> Colour {
+        let luminance = colour.luminance();
+        if luminance > self.threshold {
+            ((luminance - self.threshold) / luminance) * colour
+        } else {
+            Colour::new(0.0, 0.0, 0.0)
+        }
+    }
+}
+
+/// Weights for offsets of `-reach..=reach` pixels that sum to 1
+fn gaussian_kernel(radius: Float) -> Vec<Float> {
+    let sigma = (radius / 2.0).max(Float::EPSILON);
+    let reach = radius.ceil() as i64;
+    let weights: Vec<Float> = (-reach..=reach)
+        .map(|offset| (-0.5 * (offset as Float / sigma).powi(2)).exp())
+        .collect();
+    let total: Float = weights.iter().sum();
+    weights.iter().map(|weight| weight / total).collect()
+}
+
+/// Blurs a line of `len` colours. Weights that would fall off the ends of the line are dropped
+/// and the rest scaled up, so the edges of the image do not darken
+fn blur<F: Fn(usize) -> Colour>(kernel: &[Float], len: usize, colour: F) -> Vec<Colour> {
+    let reach = (kernel.len() / 2) as i64;
+    (0..len as i64)
+        .map(|i| {
+            let (sum, total) = kernel.iter().enumerate().fold(
+                (Colour::new(0.0, 0.0, 0.0), 0.0),
+                |(sum, total), (k, weight)| {
+                    let j = i + k as i64 - reach;
+                    if j < 0 || j >= len as i64 {
+                        (sum, total)
+                    } else {
+                        (sum + *weight * colour(j as usize), total + weight)
+                    }
+                },
+            );
+            sum / total
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn image_with_light(size: u32, light: Colour) -> Image {
+        let pixels: Vec<Pixel> = iproduct!(0..size, 0..size)
+            .map(|(row, col)| {
+                let colour = if (row, col) == (size / 2, size / 2) {
+                    light
+                } else {
+                    Colour::new(0.1, 0.1, 0.1)
+                };
+                Pixel::new(row, col, colour)
+            })
+            .collect();
+        Image::from(&pixels)
+    }
+
+    #[test]
+    fn test_gaussian_kernel_sums_to_one() {
+        for &radius in &[0.5, 3.0, 10.0] {
+            let kernel = gaussian_kernel(radius);
+            assert_eq!(kernel.len() % 2, 1);
+            assert_approx_eq!(kernel.iter().sum::<Float>(), 1.0);
+            assert!(kernel[kernel.len() / 2] >= kernel[0]);
+        }
+    }
+
+    #[test]
+    fn test_dim_images_are_unchanged() {
+        let image = image_with_light(9, Colour::new(0.5, 0.5, 0.5));
+        let bloom = Bloom {
+            threshold: 1.0,
+            radius: 3.0,
+        };
+        assert_eq!(bloom.apply(&image), image);
+    }
+
+    #[test]
+    fn test_bright_pixels_glow() {
+        let image = image_with_light(21, Colour::new(11.0, 11.0, 11.0));
+        let bloom = Bloom {
+            threshold: 1.0,
+            radius: 4.0,
+        };
+        let bloomed = bloom.apply(&image);
+
+        // The glow fades away from the light and never reaches past the radius
+        let glow = |col: u32| bloomed.get_pixel(10, col).r() - 0.1;
+        assert!(glow(11) > glow(12) && glow(12) > glow(13) && glow(13) > 0.0);
+        assert_approx_eq!(glow(15), 0.0);
+        assert!(bloomed.get_pixel(10, 10).r() > 11.0);
+
+        // Only the 10 units above the threshold are spread around
+        let added: Float = iproduct!(0..21, 0..21)
+            .map(|(row, col)| bloomed.get_pixel(row, col).r() - image.get_pixel(row, col).r())
+            .sum();
+        assert_approx_eq!(added / 10.0, 1.0, 1e-3);
+    }
+}
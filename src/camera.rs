@@ -1,18 +1,43 @@
 use crate::config::Config;
 use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
 use crate::pdf::uniform;
-use std::f64::consts::PI;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CameraError {
+    #[error("the camera cannot look at {look_at:?} from the same point")]
+    LookingAtItself { look_at: Vector },
+    #[error("the view up {view_up:?} of the camera must not be parallel to the view direction")]
+    ParallelViewUp { view_up: Vector },
+    #[error("the orthographic blend of the camera must be between 0 and 1, not {orthographic}")]
+    InvalidOrthographic { orthographic: Float },
+    #[error("the tilt of the focus plane must be between -90 and 90 degrees, not {tilt}")]
+    InvalidTilt { tilt: Float },
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct Ray {
     a: Vector,
     b: Vector,
-    time: f64,
+    time: Float,
+    // Nanometres, only set when rendering spectrally
+    wavelength: Option<Float>,
 }
 
 impl Ray {
-    pub fn new(a: Vector, b: Vector, time: f64) -> Ray {
-        Ray { a, b, time }
+    pub fn new(a: Vector, b: Vector, time: Float) -> Ray {
+        Ray {
+            a,
+            b,
+            time,
+            wavelength: None,
+        }
+    }
+
+    pub fn with_wavelength(self, wavelength: Option<Float>) -> Ray {
+        Ray { wavelength, ..self }
     }
 
     pub fn origin(&self) -> &Vector {
@@ -23,19 +48,24 @@ impl Ray {
         &self.b
     }
 
-    pub fn point(&self, distance: f64) -> Vector {
+    pub fn point(&self, distance: Float) -> Vector {
         self.a + distance * self.b
     }
 
-    pub fn time(&self) -> f64 {
+    pub fn time(&self) -> Float {
         self.time
     }
 
+    pub fn wavelength(&self) -> Option<Float> {
+        self.wavelength
+    }
+
     pub fn offset(&self, offset: Vector) -> Ray {
         Ray {
             a: self.origin() - offset,
             b: *self.direction(),
             time: self.time(),
+            wavelength: self.wavelength(),
         }
     }
 }
@@ -49,21 +79,47 @@ pub struct Camera {
     u: Vector,
     v: Vector,
     w: Vector,
-    lens_radius: f64,
-    time_start: f64,
-    time_end: f64,
+    lens_radius: Float,
+    aperture_blades: u32,
+    orthographic: Float,
+    // The plane in focus passes through the middle of the image at the focus distance, facing
+    // back along this normal
+    focus_centre: Vector,
+    focus_normal: Vector,
+    shutter_open: Float,
+    shutter_close: Float,
+    projection: Projection,
     save: CameraSave,
 }
 
+/// How directions around the camera are laid out across the image
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Projection {
+    // A flat image through a lens, covering the vertical field of view. It can be blended
+    // towards an orthographic projection and have its focus plane tilted
+    #[default]
+    Perspective,
+    // The whole sphere of directions. Columns sweep all the way around the camera starting and
+    // ending behind it, rows go from straight down to straight up. The field of view and lens
+    // are ignored and the image should be twice as wide as it is high
+    Panoramic,
+    // An equidistant fisheye, where the angle away from the middle of the image grows evenly with
+    // the distance from the middle. The image circle fits the height of the image and spans
+    // `field_of_view` degrees, usually up to 180. Anything outside of it sees the background
+    Fisheye {
+        field_of_view: Float,
+    },
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Lens {
-    vertical_fov: f64,
-    aperture: f64,
-    focus_distance: f64,
+    vertical_fov: Float,
+    aperture: Float,
+    focus_distance: Float,
 }
 
 impl Lens {
-    pub fn new(vertical_fov: f64, aperture: f64, focus_distance: f64) -> Lens {
+    pub fn new(vertical_fov: Float, aperture: Float, focus_distance: Float) -> Lens {
         Lens {
             vertical_fov,
             aperture,
@@ -72,17 +128,41 @@ impl Lens {
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CameraSave {
     look_from: Vector,
     look_at: Vector,
     view_up: Vector,
-    vertical_fov: f64,
-    aspect: f64,
-    aperture: f64,
-    focus_distance: f64,
-    time_start: f64,
-    time_end: f64,
+    vertical_fov: Float,
+    aspect: Float,
+    aperture: Float,
+    focus_distance: Float,
+    // Every ray is sent at a random time while the shutter is open, equal times give a sharp frame
+    #[serde(alias = "time_start")]
+    shutter_open: Float,
+    #[serde(alias = "time_end")]
+    shutter_close: Float,
+    #[serde(default)]
+    projection: Projection,
+    // With 3 or more blades the aperture is a regular polygon with its corners on the edge of the
+    // lens, so out of focus highlights take its shape. Fewer keeps a round aperture
+    #[serde(default)]
+    aperture_blades: u32,
+    // Degrees the camera is turned anti-clockwise around the view direction, tilting the horizon
+    // for a dutch angle
+    #[serde(default)]
+    roll: Float,
+    // How far a perspective projection is blended towards an orthographic one, from 0 to 1. Rays
+    // through every pixel are parallel at 1, covering the part of the scene the field of view
+    // covers at the focus distance
+    #[serde(default)]
+    orthographic: Float,
+    // Degrees the focus plane of a perspective projection is turned around its horizontal
+    // middle, positive tilts putting the top of the image further away. Tilting it against the
+    // slope of a scene, such as a city seen from above, narrows what is in focus to a band for
+    // the miniature look of a tilt-shift lens
+    #[serde(default)]
+    tilt: Float,
 }
 
 impl CameraSave {
@@ -90,10 +170,10 @@ impl CameraSave {
         look_from: &Vector,
         look_at: &Vector,
         view_up: &Vector,
-        aspect: f64,
+        aspect: Float,
         lens: Lens,
-        time_start: f64,
-        time_end: f64,
+        shutter_open: Float,
+        shutter_close: Float,
     ) -> CameraSave {
         CameraSave {
             look_from: *look_from,
@@ -103,26 +183,66 @@ impl CameraSave {
             aspect,
             aperture: lens.aperture,
             focus_distance: lens.focus_distance,
-            time_start,
-            time_end,
+            shutter_open,
+            shutter_close,
+            projection: Projection::Perspective,
+            aperture_blades: 0,
+            roll: 0.0,
+            orthographic: 0.0,
+            tilt: 0.0,
         }
     }
 
+    pub fn with_view(self, look_from: Vector, look_at: Vector) -> CameraSave {
+        CameraSave {
+            look_from,
+            look_at,
+            ..self
+        }
+    }
+
+    pub fn with_shutter(self, shutter_open: Float, shutter_close: Float) -> CameraSave {
+        CameraSave {
+            shutter_open,
+            shutter_close,
+            ..self
+        }
+    }
+
+    /// The same camera looking from `look_from` at `look_at` must still have a view
+    pub fn validate_view(&self, look_from: &Vector, look_at: &Vector) -> Result<(), CameraError> {
+        look_at_basis(look_from, look_at, &self.view_up, self.roll).map(|_| ())
+    }
+
+    /// The view is degenerate if the camera looks at its own position or up is along the view
+    pub fn validate(&self) -> Result<(), CameraError> {
+        if !(0.0..=1.0).contains(&self.orthographic) {
+            return Err(CameraError::InvalidOrthographic {
+                orthographic: self.orthographic,
+            });
+        }
+        if !(-90.0 < self.tilt && self.tilt < 90.0) {
+            return Err(CameraError::InvalidTilt { tilt: self.tilt });
+        }
+        look_at_basis(&self.look_from, &self.look_at, &self.view_up, self.roll).map(|_| ())
+    }
+
+    /// Panics if the camera has not passed `validate`
     pub fn into_camera(self) -> Camera {
         let lens_radius = self.aperture / 2.0;
 
         let theta = self.vertical_fov * PI / 180.0;
-        let half_height = f64::tan(theta / 2.0) * self.focus_distance;
+        let half_height = Float::tan(theta / 2.0) * self.focus_distance;
         let half_width = self.aspect * half_height;
 
-        let w = (self.look_from - self.look_at).unit_vector();
-        let u = Vector::cross(&self.view_up, &w).unit_vector();
-        let v = Vector::cross(&w, &u);
+        let (u, v, w) = look_at_basis(&self.look_from, &self.look_at, &self.view_up, self.roll)
+            .expect("camera was validated");
 
         let origin = self.look_from;
         let lower_left_corner = origin - half_width * u - half_height * v - self.focus_distance * w;
         let horizontal = 2.0 * half_width * u;
         let vertical = 2.0 * half_height * v;
+        let (sin_tilt, cos_tilt) = self.tilt.to_radians().sin_cos();
 
         Camera {
             origin,
@@ -133,60 +253,275 @@ impl CameraSave {
             v,
             w,
             lens_radius,
-            time_start: self.time_start,
-            time_end: self.time_end,
+            aperture_blades: self.aperture_blades,
+            orthographic: self.orthographic,
+            focus_centre: origin - self.focus_distance * w,
+            focus_normal: cos_tilt * w + sin_tilt * v,
+            shutter_open: self.shutter_open,
+            shutter_close: self.shutter_close,
+            projection: self.projection,
             save: self,
         }
     }
 }
 
-impl Camera {
-    pub fn pixels(&self, config: &Config) -> Vec<(u32, u32)> {
-        let height = config.height();
-        let width = config.width();
+/// The camera's right `u`, up `v` and backwards `w` directions when looking from `look_from` at
+/// `look_at` with `view_up` towards the top of the image, before turning by `roll` degrees
+fn look_at_basis(
+    look_from: &Vector,
+    look_at: &Vector,
+    view_up: &Vector,
+    roll: Float,
+) -> Result<(Vector, Vector, Vector), CameraError> {
+    let backwards = look_from - look_at;
+    if backwards.len_squared() == 0.0 {
+        return Err(CameraError::LookingAtItself { look_at: *look_at });
+    }
+    let w = backwards.unit_vector();
 
-        iproduct!(0..height, 0..width).collect()
+    // Any up that is nearly parallel to the view leaves the sideways direction down to rounding
+    let sideways = Vector::cross(view_up, &w);
+    if sideways.len() <= 1e-9 * view_up.len() || view_up.len_squared() == 0.0 {
+        return Err(CameraError::ParallelViewUp { view_up: *view_up });
     }
+    let u = sideways.unit_vector();
+    let v = Vector::cross(&w, &u);
 
-    pub fn rays(&self, row: u32, col: u32, config: &Config) -> Vec<Ray> {
-        let height = config.height();
-        let width = config.width();
+    let (sin_roll, cos_roll) = roll.to_radians().sin_cos();
+    Ok((cos_roll * u + sin_roll * v, cos_roll * v - sin_roll * u, w))
+}
 
-        (0..config.num_of_rays())
-            .map(|_| {
-                let row_fuzz: f64 = uniform();
-                let col_fuzz: f64 = uniform();
+impl Camera {
+    /// A pinhole camera looking from `look_from` at `look_at`, keeping everything in focus with
+    /// its shutter open for an instant at time 0. Use `with_roll` to turn it around the view
+    pub fn look_at(
+        look_from: &Vector,
+        look_at: &Vector,
+        view_up: &Vector,
+        vertical_fov: Float,
+        aspect: Float,
+    ) -> Result<Camera, CameraError> {
+        let focus_distance = (look_from - look_at).len();
+        let save = CameraSave::new(
+            look_from,
+            look_at,
+            view_up,
+            aspect,
+            Lens::new(vertical_fov, 0.0, focus_distance),
+            0.0,
+            0.0,
+        );
+        look_at_basis(look_from, look_at, view_up, save.roll)?;
+        Ok(save.into_camera())
+    }
 
-                let v = f64::from(row) + row_fuzz;
-                let h = f64::from(col) + col_fuzz;
+    /// The same camera turned `roll` degrees around its view direction, anticlockwise as seen
+    /// through it, for dutch angle shots
+    pub fn with_roll(self, roll: Float) -> Camera {
+        CameraSave { roll, ..self.save }.into_camera()
+    }
 
-                self.ray(h / f64::from(width), v / f64::from(height))
-            })
+    /// The rays for every sample of the pixel. Samples outside the image circle of a fisheye have
+    /// no ray and see the background of `background_ray`
+    pub fn rays(&self, row: u32, col: u32, config: &Config) -> Vec<Option<Ray>> {
+        pixel_offsets(config.num_of_rays())
+            .map(|(row_fuzz, col_fuzz)| self.ray_through(row, col, row_fuzz, col_fuzz, config))
             .collect()
     }
 
-    fn ray(&self, h: f64, v: f64) -> Ray {
-        let rd = self.lens_radius * random_point_in_unit_disk();
-        let lens_offset = self.u * rd.x() + self.v * rd.y();
-        let time = self.time_start + uniform::<f64>() * (self.time_end - self.time_start);
-        Ray {
-            a: self.origin + lens_offset,
-            b: self.lower_left_corner + h * self.horizontal + v * self.vertical
-                - self.origin
-                - lens_offset,
-            time,
+    /// The ray of the `sample`th of the pixel's `num_of_rays` samples, spread out by the pixel
+    /// filter, and the weight the filter gives it. Unlike `rays` each sample only uses the random
+    /// numbers it draws itself
+    pub fn sample_ray(
+        &self,
+        row: u32,
+        col: u32,
+        sample: u64,
+        config: &Config,
+    ) -> (Option<Ray>, Float) {
+        let (row_fuzz, col_fuzz) = pixel_offset(sample, config.num_of_rays());
+        self.filtered_ray_through(row, col, row_fuzz, col_fuzz, config)
+    }
+
+    /// A single ray through a random point of the pixel filter and its weight
+    pub fn pixel_ray(&self, row: u32, col: u32, config: &Config) -> (Option<Ray>, Float) {
+        let row_fuzz: Float = uniform();
+        let col_fuzz: Float = uniform();
+        self.filtered_ray_through(row, col, row_fuzz, col_fuzz, config)
+    }
+
+    fn filtered_ray_through(
+        &self,
+        row: u32,
+        col: u32,
+        row_fuzz: Float,
+        col_fuzz: Float,
+        config: &Config,
+    ) -> (Option<Ray>, Float) {
+        let (row_fuzz, col_fuzz, weight) = config.pixel_filter().spread(row_fuzz, col_fuzz);
+        (
+            self.ray_through(row, col, row_fuzz, col_fuzz, config),
+            weight,
+        )
+    }
+
+    fn ray_through(
+        &self,
+        row: u32,
+        col: u32,
+        row_fuzz: Float,
+        col_fuzz: Float,
+        config: &Config,
+    ) -> Option<Ray> {
+        let v = row as Float + row_fuzz;
+        let h = col as Float + col_fuzz;
+
+        self.ray(h / config.width() as Float, v / config.height() as Float)
+    }
+
+    fn ray(&self, h: Float, v: Float) -> Option<Ray> {
+        match self.projection {
+            Projection::Perspective => Some(self.perspective_ray(h, v)),
+            Projection::Panoramic => Some(self.panoramic_ray(h, v)),
+            Projection::Fisheye { field_of_view } => self.fisheye_ray(h, v, field_of_view),
         }
     }
 
-    pub fn time_start(&self) -> f64 {
-        self.time_start
+    /// Looks straight back from the camera. Its background is what is seen outside the image
+    /// circle of a fisheye
+    pub fn background_ray(&self) -> Ray {
+        Ray::new(self.origin, self.w, self.shutter_open)
     }
 
-    pub fn time_end(&self) -> f64 {
-        self.time_end
+    fn perspective_ray(&self, h: Float, v: Float) -> Ray {
+        let on_image = self.lower_left_corner + h * self.horizontal + v * self.vertical;
+        // An orthographic camera looks from the point of the lens in line with the pixel
+        let centre = self.origin
+            + self.orthographic * ((h - 0.5) * self.horizontal + (v - 0.5) * self.vertical);
+        let towards_image = on_image - centre;
+
+        // A pinhole camera does not need to sample the lens at all
+        let lens_offset = if self.lens_radius > 0.0 {
+            let rd = self.lens_radius * random_point_on_aperture(self.aperture_blades);
+            self.u * rd.x() + self.v * rd.y()
+        } else {
+            Vector::new(0.0, 0.0, 0.0)
+        };
+
+        // Rays through the lens from the pixel meet where the ray through the centre of the lens
+        // crosses the focus plane. When that ray never reaches a tilted plane the pixel is
+        // focused at infinity and they are all parallel
+        let distance = Vector::dot(&(self.focus_centre - centre), &self.focus_normal)
+            / Vector::dot(&towards_image, &self.focus_normal);
+        let direction = if distance > 0.0 && distance.is_finite() {
+            distance * towards_image - lens_offset
+        } else {
+            towards_image
+        };
+        Ray::new(centre + lens_offset, direction, self.time())
+    }
+
+    /// An equirectangular projection where `h` is the longitude and `v` the latitude. The middle
+    /// of the image looks towards `look_at`
+    fn panoramic_ray(&self, h: Float, v: Float) -> Ray {
+        let longitude = 2.0 * PI * (h - 0.5);
+        let latitude = PI * (v - 0.5);
+
+        let around = Float::sin(longitude) * self.u - Float::cos(longitude) * self.w;
+        let direction = Float::cos(latitude) * around + Float::sin(latitude) * self.v;
+
+        Ray::new(self.origin, direction, self.time())
+    }
+
+    fn fisheye_ray(&self, h: Float, v: Float, field_of_view: Float) -> Option<Ray> {
+        // Scaled so the image circle has a radius of 1 and touches the top and bottom of the image
+        let x = (2.0 * h - 1.0) * self.save.aspect;
+        let y = 2.0 * v - 1.0;
+        let radius = Float::hypot(x, y);
+        if radius > 1.0 {
+            return None;
+        }
+
+        let theta = radius * field_of_view.to_radians() / 2.0;
+        let sideways = if radius > 0.0 {
+            (x / radius) * self.u + (y / radius) * self.v
+        } else {
+            Vector::new(0.0, 0.0, 0.0)
+        };
+
+        Some(Ray::new(
+            self.origin,
+            Float::sin(theta) * sideways - Float::cos(theta) * self.w,
+            self.time(),
+        ))
+    }
+
+    fn time(&self) -> Float {
+        self.shutter_open + uniform::<Float>() * (self.shutter_close - self.shutter_open)
+    }
+
+    pub fn shutter_open(&self) -> Float {
+        self.shutter_open
+    }
+
+    pub fn shutter_close(&self) -> Float {
+        self.shutter_close
+    }
+}
+
+/// Where in the pixel each sample goes. A square number of samples is stratified by jittering one
+/// sample inside each cell of a grid over the pixel, otherwise the samples are purely random
+fn pixel_offsets(num_of_rays: u64) -> impl Iterator<Item = (Float, Float)> {
+    (0..num_of_rays).map(move |i| pixel_offset(i, num_of_rays))
+}
+
+fn pixel_offset(sample: u64, num_of_rays: u64) -> (Float, Float) {
+    match perfect_square_root(num_of_rays) {
+        Some(strata) => {
+            let row_fuzz = ((sample / strata) as Float + uniform::<Float>()) / strata as Float;
+            let col_fuzz = ((sample % strata) as Float + uniform::<Float>()) / strata as Float;
+            (row_fuzz, col_fuzz)
+        }
+        None => (uniform(), uniform()),
     }
 }
 
+fn perfect_square_root(n: u64) -> Option<u64> {
+    let root = (n as Float).sqrt().round() as u64;
+    if root * root == n {
+        Some(root)
+    } else {
+        None
+    }
+}
+
+fn random_point_on_aperture(aperture_blades: u32) -> Vector {
+    if aperture_blades >= 3 {
+        random_point_in_unit_polygon(aperture_blades)
+    } else {
+        random_point_in_unit_disk()
+    }
+}
+
+/// A uniformly random point inside the regular polygon with `sides` corners on the unit circle,
+/// one of them straight up. Every wedge between the centre and a side has the same area, so a
+/// random wedge is picked and then a random point inside of it
+fn random_point_in_unit_polygon(sides: u32) -> Vector {
+    let wedge = ((uniform::<Float>() * sides as Float) as u32).min(sides - 1);
+    let corner = |i: u32| {
+        let angle = PI / 2.0 + 2.0 * PI * i as Float / sides as Float;
+        Vector::new(angle.cos(), angle.sin(), 0.0)
+    };
+
+    // Points past the diagonal of the parallelogram are folded back into the triangle
+    let (mut a, mut b): (Float, Float) = (uniform(), uniform());
+    if a + b > 1.0 {
+        a = 1.0 - a;
+        b = 1.0 - b;
+    }
+    a * corner(wedge) + b * corner(wedge + 1)
+}
+
 fn random_point_in_unit_disk() -> Vector {
     let centre = Vector::new(1.0, 1.0, 0.0);
 
@@ -197,3 +532,398 @@ fn random_point_in_unit_disk() -> Vector {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::seed_thread_rng;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn camera(aperture: Float) -> Camera {
+        camera_with_shutter(aperture, 0.0, 0.0)
+    }
+
+    fn camera_with_shutter(aperture: Float, shutter_open: Float, shutter_close: Float) -> Camera {
+        CameraSave::new(
+            &Vector::new(0.0, 0.0, 0.0),
+            &Vector::new(0.0, 0.0, -1.0),
+            &Vector::new(0.0, 1.0, 0.0),
+            1.0,
+            Lens::new(90.0, aperture, 5.0),
+            shutter_open,
+            shutter_close,
+        )
+        .into_camera()
+    }
+
+    #[test]
+    fn test_pinhole_rays_start_at_look_from() {
+        let camera = camera(0.0);
+        for _ in 0..10 {
+            let ray = camera.ray(0.5, 0.5).unwrap();
+            assert_eq!(*ray.origin(), Vector::new(0.0, 0.0, 0.0));
+            assert_eq!(*ray.direction(), Vector::new(0.0, 0.0, -5.0));
+        }
+    }
+
+    #[test]
+    fn test_thin_lens_rays_meet_on_the_focus_plane() {
+        let camera = camera(2.0);
+        for _ in 0..10 {
+            let ray = camera.ray(0.75, 0.25).unwrap();
+            assert!(ray.origin().len() <= 1.0);
+            assert_approx_eq!(ray.origin().z(), 0.0);
+
+            // Every ray through the same pixel passes through the same point at the focus distance
+            let focus_point = ray.point(1.0);
+            assert_approx_eq!(focus_point.x(), 2.5);
+            assert_approx_eq!(focus_point.y(), -2.5);
+            assert_approx_eq!(focus_point.z(), -5.0);
+        }
+    }
+
+    #[test]
+    fn test_orthographic_rays_are_parallel() {
+        let save = |orthographic: Float| CameraSave {
+            orthographic,
+            ..CameraSave::new(
+                &Vector::new(0.0, 0.0, 0.0),
+                &Vector::new(0.0, 0.0, -1.0),
+                &Vector::new(0.0, 1.0, 0.0),
+                1.0,
+                Lens::new(90.0, 0.0, 5.0),
+                0.0,
+                0.0,
+            )
+        };
+
+        let camera = save(1.0).into_camera();
+        let ray = camera.ray(0.75, 0.25).unwrap();
+        assert_approx_eq!(ray.origin().x(), 2.5);
+        assert_approx_eq!(ray.origin().y(), -2.5);
+        assert_approx_eq!(ray.direction().unit_vector().z(), -1.0);
+
+        // Half way the rays spread half as much, still passing through the same image plane
+        let camera = save(0.5).into_camera();
+        let ray = camera.ray(0.75, 0.5).unwrap();
+        assert_approx_eq!(ray.origin().x(), 1.25);
+        assert_approx_eq!(ray.point(1.0).x(), 2.5);
+
+        assert!(save(1.5).validate().is_err());
+    }
+
+    #[test]
+    fn test_tilted_focus_plane_puts_the_top_further_away() {
+        let save = |tilt: Float| CameraSave {
+            tilt,
+            ..CameraSave::new(
+                &Vector::new(0.0, 0.0, 0.0),
+                &Vector::new(0.0, 0.0, -1.0),
+                &Vector::new(0.0, 1.0, 0.0),
+                1.0,
+                Lens::new(90.0, 2.0, 5.0),
+                0.0,
+                0.0,
+            )
+        };
+        let camera = save(30.0).into_camera();
+
+        let focus_point = |v: Float| {
+            let points: Vec<Vector> = (0..10)
+                .map(|_| camera.ray(0.5, v).unwrap().point(1.0))
+                .collect();
+            // Every ray through the pixel still meets at a single point
+            for point in &points {
+                assert_approx_eq!((point - points[0]).len(), 0.0);
+            }
+            points[0]
+        };
+        assert_approx_eq!(focus_point(0.5).z(), -5.0);
+        let (top, bottom) = (focus_point(0.75), focus_point(0.25));
+        assert!(top.z() < -5.0 && bottom.z() > -5.0);
+        // On the plane turned 30 degrees back from upright
+        assert_approx_eq!(top.y() / (-5.0 - top.z()), Float::tan(PI / 3.0), 0.001);
+
+        assert!(save(90.0).validate().is_err());
+    }
+
+    #[test]
+    fn test_bladed_aperture_is_a_polygon() {
+        seed_thread_rng(0);
+        let sides = 6;
+        let apothem = Float::cos(PI / sides as Float);
+        let mut counts = [0; 6];
+        for _ in 0..6000 {
+            let point = random_point_in_unit_polygon(sides);
+            assert_approx_eq!(point.z(), 0.0);
+
+            // Inside of every side, measured along the normal through the middle of each side
+            for side in 0..sides {
+                let angle = PI / 2.0 + (2.0 * side as Float + 1.0) * PI / sides as Float;
+                let normal = Vector::new(angle.cos(), angle.sin(), 0.0);
+                assert!(Vector::dot(&point, &normal) <= apothem + 1e-9);
+            }
+
+            let angle = (point.y().atan2(point.x()) - PI / 2.0).rem_euclid(2.0 * PI);
+            counts[((angle / (PI / 3.0)) as usize).min(5)] += 1;
+        }
+
+        // The corners reach out to the edge of the lens and every wedge is sampled evenly
+        assert!((0..1000).any(|_| random_point_in_unit_polygon(sides).len() > 0.97));
+        assert!(counts.iter().all(|&count| (850..1150).contains(&count)));
+    }
+
+    #[test]
+    fn test_few_aperture_blades_keep_a_round_aperture() {
+        let camera = |aperture_blades: u32| {
+            CameraSave {
+                aperture_blades,
+                ..CameraSave::new(
+                    &Vector::new(0.0, 0.0, 0.0),
+                    &Vector::new(0.0, 0.0, -1.0),
+                    &Vector::new(0.0, 1.0, 0.0),
+                    1.0,
+                    Lens::new(90.0, 2.0, 5.0),
+                    0.0,
+                    0.0,
+                )
+            }
+            .into_camera()
+        };
+        // Straight down is half way between two corners of a pentagon, so its lens stops short
+        // of the bottom of a round lens
+        let apothem = Float::cos(PI / 5.0);
+        let below_pentagon = |camera: Camera| {
+            (0..2000)
+                .filter(|_| camera.ray(0.5, 0.5).unwrap().origin().y() < -apothem)
+                .count()
+        };
+
+        assert_eq!(below_pentagon(camera(5)), 0);
+        assert!(below_pentagon(camera(2)) > 0);
+        assert!(below_pentagon(camera(0)) > 0);
+    }
+
+    #[test]
+    fn test_rays_are_sent_while_the_shutter_is_open() {
+        let camera = camera_with_shutter(0.0, 0.25, 0.75);
+        let times: Vec<Float> = (0..100)
+            .map(|_| camera.ray(0.5, 0.5).unwrap().time())
+            .collect();
+        assert!(times.iter().all(|time| (0.25..=0.75).contains(time)));
+        assert!(times.iter().any(|time| *time > 0.5) && times.iter().any(|time| *time < 0.5));
+
+        let sharp = camera_with_shutter(0.0, 0.5, 0.5);
+        assert_eq!(sharp.ray(0.5, 0.5).unwrap().time(), 0.5);
+    }
+
+    #[test]
+    fn test_shutter_loads_from_time_fields() {
+        let yaml = "look_from: {x: 0.0, y: 0.0, z: 0.0}
+look_at: {x: 0.0, y: 0.0, z: -1.0}
+view_up: {x: 0.0, y: 1.0, z: 0.0}
+vertical_fov: 90.0
+aspect: 1.0
+aperture: 0.0
+focus_distance: 1.0
+time_start: 0.0
+time_end: 1.0
+";
+        let camera: CameraSave = serde_yaml::from_str(yaml).unwrap();
+        let camera = camera.into_camera();
+        assert_eq!(camera.shutter_close(), 1.0);
+    }
+
+    #[test]
+    fn test_square_sample_counts_are_stratified() {
+        let mut cells = [[0; 4]; 4];
+        for (row_fuzz, col_fuzz) in pixel_offsets(16) {
+            cells[(row_fuzz * 4.0) as usize][(col_fuzz * 4.0) as usize] += 1;
+        }
+        assert_eq!(cells, [[1; 4]; 4]);
+    }
+
+    #[test]
+    fn test_stratified_samples_are_less_noisy_on_an_edge() {
+        // The fraction of a pixel on one side of a diagonal edge, estimated with 16 samples
+        let coverage = |offsets: Vec<(Float, Float)>| {
+            let covered = offsets.iter().filter(|(row, col)| row + col < 0.7).count();
+            covered as Float / offsets.len() as Float
+        };
+        let variance = |estimates: Vec<Float>| {
+            let mean = estimates.iter().sum::<Float>() / estimates.len() as Float;
+            estimates.iter().map(|e| (e - mean).powi(2)).sum::<Float>() / estimates.len() as Float
+        };
+
+        let stratified = (0..500)
+            .map(|_| coverage(pixel_offsets(16).collect()))
+            .collect();
+        let random = (0..500)
+            .map(|_| coverage((0..16).map(|_| (uniform(), uniform())).collect()))
+            .collect();
+
+        assert!(variance(stratified) < variance(random));
+    }
+
+    #[test]
+    fn test_other_sample_counts_fall_back_to_random() {
+        assert_eq!(perfect_square_root(10), None);
+        assert_eq!(perfect_square_root(1), Some(1));
+
+        let offsets: Vec<(Float, Float)> = pixel_offsets(10).collect();
+        assert_eq!(offsets.len(), 10);
+        assert!(offsets
+            .iter()
+            .all(|(row, col)| (0.0..1.0).contains(row) && (0.0..1.0).contains(col)));
+    }
+
+    #[test]
+    fn test_panoramic_rays_cover_the_sphere() {
+        let mut camera = CameraSave::new(
+            &Vector::new(1.0, 2.0, 3.0),
+            &Vector::new(1.0, 2.0, 2.0),
+            &Vector::new(0.0, 1.0, 0.0),
+            2.0,
+            Lens::new(90.0, 1.0, 5.0),
+            0.0,
+            0.0,
+        );
+        camera.projection = Projection::Panoramic;
+        let camera = camera.into_camera();
+
+        let assert_direction = |h: Float, v: Float, expected: Vector| {
+            let ray = camera.ray(h, v).unwrap();
+            assert_eq!(*ray.origin(), Vector::new(1.0, 2.0, 3.0));
+            assert_approx_eq!(ray.direction().x(), expected.x());
+            assert_approx_eq!(ray.direction().y(), expected.y());
+            assert_approx_eq!(ray.direction().z(), expected.z());
+        };
+
+        assert_direction(0.5, 0.5, Vector::new(0.0, 0.0, -1.0));
+        assert_direction(0.75, 0.5, Vector::new(1.0, 0.0, 0.0));
+        assert_direction(0.25, 0.5, Vector::new(-1.0, 0.0, 0.0));
+        assert_direction(0.0, 0.5, Vector::new(0.0, 0.0, 1.0));
+        assert_direction(1.0, 0.5, Vector::new(0.0, 0.0, 1.0));
+        assert_direction(0.5, 1.0, Vector::new(0.0, 1.0, 0.0));
+        assert_direction(0.3, 0.0, Vector::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn test_fisheye_rays_are_equidistant() {
+        let mut camera = CameraSave::new(
+            &Vector::new(0.0, 0.0, 0.0),
+            &Vector::new(0.0, 0.0, -1.0),
+            &Vector::new(0.0, 1.0, 0.0),
+            2.0,
+            Lens::new(90.0, 0.0, 5.0),
+            0.0,
+            0.0,
+        );
+        camera.projection = Projection::Fisheye {
+            field_of_view: 180.0,
+        };
+        let camera = camera.into_camera();
+
+        let direction = |h: Float, v: Float| camera.ray(h, v).unwrap().direction().unit_vector();
+
+        let centre = direction(0.5, 0.5);
+        assert_approx_eq!(centre.z(), -1.0);
+
+        // The top of the image circle is 90° away from the middle and half way there is 45°
+        let top = direction(0.5, 1.0);
+        assert_approx_eq!(top.y(), 1.0);
+        let half_way = direction(0.5, 0.75);
+        assert_approx_eq!(half_way.y(), Float::sin(PI / 4.0));
+        assert_approx_eq!(half_way.z(), -Float::cos(PI / 4.0));
+
+        // The image is twice as wide as it is high, so the circle reaches a quarter of the way in
+        let right = direction(0.75, 0.5);
+        assert_approx_eq!(right.x(), 1.0);
+
+        assert!(camera.ray(0.9, 0.5).is_none());
+        assert!(camera.ray(0.5 + 0.2, 0.5 + 0.5).is_none());
+        assert_eq!(
+            *camera.background_ray().direction(),
+            Vector::new(0.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_look_at_builds_an_orthonormal_basis() {
+        let camera = Camera::look_at(
+            &Vector::new(3.0, 2.0, 1.0),
+            &Vector::new(0.0, 0.5, -2.0),
+            &Vector::new(0.0, 1.0, 0.0),
+            40.0,
+            1.5,
+        )
+        .unwrap();
+
+        for (a, b) in &[
+            (camera.u, camera.v),
+            (camera.v, camera.w),
+            (camera.w, camera.u),
+        ] {
+            assert_approx_eq!(Vector::dot(a, b), 0.0);
+        }
+        for axis in &[camera.u, camera.v, camera.w] {
+            assert_approx_eq!(axis.len(), 1.0);
+        }
+        let forwards = (Vector::new(-3.0, -1.5, -3.0)).unit_vector();
+        assert_approx_eq!(Vector::dot(&-camera.w, &forwards), 1.0);
+        // The middle of the image is in focus at the point being looked at
+        let ray = camera.ray(0.5, 0.5).unwrap();
+        assert_approx_eq!(ray.point(1.0).y(), 0.5);
+    }
+
+    #[test]
+    fn test_roll_turns_the_camera_around_the_view() {
+        let camera = Camera::look_at(
+            &Vector::new(0.0, 0.0, 0.0),
+            &Vector::new(0.0, 0.0, -1.0),
+            &Vector::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+        )
+        .unwrap()
+        .with_roll(30.0);
+
+        // Up leans over to the left and the view direction is unchanged
+        let (sin, cos) = Float::to_radians(30.0).sin_cos();
+        assert_approx_eq!(camera.v.x(), -sin);
+        assert_approx_eq!(camera.v.y(), cos);
+        assert_approx_eq!(camera.u.x(), cos);
+        assert_approx_eq!(camera.u.y(), sin);
+        assert_approx_eq!(camera.w.z(), 1.0);
+    }
+
+    #[test]
+    fn test_degenerate_views_are_errors() {
+        let from = Vector::new(1.0, 2.0, 3.0);
+        let fov = 60.0;
+        let looking_up = Camera::look_at(
+            &from,
+            &Vector::new(1.0, 5.0, 3.0),
+            &Vector::new(0.0, 2.0, 0.0),
+            fov,
+            1.0,
+        );
+        assert!(matches!(
+            looking_up,
+            Err(CameraError::ParallelViewUp { .. })
+        ));
+        let no_up = Camera::look_at(
+            &from,
+            &Vector::new(0.0, 0.0, 0.0),
+            &Vector::new(0.0, 0.0, 0.0),
+            fov,
+            1.0,
+        );
+        assert!(matches!(no_up, Err(CameraError::ParallelViewUp { .. })));
+        let at_itself = Camera::look_at(&from, &from, &Vector::new(0.0, 1.0, 0.0), fov, 1.0);
+        assert!(matches!(
+            at_itself,
+            Err(CameraError::LookingAtItself { .. })
+        ));
+    }
+}
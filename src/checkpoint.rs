@@ -0,0 +1,261 @@
+use crate::config::Config;
+use crate::data::colour::Colour;
+use crate::data::image::{Image, Pixel};
+use crate::float::Float;
+use crate::pixel_filter::PixelFilter;
+use std::io::{Read, Write};
+use thiserror::Error;
+
+// Identifies a checkpoint file and the layout of what follows it. The colours are stored in the
+// precision the renderer was built with so each precision has its own
+#[cfg(not(feature = "f32"))]
+const MAGIC: &[u8; 8] = b"RAYTCKP2";
+#[cfg(feature = "f32")]
+const MAGIC: &[u8; 8] = b"RAYTCKF2";
+
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    #[error("the file is not a render checkpoint")]
+    NotACheckpoint(),
+    #[error("the checkpoint is for a {checkpoint} render but this render is {render}")]
+    Mismatch { checkpoint: String, render: String },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+/// The running sums of a progressive render. Every sample is seeded from the render seed and its
+/// own index, so these sums and the number of passes done are all that is needed to carry on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    seed: u64,
+    width: u32,
+    height: u32,
+    num_of_passes: u64,
+    pixel_filter: PixelFilter,
+    passes_done: u64,
+    // Row by row from row 0, each sample already weighed by the pixel filter
+    sums: Vec<Colour>,
+    // The pixel filter weights of the samples in each sum
+    weights: Vec<Float>,
+}
+
+impl Checkpoint {
+    pub fn new(config: &Config) -> Checkpoint {
+        Checkpoint {
+            seed: config.seed(),
+            width: config.width(),
+            height: config.height(),
+            num_of_passes: config.num_of_rays(),
+            pixel_filter: config.pixel_filter(),
+            passes_done: 0,
+            sums: vec![Colour::new(0.0, 0.0, 0.0); (config.width() * config.height()) as usize],
+            weights: vec![0.0; (config.width() * config.height()) as usize],
+        }
+    }
+
+    pub fn passes_done(&self) -> u64 {
+        self.passes_done
+    }
+
+    pub fn num_of_passes(&self) -> u64 {
+        self.num_of_passes
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The weighted sum of every pixel and the sum of its weights, row by row
+    pub fn sums_mut(&mut self) -> (&mut [Colour], &mut [Float]) {
+        (&mut self.sums, &mut self.weights)
+    }
+
+    pub fn finish_pass(&mut self) {
+        self.passes_done += 1;
+    }
+
+    /// The weighted average of the passes done so far
+    pub fn image(&self) -> Image {
+        let pixels: Vec<Pixel> = self
+            .sums
+            .iter()
+            .zip(&self.weights)
+            .enumerate()
+            .map(|(i, (sum, &weight))| {
+                let i = i as u32;
+                let colour = if weight > 0.0 {
+                    *sum / weight
+                } else {
+                    Colour::new(0.0, 0.0, 0.0)
+                };
+                Pixel::new(i / self.width, i % self.width, colour)
+            })
+            .collect();
+        Image::from(&pixels)
+    }
+
+    /// A checkpoint can only be resumed by a render that would have produced the same samples
+    pub fn check_matches(&self, config: &Config) -> Result<(), CheckpointError> {
+        let expected = Checkpoint::new(config);
+        let describe = |checkpoint: &Checkpoint| {
+            format!(
+                "{}x{} with {} rays, seed {} and a {} filter",
+                checkpoint.width,
+                checkpoint.height,
+                checkpoint.num_of_passes,
+                checkpoint.seed,
+                checkpoint.pixel_filter
+            )
+        };
+        if (
+            self.seed,
+            self.width,
+            self.height,
+            self.num_of_passes,
+            self.pixel_filter,
+        ) != (
+            expected.seed,
+            expected.width,
+            expected.height,
+            expected.num_of_passes,
+            expected.pixel_filter,
+        ) {
+            return Err(CheckpointError::Mismatch {
+                checkpoint: describe(self),
+                render: describe(&expected),
+            });
+        }
+        Ok(())
+    }
+
+    /// Colours are stored as their exact bits so a resumed render matches an uninterrupted one
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&self.width.to_le_bytes())?;
+        writer.write_all(&self.height.to_le_bytes())?;
+        writer.write_all(&self.num_of_passes.to_le_bytes())?;
+        writer.write_all(&[self.pixel_filter as u8])?;
+        writer.write_all(&self.passes_done.to_le_bytes())?;
+        for (sum, weight) in self.sums.iter().zip(&self.weights) {
+            for channel in &[sum.r(), sum.g(), sum.b(), *weight] {
+                writer.write_all(&channel.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> Result<Checkpoint, CheckpointError> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(CheckpointError::NotACheckpoint());
+        }
+
+        let seed = read_u64(reader)?;
+        let width = read_u32(reader)?;
+        let height = read_u32(reader)?;
+        let num_of_passes = read_u64(reader)?;
+        let mut filter_index = [0; 1];
+        reader.read_exact(&mut filter_index)?;
+        let pixel_filter = PixelFilter::variants()
+            .get(filter_index[0] as usize)
+            .and_then(|name| name.parse().ok())
+            .ok_or(CheckpointError::NotACheckpoint())?;
+        let passes_done = read_u64(reader)?;
+
+        // The size in the header is not checked against the config yet, so the pixels are only
+        // stored as they are read rather than allocated up front
+        let num_of_pixels = width as usize * height as usize;
+        let mut sums = Vec::new();
+        let mut weights = Vec::new();
+        for _ in 0..num_of_pixels {
+            let r = read_float(reader)?;
+            let g = read_float(reader)?;
+            let b = read_float(reader)?;
+            sums.push(Colour::new(r, g, b));
+            weights.push(read_float(reader)?);
+        }
+
+        Ok(Checkpoint {
+            seed,
+            width,
+            height,
+            num_of_passes,
+            pixel_filter,
+            passes_done,
+            sums,
+            weights,
+        })
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_float<R: Read>(reader: &mut R) -> std::io::Result<Float> {
+    let mut bytes = [0; std::mem::size_of::<Float>()];
+    reader.read_exact(&mut bytes)?;
+    Ok(Float::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkpoint() -> Checkpoint {
+        Checkpoint {
+            seed: 7,
+            width: 2,
+            height: 1,
+            num_of_passes: 16,
+            pixel_filter: PixelFilter::Gaussian,
+            passes_done: 2,
+            sums: vec![Colour::new(0.1, 0.2, 0.3), Colour::new(1.0 / 3.0, 5.0, 0.0)],
+            weights: vec![2.0, 2.0],
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        let mut bytes = Vec::new();
+        checkpoint().write(&mut bytes).unwrap();
+
+        let read = Checkpoint::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, checkpoint());
+        assert_eq!(
+            read.image().get_pixel(0, 1),
+            &Colour::new(1.0 / 6.0, 2.5, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_read_rejects_other_files() {
+        let bytes = b"P3\n2 1\n255\n0 0 0 0 0 0".to_vec();
+        match Checkpoint::read(&mut bytes.as_slice()) {
+            Err(CheckpointError::NotACheckpoint()) => (),
+            other => panic!("expected not a checkpoint, got {:?}", other),
+        }
+
+        // Cut off part way through the colours
+        let mut bytes = Vec::new();
+        checkpoint().write(&mut bytes).unwrap();
+        bytes.truncate(bytes.len() - 4);
+        assert!(Checkpoint::read(&mut bytes.as_slice()).is_err());
+
+        // A header claiming far more pixels than the file holds
+        let mut bytes = Vec::new();
+        checkpoint().write(&mut bytes).unwrap();
+        bytes[16..24].copy_from_slice(&[0xff; 8]);
+        assert!(Checkpoint::read(&mut bytes.as_slice()).is_err());
+    }
+}
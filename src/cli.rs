@@ -1,12 +1,40 @@
-use crate::io::SUPPORTED_IMAGE_EXT;
+use crate::animation::AnimationSettings;
+use crate::bloom::Bloom;
+use crate::config::{AdaptiveSampling, RenderOptions, RenderSettings};
+use crate::float::Float;
+use crate::io::{OutputFormat, SUPPORTED_IMAGE_EXT};
+use crate::light_sampling::LightSampling;
+use crate::passes::{DepthRange, Pass};
+use crate::pixel_filter::PixelFilter;
 use crate::scenes::Scene;
+use crate::tone_mapping::{ToneMapping, TransferFunction};
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use std::ffi::OsString;
+use std::path::Path;
 use std::str::FromStr;
 use thiserror::Error;
 
+// Colour temperatures in Kelvin from candle light to a clear blue sky, beyond which blackbodies
+// barely change colour
+const MIN_WHITE_BALANCE: Float = 1000.0;
+const MAX_WHITE_BALANCE: Float = 40000.0;
+
+// Replaced in the output path of an animation by the number of each frame, padded with zeros to
+// as many digits as there are of these in a row
+const FRAME_NUMBER_PLACEHOLDER: char = '#';
+
 pub struct ConfigPath(String);
 pub struct OutputPath(String);
+#[derive(Clone)]
 pub struct ImagePath(String);
+pub struct CheckpointPath(String);
+
+/// Where a progressive render saves its progress after every pass and which saved progress it
+/// carries on from
+pub struct CheckpointPaths {
+    pub save: Option<CheckpointPath>,
+    pub resume: Option<CheckpointPath>,
+}
 
 impl ConfigPath {
     pub fn path(&self) -> &str {
@@ -18,6 +46,47 @@ impl OutputPath {
     pub fn path(&self) -> &str {
         &self.0
     }
+
+    /// The path with `_suffix` inserted before the extension, e.g. `image_normal.png`
+    pub fn with_suffix(&self, suffix: &str) -> OutputPath {
+        match self.0.rfind('.') {
+            Some(index) => OutputPath(format!(
+                "{}_{}{}",
+                &self.0[..index],
+                suffix,
+                &self.0[index..]
+            )),
+            None => OutputPath(format!("{}_{}", self.0, suffix)),
+        }
+    }
+
+    /// The path with its extension replaced by `extension`, e.g. `image.pfm`
+    pub fn with_extension(&self, extension: &str) -> OutputPath {
+        let stem = self
+            .0
+            .rfind('.')
+            .map_or(&self.0[..], |index| &self.0[..index]);
+        OutputPath(format!("{}.{}", stem, extension))
+    }
+
+    /// The path of one frame of an animation, e.g. `frame_0012.png` for frame 12 of `frame_####.png`
+    pub fn for_frame(&self, frame: u32) -> OutputPath {
+        let start = match self.0.find(FRAME_NUMBER_PLACEHOLDER) {
+            Some(start) => start,
+            None => return OutputPath(self.0.clone()),
+        };
+        let digits = self.0[start..]
+            .chars()
+            .take_while(|&c| c == FRAME_NUMBER_PLACEHOLDER)
+            .count();
+        OutputPath(format!(
+            "{}{:0digits$}{}",
+            &self.0[..start],
+            frame,
+            &self.0[start + digits..],
+            digits = digits
+        ))
+    }
 }
 
 impl ImagePath {
@@ -28,15 +97,35 @@ impl ImagePath {
     pub fn file_name(&self) -> &str {
         &self.0.split('/').last().unwrap()
     }
+
+    /// `path` taken relative to the directory of the config it is listed in
+    pub fn relative_to(config_path: &ConfigPath, path: &str) -> ImagePath {
+        let directory = Path::new(config_path.path())
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        ImagePath(directory.join(path).to_string_lossy().into_owned())
+    }
+}
+
+impl CheckpointPath {
+    pub fn path(&self) -> &str {
+        &self.0
+    }
 }
 
 pub enum CliCommand {
     RENDER {
-        width: u32,
+        // Boxed as they are far bigger than any other command
+        options: Box<RenderOptions>,
+        // The options given explicitly, which take precedence over those saved with the scene
+        given_settings: Box<RenderSettings>,
         output_path: OutputPath,
-        num_of_rays: u64,
+        output_format: OutputFormat,
         num_of_threads: usize,
         asset_paths: Vec<ImagePath>,
+        checkpoint_paths: CheckpointPaths,
+        // Which frames to render when rendering an animation instead of a single image
+        animation: Option<AnimationSettings>,
     },
     GENERATE {
         scene: Scene,
@@ -63,6 +152,8 @@ impl CliConfig {
 enum CliParsingError {
     #[error("invalid value <{value}> for arg <{arg}>")]
     InvalidValue { arg: String, value: String },
+    #[error("min samples <{min_samples}> must be at least 2 and no more than max samples <{max_samples}>")]
+    InvalidSampleRange { min_samples: u64, max_samples: u64 },
     #[error("Config path <{0}> must end in .yaml")]
     InvalidConfigPath(String),
     #[error("Output path <{output_path}> must end in one of {supported_extensions:?}")]
@@ -70,9 +161,21 @@ enum CliParsingError {
         output_path: String,
         supported_extensions: Vec<String>,
     },
+    #[error("Output path <{0}> must have # where the frame number goes to render an animation")]
+    MissingFrameNumber(String),
+    #[error("depth far <{far}> must be further than depth near <{near}>")]
+    InvalidDepthRange { near: Float, far: Float },
 }
 
 pub fn get_cli_config() -> Result<CliConfig, anyhow::Error> {
+    get_cli_config_from(std::env::args_os())
+}
+
+fn get_cli_config_from<I, T>(args: I) -> Result<CliConfig, anyhow::Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
     let matches = App::new("Ray tracer")
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .setting(AppSettings::VersionlessSubcommands)
@@ -95,8 +198,11 @@ pub fn get_cli_config() -> Result<CliConfig, anyhow::Error> {
                         .short("w")
                         .long("width")
                         .takes_value(true)
-                        .required(true)
-                        .help("the output image width"),
+                        .required(false)
+                        .help(
+                            "the output image width. Required unless the config has a width \
+                             in its render settings",
+                        ),
                 )
                 .arg(
                     Arg::with_name("output_path")
@@ -114,7 +220,9 @@ pub fn get_cli_config() -> Result<CliConfig, anyhow::Error> {
                         .takes_value(true)
                         .required(true)
                         .default_value("100")
-                        .help("the number of rays to generate per pixel"),
+                        .help(
+                            "the number of rays to generate per pixel when not sampling adaptively",
+                        ),
                 )
                 .arg(
                     Arg::with_name("threads")
@@ -125,6 +233,295 @@ pub fn get_cli_config() -> Result<CliConfig, anyhow::Error> {
                         .default_value("4")
                         .help("the number of threads to create for the renderer"),
                 )
+                .arg(
+                    Arg::with_name("max_depth")
+                        .long("max-depth")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("50")
+                        .help("the maximum number of times a ray can bounce around the scene"),
+                )
+                .arg(
+                    Arg::with_name("roulette_depth")
+                        .long("roulette-depth")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("5")
+                        .help(
+                            "the number of bounces after which paths are randomly terminated \
+                             using russian roulette",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("exposure")
+                        .long("exposure")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .allow_hyphen_values(true)
+                        .help(
+                            "the stops to brighten the image by before tone mapping, each stop \
+                             doubles the brightness and negative stops darken it",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("white_balance")
+                        .long("white-balance")
+                        .takes_value(true)
+                        .required(false)
+                        .help(
+                            "the colour temperature in Kelvin of light that should look white, \
+                             such as 3200 for tungsten bulbs",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("tone_mapping")
+                        .long("tone-mapping")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("None")
+                        .possible_values(&ToneMapping::variants())
+                        .case_insensitive(true)
+                        .help("the operator used to bring bright colours into the output range"),
+                )
+                .arg(
+                    Arg::with_name("transfer_function")
+                        .long("transfer-function")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("2.0")
+                        .help(
+                            "how the tone mapped image is encoded for display, either Srgb for \
+                             the sRGB curve or a gamma such as 2.2",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("light_sampling")
+                        .long("light-sampling")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("Mis")
+                        .possible_values(&LightSampling::variants())
+                        .case_insensitive(true)
+                        .help(
+                            "how diffuse surfaces find the lights, by following the material, \
+                             sampling the lights or both combined with multiple importance sampling",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("pixel_filter")
+                        .long("pixel-filter")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("Box")
+                        .possible_values(&PixelFilter::variants())
+                        .case_insensitive(true)
+                        .help(
+                            "how the samples of a pixel are weighed, Tent and Gaussian spread them \
+                             over the neighbouring pixels to soften jagged edges",
+                        ),
+                )
+                .arg(Arg::with_name("adaptive").long("adaptive").help(
+                    "keep sampling noisy pixels until they converge instead of using \
+                             a fixed number of rays per pixel",
+                ))
+                .arg(
+                    Arg::with_name("min_samples")
+                        .long("min-samples")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("16")
+                        .help(
+                            "the number of rays per pixel traced before checking for convergence",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("max_samples")
+                        .long("max-samples")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("1024")
+                        .help("the most rays an adaptively sampled pixel can use"),
+                )
+                .arg(
+                    Arg::with_name("tolerance")
+                        .long("tolerance")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0.05")
+                        .help(
+                            "the relative error at which an adaptively sampled pixel is \
+                             considered converged",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("progressive")
+                        .long("progressive")
+                        .conflicts_with("adaptive")
+                        .help(
+                            "render one ray per pixel at a time, saving the image after every \
+                             pass so the render can be stopped early",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("preview")
+                        .long("preview")
+                        .conflicts_with_all(&["adaptive", "progressive", "checkpoint", "resume"])
+                        .help(
+                            "render a quick rough preview with one ray per pixel and at most 2 \
+                             bounces, whatever the rays and max depth",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("preview_ambient")
+                        .long("preview-ambient")
+                        .takes_value(true)
+                        .required(false)
+                        .requires("preview")
+                        .help(
+                            "the brightness of a grey ambient light seen by the bounces a preview \
+                             does not trace, standing in for the rest of the global illumination",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("checkpoint")
+                        .long("checkpoint")
+                        .takes_value(true)
+                        .required(false)
+                        .conflicts_with("adaptive")
+                        .help(
+                            "render progressively, saving the progress to this path after every \
+                             pass so an interrupted render can be resumed",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("resume")
+                        .long("resume")
+                        .takes_value(true)
+                        .required(false)
+                        .conflicts_with("adaptive")
+                        .help(
+                            "carry on a progressive render from a checkpoint saved with the same \
+                             scene, size, rays and seed",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("frames")
+                        .long("frames")
+                        .takes_value(true)
+                        .required(false)
+                        .conflicts_with_all(&["progressive", "checkpoint", "resume"])
+                        .help(
+                            "render this many frames of the scene's animation, each written to \
+                             the output path with its number in place of the #s, such as \
+                             frame_###.png",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("fps")
+                        .long("fps")
+                        .takes_value(true)
+                        .required(false)
+                        .default_value("24")
+                        .help("the frames per second of the animation"),
+                )
+                .arg(
+                    Arg::with_name("ray_epsilon")
+                        .long("ray-epsilon")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0.001")
+                        .help(
+                            "how far along a ray hits start to count. Raise it for scenes with \
+                             large coordinates that show shadow acne, too large lets light leak \
+                             through thin geometry",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("clamp")
+                        .long("clamp")
+                        .takes_value(true)
+                        .required(false)
+                        .help(
+                            "the brightest luminance a single sample can have. Clamping removes \
+                             fireflies but darkens the brightest highlights",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("bloom_threshold")
+                        .long("bloom-threshold")
+                        .takes_value(true)
+                        .required(false)
+                        .help(
+                            "make pixels brighter than this luminance glow, by blurring the \
+                             light above it and adding it back before tone mapping",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("bloom_radius")
+                        .long("bloom-radius")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("8")
+                        .help("how many pixels the glow of --bloom-threshold spreads out"),
+                )
+                .arg(Arg::with_name("spectral").long("spectral").help(
+                    "trace each ray at a single wavelength so dispersive glass splits light \
+                             into colours",
+                ))
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help("the seed for the random numbers used by the renderer"),
+                )
+                .arg(
+                    Arg::with_name("pass")
+                        .long("pass")
+                        .takes_value(true)
+                        .required(false)
+                        .multiple(true)
+                        .possible_values(&Pass::variants())
+                        .case_insensitive(true)
+                        .help(
+                            "auxiliary passes to render. Each pass is written to the output \
+                             path with the name of the pass appended",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("depth_near")
+                        .long("depth-near")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help(
+                            "the distance that is black in a depth pass written to an image that \
+                             is not an exr, which keeps the raw distances",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("depth_far")
+                        .long("depth-far")
+                        .takes_value(true)
+                        .required(false)
+                        .help(
+                            "the distance that is white in a depth pass written to an image that \
+                             is not an exr, along with anything further and rays that miss. \
+                             Defaults to the furthest surface in the image",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("denoiser_buffers")
+                        .long("denoiser-buffers")
+                        .conflicts_with("frames")
+                        .help(
+                            "also write the linear beauty, albedo and normal buffers external \
+                             denoisers such as Open Image Denoise take, as pfm files next to the \
+                             output with the name of the buffer appended",
+                        ),
+                )
                 .arg(
                     Arg::with_name("asset")
                         .short("a")
@@ -150,32 +547,168 @@ pub fn get_cli_config() -> Result<CliConfig, anyhow::Error> {
                         .help("the name of the scene to generate"),
                 ),
         ])
-        .get_matches();
+        .get_matches_from(args);
 
     let config_path = String::from(matches.value_of("config").unwrap());
 
     validate_config_path(&config_path)?;
 
     if let Some(subcommand) = matches.subcommand_matches("render") {
-        let width = parse::<u32>(subcommand, "width")?;
+        // Scenes can save their own render settings, so note which ones were actually given
+        let given_settings = RenderSettings {
+            width: parse_given::<u32>(subcommand, "width")?,
+            rays: parse_given::<u64>(subcommand, "rays")?,
+            max_depth: parse_given::<u32>(subcommand, "max_depth")?,
+            roulette_depth: parse_given::<u32>(subcommand, "roulette_depth")?,
+            seed: parse_given::<u64>(subcommand, "seed")?,
+            ray_epsilon: parse_given::<Float>(subcommand, "ray_epsilon")?,
+        };
         let output_path = String::from(subcommand.value_of("output_path").unwrap());
         let num_of_rays = parse::<u64>(subcommand, "rays")?;
         let num_of_threads = parse::<usize>(subcommand, "threads")?;
+        let max_depth = parse::<u32>(subcommand, "max_depth")?;
+        let roulette_depth = parse::<u32>(subcommand, "roulette_depth")?;
+        let exposure = parse::<Float>(subcommand, "exposure")?;
+        if !exposure.is_finite() {
+            return Err(CliParsingError::InvalidValue {
+                arg: String::from("exposure"),
+                value: exposure.to_string(),
+            }
+            .into());
+        }
+        let white_balance = if subcommand.is_present("white_balance") {
+            let temperature = parse::<Float>(subcommand, "white_balance")?;
+            if !(MIN_WHITE_BALANCE..=MAX_WHITE_BALANCE).contains(&temperature) {
+                return Err(CliParsingError::InvalidValue {
+                    arg: String::from("white_balance"),
+                    value: temperature.to_string(),
+                }
+                .into());
+            }
+            Some(temperature)
+        } else {
+            None
+        };
+        let tone_mapping = parse::<ToneMapping>(subcommand, "tone_mapping")?;
+        let transfer_function = parse::<TransferFunction>(subcommand, "transfer_function")?;
+        let light_sampling = parse::<LightSampling>(subcommand, "light_sampling")?;
+        let pixel_filter = parse::<PixelFilter>(subcommand, "pixel_filter")?;
+        let seed = parse::<u64>(subcommand, "seed")?;
+        let ray_epsilon = parse::<Float>(subcommand, "ray_epsilon")?;
+        if !(ray_epsilon >= 0.0 && ray_epsilon.is_finite()) {
+            return Err(CliParsingError::InvalidValue {
+                arg: String::from("ray_epsilon"),
+                value: ray_epsilon.to_string(),
+            }
+            .into());
+        }
+        let max_sample_luminance = if subcommand.is_present("clamp") {
+            let clamp = parse::<Float>(subcommand, "clamp")?;
+            if !(clamp > 0.0 && clamp.is_finite()) {
+                return Err(CliParsingError::InvalidValue {
+                    arg: String::from("clamp"),
+                    value: clamp.to_string(),
+                }
+                .into());
+            }
+            Some(clamp)
+        } else {
+            None
+        };
+        let bloom = if subcommand.is_present("bloom_threshold") {
+            Some(parse_bloom(subcommand)?)
+        } else {
+            None
+        };
+        let passes = subcommand
+            .values_of("pass")
+            .unwrap_or_default()
+            .map(|pass| {
+                pass.parse::<Pass>()
+                    .map_err(|_| CliParsingError::InvalidValue {
+                        arg: String::from("pass"),
+                        value: String::from(pass),
+                    })
+            })
+            .collect::<Result<Vec<Pass>, CliParsingError>>()?;
+        let depth_range = parse_depth_range(subcommand)?;
+        let ambient = if subcommand.is_present("preview_ambient") {
+            let ambient = parse::<Float>(subcommand, "preview_ambient")?;
+            if !(ambient >= 0.0 && ambient.is_finite()) {
+                return Err(CliParsingError::InvalidValue {
+                    arg: String::from("preview_ambient"),
+                    value: ambient.to_string(),
+                }
+                .into());
+            }
+            Some(ambient)
+        } else {
+            None
+        };
+        let adaptive_sampling = if subcommand.is_present("adaptive") {
+            Some(parse_adaptive_sampling(subcommand)?)
+        } else {
+            None
+        };
         let asset_paths: Vec<ImagePath> = subcommand
             .values_of("asset")
             .unwrap_or_default()
             .map(|path| ImagePath(String::from(path)))
             .collect();
 
-        validate_output_path(&output_path)?;
+        let animation = if subcommand.is_present("frames") {
+            Some(parse_animation(subcommand, &output_path)?)
+        } else {
+            None
+        };
+
+        let output_format = validate_output_path(&output_path)?;
+        let checkpoint_path = |name| {
+            subcommand
+                .value_of(name)
+                .map(|path| CheckpointPath(String::from(path)))
+        };
+        let checkpoint_paths = CheckpointPaths {
+            save: checkpoint_path("checkpoint"),
+            resume: checkpoint_path("resume"),
+        };
 
         return Ok(CliConfig {
             command: CliCommand::RENDER {
-                width,
+                options: Box::from(RenderOptions {
+                    // Filled in from the saved settings once the config is loaded
+                    width: given_settings.width.unwrap_or(0),
+                    num_of_rays,
+                    max_depth,
+                    roulette_depth,
+                    exposure,
+                    white_balance,
+                    tone_mapping,
+                    transfer_function,
+                    light_sampling,
+                    pixel_filter,
+                    adaptive_sampling,
+                    seed,
+                    passes,
+                    depth_range,
+                    denoiser_buffers: subcommand.is_present("denoiser_buffers"),
+                    spectral: subcommand.is_present("spectral"),
+                    max_sample_luminance,
+                    ray_epsilon,
+                    bloom,
+                    progressive: subcommand.is_present("progressive")
+                        || checkpoint_paths.save.is_some()
+                        || checkpoint_paths.resume.is_some(),
+                    preview: subcommand.is_present("preview"),
+                    ambient,
+                }),
+                given_settings: Box::from(given_settings),
                 output_path: OutputPath(output_path),
-                num_of_rays,
+                output_format,
                 num_of_threads,
                 asset_paths,
+                checkpoint_paths,
+                animation,
             },
             config_path: ConfigPath(config_path),
         });
@@ -193,6 +726,92 @@ pub fn get_cli_config() -> Result<CliConfig, anyhow::Error> {
     panic!("Unable to parse CLI args")
 }
 
+fn parse_adaptive_sampling(matches: &ArgMatches) -> Result<AdaptiveSampling, CliParsingError> {
+    let min_samples = parse::<u64>(matches, "min_samples")?;
+    let max_samples = parse::<u64>(matches, "max_samples")?;
+    let tolerance = parse::<Float>(matches, "tolerance")?;
+
+    // The variance of a pixel cannot be estimated from a single sample
+    if min_samples < 2 || min_samples > max_samples {
+        return Err(CliParsingError::InvalidSampleRange {
+            min_samples,
+            max_samples,
+        });
+    }
+
+    Ok(AdaptiveSampling {
+        min_samples,
+        max_samples,
+        tolerance,
+    })
+}
+
+fn parse_bloom(matches: &ArgMatches) -> Result<Bloom, CliParsingError> {
+    let threshold = parse::<Float>(matches, "bloom_threshold")?;
+    if !(threshold >= 0.0 && threshold.is_finite()) {
+        return Err(CliParsingError::InvalidValue {
+            arg: String::from("bloom_threshold"),
+            value: threshold.to_string(),
+        });
+    }
+    let radius = parse::<Float>(matches, "bloom_radius")?;
+    if !(radius > 0.0 && radius.is_finite()) {
+        return Err(CliParsingError::InvalidValue {
+            arg: String::from("bloom_radius"),
+            value: radius.to_string(),
+        });
+    }
+
+    Ok(Bloom { threshold, radius })
+}
+
+fn parse_depth_range(matches: &ArgMatches) -> Result<DepthRange, CliParsingError> {
+    let near = parse::<Float>(matches, "depth_near")?;
+    if !(near >= 0.0 && near.is_finite()) {
+        return Err(CliParsingError::InvalidValue {
+            arg: String::from("depth_near"),
+            value: near.to_string(),
+        });
+    }
+    let far = if matches.is_present("depth_far") {
+        let far = parse::<Float>(matches, "depth_far")?;
+        if !(far > near && far.is_finite()) {
+            return Err(CliParsingError::InvalidDepthRange { near, far });
+        }
+        Some(far)
+    } else {
+        None
+    };
+
+    Ok(DepthRange { near, far })
+}
+
+fn parse_animation(
+    matches: &ArgMatches,
+    output_path: &str,
+) -> Result<AnimationSettings, CliParsingError> {
+    let frames = parse::<u32>(matches, "frames")?;
+    if frames == 0 {
+        return Err(CliParsingError::InvalidValue {
+            arg: String::from("frames"),
+            value: frames.to_string(),
+        });
+    }
+    let fps = parse::<Float>(matches, "fps")?;
+    if !(fps > 0.0 && fps.is_finite()) {
+        return Err(CliParsingError::InvalidValue {
+            arg: String::from("fps"),
+            value: fps.to_string(),
+        });
+    }
+    // Otherwise every frame would overwrite the one before
+    if !output_path.contains(FRAME_NUMBER_PLACEHOLDER) {
+        return Err(CliParsingError::MissingFrameNumber(output_path.to_string()));
+    }
+
+    Ok(AnimationSettings { frames, fps })
+}
+
 fn validate_config_path(config_path: &str) -> Result<(), CliParsingError> {
     if !config_path.ends_with(".yaml") {
         return Err(CliParsingError::InvalidConfigPath(config_path.to_string()));
@@ -200,20 +819,18 @@ fn validate_config_path(config_path: &str) -> Result<(), CliParsingError> {
     Ok(())
 }
 
-fn validate_output_path(output_path: &str) -> Result<(), CliParsingError> {
-    if !SUPPORTED_IMAGE_EXT
+fn validate_output_path(output_path: &str) -> Result<OutputFormat, CliParsingError> {
+    let format = SUPPORTED_IMAGE_EXT
         .iter()
-        .any(|ext| output_path.ends_with(ext))
-    {
-        return Err(CliParsingError::InvalidOutputPath {
-            output_path: output_path.to_string(),
-            supported_extensions: SUPPORTED_IMAGE_EXT
-                .iter()
-                .map(|ext| (*ext).to_string())
-                .collect(),
-        });
-    }
-    Ok(())
+        .find(|ext| output_path.ends_with(*ext))
+        .and_then(|_| OutputFormat::from_path(output_path));
+    format.ok_or_else(|| CliParsingError::InvalidOutputPath {
+        output_path: output_path.to_string(),
+        supported_extensions: SUPPORTED_IMAGE_EXT
+            .iter()
+            .map(|ext| (*ext).to_string())
+            .collect(),
+    })
 }
 
 fn parse<T: FromStr>(matches: &ArgMatches, arg: &str) -> Result<T, CliParsingError> {
@@ -226,3 +843,41 @@ fn parse<T: FromStr>(matches: &ArgMatches, arg: &str) -> Result<T, CliParsingErr
         }),
     }
 }
+
+/// Like `parse` but `None` when the arg was left to its default
+fn parse_given<T: FromStr>(matches: &ArgMatches, arg: &str) -> Result<Option<T>, CliParsingError> {
+    if matches.occurrences_of(arg) == 0 {
+        return Ok(None);
+    }
+    parse(matches, arg).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_with_clamp(clamp: &str) -> Result<CliConfig, anyhow::Error> {
+        get_cli_config_from(vec![
+            "rayt",
+            "-c",
+            "scene.yaml",
+            "render",
+            "-o",
+            "out.png",
+            &format!("--clamp={}", clamp),
+        ])
+    }
+
+    #[test]
+    fn test_clamp_is_parsed() {
+        assert!(render_with_clamp("10.0").is_ok());
+    }
+
+    #[test]
+    fn test_clamp_rejects_values_that_are_not_positive_and_finite() {
+        for clamp in &["0.0", "-1.0", "NaN", "inf"] {
+            let error = render_with_clamp(clamp).err().unwrap();
+            assert!(error.to_string().contains("for arg <clamp>"), "{}", error);
+        }
+    }
+}
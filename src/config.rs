@@ -1,31 +1,195 @@
+use crate::animation::{Animation, AnimationSettings};
+use crate::bloom::Bloom;
 use crate::camera::{Camera, CameraSave};
 use crate::data::assets::Assets;
+use crate::float::Float;
+use crate::light_sampling::LightSampling;
+use crate::passes::{DepthRange, Pass};
+use crate::pdf::{EnvironmentDistribution, LightTree};
+use crate::pixel_filter::PixelFilter;
+use crate::tone_mapping::{ToneMapping, TransferFunction};
 use crate::world::background::Background;
 use crate::world::geometry::bounding_volume_hierarchy::BoundingVolumeHierarchyNode;
 use crate::world::geometry::{Geometry, Hittable};
 use crate::world::WorldSave;
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Settings for a render that are chosen on the command line instead of saved with the scene
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub width: u32,
+    pub num_of_rays: u64,
+    // Paths that bounce this many times stop collecting any more light
+    pub max_depth: u32,
+    // Paths are randomly terminated with russian roulette once they bounce this many times
+    pub roulette_depth: u32,
+    // Stops the linear image is brightened by before tone mapping, negative darkens it
+    pub exposure: Float,
+    // The colour temperature in Kelvin of light that is balanced to look white
+    pub white_balance: Option<Float>,
+    pub tone_mapping: ToneMapping,
+    // Encodes the tone mapped image for display
+    pub transfer_function: TransferFunction,
+    pub light_sampling: LightSampling,
+    // Weighs the samples of each pixel by where they land
+    pub pixel_filter: PixelFilter,
+    // When set the number of rays per pixel adapts to how noisy the pixel is
+    pub adaptive_sampling: Option<AdaptiveSampling>,
+    // Renders with the same seed produce identical images
+    pub seed: u64,
+    // Auxiliary images rendered and written next to the main image
+    pub passes: Vec<Pass>,
+    // Where the depth pass starts and ends when normalised for an image that is not high dynamic
+    // range
+    pub depth_range: DepthRange,
+    // Writes the linear buffers external denoisers take next to the image
+    pub denoiser_buffers: bool,
+    // Traces each sample at a single wavelength so dispersive materials split light into colours
+    pub spectral: bool,
+    // Traces one ray per pixel at a time and saves the image after every pass
+    pub progressive: bool,
+    // Samples brighter than this luminance are scaled down to it, removing fireflies at the cost
+    // of darkening the brightest highlights
+    pub max_sample_luminance: Option<Float>,
+    // Hits closer than this to the start of a ray are ignored so scattered rays do not hit the
+    // surface they left because of rounding. Too small gives shadow acne and too large lets
+    // light leak through thin geometry and gaps
+    pub ray_epsilon: Float,
+    // When set bright parts of the image glow before it is tone mapped
+    pub bloom: Option<Bloom>,
+    // Renders a quick rough image with one ray per pixel and few bounces, overriding any other
+    // rays or max depth
+    pub preview: bool,
+    // The grey light paths cut off at the max depth see, standing in for the bounces that were
+    // not traced. Without it they see black
+    pub ambient: Option<Float>,
+}
+
+// Enough bounces in a preview to see what a mirror reflects
+const PREVIEW_MAX_DEPTH: u32 = 2;
+
+/// Render settings that can be saved with a scene. Any of them given on the command line take
+/// precedence over the saved ones
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RenderSettings {
+    pub width: Option<u32>,
+    pub rays: Option<u64>,
+    pub max_depth: Option<u32>,
+    pub roulette_depth: Option<u32>,
+    pub seed: Option<u64>,
+    pub ray_epsilon: Option<Float>,
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("no width was given with --width or in the render settings of the config")]
+    MissingWidth(),
+    #[error("the saved ray epsilon <{ray_epsilon}> must be finite and not negative")]
+    InvalidRayEpsilon { ray_epsilon: Float },
+}
+
+impl RenderOptions {
+    /// Takes every setting that was not `given` on the command line from those `saved` with the
+    /// scene, keeping the command line defaults for any that are in neither. A preview overrides
+    /// them all
+    pub fn with_saved_settings(
+        self,
+        given: &RenderSettings,
+        saved: &RenderSettings,
+    ) -> Result<RenderOptions, ConfigError> {
+        let width = given
+            .width
+            .or(saved.width)
+            .ok_or(ConfigError::MissingWidth())?;
+        // The command line checks its own, but a saved one is only checked here
+        let ray_epsilon = given
+            .ray_epsilon
+            .or(saved.ray_epsilon)
+            .unwrap_or(self.ray_epsilon);
+        if !(ray_epsilon >= 0.0 && ray_epsilon.is_finite()) {
+            return Err(ConfigError::InvalidRayEpsilon { ray_epsilon });
+        }
+        Ok(RenderOptions {
+            width,
+            num_of_rays: given.rays.or(saved.rays).unwrap_or(self.num_of_rays),
+            max_depth: given
+                .max_depth
+                .or(saved.max_depth)
+                .unwrap_or(self.max_depth),
+            roulette_depth: given
+                .roulette_depth
+                .or(saved.roulette_depth)
+                .unwrap_or(self.roulette_depth),
+            seed: given.seed.or(saved.seed).unwrap_or(self.seed),
+            ray_epsilon,
+            ..self
+        }
+        .with_preview())
+    }
+
+    fn with_preview(self) -> RenderOptions {
+        if !self.preview {
+            return self;
+        }
+        RenderOptions {
+            num_of_rays: 1,
+            max_depth: self.max_depth.min(PREVIEW_MAX_DEPTH),
+            adaptive_sampling: None,
+            ..self
+        }
+    }
+}
+
+/// Pixels are sampled in batches of `min_samples` until the 95% confidence interval of their
+/// luminance is within `tolerance` of the mean, or `max_samples` rays have been traced
+#[derive(Debug, Clone)]
+pub struct AdaptiveSampling {
+    pub min_samples: u64,
+    pub max_samples: u64,
+    pub tolerance: Float,
+}
 
 pub struct Config {
-    width: u32,
+    options: RenderOptions,
     height: u32,
     camera: Camera,
     background: Background,
-    bvh: Geometry,
+    // A BVH of all the bounded geometries followed by those without a bounding box
+    geometries: Vec<Geometry>,
+    // Geometries that rays are sent towards because they give off light, those outside of any
+    // light group
+    lights: LightTree,
+    // The lights that reach the geometries in each light group, those in it and those outside of
+    // any. Only groups with lights of their own are listed
+    linked_lights: HashMap<u32, LightTree>,
+    // Geometries that rays are sent towards because they bend light that would be hard to find
+    // by following materials, such as glass
     attractors: Vec<Geometry>,
-    num_of_rays: u64,
+    // Sends rays towards the bright parts of an environment map when the lights are sampled
+    environment_light: Option<EnvironmentDistribution>,
     assets: Assets,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ConfigSave {
-    aspect: f64,
+    aspect: Float,
     camera: CameraSave,
     world: WorldSave,
+    // Paths of the image assets the scene uses, relative to the config file
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    assets: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    render: Option<RenderSettings>,
+    // How the scene moves when rendered as an animation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    animation: Option<Animation>,
 }
 
 impl Config {
     pub fn width(&self) -> u32 {
-        self.width
+        self.options.width
     }
 
     pub fn height(&self) -> u32 {
@@ -37,66 +201,224 @@ impl Config {
     }
 
     pub fn num_of_rays(&self) -> u64 {
-        self.num_of_rays
+        self.options.num_of_rays
+    }
+
+    pub fn max_depth(&self) -> u32 {
+        self.options.max_depth
+    }
+
+    pub fn ambient(&self) -> Option<Float> {
+        self.options.ambient
+    }
+
+    pub fn roulette_depth(&self) -> u32 {
+        self.options.roulette_depth
+    }
+
+    pub fn adaptive_sampling(&self) -> Option<&AdaptiveSampling> {
+        self.options.adaptive_sampling.as_ref()
+    }
+
+    pub fn passes(&self) -> &[Pass] {
+        &self.options.passes
+    }
+
+    pub fn denoiser_buffers(&self) -> bool {
+        self.options.denoiser_buffers
+    }
+
+    pub fn depth_range(&self) -> &DepthRange {
+        &self.options.depth_range
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.options.seed
+    }
+
+    pub fn max_sample_luminance(&self) -> Option<Float> {
+        self.options.max_sample_luminance
+    }
+
+    pub fn bloom(&self) -> Option<&Bloom> {
+        self.options.bloom.as_ref()
+    }
+
+    pub fn ray_epsilon(&self) -> Float {
+        self.options.ray_epsilon
+    }
+
+    pub fn spectral(&self) -> bool {
+        self.options.spectral
+    }
+
+    pub fn exposure(&self) -> Float {
+        self.options.exposure
+    }
+
+    pub fn white_balance(&self) -> Option<Float> {
+        self.options.white_balance
+    }
+
+    pub fn tone_mapping(&self) -> ToneMapping {
+        self.options.tone_mapping
+    }
+
+    pub fn transfer_function(&self) -> TransferFunction {
+        self.options.transfer_function
     }
 
     pub fn background(&self) -> &Background {
         &self.background
     }
 
-    pub fn bvh(&self) -> &Geometry {
-        &self.bvh
+    pub fn geometries(&self) -> &Vec<Geometry> {
+        &self.geometries
+    }
+
+    /// The lights that reach `light_group`, when lights are linked to geometries in the same
+    /// group
+    pub fn lights(&self, light_group: Option<u32>) -> &LightTree {
+        light_group
+            .and_then(|group| self.linked_lights.get(&group))
+            .unwrap_or(&self.lights)
+    }
+
+    pub fn light_sampling(&self) -> LightSampling {
+        self.options.light_sampling
+    }
+
+    pub fn pixel_filter(&self) -> PixelFilter {
+        self.options.pixel_filter
     }
 
     pub fn attractors(&self) -> &Vec<Geometry> {
         &self.attractors
     }
 
+    pub fn environment_light(&self) -> Option<&EnvironmentDistribution> {
+        self.environment_light.as_ref()
+    }
+
     pub fn assets(&self) -> &Assets {
         &self.assets
     }
 }
 
 impl ConfigSave {
-    pub fn new(aspect: f64, camera: CameraSave, world: WorldSave) -> ConfigSave {
+    pub fn new(aspect: Float, camera: CameraSave, world: WorldSave) -> ConfigSave {
         ConfigSave {
             aspect,
             camera,
             world,
+            assets: vec![],
+            render: None,
+            animation: None,
+        }
+    }
+
+    pub fn asset_paths(&self) -> &[String] {
+        &self.assets
+    }
+
+    pub fn render_settings(&self) -> RenderSettings {
+        self.render.clone().unwrap_or_default()
+    }
+
+    /// The scene at `frame` of its animation, with the camera where its keyframes put it and
+    /// the shutter open from the start of the frame for the animation's part of it
+    pub fn frame(&self, settings: &AnimationSettings, frame: u32) -> ConfigSave {
+        let animation = self.animation.clone().unwrap_or_default();
+        let time = settings.frame_time(frame);
+        let camera = match animation.camera_at(time) {
+            Some((look_from, look_at)) => self.camera.clone().with_view(look_from, look_at),
+            None => self.camera.clone(),
+        };
+        ConfigSave {
+            camera: camera.with_shutter(time, time + animation.shutter() / settings.fps),
+            ..self.clone()
         }
     }
 
-    pub fn into_config(mut self, width: u32, num_of_rays: u64, assets: Assets) -> Config {
+    pub fn into_config(mut self, options: RenderOptions, assets: Assets) -> Config {
         let camera = self.camera.into_camera();
 
-        let time_start = camera.time_start();
-        let time_end = camera.time_end();
+        let time_start = camera.shutter_open();
+        let time_end = camera.shutter_close();
 
         let geometries = self.world.drain_geometries();
 
-        let attractors = geometries
+        let (lights, attractors): (Vec<Geometry>, Vec<Geometry>) = geometries
             .iter()
             .filter(|g| g.is_attractor())
             .cloned()
+            .partition(|g| g.emits_light());
+        let (lights, linked): (Vec<Geometry>, Vec<Geometry>) =
+            lights.into_iter().partition(|g| g.light_group().is_none());
+        let mut linked_lights: HashMap<u32, Vec<Geometry>> = HashMap::new();
+        for light in linked {
+            let group = light.light_group().expect("partitioned by light group");
+            linked_lights
+                .entry(group)
+                .or_insert_with(|| lights.clone())
+                .push(light);
+        }
+        let lights_tree =
+            |lights: Vec<Geometry>| LightTree::build(lights, time_start, time_end, &assets);
+        let linked_lights = linked_lights
+            .into_iter()
+            .map(|(group, lights)| (group, lights_tree(lights)))
             .collect();
+        let lights = lights_tree(lights);
 
-        let bvh = BoundingVolumeHierarchyNode::build(geometries, time_start, time_end);
+        // Infinite geometries cannot be placed in the BVH so they are always tested separately
+        let (bounded, unbounded): (Vec<Geometry>, Vec<Geometry>) = geometries
+            .into_iter()
+            .partition(|g| g.bounding_box(time_start, time_end).is_some());
+
+        let mut geometries = Vec::with_capacity(unbounded.len() + 1);
+        if !bounded.is_empty() {
+            geometries.push(BoundingVolumeHierarchyNode::build(
+                bounded, time_start, time_end,
+            ));
+        }
+        geometries.extend(unbounded);
+
+        let background = self.world.background().clone();
+        let environment_light = if options.light_sampling.samples_lights() {
+            background.distribution(&assets)
+        } else {
+            None
+        };
 
         Config {
-            width,
-            height: (f64::from(width) / self.aspect) as u32,
+            height: (options.width as Float / self.aspect) as u32,
+            options,
             camera,
-            background: self.world.background().clone(),
-            bvh,
+            background,
+            geometries,
+            lights,
+            linked_lights,
             attractors,
-            num_of_rays,
+            environment_light,
             assets,
         }
     }
 
     pub fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.camera.validate()?;
+        if let Some(animation) = &self.animation {
+            animation.validate()?;
+            for (look_from, look_at) in animation.camera_keyframes() {
+                self.camera.validate_view(look_from, look_at)?;
+            }
+        }
         self.world.validate(assets)
     }
+
+    pub fn is_unlit(&self) -> bool {
+        self.world.is_unlit()
+    }
 }
 
 #[cfg(test)]
@@ -106,10 +428,38 @@ mod tests {
     use crate::data::colour::Colour;
     use crate::data::vector::Vector;
     use crate::world::background::Background;
+    use crate::world::geometry::plane::Plane;
     use crate::world::geometry::sphere::Sphere;
     use crate::world::materials::Material;
     use crate::world::texture::Texture;
 
+    fn options() -> RenderOptions {
+        RenderOptions {
+            width: 10,
+            num_of_rays: 1,
+            max_depth: 50,
+            roulette_depth: 5,
+            exposure: 0.0,
+            tone_mapping: ToneMapping::None,
+            light_sampling: LightSampling::Mis,
+            adaptive_sampling: None,
+            seed: 0,
+            passes: vec![],
+            depth_range: DepthRange::default(),
+            denoiser_buffers: false,
+            spectral: false,
+            progressive: false,
+            max_sample_luminance: None,
+            ray_epsilon: 0.001,
+            bloom: None,
+            transfer_function: TransferFunction::default(),
+            pixel_filter: PixelFilter::Box,
+            preview: false,
+            ambient: None,
+            white_balance: None,
+        }
+    }
+
     #[test]
     fn test_serialise_roundtrip_camera() {
         let camera = CameraSave::new(
@@ -164,6 +514,8 @@ mod tests {
                     -0.45,
                     Material::Dielectric {
                         refractive_index: 1.5,
+                        absorption: None,
+                        roughness: 0.0,
                     },
                 ),
             ],
@@ -205,9 +557,163 @@ mod tests {
             aspect: 1.5,
             camera,
             world,
+            assets: vec![String::from("../assets/earth.jpg")],
+            render: Some(RenderSettings {
+                width: Some(400),
+                ..RenderSettings::default()
+            }),
+            animation: None,
         };
 
         let serialised = serde_yaml::to_string(&saved_config).unwrap();
         serde_yaml::from_str::<ConfigSave>(&serialised).unwrap();
     }
+
+    #[test]
+    fn test_into_config_keeps_unbounded_geometries_out_of_the_bvh() {
+        let camera = CameraSave::new(
+            &Vector::new(0.0, 0.0, 0.0),
+            &Vector::new(0.0, 0.0, -1.0),
+            &Vector::new(0.0, 1.0, 0.0),
+            1.0,
+            Lens::new(90.0, 0.0, 1.0),
+            0.0,
+            0.0,
+        );
+        let material = Material::Metal {
+            albedo: Colour::new(0.8, 0.6, 0.2),
+            fuzz: 0.1,
+        };
+        let world = WorldSave::new(
+            Background::new(Colour::new(1.0, 1.0, 1.0), Colour::new(0.5, 0.0, 0.0)),
+            vec![
+                Plane::build(
+                    Vector::new(0.0, -1.0, 0.0),
+                    Vector::new(0.0, 1.0, 0.0),
+                    material.clone(),
+                ),
+                Sphere::build(Vector::new(0.0, 0.0, -1.0), 0.5, material.clone()),
+                Sphere::build(Vector::new(1.0, 0.0, -1.0), 0.5, material),
+            ],
+        );
+
+        let config =
+            ConfigSave::new(1.0, camera, world).into_config(options(), Assets::new(&[]).unwrap());
+
+        let geometries = config.geometries();
+        assert_eq!(geometries.len(), 2);
+        assert!(matches!(geometries[0], Geometry::Bvh(_)));
+        assert!(matches!(geometries[1], Geometry::Plane(_)));
+    }
+
+    #[test]
+    fn test_with_saved_settings() {
+        let saved: RenderSettings = serde_yaml::from_str("width: 300\nrays: 64\n").unwrap();
+        let given = RenderSettings {
+            rays: Some(8),
+            ..RenderSettings::default()
+        };
+
+        let options = options().with_saved_settings(&given, &saved).unwrap();
+        assert_eq!(options.width, 300);
+        assert_eq!(options.num_of_rays, 8);
+        assert_eq!(options.max_depth, 50);
+
+        let none = RenderSettings::default();
+        assert!(matches!(
+            options.with_saved_settings(&none, &none),
+            Err(ConfigError::MissingWidth())
+        ));
+
+        // A misspelt setting is an error rather than silently ignored
+        assert!(serde_yaml::from_str::<RenderSettings>("widht: 300").is_err());
+    }
+
+    #[test]
+    fn test_saved_ray_epsilon_is_checked() {
+        let none = RenderSettings::default();
+        let negative: RenderSettings =
+            serde_yaml::from_str("width: 300\nray_epsilon: -0.001\n").unwrap();
+        assert!(matches!(
+            options().with_saved_settings(&none, &negative),
+            Err(ConfigError::InvalidRayEpsilon { .. })
+        ));
+
+        let saved: RenderSettings =
+            serde_yaml::from_str("width: 300\nray_epsilon: 0.01\n").unwrap();
+        let options = options().with_saved_settings(&none, &saved).unwrap();
+        assert_eq!(options.ray_epsilon, 0.01);
+    }
+
+    #[test]
+    fn test_preview_overrides_rays_and_max_depth() {
+        let saved: RenderSettings = serde_yaml::from_str("width: 300\nrays: 64\n").unwrap();
+        let preview = RenderOptions {
+            preview: true,
+            ..options()
+        };
+
+        let options = preview
+            .with_saved_settings(&RenderSettings::default(), &saved)
+            .unwrap();
+        assert_eq!(options.width, 300);
+        assert_eq!(options.num_of_rays, 1);
+        assert_eq!(options.max_depth, PREVIEW_MAX_DEPTH);
+
+        // Shallower renders are kept shallow
+        let given = RenderSettings {
+            max_depth: Some(1),
+            ..RenderSettings::default()
+        };
+        let options = options.with_saved_settings(&given, &saved).unwrap();
+        assert_eq!(options.max_depth, 1);
+    }
+
+    #[test]
+    fn test_frame_moves_the_camera_and_opens_the_shutter() {
+        let camera = CameraSave::new(
+            &Vector::new(0.0, 0.0, 5.0),
+            &Vector::new(0.0, 0.0, 0.0),
+            &Vector::new(0.0, 1.0, 0.0),
+            1.0,
+            Lens::new(90.0, 0.0, 1.0),
+            0.0,
+            0.0,
+        );
+        let world = WorldSave::new(Background::default(), vec![]);
+        let animation = "
+camera:
+  - time: 0
+    look_from: [0, 0, 5]
+    look_at: [0, 0, 0]
+  - time: 2
+    look_from: [4, 0, 5]
+    look_at: [0, 0, 0]
+shutter: 0.5
+";
+        let saved_config = ConfigSave {
+            animation: Some(serde_yaml::from_str(animation).unwrap()),
+            ..ConfigSave::new(1.0, camera, world)
+        };
+        let settings = AnimationSettings {
+            frames: 48,
+            fps: 24.0,
+        };
+
+        // With only two keyframes the spline is a straight line, though not at an even speed
+        let frame = saved_config.frame(&settings, 24).camera.into_camera();
+        assert_eq!(frame.background_ray().origin().z(), 5.0);
+        assert!(frame.background_ray().origin().x() > 0.0);
+        assert_eq!(frame.shutter_open(), 1.0);
+        assert_eq!(frame.shutter_close(), 1.0 + 0.5 / 24.0);
+
+        // A scene without an animation holds still but still blurs over each frame
+        let still = ConfigSave {
+            animation: None,
+            ..saved_config
+        };
+        let frame = still.frame(&settings, 12).camera.into_camera();
+        assert_eq!(frame.background_ray().origin().x(), 0.0);
+        assert_eq!(frame.shutter_open(), 0.5);
+    }
 }
@@ -10,6 +10,12 @@ pub enum AssetValidationError {
     MissingAsset { asset_name: String },
 }
 
+// Appended to the name of an image with an alpha channel to get the alpha as its own asset
+const ALPHA_SUFFIX: &str = ":alpha";
+
+/// The images loaded for textures, by file name. Images with an alpha channel also have it as a
+/// grey image named with `ALPHA_SUFFIX` appended, such as `leaf.png:alpha`, for cutouts
+#[derive(Default, Clone)]
 pub struct Assets {
     assets: HashMap<String, Image>,
 }
@@ -18,10 +24,12 @@ impl Assets {
     pub fn new(asset_paths: &[ImagePath]) -> Result<Assets, anyhow::Error> {
         let mut assets: HashMap<String, Image> = HashMap::new();
         for asset_path in asset_paths {
-            assets.insert(
-                String::from(asset_path.file_name()),
-                load_image(asset_path)?,
-            );
+            let (image, alpha) = load_image(asset_path)?;
+            let name = String::from(asset_path.file_name());
+            if let Some(alpha) = alpha {
+                assets.insert(format!("{}{}", name, ALPHA_SUFFIX), alpha);
+            }
+            assets.insert(name, image);
         }
 
         Ok(Assets { assets })
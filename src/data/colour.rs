@@ -1,40 +1,44 @@
+use crate::float::Float;
+use crate::spectrum::blackbody_colour;
 use image::Rgb;
 use std::cmp::min;
 use std::iter::Sum;
 use std::ops;
 
-const RGB_MULT: f64 = 255.99;
+const RGB_MULT: Float = 255.99;
 const RGB_MAX: u64 = 255;
+// The white point of sRGB, D65, is close to the colour of a blackbody at this temperature
+const D65_TEMPERATURE: Float = 6504.0;
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Colour {
-    r: f64,
-    g: f64,
-    b: f64,
+    r: Float,
+    g: Float,
+    b: Float,
 }
 
 impl Colour {
-    pub fn new(r: f64, g: f64, b: f64) -> Colour {
+    pub fn new(r: Float, g: Float, b: Float) -> Colour {
         Colour { r, g, b }
     }
 
-    pub fn r(&self) -> f64 {
+    pub fn r(&self) -> Float {
         self.r
     }
 
-    pub fn g(&self) -> f64 {
+    pub fn g(&self) -> Float {
         self.g
     }
 
-    pub fn b(&self) -> f64 {
+    pub fn b(&self) -> Float {
         self.b
     }
 
-    pub fn len(&self) -> f64 {
+    pub fn len(&self) -> Float {
         (&self).len_squared().sqrt()
     }
 
-    pub fn len_squared(&self) -> f64 {
+    pub fn len_squared(&self) -> Float {
         self.r * self.r + self.g * self.g + self.b * self.b
     }
 
@@ -47,7 +51,7 @@ impl Colour {
         }
     }
 
-    pub fn dot(lhs: &Colour, rhs: &Colour) -> f64 {
+    pub fn dot(lhs: &Colour, rhs: &Colour) -> Float {
         lhs.r * rhs.r + lhs.g * rhs.g + lhs.b * rhs.b
     }
 
@@ -68,21 +72,63 @@ impl Colour {
         Rgb([r, g, b])
     }
 
-    pub fn gamma_2(self) -> Colour {
-        Colour {
-            r: self.r.sqrt(),
-            g: self.g.sqrt(),
-            b: self.b.sqrt(),
+    /// The perceived brightness using the Rec. 709 weights
+    pub fn luminance(&self) -> Float {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+
+    pub fn max_channel(&self) -> Float {
+        self.r.max(self.g).max(self.b)
+    }
+
+    /// What to multiply a render by so light from a blackbody at `temperature` Kelvin looks
+    /// white, such as 3200 for tungsten bulbs. Warmer light is made neutral by shifting the image
+    /// towards blue and cooler light by shifting it towards orange, keeping the brightness
+    pub fn white_balance(temperature: Float) -> Colour {
+        let white = blackbody_colour(D65_TEMPERATURE);
+        let light = blackbody_colour(temperature);
+        let balance = Colour::new(white.r / light.r, white.g / light.g, white.b / light.b);
+        balance / balance.luminance()
+    }
+}
+
+/// How the channels of a colour are encoded. Image textures painted for display, and colours
+/// copied from colour pickers, are usually sRGB and must be decoded to linear before rendering
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ColourSpace {
+    #[default]
+    Linear,
+    // Encoded with the piecewise sRGB curve
+    Srgb,
+}
+
+impl ColourSpace {
+    pub fn to_linear(self, colour: &Colour) -> Colour {
+        match self {
+            ColourSpace::Linear => *colour,
+            ColourSpace::Srgb => Colour::new(
+                srgb_to_linear(colour.r),
+                srgb_to_linear(colour.g),
+                srgb_to_linear(colour.b),
+            ),
         }
     }
 }
 
+fn srgb_to_linear(channel: Float) -> Float {
+    if channel <= 0.040_45 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
 impl From<&Rgb<u8>> for Colour {
     fn from(rgb: &Rgb<u8>) -> Self {
         Colour {
-            r: f64::from(rgb[0]) / RGB_MULT,
-            g: f64::from(rgb[1]) / RGB_MULT,
-            b: f64::from(rgb[2]) / RGB_MULT,
+            r: Float::from(rgb[0]) / RGB_MULT,
+            g: Float::from(rgb[1]) / RGB_MULT,
+            b: Float::from(rgb[2]) / RGB_MULT,
         }
     }
 }
@@ -117,7 +163,7 @@ fn mul_colours(lhs: &Colour, rhs: &Colour) -> Colour {
     }
 }
 
-fn add_colour_and_scalar(colour: &Colour, scalar: f64) -> Colour {
+fn add_colour_and_scalar(colour: &Colour, scalar: Float) -> Colour {
     Colour {
         r: colour.r + scalar,
         g: colour.g + scalar,
@@ -125,7 +171,7 @@ fn add_colour_and_scalar(colour: &Colour, scalar: f64) -> Colour {
     }
 }
 
-fn mul_colour_and_scalar(colour: &Colour, scalar: f64) -> Colour {
+fn mul_colour_and_scalar(colour: &Colour, scalar: Float) -> Colour {
     Colour {
         r: colour.r * scalar,
         g: colour.g * scalar,
@@ -133,7 +179,7 @@ fn mul_colour_and_scalar(colour: &Colour, scalar: f64) -> Colour {
     }
 }
 
-fn div_colour_and_scalar(colour: &Colour, scalar: f64) -> Colour {
+fn div_colour_and_scalar(colour: &Colour, scalar: Float) -> Colour {
     Colour {
         r: colour.r / scalar,
         g: colour.g / scalar,
@@ -205,15 +251,15 @@ impl ops::Mul<Colour> for Colour {
     }
 }
 
-impl ops::Add<f64> for &Colour {
+impl ops::Add<Float> for &Colour {
     type Output = Colour;
 
-    fn add(self, rhs: f64) -> Colour {
+    fn add(self, rhs: Float) -> Colour {
         add_colour_and_scalar(self, rhs)
     }
 }
 
-impl ops::Add<&Colour> for f64 {
+impl ops::Add<&Colour> for Float {
     type Output = Colour;
 
     fn add(self, rhs: &Colour) -> Colour {
@@ -221,15 +267,15 @@ impl ops::Add<&Colour> for f64 {
     }
 }
 
-impl ops::Add<f64> for Colour {
+impl ops::Add<Float> for Colour {
     type Output = Colour;
 
-    fn add(self, rhs: f64) -> Colour {
+    fn add(self, rhs: Float) -> Colour {
         add_colour_and_scalar(&self, rhs)
     }
 }
 
-impl ops::Add<Colour> for f64 {
+impl ops::Add<Colour> for Float {
     type Output = Colour;
 
     fn add(self, rhs: Colour) -> Colour {
@@ -237,23 +283,23 @@ impl ops::Add<Colour> for f64 {
     }
 }
 
-impl ops::Mul<f64> for &Colour {
+impl ops::Mul<Float> for &Colour {
     type Output = Colour;
 
-    fn mul(self, rhs: f64) -> Colour {
+    fn mul(self, rhs: Float) -> Colour {
         mul_colour_and_scalar(self, rhs)
     }
 }
 
-impl ops::Mul<f64> for Colour {
+impl ops::Mul<Float> for Colour {
     type Output = Colour;
 
-    fn mul(self, rhs: f64) -> Colour {
+    fn mul(self, rhs: Float) -> Colour {
         mul_colour_and_scalar(&self, rhs)
     }
 }
 
-impl ops::Mul<&Colour> for f64 {
+impl ops::Mul<&Colour> for Float {
     type Output = Colour;
 
     fn mul(self, rhs: &Colour) -> Colour {
@@ -261,7 +307,7 @@ impl ops::Mul<&Colour> for f64 {
     }
 }
 
-impl ops::Mul<Colour> for f64 {
+impl ops::Mul<Colour> for Float {
     type Output = Colour;
 
     fn mul(self, rhs: Colour) -> Colour {
@@ -269,18 +315,18 @@ impl ops::Mul<Colour> for f64 {
     }
 }
 
-impl ops::Div<f64> for &Colour {
+impl ops::Div<Float> for &Colour {
     type Output = Colour;
 
-    fn div(self, rhs: f64) -> Colour {
+    fn div(self, rhs: Float) -> Colour {
         div_colour_and_scalar(self, rhs)
     }
 }
 
-impl ops::Div<f64> for Colour {
+impl ops::Div<Float> for Colour {
     type Output = Colour;
 
-    fn div(self, rhs: f64) -> Colour {
+    fn div(self, rhs: Float) -> Colour {
         div_colour_and_scalar(&self, rhs)
     }
 }
@@ -456,4 +502,32 @@ mod tests {
         assert_eq!(colour / scalar, expected_result);
         assert_eq!(colour / scalar, expected_result);
     }
+
+    #[test]
+    fn test_srgb_to_linear() {
+        let colour = Colour::new(0.0, 0.5, 1.0);
+        assert_eq!(ColourSpace::Linear.to_linear(&colour), colour);
+
+        let linear = ColourSpace::Srgb.to_linear(&colour);
+        assert_approx_eq!(linear.r(), 0.0);
+        assert_approx_eq!(linear.g(), 0.214, 1e-3);
+        assert_approx_eq!(linear.b(), 1.0);
+        // The linear piece near black
+        assert_approx_eq!(srgb_to_linear(0.02), 0.02 / 12.92);
+    }
+
+    #[test]
+    fn test_white_balance() {
+        let neutral = Colour::white_balance(D65_TEMPERATURE);
+        assert_approx_eq!(neutral.r(), 1.0);
+        assert_approx_eq!(neutral.g(), 1.0);
+        assert_approx_eq!(neutral.b(), 1.0);
+
+        // Balancing for warm light cools the image down without changing its brightness
+        let tungsten = Colour::white_balance(3200.0);
+        assert!(tungsten.b() > 1.0 && tungsten.r() < 1.0);
+        assert_approx_eq!(tungsten.luminance(), 1.0);
+        let shade = Colour::white_balance(9000.0);
+        assert!(shade.r() > 1.0 && shade.b() < 1.0);
+    }
 }
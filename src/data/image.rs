@@ -1,5 +1,5 @@
 use crate::data::colour::Colour;
-use image::{DynamicImage, ImageBuffer, RgbImage};
+use image::{ColorType, DynamicImage, GenericImageView, ImageBuffer, RgbImage};
 
 pub struct Pixel {
     row: u32,
@@ -33,6 +33,18 @@ impl Image {
         &self.pixels[row as usize][col as usize]
     }
 
+    pub fn map<F: Fn(&Colour) -> Colour>(&self, f: F) -> Image {
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|row| row.iter().map(&f).collect())
+                .collect(),
+        }
+    }
+
     pub fn into_rgb_image(self) -> RgbImage {
         let mut image: RgbImage = ImageBuffer::new(self.width, self.height);
 
@@ -49,6 +61,25 @@ impl Image {
 
         image
     }
+
+    /// The alpha channel of `image` as a grey image running from black where it is transparent to
+    /// white where it is opaque, if it has one
+    pub fn alpha_of(image: &DynamicImage) -> Option<Image> {
+        match image.color() {
+            ColorType::GrayA(_) | ColorType::RGBA(_) | ColorType::BGRA(_) => (),
+            _ => return None,
+        }
+        let (width, height) = image.dimensions();
+        let rgba = image.to_rgba();
+        let pixels: Vec<Pixel> = iproduct!(0..width, 0..height)
+            .map(|(x, y)| {
+                let alpha = Colour::from(&image::Rgb([rgba.get_pixel(x, y)[3]; 3]));
+                // Translate from the coordinate system expected by the image crate
+                Pixel::new(height - y - 1, x, alpha)
+            })
+            .collect();
+        Some(Image::from(&pixels))
+    }
 }
 
 impl From<&Vec<Pixel>> for Image {
@@ -148,4 +179,18 @@ mod tests {
         assert_colour_eq(image.get_pixel(1, 0), &Colour::new(0.0, 1.0, 0.0));
         assert_colour_eq(image.get_pixel(1, 1), &Colour::new(1.0, 0.0, 0.0));
     }
+
+    #[test]
+    fn test_alpha_of_image() {
+        // Opaque at the top left and transparent below it, in the image crate's coordinates
+        let mut rgba = image::RgbaImage::from_pixel(1, 2, image::Rgba([200, 100, 50, 255]));
+        rgba.put_pixel(0, 1, image::Rgba([200, 100, 50, 0]));
+
+        let alpha = Image::alpha_of(&DynamicImage::ImageRgba8(rgba)).unwrap();
+        assert_colour_eq(alpha.get_pixel(1, 0), &Colour::new(1.0, 1.0, 1.0));
+        assert_colour_eq(alpha.get_pixel(0, 0), &Colour::new(0.0, 0.0, 0.0));
+
+        let rgb = image::RgbImage::from_pixel(1, 1, image::Rgb([200, 100, 50]));
+        assert!(Image::alpha_of(&DynamicImage::ImageRgb8(rgb)).is_none());
+    }
 }
@@ -1,4 +1,7 @@
 pub mod assets;
 pub mod colour;
 pub mod image;
+pub mod quaternion;
 pub mod vector;
+#[cfg(all(feature = "simd", not(feature = "f32"), target_arch = "x86_64"))]
+pub mod vector_simd;
@@ -0,0 +1,153 @@
+use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
+use std::ops::Mul;
+
+// Above this cosine between two rotations they are blended linearly instead of along the arc
+const SLERP_LINEAR_THRESHOLD: Float = 0.9995;
+
+/// A rotation stored as a unit quaternion. Multiplying two rotations gives the rotation that
+/// applies the right hand one first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    w: Float,
+    x: Float,
+    y: Float,
+    z: Float,
+}
+
+impl Quaternion {
+    /// A rotation of `angle` degrees anticlockwise about `axis` when looking back along it
+    pub fn from_axis_angle(axis: &Vector, angle: Float) -> Quaternion {
+        let half_radians = (PI / 180.0) * angle / 2.0;
+        let axis = axis.unit_vector();
+        let sin_half = half_radians.sin();
+        Quaternion {
+            w: half_radians.cos(),
+            x: axis.x() * sin_half,
+            y: axis.y() * sin_half,
+            z: axis.z() * sin_half,
+        }
+    }
+
+    /// The opposite rotation
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion {
+            w: self.w,
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+
+    /// The rotation `t` of the way from this one to `other`, turning at an even rate the short
+    /// way round
+    pub fn slerp(&self, other: &Quaternion, t: Float) -> Quaternion {
+        let mut dot = self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z;
+        // q and -q are the same rotation, pick whichever is closer
+        let sign = if dot < 0.0 { -1.0 } else { 1.0 };
+        dot *= sign;
+
+        let (a, b) = if dot > SLERP_LINEAR_THRESHOLD {
+            // Nearly the same rotation, where the angle between them is too small to divide by
+            (1.0 - t, t)
+        } else {
+            let angle = dot.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        let b = sign * b;
+        let blended = Quaternion {
+            w: a * self.w + b * other.w,
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+        };
+        let len = (blended.w * blended.w
+            + blended.x * blended.x
+            + blended.y * blended.y
+            + blended.z * blended.z)
+            .sqrt();
+        Quaternion {
+            w: blended.w / len,
+            x: blended.x / len,
+            y: blended.y / len,
+            z: blended.z / len,
+        }
+    }
+
+    pub fn rotate_vector(&self, v: &Vector) -> Vector {
+        // Expands q * v * conjugate(q) for a pure quaternion v, which saves most of the products
+        let axis = Vector::new(self.x, self.y, self.z);
+        let t = 2.0 * Vector::cross(&axis, v);
+        v + self.w * t + Vector::cross(&axis, &t)
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, other: Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w * other.w - self.x * other.x - self.y * other.y - self.z * other.z,
+            x: self.w * other.x + self.x * other.w + self.y * other.z - self.z * other.y,
+            y: self.w * other.y - self.x * other.z + self.y * other.w + self.z * other.x,
+            z: self.w * other.z + self.x * other.y - self.y * other.x + self.z * other.w,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn assert_vectors_eq(left: &Vector, right: &Vector) {
+        assert_approx_eq!(left.x(), right.x());
+        assert_approx_eq!(left.y(), right.y());
+        assert_approx_eq!(left.z(), right.z());
+    }
+
+    #[test]
+    fn test_rotate_vector_matches_rotate_y() {
+        let v = Vector::new(1.0, 2.0, 3.0);
+        let rotation = Quaternion::from_axis_angle(&Vector::new(0.0, 2.0, 0.0), 30.0);
+        assert_vectors_eq(&rotation.rotate_vector(&v), &v.rotate_y(30.0));
+    }
+
+    #[test]
+    fn test_composing_rotations() {
+        let axis = Vector::new(1.0, 1.0, 0.0);
+        let eighth = Quaternion::from_axis_angle(&axis, 45.0);
+        let quarter = Quaternion::from_axis_angle(&axis, 90.0);
+
+        let composed = eighth * eighth;
+        assert_approx_eq!(composed.w, quarter.w);
+        assert_approx_eq!(composed.x, quarter.x);
+        assert_approx_eq!(composed.y, quarter.y);
+        assert_approx_eq!(composed.z, quarter.z);
+
+        let v = Vector::new(0.0, 0.0, 1.0);
+        assert_vectors_eq(&composed.rotate_vector(&v), &quarter.rotate_vector(&v));
+    }
+
+    #[test]
+    fn test_conjugate_undoes_rotation() {
+        let v = Vector::new(-2.0, 0.5, 4.0);
+        let rotation = Quaternion::from_axis_angle(&Vector::new(0.3, -1.0, 2.0), 123.0);
+        let back = rotation
+            .conjugate()
+            .rotate_vector(&rotation.rotate_vector(&v));
+        assert_vectors_eq(&back, &v);
+    }
+
+    #[test]
+    fn test_slerp() {
+        let v = Vector::new(1.0, 0.0, 0.0);
+        let start = Quaternion::from_axis_angle(&Vector::new(0.0, 1.0, 0.0), 0.0);
+        let end = Quaternion::from_axis_angle(&Vector::new(0.0, 1.0, 0.0), 90.0);
+        assert_vectors_eq(&start.slerp(&end, 0.5).rotate_vector(&v), &v.rotate_y(45.0));
+        assert_vectors_eq(&start.slerp(&end, 1.0).rotate_vector(&v), &v.rotate_y(90.0));
+        assert_vectors_eq(&start.slerp(&start, 0.3).rotate_vector(&v), &v);
+    }
+}
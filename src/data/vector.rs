@@ -1,36 +1,39 @@
 use crate::float;
-use std::f64::consts::PI;
+use crate::float::consts::PI;
+use crate::float::Float;
 use std::ops;
 
+// The simd backend loads x and y together so they must sit next to each other
+#[repr(C)]
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Vector {
-    x: f64,
-    y: f64,
-    z: f64,
+    x: Float,
+    y: Float,
+    z: Float,
 }
 
 impl Vector {
-    pub fn new(x: f64, y: f64, z: f64) -> Vector {
+    pub fn new(x: Float, y: Float, z: Float) -> Vector {
         Vector { x, y, z }
     }
 
-    pub fn x(&self) -> f64 {
+    pub fn x(&self) -> Float {
         self.x
     }
 
-    pub fn y(&self) -> f64 {
+    pub fn y(&self) -> Float {
         self.y
     }
 
-    pub fn z(&self) -> f64 {
+    pub fn z(&self) -> Float {
         self.z
     }
 
-    pub fn len(&self) -> f64 {
+    pub fn len(&self) -> Float {
         (&self).len_squared().sqrt()
     }
 
-    pub fn len_squared(&self) -> f64 {
+    pub fn len_squared(&self) -> Float {
         self.x * self.x + self.y * self.y + self.z * self.z
     }
 
@@ -43,16 +46,12 @@ impl Vector {
         }
     }
 
-    pub fn dot(lhs: &Vector, rhs: &Vector) -> f64 {
-        lhs.x * rhs.x + lhs.y * rhs.y + lhs.z * rhs.z
+    pub fn dot(lhs: &Vector, rhs: &Vector) -> Float {
+        backend::dot(lhs, rhs)
     }
 
     pub fn cross(lhs: &Vector, rhs: &Vector) -> Vector {
-        Vector {
-            x: lhs.y * rhs.z - lhs.z * rhs.y,
-            y: -(lhs.x * rhs.z - lhs.z * rhs.x),
-            z: lhs.x * rhs.y - lhs.y * rhs.x,
-        }
+        backend::cross(lhs, rhs)
     }
 
     pub fn min(&self, vector: &Vector) -> Vector {
@@ -71,7 +70,19 @@ impl Vector {
         }
     }
 
-    pub fn rotate_y(&self, angle: f64) -> Vector {
+    pub fn rotate_x(&self, angle: Float) -> Vector {
+        let radians = (PI / 180.0) * angle;
+        let sin_theta = radians.sin();
+        let cos_theta = radians.cos();
+
+        Vector {
+            x: self.x,
+            y: cos_theta * self.y - sin_theta * self.z,
+            z: sin_theta * self.y + cos_theta * self.z,
+        }
+    }
+
+    pub fn rotate_y(&self, angle: Float) -> Vector {
         let radians = (PI / 180.0) * angle;
         let sin_theta = radians.sin();
         let cos_theta = radians.cos();
@@ -82,22 +93,77 @@ impl Vector {
             z: -sin_theta * self.x + cos_theta * self.z,
         }
     }
+
+    pub fn rotate_z(&self, angle: Float) -> Vector {
+        let radians = (PI / 180.0) * angle;
+        let sin_theta = radians.sin();
+        let cos_theta = radians.cos();
+
+        Vector {
+            x: cos_theta * self.x - sin_theta * self.y,
+            y: sin_theta * self.x + cos_theta * self.y,
+            z: self.z,
+        }
+    }
 }
 
-fn add_vectors(lhs: &Vector, rhs: &Vector) -> Vector {
-    Vector {
-        x: lhs.x + rhs.x,
-        y: lhs.y + rhs.y,
-        z: lhs.z + rhs.z,
+#[cfg(all(feature = "simd", not(feature = "f32"), target_arch = "x86_64"))]
+use crate::data::vector_simd as backend;
+#[cfg(not(all(feature = "simd", not(feature = "f32"), target_arch = "x86_64")))]
+use scalar as backend;
+
+/// The plain Float versions of the operations the simd feature replaces
+#[cfg_attr(
+    all(feature = "simd", not(feature = "f32"), target_arch = "x86_64"),
+    allow(dead_code)
+)]
+pub mod scalar {
+    use super::Vector;
+    use crate::float::Float;
+
+    pub fn dot(lhs: &Vector, rhs: &Vector) -> Float {
+        lhs.x * rhs.x + lhs.y * rhs.y + lhs.z * rhs.z
+    }
+
+    pub fn cross(lhs: &Vector, rhs: &Vector) -> Vector {
+        Vector {
+            x: lhs.y * rhs.z - lhs.z * rhs.y,
+            y: -(lhs.x * rhs.z - lhs.z * rhs.x),
+            z: lhs.x * rhs.y - lhs.y * rhs.x,
+        }
+    }
+
+    pub fn add(lhs: &Vector, rhs: &Vector) -> Vector {
+        Vector {
+            x: lhs.x + rhs.x,
+            y: lhs.y + rhs.y,
+            z: lhs.z + rhs.z,
+        }
+    }
+
+    pub fn subtract(lhs: &Vector, rhs: &Vector) -> Vector {
+        Vector {
+            x: lhs.x - rhs.x,
+            y: lhs.y - rhs.y,
+            z: lhs.z - rhs.z,
+        }
+    }
+
+    pub fn scale(lhs: &Vector, rhs: Float) -> Vector {
+        Vector {
+            x: lhs.x * rhs,
+            y: lhs.y * rhs,
+            z: lhs.z * rhs,
+        }
     }
 }
 
+fn add_vectors(lhs: &Vector, rhs: &Vector) -> Vector {
+    backend::add(lhs, rhs)
+}
+
 fn subtract_vectors(lhs: &Vector, rhs: &Vector) -> Vector {
-    Vector {
-        x: lhs.x - rhs.x,
-        y: lhs.y - rhs.y,
-        z: lhs.z - rhs.z,
-    }
+    backend::subtract(lhs, rhs)
 }
 
 fn neg_vector(vector: &Vector) -> Vector {
@@ -108,7 +174,7 @@ fn neg_vector(vector: &Vector) -> Vector {
     }
 }
 
-fn add_vector_and_scalar(lhs: &Vector, rhs: f64) -> Vector {
+fn add_vector_and_scalar(lhs: &Vector, rhs: Float) -> Vector {
     Vector {
         x: lhs.x + rhs,
         y: lhs.y + rhs,
@@ -116,15 +182,11 @@ fn add_vector_and_scalar(lhs: &Vector, rhs: f64) -> Vector {
     }
 }
 
-fn mul_vector_and_scalar(lhs: &Vector, rhs: f64) -> Vector {
-    Vector {
-        x: lhs.x * rhs,
-        y: lhs.y * rhs,
-        z: lhs.z * rhs,
-    }
+fn mul_vector_and_scalar(lhs: &Vector, rhs: Float) -> Vector {
+    backend::scale(lhs, rhs)
 }
 
-fn div_vector_and_scalar(lhs: &Vector, rhs: f64) -> Vector {
+fn div_vector_and_scalar(lhs: &Vector, rhs: Float) -> Vector {
     Vector {
         x: lhs.x / rhs,
         y: lhs.y / rhs,
@@ -212,15 +274,15 @@ impl ops::Neg for Vector {
     }
 }
 
-impl ops::Add<f64> for &Vector {
+impl ops::Add<Float> for &Vector {
     type Output = Vector;
 
-    fn add(self, rhs: f64) -> Vector {
+    fn add(self, rhs: Float) -> Vector {
         add_vector_and_scalar(self, rhs)
     }
 }
 
-impl ops::Add<&Vector> for f64 {
+impl ops::Add<&Vector> for Float {
     type Output = Vector;
 
     fn add(self, rhs: &Vector) -> Vector {
@@ -228,15 +290,15 @@ impl ops::Add<&Vector> for f64 {
     }
 }
 
-impl ops::Add<f64> for Vector {
+impl ops::Add<Float> for Vector {
     type Output = Vector;
 
-    fn add(self, rhs: f64) -> Vector {
+    fn add(self, rhs: Float) -> Vector {
         add_vector_and_scalar(&self, rhs)
     }
 }
 
-impl ops::Add<Vector> for f64 {
+impl ops::Add<Vector> for Float {
     type Output = Vector;
 
     fn add(self, rhs: Vector) -> Vector {
@@ -244,23 +306,23 @@ impl ops::Add<Vector> for f64 {
     }
 }
 
-impl ops::Mul<f64> for &Vector {
+impl ops::Mul<Float> for &Vector {
     type Output = Vector;
 
-    fn mul(self, rhs: f64) -> Vector {
+    fn mul(self, rhs: Float) -> Vector {
         mul_vector_and_scalar(self, rhs)
     }
 }
 
-impl ops::Mul<f64> for Vector {
+impl ops::Mul<Float> for Vector {
     type Output = Vector;
 
-    fn mul(self, rhs: f64) -> Vector {
+    fn mul(self, rhs: Float) -> Vector {
         mul_vector_and_scalar(&self, rhs)
     }
 }
 
-impl ops::Mul<&Vector> for f64 {
+impl ops::Mul<&Vector> for Float {
     type Output = Vector;
 
     fn mul(self, rhs: &Vector) -> Vector {
@@ -268,7 +330,7 @@ impl ops::Mul<&Vector> for f64 {
     }
 }
 
-impl ops::Mul<Vector> for f64 {
+impl ops::Mul<Vector> for Float {
     type Output = Vector;
 
     fn mul(self, rhs: Vector) -> Vector {
@@ -276,18 +338,18 @@ impl ops::Mul<Vector> for f64 {
     }
 }
 
-impl ops::Div<f64> for Vector {
+impl ops::Div<Float> for Vector {
     type Output = Vector;
 
-    fn div(self, rhs: f64) -> Vector {
+    fn div(self, rhs: Float) -> Vector {
         div_vector_and_scalar(&self, rhs)
     }
 }
 
-impl ops::Div<f64> for &Vector {
+impl ops::Div<Float> for &Vector {
     type Output = Vector;
 
-    fn div(self, rhs: f64) -> Vector {
+    fn div(self, rhs: Float) -> Vector {
         div_vector_and_scalar(&self, rhs)
     }
 }
@@ -510,6 +572,21 @@ mod tests {
         assert_eq!(vector_a.max(&vector_b), expected_result);
     }
 
+    #[test]
+    fn test_rotate_x() {
+        let vector = Vector::new(0.5, 3.0, 0.5);
+
+        let rotated = vector.rotate_x(90.0);
+        assert_approx_eq!(rotated.x(), 0.5);
+        assert_approx_eq!(rotated.y(), -0.5);
+        assert_approx_eq!(rotated.z(), 3.0);
+
+        let rotated = vector.rotate_x(-90.0);
+        assert_approx_eq!(rotated.x(), 0.5);
+        assert_approx_eq!(rotated.y(), 0.5);
+        assert_approx_eq!(rotated.z(), -3.0);
+    }
+
     #[test]
     fn test_rotate_y() {
         let vector = Vector::new(3.0, 0.5, 0.5);
@@ -524,4 +601,19 @@ mod tests {
         assert_approx_eq!(rotated.y(), 0.5);
         assert_approx_eq!(rotated.z(), 3.0);
     }
+
+    #[test]
+    fn test_rotate_z() {
+        let vector = Vector::new(3.0, 0.5, 0.5);
+
+        let rotated = vector.rotate_z(90.0);
+        assert_approx_eq!(rotated.x(), -0.5);
+        assert_approx_eq!(rotated.y(), 3.0);
+        assert_approx_eq!(rotated.z(), 0.5);
+
+        let rotated = vector.rotate_z(-90.0);
+        assert_approx_eq!(rotated.x(), 0.5);
+        assert_approx_eq!(rotated.y(), -3.0);
+        assert_approx_eq!(rotated.z(), 0.5);
+    }
 }
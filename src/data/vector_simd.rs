@@ -0,0 +1,85 @@
+//! SSE2 versions of the Vector operations that ray tracing spends most of its time in
+//!
+//! x and y are worked on together in one register and z on its own. Every result is rounded in
+//! the same order as the scalar versions so both give exactly the same images.
+//!
+//! SSE2 is part of every x86_64 processor so the intrinsics are always safe to call here.
+
+use crate::data::vector::Vector;
+use std::arch::x86_64::*;
+
+/// x and y of `vector` in the low and high halves of a register
+fn load_xy(vector: &Vector) -> __m128d {
+    // Vector is repr(C) so x and y are the first two f64s of it
+    unsafe { _mm_loadu_pd(vector as *const Vector as *const f64) }
+}
+
+fn to_vector(xy: __m128d, z: f64) -> Vector {
+    unsafe { Vector::new(_mm_cvtsd_f64(xy), _mm_cvtsd_f64(_mm_unpackhi_pd(xy, xy)), z) }
+}
+
+pub fn dot(lhs: &Vector, rhs: &Vector) -> f64 {
+    unsafe {
+        let products = _mm_mul_pd(load_xy(lhs), load_xy(rhs));
+        let xy = _mm_add_sd(products, _mm_unpackhi_pd(products, products));
+        _mm_cvtsd_f64(xy) + lhs.z() * rhs.z()
+    }
+}
+
+pub fn cross(lhs: &Vector, rhs: &Vector) -> Vector {
+    unsafe {
+        // (ly, lz) * (rz, rx) - (lz, lx) * (ry, rz) gives x and y of the cross product at once
+        let a = _mm_mul_pd(_mm_set_pd(lhs.z(), lhs.y()), _mm_set_pd(rhs.x(), rhs.z()));
+        let b = _mm_mul_pd(_mm_set_pd(lhs.x(), lhs.z()), _mm_set_pd(rhs.z(), rhs.y()));
+        let z = lhs.x() * rhs.y() - lhs.y() * rhs.x();
+        to_vector(_mm_sub_pd(a, b), z)
+    }
+}
+
+pub fn add(lhs: &Vector, rhs: &Vector) -> Vector {
+    unsafe {
+        let xy = _mm_add_pd(load_xy(lhs), load_xy(rhs));
+        to_vector(xy, lhs.z() + rhs.z())
+    }
+}
+
+pub fn subtract(lhs: &Vector, rhs: &Vector) -> Vector {
+    unsafe {
+        let xy = _mm_sub_pd(load_xy(lhs), load_xy(rhs));
+        to_vector(xy, lhs.z() - rhs.z())
+    }
+}
+
+pub fn scale(lhs: &Vector, rhs: f64) -> Vector {
+    unsafe {
+        let xy = _mm_mul_pd(load_xy(lhs), _mm_set1_pd(rhs));
+        to_vector(xy, lhs.z() * rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::vector::scalar;
+    use crate::pdf::uniform;
+
+    fn random_vector() -> Vector {
+        let component = || 200.0 * uniform::<f64>() - 100.0;
+        Vector::new(component(), component(), component())
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        for _ in 0..1000 {
+            let lhs = random_vector();
+            let rhs = random_vector();
+            let k = 10.0 * uniform::<f64>() - 5.0;
+
+            assert_eq!(dot(&lhs, &rhs), scalar::dot(&lhs, &rhs));
+            assert_eq!(cross(&lhs, &rhs), scalar::cross(&lhs, &rhs));
+            assert_eq!(add(&lhs, &rhs), scalar::add(&lhs, &rhs));
+            assert_eq!(subtract(&lhs, &rhs), scalar::subtract(&lhs, &rhs));
+            assert_eq!(scale(&lhs, k), scalar::scale(&lhs, k));
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::data::image::Image;
+use std::io::Write;
+
+// Only the subset of OpenEXR needed to store a linear image is written: a single part,
+// uncompressed scanline image with 32 bit float B, G and R channels
+const MAGIC_NUMBER: u32 = 20_000_630;
+const VERSION: u32 = 2;
+const PIXEL_TYPE_FLOAT: i32 = 2;
+const NO_COMPRESSION: u8 = 0;
+const INCREASING_Y: u8 = 0;
+// Readers expect the channels sorted by name
+const CHANNELS: [&str; 3] = ["B", "G", "R"];
+
+/// Writes the image as an OpenEXR file so colours brighter than white are kept
+pub fn write_exr<W: Write>(image: &Image, writer: &mut W) -> std::io::Result<()> {
+    let width = image.width();
+    let height = image.height();
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&MAGIC_NUMBER.to_le_bytes());
+    header.extend_from_slice(&VERSION.to_le_bytes());
+
+    let mut channels = Vec::new();
+    for channel in &CHANNELS {
+        push_string(&mut channels, channel);
+        channels.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+        // Not perceptually linear followed by 3 reserved bytes
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        // No subsampling in x or y
+        channels.extend_from_slice(&1_i32.to_le_bytes());
+        channels.extend_from_slice(&1_i32.to_le_bytes());
+    }
+    channels.push(0);
+    push_attribute(&mut header, "channels", "chlist", &channels);
+
+    push_attribute(&mut header, "compression", "compression", &[NO_COMPRESSION]);
+
+    let mut window = Vec::new();
+    for value in &[0, 0, width as i32 - 1, height as i32 - 1] {
+        window.extend_from_slice(&value.to_le_bytes());
+    }
+    push_attribute(&mut header, "dataWindow", "box2i", &window);
+    push_attribute(&mut header, "displayWindow", "box2i", &window);
+
+    push_attribute(&mut header, "lineOrder", "lineOrder", &[INCREASING_Y]);
+    push_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0_f32.to_le_bytes(),
+    );
+    push_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    push_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0_f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    // Every scanline is its own block. The offset table after the header points at each of them
+    let block_size = 8 + CHANNELS.len() * width as usize * 4;
+    let first_block = header.len() + height as usize * 8;
+
+    let mut offsets = Vec::with_capacity(height as usize * 8);
+    for y in 0..height as usize {
+        let offset = (first_block + y * block_size) as u64;
+        offsets.extend_from_slice(&offset.to_le_bytes());
+    }
+
+    writer.write_all(&header)?;
+    writer.write_all(&offsets)?;
+
+    let data_size = (block_size - 8) as i32;
+    let mut block = Vec::with_capacity(block_size);
+    for y in 0..height {
+        block.clear();
+        block.extend_from_slice(&(y as i32).to_le_bytes());
+        block.extend_from_slice(&data_size.to_le_bytes());
+
+        // Row 0 of the image is at the bottom while EXR scanlines go from the top down
+        let row = height - y - 1;
+        for channel in &CHANNELS {
+            for col in 0..width {
+                let colour = image.get_pixel(row, col);
+                let value = match *channel {
+                    "B" => colour.b(),
+                    "G" => colour.g(),
+                    _ => colour.r(),
+                };
+                block.extend_from_slice(&(value as f32).to_le_bytes());
+            }
+        }
+        writer.write_all(&block)?;
+    }
+
+    Ok(())
+}
+
+fn push_string(bytes: &mut Vec<u8>, value: &str) {
+    bytes.extend_from_slice(value.as_bytes());
+    bytes.push(0);
+}
+
+fn push_attribute(bytes: &mut Vec<u8>, name: &str, attribute_type: &str, value: &[u8]) {
+    push_string(bytes, name);
+    push_string(bytes, attribute_type);
+    bytes.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    bytes.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::Colour;
+    use crate::data::image::Pixel;
+
+    fn read_f32(bytes: &[u8], offset: usize) -> f32 {
+        let mut value = [0; 4];
+        value.copy_from_slice(&bytes[offset..offset + 4]);
+        f32::from_le_bytes(value)
+    }
+
+    #[test]
+    fn test_write_exr_keeps_bright_values() {
+        let image = Image::from(&vec![
+            Pixel::new(0, 0, Colour::new(0.0, 0.0, 0.0)),
+            Pixel::new(0, 1, Colour::new(0.25, 0.5, 0.75)),
+            Pixel::new(1, 0, Colour::new(12.0, 3.0, 1.5)),
+            Pixel::new(1, 1, Colour::new(1.0, 1.0, 1.0)),
+        ]);
+
+        let mut bytes = Vec::new();
+        write_exr(&image, &mut bytes).unwrap();
+
+        assert_eq!(&bytes[0..4], &[0x76, 0x2f, 0x31, 0x01]);
+
+        // Two scanlines of 2 pixels with 3 channels each, after their y and size fields
+        let block_size = 8 + 3 * 2 * 4;
+        let first_block = bytes.len() - 2 * block_size;
+        let mut offset = [0; 8];
+        offset.copy_from_slice(&bytes[first_block - 16..first_block - 8]);
+        assert_eq!(u64::from_le_bytes(offset) as usize, first_block);
+
+        // The first scanline is the top row of the image, its red channel comes last
+        let red = first_block + 8 + 2 * 2 * 4;
+        assert_eq!(read_f32(&bytes, red), 12.0);
+        assert_eq!(read_f32(&bytes, red + 4), 1.0);
+
+        let second_block = first_block + block_size;
+        assert_eq!(read_f32(&bytes, second_block + 8 + 4), 0.75);
+    }
+}
@@ -1,6 +1,18 @@
 use std::cmp::Ordering;
 
-pub fn max(left: f64, right: f64) -> f64 {
+/// The precision everything is rendered in. The `f32` feature halves the memory used by scenes
+/// and images at the cost of accuracy
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+#[cfg(feature = "f32")]
+pub use std::f32::consts;
+#[cfg(not(feature = "f32"))]
+pub use std::f64::consts;
+
+pub fn max(left: Float, right: Float) -> Float {
     // If any value is NaN return the right value
     let ord = left.partial_cmp(&right).unwrap_or(Ordering::Less);
     match ord {
@@ -10,7 +22,7 @@ pub fn max(left: f64, right: f64) -> f64 {
     }
 }
 
-pub fn min(left: f64, right: f64) -> f64 {
+pub fn min(left: Float, right: Float) -> Float {
     // If any value is NaN return the right value
     let ord = left.partial_cmp(&right).unwrap_or(Ordering::Greater);
     match ord {
@@ -30,9 +42,9 @@ mod tests {
         assert_eq!(max(1.0, 2.0), 2.0);
         assert_eq!(max(2.0, 1.0), 2.0);
         assert_eq!(max(2.0, 2.0), 2.0);
-        assert_eq!(max(std::f64::NAN, 2.0), 2.0);
-        assert!(max(2.0, std::f64::NAN).is_nan());
-        assert!(max(std::f64::NAN, std::f64::NAN).is_nan());
+        assert_eq!(max(Float::NAN, 2.0), 2.0);
+        assert!(max(2.0, Float::NAN).is_nan());
+        assert!(max(Float::NAN, Float::NAN).is_nan());
     }
 
     #[allow(clippy::float_cmp)]
@@ -41,8 +53,8 @@ mod tests {
         assert_eq!(min(1.0, 2.0), 1.0);
         assert_eq!(min(2.0, 1.0), 1.0);
         assert_eq!(min(2.0, 2.0), 2.0);
-        assert_eq!(min(std::f64::NAN, 2.0), 2.0);
-        assert!(min(2.0, std::f64::NAN).is_nan());
-        assert!(min(std::f64::NAN, std::f64::NAN).is_nan());
+        assert_eq!(min(Float::NAN, 2.0), 2.0);
+        assert!(min(2.0, Float::NAN).is_nan());
+        assert!(min(Float::NAN, Float::NAN).is_nan());
     }
 }
@@ -1,17 +1,104 @@
-use crate::cli::{ConfigPath, ImagePath, OutputPath};
+use crate::checkpoint::Checkpoint;
+use crate::cli::{CheckpointPath, ConfigPath, ImagePath, OutputPath};
 use crate::config::ConfigSave;
 use crate::data::image::Image;
+use crate::exr::write_exr;
+use crate::pfm::write_pfm;
+use crate::renderer::DenoiserBuffers;
+use serde_yaml::Value;
 use std;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
 
-pub const SUPPORTED_IMAGE_EXT: [&str; 4] = [".ppm", ".jpeg", ".jpg", ".png"];
+// The geometries that read a file when they are loaded, with the field that holds its path
+const GEOMETRY_FILES: [(&str, &str); 2] = [("Mesh", "path"), ("HeightField", "image")];
 
-pub fn write_image(image: Image, output_path: &OutputPath) -> std::io::Result<()> {
-    image.into_rgb_image().save(output_path.path())
+pub const SUPPORTED_IMAGE_EXT: [&str; 5] = [".ppm", ".jpeg", ".jpg", ".png", ".exr"];
+
+/// The file format of the rendered image, picked from the extension of the output path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Png,
+    Ppm,
+    Jpeg,
+    Exr,
+}
+
+impl OutputFormat {
+    pub fn from_path(path: &str) -> Option<OutputFormat> {
+        let extension = path.rsplit('.').next()?.to_lowercase();
+        match extension.as_str() {
+            "png" => Some(OutputFormat::Png),
+            "ppm" => Some(OutputFormat::Ppm),
+            "jpeg" | "jpg" => Some(OutputFormat::Jpeg),
+            "exr" => Some(OutputFormat::Exr),
+            _ => None,
+        }
+    }
+
+    /// High dynamic range formats store the linear colours, others need them tone mapped and
+    /// gamma corrected into 8 bits first
+    pub fn is_high_dynamic_range(self) -> bool {
+        self == OutputFormat::Exr
+    }
+}
+
+pub fn write_image(
+    image: Image,
+    output_path: &OutputPath,
+    format: OutputFormat,
+) -> std::io::Result<()> {
+    match format {
+        OutputFormat::Exr => {
+            let mut writer = BufWriter::new(File::create(output_path.path())?);
+            write_exr(&image, &mut writer)?;
+            writer.flush()
+        }
+        _ => image.into_rgb_image().save(output_path.path()),
+    }
 }
 
-pub fn load_image(image_path: &ImagePath) -> Result<Image, anyhow::Error> {
+/// Writes each of the buffers as a pfm file named after the output path with the buffer appended
+pub fn write_denoiser_buffers(
+    buffers: &DenoiserBuffers,
+    output_path: &OutputPath,
+) -> std::io::Result<()> {
+    for (name, pixels) in &[
+        ("beauty", &buffers.beauty),
+        ("albedo", &buffers.albedo),
+        ("normal", &buffers.normal),
+    ] {
+        let path = output_path.with_suffix(name).with_extension("pfm");
+        let mut writer = BufWriter::new(File::create(path.path())?);
+        write_pfm(buffers.width, buffers.height, pixels, &mut writer)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Writes next to the checkpoint first and then moves it into place, so being interrupted part
+/// way through writing never loses the previous checkpoint
+pub fn save_checkpoint(
+    checkpoint_path: &CheckpointPath,
+    checkpoint: &Checkpoint,
+) -> std::io::Result<()> {
+    let partial_path = format!("{}.partial", checkpoint_path.path());
+    let mut writer = BufWriter::new(File::create(&partial_path)?);
+    checkpoint.write(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    std::fs::rename(&partial_path, checkpoint_path.path())
+}
+
+pub fn load_checkpoint(checkpoint_path: &CheckpointPath) -> Result<Checkpoint, anyhow::Error> {
+    let mut reader = BufReader::new(File::open(checkpoint_path.path())?);
+    Ok(Checkpoint::read(&mut reader)?)
+}
+
+/// Loads an image along with its alpha channel when it has one
+pub fn load_image(image_path: &ImagePath) -> Result<(Image, Option<Image>), anyhow::Error> {
     let image = image::open(image_path.path())?;
-    Ok(Image::from(&image))
+    Ok((Image::from(&image), Image::alpha_of(&image)))
 }
 
 pub fn save_config(config_path: &ConfigPath, config_save: ConfigSave) -> Result<(), anyhow::Error> {
@@ -20,8 +107,72 @@ pub fn save_config(config_path: &ConfigPath, config_save: ConfigSave) -> Result<
     Ok(())
 }
 
+/// Files the geometries read are relative to the config, like its assets
 pub fn load_config(config_path: &ConfigPath) -> Result<ConfigSave, anyhow::Error> {
     let read = std::fs::read_to_string(config_path.path())?;
-    let deserialised: ConfigSave = serde_yaml::from_str(&read)?;
+    let mut value: Value = serde_yaml::from_str(&read)?;
+    resolve_geometry_files(&mut value, &|path| {
+        String::from(ImagePath::relative_to(config_path, path).path())
+    });
+    let deserialised: ConfigSave = serde_yaml::from_value(value)?;
     Ok(deserialised)
 }
+
+/// Replaces the path of every file a geometry in `value` reads with `resolve` of it
+fn resolve_geometry_files(value: &mut Value, resolve: &dyn Fn(&str) -> String) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, inner) in mapping.iter_mut() {
+                let field = GEOMETRY_FILES
+                    .iter()
+                    .find(|(geometry, _)| key.as_str() == Some(geometry))
+                    .map(|(_, field)| Value::from(*field));
+                if let (Some(field), Value::Mapping(geometry)) = (field, &mut *inner) {
+                    if let Some(Value::String(path)) = geometry.get_mut(&field) {
+                        *path = resolve(path);
+                    }
+                }
+                resolve_geometry_files(inner, resolve);
+            }
+        }
+        Value::Sequence(sequence) => {
+            for inner in sequence {
+                resolve_geometry_files(inner, resolve);
+            }
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geometry_files_are_resolved_at_any_depth() {
+        let mut value: Value = serde_yaml::from_str(
+            "geometries:\n\
+             - Mesh:\n    path: teapot.obj\n\
+             - Translate:\n    geometry:\n      Mesh:\n        path: cup.obj\n\
+             - Sphere:\n    path: sphere.obj\n\
+             - HeightField:\n    image: hills.png\n",
+        )
+        .unwrap();
+        resolve_geometry_files(&mut value, &|path| format!("scenes/{}", path));
+
+        let geometries = value["geometries"].as_sequence().unwrap();
+        assert_eq!(
+            geometries[0]["Mesh"]["path"].as_str(),
+            Some("scenes/teapot.obj")
+        );
+        assert_eq!(
+            geometries[1]["Translate"]["geometry"]["Mesh"]["path"].as_str(),
+            Some("scenes/cup.obj")
+        );
+        assert_eq!(geometries[2]["Sphere"]["path"].as_str(), Some("sphere.obj"));
+        assert_eq!(
+            geometries[3]["HeightField"]["image"].as_str(),
+            Some("scenes/hills.png")
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use crate::float::Float;
+
+arg_enum! {
+    /// How light reaching diffuse surfaces is found. Lights can be reached by following the
+    /// material, by sampling a point on a light directly, or by both with multiple importance
+    /// sampling weighing each by how likely it was to find that light
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum LightSampling {
+        Material,
+        Lights,
+        Mis,
+    }
+}
+
+impl LightSampling {
+    /// Whether a ray is sent straight towards the lights from every diffuse bounce
+    pub fn samples_lights(self) -> bool {
+        self != LightSampling::Material
+    }
+
+    /// The share of the light found by sampling a light in a direction with pdfs of `light_pdf`
+    /// for the lights and `material_pdf` for the material
+    pub fn light_weight(self, light_pdf: Float, material_pdf: Float) -> Float {
+        match self {
+            LightSampling::Material => 0.0,
+            LightSampling::Lights if light_pdf > 0.0 => 1.0,
+            LightSampling::Lights => 0.0,
+            LightSampling::Mis => power_heuristic(light_pdf, material_pdf),
+        }
+    }
+
+    /// The share of the light found by following the material into a light, which is the rest of
+    /// what the light sample did not account for
+    pub fn material_weight(self, material_pdf: Float, light_pdf: Float) -> Float {
+        match self {
+            LightSampling::Material => 1.0,
+            LightSampling::Lights if light_pdf > 0.0 => 0.0,
+            LightSampling::Lights => 1.0,
+            LightSampling::Mis => power_heuristic(material_pdf, light_pdf),
+        }
+    }
+}
+
+/// Veach's power heuristic with an exponent of 2, which trusts whichever strategy is much more
+/// likely to sample a direction more than the balance heuristic does
+fn power_heuristic(pdf: Float, other_pdf: Float) -> Float {
+    let squared = pdf * pdf;
+    let total = squared + other_pdf * other_pdf;
+    if total > 0.0 {
+        squared / total
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_weights_sum_to_one() {
+        for &(light_pdf, material_pdf) in &[(1.0, 1.0), (4.0, 0.5), (0.0, 0.3), (2.0, 0.0)] {
+            for sampling in &[
+                LightSampling::Material,
+                LightSampling::Lights,
+                LightSampling::Mis,
+            ] {
+                let total = sampling.light_weight(light_pdf, material_pdf)
+                    + sampling.material_weight(material_pdf, light_pdf);
+                assert_approx_eq!(total, 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_power_heuristic() {
+        assert_approx_eq!(power_heuristic(1.0, 1.0), 0.5);
+        assert_approx_eq!(power_heuristic(3.0, 1.0), 0.9);
+        assert_approx_eq!(power_heuristic(0.0, 0.0), 0.0);
+    }
+}
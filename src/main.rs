@@ -5,24 +5,42 @@ extern crate clap;
 #[macro_use]
 extern crate serde_derive;
 
+mod animation;
+mod bloom;
 mod camera;
+mod checkpoint;
 mod cli;
 mod config;
 mod data;
+mod exr;
 mod float;
 mod io;
+mod light_sampling;
 mod onb;
+mod passes;
 mod pdf;
+mod pfm;
+mod pixel_filter;
 mod renderer;
 mod scenes;
+mod spectrum;
+mod stats;
+mod tone_mapping;
 mod world;
 
-use crate::cli::{get_cli_config, CliCommand, ConfigPath, ImagePath, OutputPath};
-use crate::config::Config;
+use crate::animation::AnimationSettings;
+use crate::checkpoint::Checkpoint;
+use crate::cli::{get_cli_config, CheckpointPaths, CliCommand, ConfigPath, ImagePath, OutputPath};
+use crate::config::{Config, ConfigSave, RenderOptions, RenderSettings};
 use crate::data::assets::Assets;
-use crate::io::{load_config, save_config};
-use crate::renderer::render;
+use crate::data::colour::Colour;
+use crate::data::image::Image;
+use crate::io::{load_checkpoint, load_config, save_checkpoint, save_config, OutputFormat};
+use crate::passes::{normalise_depths, Pass};
+use crate::renderer::{denoiser_buffers, render, render_pass, render_progressive, RenderOutput};
 use crate::scenes::{build_scene_config, Scene};
+use crate::stats::RenderStats;
+use crate::tone_mapping::expose;
 use console::style;
 use indicatif::{FormattedDuration, ProgressBar, ProgressStyle};
 use std::process;
@@ -42,19 +60,25 @@ fn run() -> Result<(), anyhow::Error> {
 
     match cli_config.command() {
         CliCommand::RENDER {
-            width,
+            options,
+            given_settings,
             output_path,
-            num_of_rays,
+            output_format,
             num_of_threads,
             asset_paths,
+            checkpoint_paths,
+            animation,
         } => {
             run_render(
                 &cli_config.config_path(),
-                *width,
+                options,
+                given_settings,
                 &output_path,
-                *num_of_rays,
+                *output_format,
                 *num_of_threads,
                 asset_paths,
+                checkpoint_paths,
+                animation.as_ref(),
             )?;
         }
         CliCommand::GENERATE { scene } => {
@@ -65,13 +89,17 @@ fn run() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_render(
     config_path: &ConfigPath,
-    width: u32,
+    options: &RenderOptions,
+    given_settings: &RenderSettings,
     output_path: &OutputPath,
-    num_of_rays: u64,
+    output_format: OutputFormat,
     num_of_threads: usize,
     asset_paths: &[ImagePath],
+    checkpoint_paths: &CheckpointPaths,
+    animation: Option<&AnimationSettings>,
 ) -> Result<(), anyhow::Error> {
     rayon::ThreadPoolBuilder::new()
         .num_threads(num_of_threads)
@@ -79,23 +107,65 @@ fn run_render(
 
     let started = Instant::now();
 
-    let mut step_logger = StepLogger::new(7);
+    let num_of_steps = if animation.is_some() {
+        4
+    } else {
+        7 + u8::from(!options.passes.is_empty()) + u8::from(options.denoiser_buffers)
+    };
+    let mut step_logger = StepLogger::new(num_of_steps);
 
     step_logger.log("Loading image yaml");
     let config_save = load_config(config_path)?;
+    let options = options
+        .clone()
+        .with_saved_settings(given_settings, &config_save.render_settings())?;
 
     step_logger.log("Loading assets");
-    let assets = Assets::new(asset_paths)?;
+    let assets = Assets::new(&all_asset_paths(config_path, &config_save, asset_paths))?;
 
     step_logger.log("Validating assets");
     config_save.validate(&assets)?;
+    if config_save.is_unlit() {
+        eprintln!(
+            "{} nothing in the scene emits light and the background is black, the image will be black",
+            style("warning:").yellow()
+        );
+    }
+
+    if let Some(settings) = animation {
+        step_logger.log(&format!("Rendering {} frames", settings.frames));
+        render_animation(
+            &config_save,
+            &options,
+            &assets,
+            settings,
+            output_path,
+            output_format,
+        )?;
+        println!("Done in {}", FormattedDuration(started.elapsed()));
+        return Ok(());
+    }
 
     step_logger.log("Creating config (constructing BVH)");
-    let config = config_save.into_config(width, num_of_rays, assets);
+    let config = config_save.into_config(options.clone(), assets);
 
     step_logger.log("Rendering");
-    let progress_bar = progress_bar(&config);
-    let render_output = render(&config, &progress_bar);
+    let progress_bar = progress_bar();
+    let render_output = if options.progressive {
+        render_progressively(
+            &config,
+            &progress_bar,
+            output_path,
+            output_format,
+            checkpoint_paths,
+        )?
+    } else {
+        render(&config, |tiles_done, tiles_total| {
+            progress_bar.set_length(tiles_total as u64);
+            progress_bar.set_position(tiles_done as u64);
+        })
+    };
+    progress_bar.finish();
 
     if render_output.failed_rays > 0 {
         step_logger.log(&format!(
@@ -106,14 +176,179 @@ fn run_render(
         step_logger.log("Checking for errors: no errors")
     }
 
+    if RenderStats::enabled() {
+        println!("{}", render_output.stats);
+    }
+
+    if config.denoiser_buffers() {
+        step_logger.log("Rendering and printing denoiser buffers");
+        let buffers = denoiser_buffers(&config, &render_output.image);
+        io::write_denoiser_buffers(&buffers, output_path)?;
+    }
+
     step_logger.log("Printing image");
-    io::write_image(render_output.image, output_path)?;
+    write_render(&config, render_output.image, output_path, output_format)?;
+
+    if !config.passes().is_empty() {
+        step_logger.log("Rendering and printing passes");
+        for pass in config.passes() {
+            write_pass(&config, *pass, output_path, output_format)?;
+        }
+    }
 
     println!("Done in {}", FormattedDuration(started.elapsed()));
 
     Ok(())
 }
 
+/// Renders and writes every frame in turn, each with its own BVH as the geometries are bounded
+/// over the time its shutter is open
+fn render_animation(
+    config_save: &ConfigSave,
+    options: &RenderOptions,
+    assets: &Assets,
+    settings: &AnimationSettings,
+    output_path: &OutputPath,
+    output_format: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    for frame in 0..settings.frames {
+        let frame_path = output_path.for_frame(frame);
+        let config = config_save
+            .frame(settings, frame)
+            .into_config(options.clone(), assets.clone());
+
+        let progress_bar = progress_bar();
+        let render_output = render(&config, |tiles_done, tiles_total| {
+            progress_bar.set_length(tiles_total as u64);
+            progress_bar.set_position(tiles_done as u64);
+        });
+        progress_bar.finish();
+
+        if render_output.failed_rays > 0 {
+            eprintln!(
+                "{} found {} rays with errors in frame {}",
+                style("warning:").yellow(),
+                render_output.failed_rays,
+                frame
+            );
+        }
+        if RenderStats::enabled() {
+            println!("{}", render_output.stats);
+        }
+
+        write_render(&config, render_output.image, &frame_path, output_format)?;
+        for pass in config.passes() {
+            write_pass(&config, *pass, &frame_path, output_format)?;
+        }
+        println!("Printed frame {} to {}", frame, frame_path.path());
+    }
+    Ok(())
+}
+
+/// Overwrites the output image with the average of the passes so far after every pass, along
+/// with the checkpoint when there is one
+fn render_progressively(
+    config: &Config,
+    progress_bar: &ProgressBar,
+    output_path: &OutputPath,
+    output_format: OutputFormat,
+    checkpoint_paths: &CheckpointPaths,
+) -> Result<RenderOutput, anyhow::Error> {
+    let checkpoint = match &checkpoint_paths.resume {
+        Some(resume_path) => {
+            let checkpoint = load_checkpoint(resume_path)?;
+            checkpoint.check_matches(config)?;
+            checkpoint
+        }
+        None => Checkpoint::new(config),
+    };
+
+    let mut write_result = Ok(());
+    let render_output = render_progressive(config, checkpoint, |checkpoint| {
+        progress_bar.set_length(checkpoint.num_of_passes());
+        progress_bar.set_position(checkpoint.passes_done());
+        if write_result.is_err() {
+            return;
+        }
+        write_result = write_render(config, checkpoint.image(), output_path, output_format);
+        if let (Ok(()), Some(save_path)) = (&write_result, &checkpoint_paths.save) {
+            write_result = save_checkpoint(save_path, checkpoint);
+        }
+    });
+    write_result?;
+    Ok(render_output)
+}
+
+/// Renders `pass` and writes it next to the image at `output_path`. Depths are only normalised
+/// when the format can not keep the distances as they are
+fn write_pass(
+    config: &Config,
+    pass: Pass,
+    output_path: &OutputPath,
+    output_format: OutputFormat,
+) -> std::io::Result<()> {
+    let image = render_pass(config, pass);
+    let image = if pass == Pass::Depth && !output_format.is_high_dynamic_range() {
+        normalise_depths(&image, config.depth_range())
+    } else {
+        image
+    };
+    io::write_image(
+        image,
+        &output_path.with_suffix(pass.file_suffix()),
+        output_format,
+    )
+}
+
+fn write_render(
+    config: &Config,
+    image: Image,
+    output_path: &OutputPath,
+    output_format: OutputFormat,
+) -> std::io::Result<()> {
+    let image = expose(&image, config.exposure());
+    let image = match config.white_balance() {
+        Some(temperature) => {
+            let balance = Colour::white_balance(temperature);
+            image.map(|colour| balance * colour)
+        }
+        None => image,
+    };
+    let image = match config.bloom() {
+        Some(bloom) => bloom.apply(&image),
+        None => image,
+    };
+    let image = if output_format.is_high_dynamic_range() {
+        image
+    } else {
+        config
+            .tone_mapping()
+            .display_image(&image, config.transfer_function())
+    };
+    io::write_image(image, output_path, output_format)
+}
+
+/// The assets given on the command line followed by those listed in the config. A listed asset is
+/// skipped if one with the same file name was given, so it can be swapped without editing the
+/// config
+fn all_asset_paths(
+    config_path: &ConfigPath,
+    config_save: &ConfigSave,
+    given: &[ImagePath],
+) -> Vec<ImagePath> {
+    let mut asset_paths: Vec<ImagePath> = given.to_vec();
+    for path in config_save.asset_paths() {
+        let path = ImagePath::relative_to(config_path, path);
+        if !given
+            .iter()
+            .any(|asset| asset.file_name() == path.file_name())
+        {
+            asset_paths.push(path);
+        }
+    }
+    asset_paths
+}
+
 fn run_generate(scene: &Scene, config_path: &ConfigPath) -> Result<(), anyhow::Error> {
     let mut step_logger = StepLogger::new(2);
 
@@ -125,15 +360,14 @@ fn run_generate(scene: &Scene, config_path: &ConfigPath) -> Result<(), anyhow::E
     Ok(())
 }
 
-fn progress_bar(config: &Config) -> ProgressBar {
+fn progress_bar() -> ProgressBar {
     let progress_style = ProgressStyle::default_bar()
         .template(PROGRESS_BAR_STYLE)
         .progress_chars("##-");
-    let bar_size = u64::from(config.height() * config.width());
-    let progress_bar = ProgressBar::new(bar_size);
+    // The length is set by the renderer once it knows how many tiles there are
+    let progress_bar = ProgressBar::new(0);
     progress_bar.set_style(progress_style);
     progress_bar.tick();
-    progress_bar.set_draw_delta(bar_size / 1000);
 
     progress_bar
 }
@@ -26,6 +26,14 @@ impl Onb {
         Onb { u, v, w }
     }
 
+    pub fn u(&self) -> &Vector {
+        &self.u
+    }
+
+    pub fn v(&self) -> &Vector {
+        &self.v
+    }
+
     pub fn w(&self) -> &Vector {
         &self.w
     }
@@ -0,0 +1,171 @@
+use crate::camera::Ray;
+use crate::config::Config;
+use crate::data::colour::Colour;
+use crate::data::image::Image;
+use crate::float::Float;
+use crate::world::geometry::HitResult;
+use std::cmp::Ordering;
+
+arg_enum! {
+    /// Auxiliary images describing the first surface seen through each pixel. They are written
+    /// next to the rendered image for compositing and denoising
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Pass {
+        Normal,
+        Albedo,
+        Depth,
+    }
+}
+
+impl Pass {
+    /// Appended to the output file name of the rendered image to get the file name of the pass
+    pub fn file_suffix(self) -> &'static str {
+        match self {
+            Pass::Normal => "normal",
+            Pass::Albedo => "albedo",
+            Pass::Depth => "depth",
+        }
+    }
+
+    /// The value of the pass for a single primary ray. Depth is the distance to the hit, or
+    /// infinity for a miss, kept as it is for high dynamic range images and normalised for others
+    /// once the whole image is known
+    pub fn value(self, ray: &Ray, hit: Option<&HitResult>, config: &Config) -> Colour {
+        match (self, hit) {
+            // World space normals facing the camera, mapped from [-1, 1] into [0, 1]
+            (Pass::Normal, Some(hit)) => {
+                let normal = hit.face_normal().unit_vector();
+                Colour::new(
+                    (normal.x() + 1.0) / 2.0,
+                    (normal.y() + 1.0) / 2.0,
+                    (normal.z() + 1.0) / 2.0,
+                )
+            }
+            (Pass::Normal, None) => Colour::new(0.0, 0.0, 0.0),
+            (Pass::Albedo, Some(hit)) => hit.material.albedo(hit, config.assets()),
+            (Pass::Albedo, None) => config.background().colour(ray, config.assets()),
+            (Pass::Depth, Some(hit)) => {
+                let distance = (hit.point - ray.origin()).len();
+                Colour::new(distance, distance, distance)
+            }
+            (Pass::Depth, None) => Colour::new(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+        }
+    }
+
+    /// Combines the values of the samples of a pixel. Averaging depths across an edge would place
+    /// the pixel in between the two surfaces so the nearest depth is kept instead. A NaN depth is
+    /// only kept when every sample is NaN
+    pub fn combine(self, values: &[Colour]) -> Colour {
+        match self {
+            Pass::Depth => values
+                .iter()
+                .copied()
+                .min_by(|a, b| {
+                    a.r()
+                        .is_nan()
+                        .cmp(&b.r().is_nan())
+                        .then_with(|| a.r().partial_cmp(&b.r()).unwrap_or(Ordering::Equal))
+                })
+                .unwrap_or_else(|| Colour::new(Float::INFINITY, Float::INFINITY, Float::INFINITY)),
+            _ => values.iter().copied().sum::<Colour>() / values.len() as Float,
+        }
+    }
+}
+
+/// The distances the normalised depth pass maps to 0 and 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthRange {
+    pub near: Float,
+    // The furthest surface in the image when not set
+    pub far: Option<Float>,
+}
+
+impl Default for DepthRange {
+    fn default() -> Self {
+        DepthRange {
+            near: 0.0,
+            far: None,
+        }
+    }
+}
+
+/// Scales depths into [0, 1] from `near` to `far`, clamping those outside. Misses are at `far`
+pub fn normalise_depths(depths: &Image, range: &DepthRange) -> Image {
+    let far = range.far.unwrap_or_else(|| {
+        iproduct!(0..depths.height(), 0..depths.width())
+            .map(|(row, col)| depths.get_pixel(row, col).r())
+            .filter(|depth| depth.is_finite())
+            .fold(range.near, Float::max)
+    });
+
+    depths.map(|depth| {
+        let normalised = if depth.r().is_finite() && far > range.near {
+            ((depth.r() - range.near) / (far - range.near)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        Colour::new(normalised, normalised, normalised)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::image::Pixel;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_depth_keeps_the_nearest_sample() {
+        let depths = [
+            Colour::new(3.0, 3.0, 3.0),
+            Colour::new(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+            Colour::new(2.0, 2.0, 2.0),
+        ];
+        assert_approx_eq!(Pass::Depth.combine(&depths).r(), 2.0);
+        assert_approx_eq!(
+            Pass::Normal
+                .combine(&[Colour::new(0.0, 0.5, 1.0), Colour::new(1.0, 0.5, 0.0)])
+                .r(),
+            0.5
+        );
+    }
+
+    #[test]
+    fn test_depth_skips_nan_samples() {
+        let depths = [
+            Colour::new(Float::NAN, Float::NAN, Float::NAN),
+            Colour::new(3.0, 3.0, 3.0),
+            Colour::new(-Float::NAN, -Float::NAN, -Float::NAN),
+        ];
+        assert_approx_eq!(Pass::Depth.combine(&depths).r(), 3.0);
+        assert!(Pass::Depth.combine(&depths[..1]).r().is_nan());
+    }
+
+    #[test]
+    fn test_normalise_depths() {
+        let depths = Image::from(&vec![
+            Pixel::new(0, 0, Colour::new(1.0, 1.0, 1.0)),
+            Pixel::new(0, 1, Colour::new(4.0, 4.0, 4.0)),
+            Pixel::new(
+                0,
+                2,
+                Colour::new(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+            ),
+        ]);
+        let furthest = normalise_depths(&depths, &DepthRange::default());
+        assert_approx_eq!(furthest.get_pixel(0, 0).r(), 0.25);
+        assert_approx_eq!(furthest.get_pixel(0, 1).r(), 1.0);
+        assert_approx_eq!(furthest.get_pixel(0, 2).r(), 1.0);
+
+        let range = DepthRange {
+            near: 2.0,
+            far: Some(3.0),
+        };
+        let ranged = normalise_depths(&depths, &range);
+        assert_approx_eq!(ranged.get_pixel(0, 0).r(), 0.0);
+        assert_approx_eq!(ranged.get_pixel(0, 1).r(), 1.0);
+        assert_approx_eq!(ranged.get_pixel(0, 2).r(), 1.0);
+        let between = Image::from(&vec![Pixel::new(0, 0, Colour::new(2.25, 2.25, 2.25))]);
+        assert_approx_eq!(normalise_depths(&between, &range).get_pixel(0, 0).r(), 0.25);
+    }
+}
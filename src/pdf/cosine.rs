@@ -1,11 +1,12 @@
-//! PDF p(direction = cosθ / π
+//! PDF p(direction) = cosθ / π
 
 use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
 use crate::onb::Onb;
 use crate::pdf::uniform;
-use std::f64::consts::PI;
 
-pub fn value(onb: &Onb, direction: &Vector) -> f64 {
+pub fn value(onb: &Onb, direction: &Vector) -> Float {
     let cosine = Vector::dot(&direction.unit_vector(), onb.w());
     if cosine <= 0.0 {
         0.0
@@ -19,15 +20,64 @@ pub fn generate(onb: &Onb) -> Vector {
 }
 
 fn random_cosine_direction() -> Vector {
-    let r1 = uniform::<f64>();
-    let r2 = uniform::<f64>();
+    let r1 = uniform::<Float>();
+    let r2 = uniform::<Float>();
 
-    let z = f64::sqrt(1.0 - r2);
+    let z = Float::sqrt(1.0 - r2);
 
     let phi = 2.0 * PI * r1;
 
-    let x = f64::cos(phi) * f64::sqrt(r2);
-    let y = f64::sin(phi) * f64::sqrt(r2);
+    let x = Float::cos(phi) * Float::sqrt(r2);
+    let y = Float::sin(phi) * Float::sqrt(r2);
 
     Vector::new(x, y, z)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::seed_thread_rng;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_generate_is_cosine_distributed() {
+        seed_thread_rng(0);
+        let normal = Vector::new(1.0, 2.0, -0.5).unit_vector();
+        let onb = Onb::build_from_w(&normal);
+
+        // With p = cosθ / π the fraction of directions with cos²θ below x is exactly x, so every
+        // equal width bin of cos²θ gets the same share of the samples
+        let num_of_samples = 100_000;
+        let num_of_bins = 10;
+        let mut bins = vec![0; num_of_bins];
+        let mut cosine_sum = 0.0;
+        for _ in 0..num_of_samples {
+            let direction = generate(&onb);
+            assert_approx_eq!(direction.len(), 1.0);
+
+            let cosine = Vector::dot(&direction, &normal);
+            assert!(cosine >= 0.0);
+            let bin = ((cosine * cosine) * num_of_bins as Float) as usize;
+            bins[bin.min(num_of_bins - 1)] += 1;
+            cosine_sum += cosine;
+        }
+
+        let expected = (num_of_samples / num_of_bins) as Float;
+        for count in bins {
+            assert!((count as Float - expected).abs() < 0.05 * expected);
+        }
+        // The mean of cosθ over the hemisphere weighted by cosθ / π is 2/3
+        assert_approx_eq!(cosine_sum / num_of_samples as Float, 2.0 / 3.0, 0.01);
+    }
+
+    #[test]
+    fn test_value() {
+        let onb = Onb::build_from_w(&Vector::new(0.0, 0.0, 2.0));
+        assert_approx_eq!(value(&onb, &Vector::new(0.0, 0.0, 3.0)), 1.0 / PI);
+        assert_approx_eq!(
+            value(&onb, &Vector::new(1.0, 0.0, 1.0)),
+            Float::sqrt(0.5) / PI
+        );
+        assert_approx_eq!(value(&onb, &Vector::new(0.0, 1.0, -1.0)), 0.0);
+    }
+}
@@ -0,0 +1,173 @@
+//! PDF that samples directions towards the bright parts of an environment map
+//!
+//! The texture coords of the map are split into a grid of cells, each weighed by its luminance
+//! times sin(θ) as cells near the poles cover less of the sphere. A row of cells is picked from
+//! the marginal CDF, then a cell in it from that row's conditional CDF, and the point is spread
+//! evenly over the cell. A pdf p over the texture coords is p / (2π² sin(θ)) over solid angle.
+
+use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
+use crate::pdf::uniform;
+use crate::world::geometry::sphere::sphere_texture_coords;
+
+#[derive(Debug)]
+pub struct EnvironmentDistribution {
+    rows: usize,
+    cols: usize,
+    // The running total of the row weights from 0 up to 1, one more than there are rows
+    row_cdf: Vec<Float>,
+    // The same for the cells along every row, one after the other
+    col_cdfs: Vec<Float>,
+    // The pdf over the texture coords of every cell, row by row
+    cell_pdfs: Vec<Float>,
+}
+
+impl EnvironmentDistribution {
+    /// `luminance` holds the cells row by row, starting from row 0 at the bottom of the map.
+    /// There is nothing to sample when every cell is black
+    pub fn build(luminance: &[Float], rows: usize, cols: usize) -> Option<EnvironmentDistribution> {
+        let weights: Vec<Float> = iproduct!(0..rows, 0..cols)
+            .map(|(row, col)| {
+                let sin_theta = (PI * (row as Float + 0.5) / rows as Float).sin();
+                luminance[row * cols + col].max(0.0) * sin_theta
+            })
+            .collect();
+        let total: Float = weights.iter().sum();
+        if !(total > 0.0 && total.is_finite()) {
+            return None;
+        }
+
+        let row_weights: Vec<Float> = weights.chunks(cols).map(|row| row.iter().sum()).collect();
+        let col_cdfs = weights
+            .chunks(cols)
+            .zip(&row_weights)
+            .flat_map(|(row, &row_weight)| cdf(row, row_weight))
+            .collect();
+        let cells = (rows * cols) as Float;
+
+        Some(EnvironmentDistribution {
+            rows,
+            cols,
+            row_cdf: cdf(&row_weights, total),
+            col_cdfs,
+            cell_pdfs: weights
+                .iter()
+                .map(|weight| weight / total * cells)
+                .collect(),
+        })
+    }
+
+    pub fn value(&self, direction: &Vector) -> Float {
+        let (row, col) =
+            sphere_texture_coords(&direction.unit_vector(), &Vector::new(0.0, 0.0, 0.0), 1.0);
+        let sin_theta = (PI * row).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        let cell_row = ((row * self.rows as Float) as usize).min(self.rows - 1);
+        let cell_col = ((col * self.cols as Float) as usize).min(self.cols - 1);
+        self.cell_pdfs[cell_row * self.cols + cell_col] / (2.0 * PI * PI * sin_theta)
+    }
+
+    pub fn generate(&self) -> Vector {
+        let (cell_row, row_offset) = sample_cdf(&self.row_cdf, uniform());
+        let col_cdf = &self.col_cdfs[cell_row * (self.cols + 1)..(cell_row + 1) * (self.cols + 1)];
+        let (cell_col, col_offset) = sample_cdf(col_cdf, uniform());
+        EnvironmentDistribution::direction(
+            (cell_row as Float + row_offset) / self.rows as Float,
+            (cell_col as Float + col_offset) / self.cols as Float,
+        )
+    }
+
+    /// The direction an environment map shows at `(row, col)`, the opposite of
+    /// `sphere_texture_coords`
+    pub fn direction(row: Float, col: Float) -> Vector {
+        let (sin_theta, cos_theta) = (PI * row).sin_cos();
+        let phi = 2.0 * PI * (col - 0.5);
+        Vector::new(sin_theta * phi.sin(), -cos_theta, sin_theta * phi.cos())
+    }
+}
+
+/// The running total of `weights` divided by their `total`. A row with no weight at all is never
+/// picked, so it is given an even CDF only to keep it free of NaNs
+fn cdf(weights: &[Float], total: Float) -> Vec<Float> {
+    let mut running = 0.0;
+    let mut cdf = vec![0.0];
+    for (i, weight) in weights.iter().enumerate() {
+        running += weight;
+        cdf.push(if total > 0.0 {
+            running / total
+        } else {
+            (i + 1) as Float / weights.len() as Float
+        });
+    }
+    // Rounding must not leave a gap at the top that a random number could fall into
+    *cdf.last_mut().unwrap() = 1.0;
+    cdf
+}
+
+/// The bin of `cdf` that `r` in [0, 1) falls into and how far through the bin it is
+fn sample_cdf(cdf: &[Float], r: Float) -> (usize, Float) {
+    // The last bin starting at or below r, which skips over any bins with no weight
+    let bin = (cdf.partition_point(|&total| total <= r) - 1).min(cdf.len() - 2);
+    let width = cdf[bin + 1] - cdf[bin];
+    let offset = if width > 0.0 {
+        (r - cdf[bin]) / width
+    } else {
+        0.5
+    };
+    (bin, offset.clamp(0.0, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::{random_unit_vector, seed_thread_rng};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_direction_is_the_opposite_of_texture_coords() {
+        for &(row, col) in &[(0.25, 0.1), (0.5, 0.5), (0.9, 0.75)] {
+            let (found_row, found_col) = sphere_texture_coords(
+                &EnvironmentDistribution::direction(row, col),
+                &Vector::new(0.0, 0.0, 0.0),
+                1.0,
+            );
+            assert_approx_eq!(found_row, row);
+            assert_approx_eq!(found_col, col);
+        }
+    }
+
+    #[test]
+    fn test_value_integrates_to_one() {
+        seed_thread_rng(0);
+        let luminance: Vec<Float> = (0..32).map(|i| (i % 5) as Float).collect();
+        let distribution = EnvironmentDistribution::build(&luminance, 4, 8).unwrap();
+
+        // Averaging over uniform directions, whose pdf is 1 / 4π
+        let num_of_samples = 100_000;
+        let total: Float = (0..num_of_samples)
+            .map(|_| distribution.value(&random_unit_vector()))
+            .sum();
+        assert_approx_eq!(4.0 * PI * total / num_of_samples as Float, 1.0, 0.02);
+    }
+
+    #[test]
+    fn test_generate_finds_the_bright_cell() {
+        seed_thread_rng(0);
+        let mut luminance = vec![0.0; 32];
+        luminance[2 * 8 + 3] = 10.0;
+        let distribution = EnvironmentDistribution::build(&luminance, 4, 8).unwrap();
+
+        for _ in 0..1000 {
+            let direction = distribution.generate();
+            let (row, col) = sphere_texture_coords(&direction, &Vector::new(0.0, 0.0, 0.0), 1.0);
+            assert!((0.5..=0.75).contains(&row));
+            assert!((0.375..=0.5).contains(&col));
+            assert!(distribution.value(&direction) > 0.0);
+        }
+
+        assert!(EnvironmentDistribution::build(&[0.0; 32], 4, 8).is_none());
+    }
+}
@@ -1,11 +1,12 @@
 //! PDF that samples towards a list of geometries
 
 use crate::data::vector::Vector;
+use crate::float::Float;
 use crate::pdf::uniform_between;
 use crate::world::geometry::{Geometry, Hittable};
 
-pub fn value(geometries: &[Geometry], origin: &Vector, direction: &Vector) -> f64 {
-    let weight = 1.0 / geometries.len() as f64;
+pub fn value(geometries: &[Geometry], origin: &Vector, direction: &Vector) -> Float {
+    let weight = 1.0 / geometries.len() as Float;
     geometries
         .iter()
         .map(|geo| weight * geo.pdf_value(origin, direction))
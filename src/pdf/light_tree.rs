@@ -0,0 +1,403 @@
+//! PDF that picks one of many lights by how much it is likely to light the shading point
+//!
+//! The lights are grouped into a binary tree by where they are, every node holding the total
+//! power of the lights below it and the box around them. From a point the tree is walked down
+//! from the root, going to either child with a probability proportional to its importance, its
+//! power over the squared distance to its box. Close and bright lights are picked far more often
+//! than the rest, and the probability of picking a light is the product of the probabilities
+//! along the way to it, so the estimate stays unbiased. A light is then sampled as on its own.
+
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::pdf::uniform;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, Hittable};
+
+// Lights found to give off less than this share of the average power are treated as if they gave
+// off this much, so lights the estimate misses, such as narrow spotlights, are still picked
+const MIN_POWER_SHARE: Float = 1e-3;
+
+#[derive(Debug)]
+pub struct LightTree {
+    lights: Vec<Geometry>,
+    // The root first, every interior node followed by its first child
+    nodes: Vec<LightNode>,
+}
+
+#[derive(Debug)]
+struct LightNode {
+    // Unbounded lights have no box, nor do the nodes above them
+    bounds: Option<AxisAlignedBoundingBox>,
+    power: Float,
+    contents: LightNodeContents,
+}
+
+#[derive(Debug)]
+enum LightNodeContents {
+    Leaf { light: usize },
+    Interior { second_child: usize },
+}
+
+impl LightTree {
+    /// The tree over `lights` where they are between `time_start` and `time_end`
+    pub fn build(
+        lights: Vec<Geometry>,
+        time_start: Float,
+        time_end: Float,
+        assets: &Assets,
+    ) -> LightTree {
+        let powers: Vec<Float> = lights
+            .iter()
+            .map(|light| estimate_power(light, time_start, time_end, assets))
+            .collect();
+        let mean_power = powers.iter().sum::<Float>() / powers.len().max(1) as Float;
+        let mut entries: Vec<(usize, Option<AxisAlignedBoundingBox>, Float)> = powers
+            .iter()
+            .enumerate()
+            .map(|(i, &power)| {
+                let power = if mean_power > 0.0 {
+                    power.max(MIN_POWER_SHARE * mean_power)
+                } else {
+                    1.0
+                };
+                (i, lights[i].bounding_box(time_start, time_end), power)
+            })
+            .collect();
+
+        let mut tree = LightTree {
+            nodes: Vec::with_capacity(2 * lights.len()),
+            lights,
+        };
+        if !entries.is_empty() {
+            tree.add_node(&mut entries);
+        }
+        tree
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty()
+    }
+
+    /// Appends the node over `entries` followed by all of its descendants, splitting them in half
+    /// along the longest side of the box around their centres
+    fn add_node(&mut self, entries: &mut [(usize, Option<AxisAlignedBoundingBox>, Float)]) {
+        let bounds =
+            entries
+                .iter()
+                .skip(1)
+                .fold(entries[0].1.clone(), |bounds, (_, light_bounds, _)| {
+                    AxisAlignedBoundingBox::surrounding(&bounds, light_bounds)
+                });
+        let power = entries.iter().map(|(_, _, power)| power).sum();
+        if let [(light, _, _)] = entries {
+            self.nodes.push(LightNode {
+                bounds,
+                power,
+                contents: LightNodeContents::Leaf { light: *light },
+            });
+            return;
+        }
+
+        // Unbounded lights sort last, as if they were infinitely far away
+        let centre = |bounds: &Option<AxisAlignedBoundingBox>| {
+            bounds
+                .as_ref()
+                .map_or(Vector::new(Float::MAX, Float::MAX, Float::MAX), |bounds| {
+                    bounds.centroid()
+                })
+        };
+        let (low, high) = entries.iter().fold(
+            (
+                Vector::new(Float::MAX, Float::MAX, Float::MAX),
+                Vector::new(Float::MIN, Float::MIN, Float::MIN),
+            ),
+            |(low, high), (_, bounds, _)| {
+                let centre = centre(bounds);
+                (low.min(&centre), high.max(&centre))
+            },
+        );
+        let extent = high - low;
+        let along = |v: &Vector| {
+            if extent.x() >= extent.y() && extent.x() >= extent.z() {
+                v.x()
+            } else if extent.y() >= extent.z() {
+                v.y()
+            } else {
+                v.z()
+            }
+        };
+        entries.sort_by(|(_, a, _), (_, b, _)| {
+            along(&centre(a))
+                .partial_cmp(&along(&centre(b)))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let (first, second) = entries.split_at_mut(entries.len() / 2);
+
+        let index = self.nodes.len();
+        self.nodes.push(LightNode {
+            bounds,
+            power,
+            contents: LightNodeContents::Interior { second_child: 0 },
+        });
+        self.add_node(first);
+        self.nodes[index].contents = LightNodeContents::Interior {
+            second_child: self.nodes.len(),
+        };
+        self.add_node(second);
+    }
+
+    /// Picks a light to sample from `point`, along with the probability it was picked with
+    pub fn sample(&self, point: &Vector) -> (&Geometry, Float) {
+        let mut index = 0;
+        let mut probability = 1.0;
+        loop {
+            match self.nodes[index].contents {
+                LightNodeContents::Leaf { light } => return (&self.lights[light], probability),
+                LightNodeContents::Interior { second_child } => {
+                    let first = self.first_child_probability(index + 1, second_child, point);
+                    if uniform::<Float>() < first {
+                        index += 1;
+                        probability *= first;
+                    } else {
+                        index = second_child;
+                        probability *= 1.0 - first;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn generate(&self, origin: &Vector) -> Vector {
+        self.sample(origin).0.random(origin)
+    }
+
+    /// How likely sampling a light from `origin` is to pick `direction`, summed over every light
+    /// it could have picked. Whole branches of lights that the direction misses are skipped
+    pub fn value(&self, origin: &Vector, direction: &Vector) -> Float {
+        if self.nodes.is_empty() {
+            return 0.0;
+        }
+        let ray = Ray::new(*origin, *direction, 0.0);
+        let mut total = 0.0;
+        let mut stack = vec![(0, 1.0)];
+        while let Some((index, probability)) = stack.pop() {
+            let node = &self.nodes[index];
+            if let Some(bounds) = &node.bounds {
+                if !bounds.intersection(&ray, 0.0, Float::MAX) {
+                    continue;
+                }
+            }
+            match node.contents {
+                LightNodeContents::Leaf { light } => {
+                    total += probability * self.lights[light].pdf_value(origin, direction)
+                }
+                LightNodeContents::Interior { second_child } => {
+                    let first = self.first_child_probability(index + 1, second_child, origin);
+                    stack.push((index + 1, probability * first));
+                    stack.push((second_child, probability * (1.0 - first)));
+                }
+            }
+        }
+        total
+    }
+
+    fn first_child_probability(&self, first: usize, second: usize, point: &Vector) -> Float {
+        let first = self.nodes[first].importance(point);
+        let second = self.nodes[second].importance(point);
+        let total = first + second;
+        if total > 0.0 && total.is_finite() {
+            first / total
+        } else {
+            0.5
+        }
+    }
+}
+
+impl LightNode {
+    /// Power falls off with the square of the distance to the box, but never faster than from
+    /// its closest corner so points inside it do not favour one side
+    fn importance(&self, point: &Vector) -> Float {
+        match &self.bounds {
+            Some(bounds) => {
+                let half_diagonal_squared = 0.25 * (bounds.max() - bounds.min()).len_squared();
+                let distance_squared = (bounds.centroid() - point).len_squared();
+                self.power / distance_squared.max(half_diagonal_squared)
+            }
+            None => self.power,
+        }
+    }
+}
+
+/// Roughly the power a light gives off, the brightness seen looking at it along each axis times
+/// the surface area of its box. Only the relative powers of lights matter to the tree, and a
+/// poor estimate only adds noise
+fn estimate_power(light: &Geometry, time_start: Float, time_end: Float, assets: &Assets) -> Float {
+    let bounds = match light.bounding_box(time_start, time_end) {
+        Some(bounds) => bounds,
+        None => return 1.0,
+    };
+    let centre = bounds.centroid();
+    let reach = (bounds.max() - bounds.min()).len() + 1.0;
+    let axes = [
+        Vector::new(1.0, 0.0, 0.0),
+        Vector::new(0.0, 1.0, 0.0),
+        Vector::new(0.0, 0.0, 1.0),
+    ];
+    let brightness: Float = axes
+        .iter()
+        .flat_map(|axis| vec![*axis, -axis])
+        .filter_map(|towards| {
+            let ray = Ray::new(centre + reach * towards, -towards, time_start);
+            light.hit(&ray, 0.0, Float::MAX).map(|hit| {
+                hit.material
+                    .emitted(
+                        hit.front_face(),
+                        hit.texture_coords,
+                        &hit.point,
+                        &towards,
+                        assets,
+                    )
+                    .luminance()
+            })
+        })
+        .sum();
+    // A point or a line has no area, but still gives off light
+    brightness / 6.0 * bounds.surface_area().max(Float::EPSILON)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::Colour;
+    use crate::pdf::{random_unit_vector, seed_thread_rng};
+    use crate::world::geometry::sphere::Sphere;
+    use crate::world::materials::Material;
+    use crate::world::texture::Texture;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn light(centre: Vector, radius: Float, brightness: Float) -> Geometry {
+        Sphere::build(
+            centre,
+            radius,
+            Material::DiffuseLight {
+                emit: Texture::Constant {
+                    colour: Colour::new(brightness, brightness, brightness),
+                },
+                strength: 1.0,
+                two_sided: false,
+                spotlight: None,
+            },
+        )
+    }
+
+    fn tree(lights: Vec<Geometry>) -> LightTree {
+        LightTree::build(lights, 0.0, 0.0, &Assets::default())
+    }
+
+    /// 200 small lights in a 20 by 10 grid on the floor
+    fn grid() -> Vec<Geometry> {
+        iproduct!(0..20, 0..10)
+            .map(|(x, z)| light(Vector::new(x as Float, 0.0, z as Float), 0.1, 1.0))
+            .collect()
+    }
+
+    #[test]
+    fn test_sample_probabilities() {
+        seed_thread_rng(0);
+        let bright = light(Vector::new(0.0, 0.0, 0.0), 1.0, 4.0);
+        let dim = light(Vector::new(10.0, 0.0, 0.0), 1.0, 1.0);
+        let tree = tree(vec![bright, dim]);
+        let point = Vector::new(1.0, 3.0, 0.0);
+
+        let num_of_samples = 10_000;
+        let mut picked_bright = 0;
+        for _ in 0..num_of_samples {
+            let (light, probability) = tree.sample(&point);
+            let is_bright = light.bounding_box(0.0, 0.0).unwrap().centroid().x() == 0.0;
+            if is_bright {
+                picked_bright += 1;
+            }
+            // The probability returned is the chance of picking that light
+            let other = tree.sample(&point);
+            if std::ptr::eq(other.0, light) {
+                assert_approx_eq!(other.1, probability);
+            }
+        }
+        // Brighter and nearer, 4 * 90 / 10 = 36 times as important
+        let expected = 36.0 / 37.0;
+        assert_approx_eq!(
+            picked_bright as Float / num_of_samples as Float,
+            expected,
+            0.01
+        );
+    }
+
+    #[test]
+    fn test_value_integrates_to_one() {
+        seed_thread_rng(0);
+        let lights = vec![
+            light(Vector::new(3.0, 0.0, 0.0), 1.0, 1.0),
+            light(Vector::new(-3.0, 0.0, 0.0), 1.0, 2.0),
+            light(Vector::new(0.0, 4.0, 0.0), 1.5, 1.0),
+            light(Vector::new(0.0, 0.0, -5.0), 0.5, 8.0),
+        ];
+        let tree = tree(lights);
+        let origin = Vector::new(0.0, 0.0, 0.0);
+        let num_of_samples = 200_000;
+        let total: Float = (0..num_of_samples)
+            .map(|_| tree.value(&origin, &random_unit_vector()))
+            .sum();
+        assert_approx_eq!(
+            4.0 * crate::float::consts::PI * total / num_of_samples as Float,
+            1.0,
+            0.03
+        );
+
+        let direction = tree.generate(&origin);
+        assert!(tree.value(&origin, &direction) > 0.0);
+    }
+
+    #[test]
+    fn test_many_lights_converge_faster_than_uniform_selection() {
+        seed_thread_rng(0);
+        let lights = grid();
+        let tree = tree(grid());
+        // Just above the corner of the grid, where most of the lights are far away
+        let point = Vector::new(0.0, 0.5, 0.0);
+
+        // Estimates of the total solid angle of the lights, which picking each light in
+        // proportion to the solid angle it covers would find exactly
+        let estimates = |generate: &dyn Fn() -> Vector, value: &dyn Fn(&Vector) -> Float| {
+            (0..4000)
+                .map(|_| {
+                    let direction = generate();
+                    let pdf = value(&direction);
+                    if pdf > 0.0 {
+                        1.0 / pdf
+                    } else {
+                        0.0
+                    }
+                })
+                .collect::<Vec<Float>>()
+        };
+        let mean_and_variance = |estimates: Vec<Float>| {
+            let mean = estimates.iter().sum::<Float>() / estimates.len() as Float;
+            let variance = estimates.iter().map(|e| (e - mean).powi(2)).sum::<Float>()
+                / estimates.len() as Float;
+            (mean, variance)
+        };
+        let (tree_mean, tree_variance) =
+            mean_and_variance(estimates(&|| tree.generate(&point), &|direction| {
+                tree.value(&point, direction)
+            }));
+        let (uniform_mean, uniform_variance) = mean_and_variance(estimates(
+            &|| crate::pdf::geometry::generate(&lights, &point),
+            &|direction| crate::pdf::geometry::value(&lights, &point, direction),
+        ));
+
+        assert_approx_eq!(tree_mean / uniform_mean, 1.0, 0.1);
+        assert!(tree_variance < 0.1 * uniform_variance);
+    }
+}
@@ -1,18 +1,43 @@
-//! Mixture of PDFs
+//! Mixture of PDFs with equal weights
 
 use crate::data::vector::Vector;
-use crate::pdf::{uniform, Pdf};
+use crate::float::Float;
+use crate::pdf::{uniform_between, Pdf};
 
-const MIXTURE_RATIO: f64 = 0.5;
+pub fn value(pdfs: &[Pdf], direction: &Vector) -> Float {
+    let weight = 1.0 / pdfs.len() as Float;
+    pdfs.iter().map(|pdf| weight * pdf.value(direction)).sum()
+}
 
-pub fn value(pdf_a: &Pdf, pdf_b: &Pdf, direction: &Vector) -> f64 {
-    MIXTURE_RATIO * pdf_a.value(direction) + (1.0 - MIXTURE_RATIO) * pdf_b.value(direction)
+pub fn generate(pdfs: &[Pdf]) -> Vector {
+    let choice = uniform_between::<usize>(0, pdfs.len());
+    pdfs[choice].generate()
 }
 
-pub fn generate(pdf_a: &Pdf, pdf_b: &Pdf) -> Vector {
-    if uniform::<f64>() < MIXTURE_RATIO {
-        pdf_a.generate()
-    } else {
-        pdf_b.generate()
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::float::consts::PI;
+    use crate::onb::Onb;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_value_averages_components() {
+        let up = Pdf::Cosine(Onb::build_from_w(&Vector::new(0.0, 1.0, 0.0)));
+        let down = Pdf::Cosine(Onb::build_from_w(&Vector::new(0.0, -1.0, 0.0)));
+        let direction = Vector::new(0.0, 1.0, 0.0);
+
+        assert_approx_eq!(value(&[up, down], &direction), 0.5 / PI);
+    }
+
+    #[test]
+    fn test_generate_samples_every_component() {
+        let up = Pdf::Cosine(Onb::build_from_w(&Vector::new(0.0, 1.0, 0.0)));
+        let down = Pdf::Cosine(Onb::build_from_w(&Vector::new(0.0, -1.0, 0.0)));
+        let pdfs = [up, down];
+
+        let directions: Vec<Vector> = (0..100).map(|_| generate(&pdfs)).collect();
+        assert!(directions.iter().any(|direction| direction.y() > 0.0));
+        assert!(directions.iter().any(|direction| direction.y() < 0.0));
     }
 }
@@ -11,16 +11,25 @@
 //! - r1 = integral_0_θ ( 2π * f(t) * sin(t) * dt )
 
 use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
 use crate::onb::Onb;
 use crate::world::geometry::Geometry;
 use rand::distributions::uniform::SampleUniform;
 use rand::distributions::Standard;
 use rand::prelude::*;
+use rand::rngs::SmallRng;
+use std::cell::RefCell;
 
 mod cosine;
+mod environment;
 mod geometry;
+mod light_tree;
 mod mixture;
 
+pub use environment::EnvironmentDistribution;
+pub use light_tree::LightTree;
+
 #[derive(Debug)]
 pub enum Pdf<'a> {
     Cosine(Onb),
@@ -28,21 +37,24 @@ pub enum Pdf<'a> {
         geometries: &'a Vec<Geometry>,
         origin: Vector,
     },
-    Mixture(Box<Pdf<'a>>, Box<Pdf<'a>>),
+    LightTree {
+        tree: &'a LightTree,
+        origin: Vector,
+    },
+    Environment(&'a EnvironmentDistribution),
+    Mixture(Vec<Pdf<'a>>),
 }
 
 impl Pdf<'_> {
-    pub fn boxed(self) -> Box<Self> {
-        Box::from(self)
-    }
-
-    pub fn value(&self, direction: &Vector) -> f64 {
+    pub fn value(&self, direction: &Vector) -> Float {
         match self {
             Pdf::Cosine(onb) => cosine::value(&onb, direction),
             Pdf::Geometry { geometries, origin } => {
                 geometry::value(&geometries, &origin, direction)
             }
-            Pdf::Mixture(pdf_a, pdf_b) => mixture::value(&pdf_a, &pdf_b, direction),
+            Pdf::LightTree { tree, origin } => tree.value(origin, direction),
+            Pdf::Environment(distribution) => distribution.value(direction),
+            Pdf::Mixture(pdfs) => mixture::value(pdfs, direction),
         }
     }
 
@@ -50,17 +62,28 @@ impl Pdf<'_> {
         match self {
             Pdf::Cosine(onb) => cosine::generate(&onb),
             Pdf::Geometry { geometries, origin } => geometry::generate(&geometries, &origin),
-            Pdf::Mixture(pdf_a, pdf_b) => mixture::generate(&pdf_a, &pdf_b),
+            Pdf::LightTree { tree, origin } => tree.generate(origin),
+            Pdf::Environment(distribution) => distribution.generate(),
+            Pdf::Mixture(pdfs) => mixture::generate(pdfs),
         }
     }
 }
 
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+/// Reseeds the random number generator of the current thread. The renderer reseeds before every
+/// pixel so the image does not depend on which thread ends up rendering which pixel
+pub fn seed_thread_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
 pub fn uniform<T>() -> T
 where
     Standard: Distribution<T>,
 {
-    let mut rng = rand::thread_rng();
-    rng.gen::<T>()
+    RNG.with(|rng| rng.borrow_mut().gen::<T>())
 }
 
 pub fn uniform_between<T>(low: T, high: T) -> T
@@ -68,17 +91,62 @@ where
     Standard: Distribution<T>,
     T: SampleUniform,
 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range::<T, T, T>(low, high)
+    RNG.with(|rng| rng.borrow_mut().gen_range::<T, T, T>(low, high))
 }
 
+/// A point spread uniformly through the unit ball. The volume inside radius r grows as r³, so
+/// the cube root of a uniform number places points evenly through the volume
 pub fn random_point_in_unit_sphere() -> Vector {
-    let centre = Vector::new(1.0, 1.0, 1.0);
+    uniform::<Float>().cbrt() * random_unit_vector()
+}
+
+/// A direction spread uniformly over the unit sphere. Slices of a sphere between two heights have
+/// an area proportional to the gap between them, so z itself can be chosen uniformly
+pub fn random_unit_vector() -> Vector {
+    let z = 1.0 - 2.0 * uniform::<Float>();
+    let phi = 2.0 * PI * uniform::<Float>();
+    let r = Float::sqrt((1.0 - z * z).max(0.0));
+    Vector::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_thread_rng_is_deterministic() {
+        seed_thread_rng(42);
+        let first: Vec<Float> = (0..10).map(|_| uniform()).collect();
+
+        seed_thread_rng(42);
+        let second: Vec<Float> = (0..10).map(|_| uniform()).collect();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_random_point_in_unit_sphere_is_uniform() {
+        seed_thread_rng(0);
+        let num_of_samples = 100_000;
+        let num_of_shells = 8;
+        let mut shells = vec![0; num_of_shells];
+        let mut octants = [0; 8];
+        for _ in 0..num_of_samples {
+            let point = random_point_in_unit_sphere();
+            assert!(point.len_squared() <= 1.0);
+
+            // Shells between cube roots of equal steps all hold the same volume
+            let shell = (point.len().powi(3) * num_of_shells as Float) as usize;
+            shells[shell.min(num_of_shells - 1)] += 1;
+            let octant = (point.x() > 0.0) as usize
+                + 2 * (point.y() > 0.0) as usize
+                + 4 * (point.z() > 0.0) as usize;
+            octants[octant] += 1;
+        }
 
-    loop {
-        let point = 2.0 * Vector::new(uniform(), uniform(), uniform()) - centre;
-        if point.len_squared() < 1.0 {
-            return point;
+        for count in shells.iter().chain(octants.iter()) {
+            let expected = (num_of_samples / 8) as Float;
+            assert!((*count as Float - expected).abs() < 0.05 * expected);
         }
     }
 }
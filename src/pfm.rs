@@ -0,0 +1,56 @@
+use std::io::Write;
+
+// A negative scale in the header marks the floats as little endian
+const LITTLE_ENDIAN_SCALE: &str = "-1.0";
+
+/// Writes 32 bit float RGB pixels, given row by row from the top left, as a Portable Float Map.
+/// It is the format the example tool of Open Image Denoise reads and writes
+pub fn write_pfm<W: Write>(
+    width: u32,
+    height: u32,
+    pixels: &[[f32; 3]],
+    writer: &mut W,
+) -> std::io::Result<()> {
+    write!(
+        writer,
+        "PF\n{} {}\n{}\n",
+        width, height, LITTLE_ENDIAN_SCALE
+    )?;
+    // Scanlines go from the bottom up
+    for row in pixels.chunks(width as usize).rev() {
+        for pixel in row {
+            for channel in pixel {
+                writer.write_all(&channel.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_pfm_starts_at_the_bottom_row() {
+        let pixels = [
+            [1.0, 2.0, 3.0],
+            [4.0, 5.0, 6.0],
+            [7.0, 8.0, 9.0],
+            [10.0, 11.0, 12.0],
+        ];
+        let mut bytes = Vec::new();
+        write_pfm(2, 2, &pixels, &mut bytes).unwrap();
+
+        let header = b"PF\n2 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes.len(), header.len() + 4 * 3 * 4);
+        let first = f32::from_le_bytes([
+            bytes[header.len()],
+            bytes[header.len() + 1],
+            bytes[header.len() + 2],
+            bytes[header.len() + 3],
+        ]);
+        assert_eq!(first, 7.0);
+    }
+}
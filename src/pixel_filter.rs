@@ -0,0 +1,84 @@
+use crate::float::Float;
+
+// How far the tent reaches from the middle of the pixel
+const TENT_RADIUS: Float = 1.0;
+// The Gaussian is cut off at three standard deviations
+const GAUSSIAN_RADIUS: Float = 1.5;
+const GAUSSIAN_STANDARD_DEVIATION: Float = 0.5;
+
+arg_enum! {
+    /// How the samples of a pixel are weighed against each other. Box spreads them evenly over the
+    /// pixel and takes the plain average, the others spread them further out over the neighbouring
+    /// pixels and weigh each by how far it is from the middle, softening hard edges
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum PixelFilter {
+        Box,
+        Tent,
+        Gaussian,
+    }
+}
+
+impl PixelFilter {
+    /// How far from the middle of the pixel samples go, in pixels
+    pub fn radius(self) -> Float {
+        match self {
+            PixelFilter::Box => 0.5,
+            PixelFilter::Tent => TENT_RADIUS,
+            PixelFilter::Gaussian => GAUSSIAN_RADIUS,
+        }
+    }
+
+    /// Moves a sample at `(row_fuzz, col_fuzz)` in the pixel, each in [0, 1), out over the filter
+    /// and gives its weight. Returns the new fuzz, relative to the pixel's corner, and the weight
+    pub fn spread(self, row_fuzz: Float, col_fuzz: Float) -> (Float, Float, Float) {
+        let width = 2.0 * self.radius();
+        let row_offset = (row_fuzz - 0.5) * width;
+        let col_offset = (col_fuzz - 0.5) * width;
+        (
+            0.5 + row_offset,
+            0.5 + col_offset,
+            self.weight(row_offset) * self.weight(col_offset),
+        )
+    }
+
+    /// The weight in one direction of a sample `offset` pixels from the middle
+    fn weight(self, offset: Float) -> Float {
+        match self {
+            PixelFilter::Box => 1.0,
+            PixelFilter::Tent => (1.0 - offset.abs() / TENT_RADIUS).max(0.0),
+            PixelFilter::Gaussian => {
+                // Shifted down so the weight falls to 0 at the radius instead of stopping short
+                let gaussian =
+                    |x: Float| Float::exp(-x * x / (2.0 * GAUSSIAN_STANDARD_DEVIATION.powi(2)));
+                (gaussian(offset) - gaussian(GAUSSIAN_RADIUS)).max(0.0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_box_filter_keeps_samples_in_the_pixel() {
+        assert_eq!(PixelFilter::Box.spread(0.25, 0.9), (0.25, 0.9, 1.0));
+    }
+
+    #[test]
+    fn test_filters_weigh_the_middle_most() {
+        for filter in &[PixelFilter::Tent, PixelFilter::Gaussian] {
+            let (row, col, middle) = filter.spread(0.5, 0.5);
+            assert_eq!((row, col), (0.5, 0.5));
+
+            let (row, col, edge) = filter.spread(0.0, 0.5);
+            assert_approx_eq!(row, 0.5 - filter.radius());
+            assert_eq!(col, 0.5);
+            assert_approx_eq!(edge, 0.0);
+
+            let (_, _, between) = filter.spread(0.3, 0.6);
+            assert!(0.0 < between && between < middle);
+        }
+    }
+}
@@ -1,161 +1,1262 @@
 use crate::camera::Ray;
-use crate::config::Config;
+use crate::checkpoint::Checkpoint;
+use crate::config::{AdaptiveSampling, Config};
 use crate::data::colour::Colour;
 use crate::data::image::{Image, Pixel};
-use crate::pdf::Pdf;
-use crate::world::geometry::{HitResult, Hittable};
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::passes::Pass;
+use crate::pdf::{seed_thread_rng, uniform, Pdf};
+use crate::spectrum;
+use crate::stats::{self, RenderCounters, RenderStats};
+use crate::world::geometry::light_group::illuminates;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::lambertian::non_degenerate_direction;
 use crate::world::materials::ScatterResult;
-use indicatif::ProgressBar;
 use rand::seq::SliceRandom;
 use rayon::prelude::*;
 use std::panic;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
 
-const MAX_SCATTER_DEPTH: u64 = 50;
+// Even paths that carry almost no light survive russian roulette this often
+const MIN_SURVIVAL_PROBABILITY: Float = 0.05;
+// Rays passing through a hole in a cutout carry on at least this far past it, so the same hole is
+// not hit again when the ray epsilon is 0
+const MIN_CUTOUT_STEP: Float = 1e-6;
 
 pub struct RenderOutput {
     pub image: Image,
     pub failed_rays: usize,
+    pub stats: RenderStats,
 }
 
-pub fn render(config: &Config, progress_bar: &ProgressBar) -> RenderOutput {
-    let failed_rays = AtomicUsize::new(0);
-    let mut pixel_coords = config.camera().pixels(&config);
+/// The buffers external denoisers such as Open Image Denoise take, row by row from the top left
+/// of the image. Everything is linear: the beauty is the render before exposure, bloom and tone
+/// mapping, the albedo and normals come from the first surface seen through each pixel
+pub struct DenoiserBuffers {
+    pub width: u32,
+    pub height: u32,
+    pub beauty: Vec<[f32; 3]>,
+    pub albedo: Vec<[f32; 3]>,
+    // World space normals facing the camera, with components in [-1, 1]
+    pub normal: Vec<[f32; 3]>,
+}
+
+/// Renders the image in square tiles of this many pixels. Tiles keep the rays of one thread close
+/// together in the scene which is friendlier to the cache than whole rows
+const TILE_SIZE: u32 = 32;
+// An odd constant with well mixed bits, the 64 bit golden ratio
+const SEED_SCRAMBLE: u64 = 0x9E37_79B9_7F4A_7C15;
+// Another, from the SplitMix64 finaliser, so sample seeds do not line up with render seeds
+const SAMPLE_SCRAMBLE: u64 = 0xBF58_476D_1CE4_E5B9;
+// Adaptive sampling stops once the 95% confidence interval is within the tolerance
+const CONFIDENCE_Z_SCORE: Float = 1.96;
+// Keeps the tolerance of nearly black pixels from shrinking to nothing
+const MIN_CONVERGENCE_LUMINANCE: Float = 0.01;
+
+#[derive(Debug, PartialEq)]
+struct Tile {
+    row_start: u32,
+    row_end: u32,
+    col_start: u32,
+    col_end: u32,
+}
+
+impl Tile {
+    fn pixels(&self) -> impl Iterator<Item = (u32, u32)> {
+        iproduct!(self.row_start..self.row_end, self.col_start..self.col_end)
+    }
+}
 
-    // When pixels are processed in order patterns in the image can affect the remaining time
+fn tiles(height: u32, width: u32, tile_size: u32) -> Vec<Tile> {
+    iproduct!(
+        (0..height).step_by(tile_size as usize),
+        (0..width).step_by(tile_size as usize)
+    )
+    .map(|(row_start, col_start)| Tile {
+        row_start,
+        row_end: (row_start + tile_size).min(height),
+        col_start,
+        col_end: (col_start + tile_size).min(width),
+    })
+    .collect()
+}
+
+/// Renders the image tile by tile across the rayon thread pool. `on_progress` is called with the
+/// number of finished tiles and the total number of tiles every time a tile is completed
+pub fn render<F>(config: &Config, on_progress: F) -> RenderOutput
+where
+    F: FnMut(usize, usize) + Send,
+{
+    let started = Instant::now();
+    let counters = RenderCounters::default();
+    let mut tiles = tiles(config.height(), config.width(), TILE_SIZE);
+
+    // When tiles are processed in order patterns in the image can affect the remaining time
     // estimate produced by the progress bar, e.g. a empty part of the image can be processed first
     // thus producing a false low remaining time estimate while the rest of the image might have a
     // very high number of objects and be slower to process.
-    // Shuffle pixels to break up these patterns and improve the quality of the estimate
+    // Shuffle tiles to break up these patterns and improve the quality of the estimate
     let mut rng = rand::thread_rng();
-    pixel_coords.shuffle(&mut rng);
+    tiles.shuffle(&mut rng);
+
+    let tiles_total = tiles.len();
+    let tiles_done = AtomicUsize::new(0);
+    let on_progress = Mutex::new(on_progress);
 
-    let pixels: Vec<Pixel> = pixel_coords
+    let pixels: Vec<Pixel> = tiles
         .par_iter()
-        .map(|(row, col)| pixel(*row, *col, &config, &progress_bar, &failed_rays))
-        .collect();
+        .flat_map(|tile| {
+            let pixels: Vec<Pixel> = tile
+                .pixels()
+                .map(|(row, col)| pixel(row, col, &config, &counters))
+                .collect();
+
+            // Count while holding the lock so the callback always sees increasing values
+            let mut on_progress = on_progress.lock().unwrap();
+            let done = tiles_done.fetch_add(1, Ordering::SeqCst) + 1;
+            (*on_progress)(done, tiles_total);
 
-    progress_bar.finish();
+            pixels
+        })
+        .collect();
 
     let image = Image::from(&pixels);
     RenderOutput {
         image,
-        failed_rays: failed_rays.load(Ordering::SeqCst),
+        failed_rays: counters.failed_rays(),
+        stats: counters.stats(started.elapsed()),
     }
 }
 
-fn pixel(
-    row: u32,
-    col: u32,
+/// Renders one sample per pixel at a time, adding each pass to the sums of `checkpoint` and
+/// carrying on from however many passes it already has. `on_pass` is called after every pass so
+/// the average so far can be saved or shown before the render finishes. Every sample has its own
+/// seed so the final average is the same image `render` produces for the seed
+pub fn render_progressive<F>(
     config: &Config,
-    progress_bar: &ProgressBar,
-    failed_rays: &AtomicUsize,
-) -> Pixel {
-    let rays = config.camera().rays(row, col, &config);
-
-    let colour_sum = panic::catch_unwind(|| {
-        rays.iter()
-            .map(|ray| colour(&ray, &config, 0, failed_rays))
-            .sum()
+    mut checkpoint: Checkpoint,
+    mut on_pass: F,
+) -> RenderOutput
+where
+    F: FnMut(&Checkpoint),
+{
+    let started = Instant::now();
+    let counters = RenderCounters::default();
+    let width = checkpoint.width();
+
+    for sample in checkpoint.passes_done()..checkpoint.num_of_passes() {
+        let (sums, weights) = checkpoint.sums_mut();
+        sums.par_iter_mut()
+            .zip(weights.par_iter_mut())
+            .enumerate()
+            .for_each(|(i, (sum, weight_sum))| {
+                let (row, col) = (i as u32 / width, i as u32 % width);
+                let (colour, weight) = exit_on_panic(panic::catch_unwind(|| {
+                    pixel_sample(row, col, sample, config, &counters)
+                }));
+                *sum = *sum + colour;
+                *weight_sum += weight;
+            });
+
+        checkpoint.finish_pass();
+        on_pass(&checkpoint);
+    }
+
+    RenderOutput {
+        image: checkpoint.image(),
+        failed_rays: counters.failed_rays(),
+        stats: counters.stats(started.elapsed()),
+    }
+}
+
+/// Renders an auxiliary pass from the first surface hit by each primary ray, without tracing any
+/// further bounces
+pub fn render_pass(config: &Config, pass: Pass) -> Image {
+    let coords: Vec<(u32, u32)> = iproduct!(0..config.height(), 0..config.width()).collect();
+    let values: Vec<Colour> = coords
+        .par_iter()
+        .map(|&(row, col)| {
+            seed_thread_rng(pixel_seed(config.seed(), row, col));
+            // Passes are not part of the render's stats, so tests left over from them are dropped
+            RenderCounters::default().gather_thread_counts();
+            let samples: Vec<Colour> = config
+                .camera()
+                .rays(row, col, config)
+                .iter()
+                .map(|ray| match ray {
+                    Some(ray) => pass.value(ray, first_hit(ray, config).as_ref(), config),
+                    None => pass.value(&config.camera().background_ray(), None, config),
+                })
+                .collect();
+            pass.combine(&samples)
+        })
+        .collect();
+
+    let pixels: Vec<Pixel> = coords
+        .iter()
+        .zip(values)
+        .map(|(&(row, col), colour)| Pixel::new(row, col, colour))
+        .collect();
+    Image::from(&pixels)
+}
+
+/// The buffers for handing `beauty`, the image rendered from `config`, to a denoiser along with
+/// the albedo and normal passes
+pub fn denoiser_buffers(config: &Config, beauty: &Image) -> DenoiserBuffers {
+    let albedo = render_pass(config, Pass::Albedo);
+    // The normal pass is mapped into [0, 1] for saving as an image, so map it back
+    let normal = render_pass(config, Pass::Normal).map(|colour| {
+        let unmapped = |c: Float| 2.0 * c - 1.0;
+        Colour::new(
+            unmapped(colour.r()),
+            unmapped(colour.g()),
+            unmapped(colour.b()),
+        )
+    });
+
+    DenoiserBuffers {
+        width: beauty.width(),
+        height: beauty.height(),
+        beauty: denoiser_buffer(beauty),
+        albedo: denoiser_buffer(&albedo),
+        normal: denoiser_buffer(&normal),
+    }
+}
+
+/// Images count rows up from the bottom, denoisers expect them down from the top
+fn denoiser_buffer(image: &Image) -> Vec<[f32; 3]> {
+    iproduct!((0..image.height()).rev(), 0..image.width())
+        .map(|(row, col)| {
+            let colour = image.get_pixel(row, col);
+            [colour.r() as f32, colour.g() as f32, colour.b() as f32]
+        })
+        .collect()
+}
+
+fn pixel(row: u32, col: u32, config: &Config, counters: &RenderCounters) -> Pixel {
+    seed_thread_rng(pixel_seed(config.seed(), row, col));
+    let colour = panic::catch_unwind(|| match config.adaptive_sampling() {
+        None => {
+            let (colour_sum, weight_sum) = (0..config.num_of_rays())
+                .map(|sample| pixel_sample(row, col, sample, config, counters))
+                .fold(
+                    (Colour::new(0.0, 0.0, 0.0), 0.0),
+                    |(colour_sum, weight_sum), (colour, weight)| {
+                        (colour_sum + colour, weight_sum + weight)
+                    },
+                );
+            filtered_average(colour_sum, weight_sum)
+        }
+        Some(sampling) => {
+            let (colour, _) = adaptive_colour(sampling, || {
+                let (ray, weight) = config.camera().pixel_ray(row, col, config);
+                (sample_colour(ray.as_ref(), config, counters), weight)
+            });
+            colour
+        }
     });
-    let colour_sum: Colour = match colour_sum {
-        Ok(colour_sum) => colour_sum,
+    let colour = exit_on_panic(colour);
+
+    // Translate into the coordinate system expected by the image crate
+    Pixel::new(row, col, colour)
+}
+
+fn exit_on_panic<T>(result: std::thread::Result<T>) -> T {
+    match result {
+        Ok(value) => value,
         Err(err) => {
             // A rayon parallel iter will not terminate other threads when one panics
             eprintln!("A rendering thread panicked {:?}", err);
             std::process::exit(1);
         }
-    };
+    }
+}
 
-    let colour = colour_sum / (rays.len() as f64);
-    let colour = colour.gamma_2();
+/// One of the fixed number of samples of a pixel, seeded on its own so the pixel comes out the
+/// same whether its samples are traced together or in progressive passes. Returns the colour
+/// already weighed by the pixel filter along with the weight
+fn pixel_sample(
+    row: u32,
+    col: u32,
+    sample: u64,
+    config: &Config,
+    counters: &RenderCounters,
+) -> (Colour, Float) {
+    seed_thread_rng(sample_seed(config.seed(), row, col, sample));
+    let (ray, weight) = config.camera().sample_ray(row, col, sample, config);
+    (
+        sample_colour(ray.as_ref(), config, counters) * weight,
+        weight,
+    )
+}
 
-    progress_bar.inc(1);
+/// The weighted average of a pixel, which is black if every sample landed where the filter gives
+/// no weight
+fn filtered_average(colour_sum: Colour, weight_sum: Float) -> Colour {
+    if weight_sum > 0.0 {
+        colour_sum / weight_sum
+    } else {
+        Colour::new(0.0, 0.0, 0.0)
+    }
+}
 
-    // Translate into the coordinate system expected by the image crate
-    Pixel::new(row, col, colour)
+/// Averages samples of a pixel in batches until the estimate converges, returning the average and
+/// the number of samples it took. Each sample comes with the weight the pixel filter gives it
+fn adaptive_colour<F: FnMut() -> (Colour, Float)>(
+    sampling: &AdaptiveSampling,
+    mut sample: F,
+) -> (Colour, u64) {
+    let mut sum = Colour::new(0.0, 0.0, 0.0);
+    let mut weight_sum = 0.0;
+    let mut luminance_sum = 0.0;
+    let mut luminance_squared_sum = 0.0;
+    let mut count = 0;
+
+    while count < sampling.max_samples {
+        let batch_size = sampling.min_samples.min(sampling.max_samples - count);
+        for _ in 0..batch_size {
+            let (colour, weight) = sample();
+            let luminance = colour.luminance();
+            sum = sum + colour * weight;
+            weight_sum += weight;
+            luminance_sum += luminance;
+            luminance_squared_sum += luminance * luminance;
+        }
+        count += batch_size;
+
+        let n = count as Float;
+        let mean = luminance_sum / n;
+        // Rounding can make the variance of a constant pixel very slightly negative
+        let variance = ((luminance_squared_sum - luminance_sum * mean) / (n - 1.0)).max(0.0);
+        let error = CONFIDENCE_Z_SCORE * (variance / n).sqrt();
+        if error <= sampling.tolerance * mean.max(MIN_CONVERGENCE_LUMINANCE) {
+            break;
+        }
+    }
+
+    (filtered_average(sum, weight_sum), count)
 }
 
-fn colour(ray: &Ray, config: &Config, depth: u64, failed_rays: &AtomicUsize) -> Colour {
-    config
-        .bvh()
-        .hit(&ray, 0.001, core::f64::MAX)
-        .map(|hit| {
-            let emitted = hit.material.emitted(
-                hit.front_face(),
-                hit.texture_coords,
-                &hit.point,
-                &config.assets(),
+/// Every pixel gets its own seed so the image is identical regardless of the number of threads
+fn pixel_seed(seed: u64, row: u32, col: u32) -> u64 {
+    // Scrambling the render seed keeps different seeds from reusing each other's pixel seeds
+    seed.wrapping_mul(SEED_SCRAMBLE) ^ ((u64::from(row) << 32) | u64::from(col))
+}
+
+fn sample_seed(seed: u64, row: u32, col: u32, sample: u64) -> u64 {
+    pixel_seed(seed, row, col) ^ sample.wrapping_add(1).wrapping_mul(SAMPLE_SCRAMBLE)
+}
+
+/// The colour seen by one sample of a pixel, which is only the background when the camera had no
+/// ray to send for it
+fn sample_colour(ray: Option<&Ray>, config: &Config, counters: &RenderCounters) -> Colour {
+    if ray.is_some() {
+        counters.add_primary_ray();
+    }
+    let colour = match ray {
+        Some(ray) if config.spectral() => {
+            let wavelength = spectrum::sample_wavelength();
+            let ray = ray.with_wavelength(Some(wavelength));
+            let colour = colour(
+                &ray,
+                config,
+                0,
+                &Colour::new(1.0, 1.0, 1.0),
+                1.0,
+                None,
+                counters,
             );
+            colour * spectrum::wavelength_weight(wavelength)
+        }
+        Some(ray) => colour(
+            ray,
+            config,
+            0,
+            &Colour::new(1.0, 1.0, 1.0),
+            1.0,
+            None,
+            counters,
+        ),
+        None => config
+            .background()
+            .colour(&config.camera().background_ray(), config.assets()),
+    };
 
-            if depth >= MAX_SCATTER_DEPTH {
-                return emitted;
-            }
+    counters.gather_thread_counts();
+
+    match config.max_sample_luminance() {
+        Some(max_luminance) => clamp_luminance(colour, max_luminance),
+        None => colour,
+    }
+}
+
+/// Scales the whole colour down so its hue is kept
+fn clamp_luminance(colour: Colour, max_luminance: Float) -> Colour {
+    let luminance = colour.luminance();
+    if luminance > max_luminance {
+        colour * (max_luminance / luminance)
+    } else {
+        colour
+    }
+}
+
+/// `throughput` is the fraction of the light reaching this ray that makes it back to the camera.
+/// Only `emission_weight` of the light given off by the surface the ray hits is counted, the rest
+/// having already been found by sampling the lights, and none of it if the surface the ray left
+/// is in `light_group` and the light is linked to another
+fn colour(
+    ray: &Ray,
+    config: &Config,
+    depth: u32,
+    throughput: &Colour,
+    emission_weight: Float,
+    light_group: Option<u32>,
+    counters: &RenderCounters,
+) -> Colour {
+    if depth >= config.max_depth() {
+        // The path has bounced too many times to contribute any more light, other than the
+        // ambient light standing in for the rest of the path
+        let ambient = config.ambient().unwrap_or(0.0);
+        return Colour::new(ambient, ambient, ambient);
+    }
+
+    first_hit(ray, config)
+        .map(|hit| {
+            let hit = HitResult {
+                material: hit.material.select(),
+                ..hit
+            };
+            // The camera sees every light, only the light reaching surfaces is linked
+            let emission_weight = if depth == 0 || illuminates(hit.light_group, light_group) {
+                emission_weight
+            } else {
+                0.0
+            };
+            let emitted = emission_weight
+                * hit.material.emitted(
+                    hit.front_face(),
+                    hit.texture_coords,
+                    &hit.point,
+                    &-*ray.direction(),
+                    &config.assets(),
+                );
 
             hit.material
                 .scatter(&hit, &config.assets())
                 .map(|scatter| {
-                    colour_from_scatter(config, depth, &hit, emitted, scatter, failed_rays)
+                    colour_from_scatter(config, depth, throughput, &hit, emitted, scatter, counters)
                 })
                 .unwrap_or(emitted)
         })
-        .unwrap_or_else(|| background(&ray, &config))
+        .unwrap_or_else(|| {
+            let background = background(&ray, &config);
+            if config.environment_light().is_some() {
+                // Like light from a surface, some was already found by sampling the environment
+                emission_weight * background
+            } else {
+                background
+            }
+        })
+}
+
+/// The nearest hit that is not in a hole cut out of a surface
+fn first_hit(ray: &Ray, config: &Config) -> Option<HitResult> {
+    let mut tmin = config.ray_epsilon();
+    loop {
+        let hit = config
+            .geometries()
+            .iter()
+            .filter_map(|geometry| {
+                // BVHs count the tests against their own primitives
+                if !matches!(geometry, Geometry::Bvh(_)) {
+                    stats::count_intersection_test();
+                }
+                geometry.hit(ray, tmin, Float::MAX)
+            })
+            .min()?;
+        if !hit.material.is_cut_out(&hit, config.assets()) {
+            return Some(hit);
+        }
+        // Carry on past the hole, as far past it as rays start from the surfaces they leave
+        tmin = hit.distance + config.ray_epsilon().max(MIN_CUTOUT_STEP);
+    }
 }
 
 fn colour_from_scatter(
     config: &Config,
-    depth: u64,
+    depth: u32,
+    throughput: &Colour,
     hit: &HitResult,
     emitted: Colour,
     scatter: ScatterResult,
-    failed_rays: &AtomicUsize,
+    counters: &RenderCounters,
 ) -> Colour {
-    match scatter {
+    let (weight, scattered, direct, emission_weight) = match scatter {
         ScatterResult::Specular { attenuation, ray } => {
-            emitted + attenuation * colour(&ray, &config, depth + 1, failed_rays)
+            (attenuation, ray, Colour::new(0.0, 0.0, 0.0), 1.0)
         }
         ScatterResult::Diffuse { attenuation, pdf } => {
             let attractors = config.attractors();
             let pdf = if attractors.is_empty() {
                 pdf
             } else {
-                Pdf::Mixture(
-                    pdf.boxed(),
+                // Half of the rays follow the material, the other half go towards the attractors
+                Pdf::Mixture(vec![
+                    pdf,
                     Pdf::Geometry {
                         geometries: &attractors,
                         origin: hit.point,
-                    }
-                    .boxed(),
-                )
+                    },
+                ])
             };
+            let direct = direct_light(config, hit, &attenuation, &pdf);
 
-            let direction = pdf.generate();
+            let direction = non_degenerate_direction(pdf.generate(), &hit.face_normal());
             let pdf_value = pdf.value(&direction);
 
             if pdf_value <= 0.0 {
                 // This means there is no valid scattered ray we should sample.
                 // Return just emitted to avoid a NaN from the division by 0
-                failed_rays.fetch_add(1, Ordering::SeqCst);
-                return emitted;
+                counters.add_failed_ray();
+                return emitted + direct;
             }
 
             let scattered = Ray::new(hit.point, direction, hit.ray.time());
-            let scattering_pdf = hit.material.scattering_pdf(&hit.face_normal(), &scattered);
-            let scatter_colour =
-                attenuation * scattering_pdf * colour(&scattered, &config, depth + 1, failed_rays)
-                    / pdf_value;
-            emitted + scatter_colour
+            let scattering_pdf = hit
+                .material
+                .scattering_pdf(hit, &scattered, config.assets());
+            let emission_weight = config
+                .light_sampling()
+                .material_weight(pdf_value, light_pdf(config, hit, &direction));
+            (
+                attenuation * scattering_pdf / pdf_value,
+                scattered,
+                direct,
+                emission_weight,
+            )
         }
+    };
+
+    // Materials build new rays without knowing about the wavelength being traced
+    let scattered = scattered.with_wavelength(hit.ray.wavelength());
+    let throughput = throughput * weight;
+    let survival_probability = survival_probability(config.roulette_depth(), depth, &throughput);
+    if survival_probability < 1.0 && uniform::<Float>() >= survival_probability {
+        return emitted + direct;
     }
+
+    // Surviving paths make up for the terminated ones so the image stays unbiased
+    counters.add_scattered_ray();
+    let incoming = colour(
+        &scattered,
+        config,
+        depth + 1,
+        &throughput,
+        emission_weight,
+        hit.light_group,
+        counters,
+    );
+    emitted + direct + weight * incoming / survival_probability
 }
 
-fn background(ray: &Ray, config: &Config) -> Colour {
-    let unit_direction = ray.direction().unit_vector();
-    let t = 0.5 * (unit_direction.y() + 1.0);
+/// Light reaching `hit` straight from a point sampled on one of the lights, weighted against the
+/// chance of finding the same light by scattering along `pdf`
+fn direct_light(config: &Config, hit: &HitResult, attenuation: &Colour, pdf: &Pdf) -> Colour {
+    let black = Colour::new(0.0, 0.0, 0.0);
+    let lights = match lights_pdf(config, hit) {
+        Some(lights) => lights,
+        None => return black,
+    };
+    let direction = lights.generate();
+    let light_pdf = lights.value(&direction);
+    // Also rules out the NaNs from sampling a light from a point on it
+    if !(light_pdf > 0.0 && light_pdf.is_finite()) {
+        return black;
+    }
 
-    linear_interpolation(t, &config.background().bottom(), &config.background().top())
+    let ray = Ray::new(hit.point, direction, hit.ray.time()).with_wavelength(hit.ray.wavelength());
+    let scattering_pdf = hit.material.scattering_pdf(hit, &ray, config.assets());
+    if scattering_pdf <= 0.0 {
+        return black;
+    }
+
+    // Whatever is hit first may be a different light or something blocking the sampled one
+    let light = match first_hit(&ray, config) {
+        Some(light_hit) if !illuminates(light_hit.light_group, hit.light_group) => return black,
+        Some(light_hit) => light_hit.material.emitted(
+            light_hit.front_face(),
+            light_hit.texture_coords,
+            &light_hit.point,
+            &-direction,
+            config.assets(),
+        ),
+        // Reaching the environment is only counted here when it is one of the lights sampled
+        None if config.environment_light().is_some() => background(&ray, config),
+        None => return black,
+    };
+
+    let weight = config
+        .light_sampling()
+        .light_weight(light_pdf, pdf.value(&direction));
+    weight * scattering_pdf / light_pdf * (attenuation * light)
+}
+
+/// How likely sampling the lights from `hit` is to pick `direction`
+fn light_pdf(config: &Config, hit: &HitResult, direction: &Vector) -> Float {
+    lights_pdf(config, hit).map_or(0.0, |lights| lights.value(direction))
 }
 
-fn linear_interpolation(t: f64, colour_a: &Colour, colour_b: &Colour) -> Colour {
-    (1.0 - t) * colour_a + t * colour_b
+/// Sampling the lights from `hit` picks the geometries that give off light onto it or the
+/// environment map evenly, when there are both. None when there is nothing to sample
+fn lights_pdf<'a>(config: &'a Config, hit: &HitResult) -> Option<Pdf<'a>> {
+    if !config.light_sampling().samples_lights() {
+        return None;
+    }
+    let lights = config.lights(hit.light_group);
+    let geometries = if lights.is_empty() {
+        None
+    } else {
+        Some(Pdf::LightTree {
+            tree: lights,
+            origin: hit.point,
+        })
+    };
+    match (geometries, config.environment_light().map(Pdf::Environment)) {
+        (Some(geometries), Some(environment)) => Some(Pdf::Mixture(vec![geometries, environment])),
+        (geometries, environment) => geometries.or(environment),
+    }
+}
+
+/// Russian roulette: past the roulette depth a path survives with a probability based on how much
+/// light it can still carry back to the camera
+fn survival_probability(roulette_depth: u32, depth: u32, throughput: &Colour) -> Float {
+    if depth < roulette_depth {
+        return 1.0;
+    }
+    throughput
+        .max_channel()
+        .clamp(MIN_SURVIVAL_PROBABILITY, 1.0)
+}
+
+fn background(ray: &Ray, config: &Config) -> Colour {
+    config.background().colour(ray, config.assets())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{CameraSave, Lens};
+    use crate::config::{ConfigSave, RenderOptions};
+    use crate::data::assets::Assets;
+    use crate::light_sampling::LightSampling;
+    use crate::passes::{normalise_depths, DepthRange};
+    use crate::pixel_filter::PixelFilter;
+    use crate::tone_mapping::{ToneMapping, TransferFunction};
+    use crate::world::background::Background;
+    use crate::world::geometry::rectangle::XzRect;
+    use crate::world::geometry::sphere::Sphere;
+    use crate::world::materials::Material;
+    use crate::world::texture::{Axis, Texture};
+    use crate::world::WorldSave;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn mirror_sphere_config(max_depth: u32) -> Config {
+        mirror_sphere_config_with_seed(max_depth, 0)
+    }
+
+    fn mirror_sphere_config_with_seed(max_depth: u32, seed: u64) -> Config {
+        let mirror = Material::Metal {
+            albedo: Colour::new(0.95, 0.95, 0.95),
+            fuzz: 0.0,
+        };
+        sphere_config(mirror, max_depth, seed, 0.001)
+    }
+
+    fn sphere_config(material: Material, max_depth: u32, seed: u64, ray_epsilon: Float) -> Config {
+        let options = RenderOptions {
+            ray_epsilon,
+            ..options(max_depth, seed)
+        };
+        sphere_config_with_options(material, options)
+    }
+
+    fn sphere_config_with_options(material: Material, options: RenderOptions) -> Config {
+        let camera = CameraSave::new(
+            &Vector::new(0.0, 0.0, 3.0),
+            &Vector::new(0.0, 0.0, 0.0),
+            &Vector::new(0.0, 1.0, 0.0),
+            1.0,
+            Lens::new(40.0, 0.0, 3.0),
+            0.0,
+            1.0,
+        );
+        let world = WorldSave::new(
+            Background::new(Colour::new(1.0, 1.0, 1.0), Colour::new(0.5, 0.7, 1.0)),
+            vec![Sphere::build(Vector::new(0.0, 0.0, 0.0), 1.0, material)],
+        );
+        ConfigSave::new(1.0, camera, world).into_config(options, Assets::new(&[]).unwrap())
+    }
+
+    fn options(max_depth: u32, seed: u64) -> RenderOptions {
+        RenderOptions {
+            width: 8,
+            num_of_rays: 4,
+            max_depth,
+            roulette_depth: max_depth,
+            exposure: 0.0,
+            tone_mapping: ToneMapping::None,
+            light_sampling: LightSampling::Mis,
+            adaptive_sampling: None,
+            seed,
+            passes: vec![],
+            depth_range: DepthRange::default(),
+            denoiser_buffers: false,
+            spectral: false,
+            progressive: false,
+            max_sample_luminance: None,
+            ray_epsilon: 0.001,
+            bloom: None,
+            transfer_function: TransferFunction::default(),
+            pixel_filter: PixelFilter::Box,
+            preview: false,
+            ambient: None,
+            white_balance: None,
+        }
+    }
+
+    /// A grey floor lit by a small light in an otherwise black scene
+    fn small_light_config(light_sampling: LightSampling, seed: u64) -> Config {
+        linked_light_config(light_sampling, seed, None, None)
+    }
+
+    /// The same with the floor and the light in the given light groups
+    fn linked_light_config(
+        light_sampling: LightSampling,
+        seed: u64,
+        floor_group: Option<u32>,
+        light_group: Option<u32>,
+    ) -> Config {
+        let camera = CameraSave::new(
+            &Vector::new(0.0, 3.0, 3.0),
+            &Vector::new(0.0, 0.0, 0.0),
+            &Vector::new(0.0, 1.0, 0.0),
+            1.0,
+            Lens::new(60.0, 0.0, 3.0),
+            0.0,
+            1.0,
+        );
+        let grey = Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.5, 0.5, 0.5),
+            },
+        };
+        let light = Material::DiffuseLight {
+            emit: Texture::Constant {
+                colour: Colour::new(50.0, 50.0, 50.0),
+            },
+            strength: 1.0,
+            two_sided: false,
+            spotlight: None,
+        };
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let in_group = |geometry: Geometry, group: Option<u32>| match group {
+            Some(group) => geometry.in_light_group(group),
+            None => geometry,
+        };
+        let world = WorldSave::new(
+            Background::new(black, black),
+            vec![
+                in_group(
+                    XzRect::build((-3.0, 3.0), (-3.0, 3.0), 0.0, grey),
+                    floor_group,
+                ),
+                // Shining down onto the floor, so the camera only sees its dark back
+                in_group(
+                    XzRect::build((-0.2, 0.2), (-0.2, 0.2), 1.0, light).flip(),
+                    light_group,
+                ),
+            ],
+        );
+        let options = RenderOptions {
+            width: 16,
+            light_sampling,
+            ..options(5, seed)
+        };
+        ConfigSave::new(1.0, camera, world).into_config(options, Assets::new(&[]).unwrap())
+    }
+
+    /// The mean brightness of two renders with different seeds and how much they differ by
+    fn brightness_and_noise(light_sampling: LightSampling) -> (Float, Float) {
+        let first = render(&small_light_config(light_sampling, 0), |_, _| ()).image;
+        let second = render(&small_light_config(light_sampling, 1), |_, _| ()).image;
+        let mut noise = 0.0;
+        for row in 0..first.height() {
+            for col in 0..first.width() {
+                let difference =
+                    first.get_pixel(row, col).luminance() - second.get_pixel(row, col).luminance();
+                noise += difference.powi(2);
+            }
+        }
+        let brightness = (total_brightness(&first) + total_brightness(&second)) / 2.0;
+        (brightness, noise)
+    }
+
+    /// A grey floor lit by a small bright patch of sky straight above it
+    fn sky_patch_config(light_sampling: LightSampling, num_of_rays: u64, seed: u64) -> Config {
+        let camera = CameraSave::new(
+            &Vector::new(0.0, 3.0, 3.0),
+            &Vector::new(0.0, 0.0, 0.0),
+            &Vector::new(0.0, 1.0, 0.0),
+            1.0,
+            Lens::new(40.0, 0.0, 3.0),
+            0.0,
+            1.0,
+        );
+        let grey = Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.5, 0.5, 0.5),
+            },
+        };
+        // Only the top twentieth of the rows of the map are lit
+        let sky = Texture::Transform {
+            scale: (20.0, 1.0),
+            offset: (-19.0, 0.0),
+            rotation: 0.0,
+            inner: Box::from(Texture::Gradient {
+                start: Colour::new(0.0, 0.0, 0.0),
+                end: Colour::new(40.0, 40.0, 40.0),
+                axis: Axis::Row,
+            }),
+        };
+        let world = WorldSave::new(
+            Background::Environment { texture: sky },
+            vec![XzRect::build((-3.0, 3.0), (-3.0, 3.0), 0.0, grey)],
+        );
+        let options = RenderOptions {
+            width: 16,
+            num_of_rays,
+            light_sampling,
+            ..options(5, seed)
+        };
+        ConfigSave::new(1.0, camera, world).into_config(options, Assets::new(&[]).unwrap())
+    }
+
+    fn total_brightness(image: &Image) -> Float {
+        let mut total = 0.0;
+        for row in 0..image.height() {
+            for col in 0..image.width() {
+                let colour = image.get_pixel(row, col);
+                total += colour.r() + colour.g() + colour.b();
+            }
+        }
+        total
+    }
+
+    #[test]
+    fn test_tiles_cover_image() {
+        let tiles = tiles(70, 40, 32);
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(
+            tiles[5],
+            Tile {
+                row_start: 64,
+                row_end: 70,
+                col_start: 32,
+                col_end: 40,
+            }
+        );
+
+        let num_of_pixels: usize = tiles.iter().map(|tile| tile.pixels().count()).sum();
+        assert_eq!(num_of_pixels, 70 * 40);
+    }
+
+    #[test]
+    fn test_clamp_luminance() {
+        let firefly = clamp_luminance(Colour::new(400.0, 200.0, 100.0), 10.0);
+        assert_approx_eq!(firefly.luminance(), 10.0);
+        assert_approx_eq!(firefly.r() / firefly.g(), 2.0);
+
+        let dim = Colour::new(0.5, 0.2, 0.1);
+        assert_eq!(clamp_luminance(dim, 10.0), dim);
+    }
+
+    #[test]
+    fn test_survival_probability() {
+        let dim = Colour::new(0.3, 0.1, 0.0);
+        assert_eq!(survival_probability(5, 4, &dim), 1.0);
+        assert_eq!(survival_probability(5, 5, &dim), 0.3);
+
+        let black = Colour::new(0.0, 0.0, 0.0);
+        assert_eq!(survival_probability(5, 5, &black), MIN_SURVIVAL_PROBABILITY);
+
+        let bright = Colour::new(2.0, 0.5, 0.5);
+        assert_eq!(survival_probability(5, 5, &bright), 1.0);
+    }
+
+    #[test]
+    fn test_adaptive_sampling_constant_colour_converges_at_min_samples() {
+        let sampling = AdaptiveSampling {
+            min_samples: 8,
+            max_samples: 256,
+            tolerance: 0.01,
+        };
+        let (colour, count) = adaptive_colour(&sampling, || (Colour::new(0.2, 0.4, 0.6), 1.0));
+        assert_eq!(count, 8);
+        assert_approx_eq!(colour.g(), 0.4);
+    }
+
+    #[test]
+    fn test_adaptive_sampling_noisy_colour_reaches_max_samples() {
+        let sampling = AdaptiveSampling {
+            min_samples: 8,
+            max_samples: 100,
+            tolerance: 0.01,
+        };
+        let mut bright = false;
+        let (colour, count) = adaptive_colour(&sampling, || {
+            bright = !bright;
+            if bright {
+                (Colour::new(1.0, 1.0, 1.0), 1.0)
+            } else {
+                (Colour::new(0.0, 0.0, 0.0), 1.0)
+            }
+        });
+        assert_eq!(count, 100);
+        assert_approx_eq!(colour.r(), 0.5);
+    }
+
+    #[test]
+    fn test_render_is_reproducible_for_a_seed() {
+        let render_with_threads = |num_of_threads: usize, seed: u64| {
+            let config = mirror_sphere_config_with_seed(50, seed);
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(num_of_threads)
+                .build()
+                .unwrap()
+                .install(|| render(&config, |_, _| ()).image)
+        };
+
+        let image = render_with_threads(1, 7);
+        assert_eq!(image, render_with_threads(1, 7));
+        assert_eq!(image, render_with_threads(4, 7));
+        assert_ne!(image, render_with_threads(1, 8));
+    }
+
+    #[test]
+    fn test_back_of_one_sided_light_is_dark() {
+        let render_light = |two_sided: bool| {
+            let camera = CameraSave::new(
+                &Vector::new(0.0, 3.0, 3.0),
+                &Vector::new(0.0, 0.0, 0.0),
+                &Vector::new(0.0, 1.0, 0.0),
+                1.0,
+                Lens::new(60.0, 0.0, 3.0),
+                0.0,
+                1.0,
+            );
+            let light = Material::DiffuseLight {
+                emit: Texture::Constant {
+                    colour: Colour::new(4.0, 4.0, 4.0),
+                },
+                strength: 1.0,
+                two_sided,
+                spotlight: None,
+            };
+            let black = Colour::new(0.0, 0.0, 0.0);
+            // Facing down, away from the camera above it, like a ceiling light seen from above
+            let world = WorldSave::new(
+                Background::new(black, black),
+                vec![XzRect::build((-2.0, 2.0), (-2.0, 2.0), 0.0, light).flip()],
+            );
+            let config = ConfigSave::new(1.0, camera, world)
+                .into_config(options(5, 0), Assets::new(&[]).unwrap());
+            total_brightness(&render(&config, |_, _| ()).image)
+        };
+
+        assert_eq!(render_light(false), 0.0);
+        assert!(render_light(true) > 0.0);
+    }
+
+    #[test]
+    fn test_render_stats() {
+        let config = mirror_sphere_config(50);
+        let stats = render(&config, |_, _| ()).stats;
+        assert!(stats.elapsed.as_nanos() > 0);
+
+        if RenderStats::enabled() {
+            let pixels = u64::from(config.width() * config.height());
+            assert_eq!(stats.primary_rays, pixels * config.num_of_rays());
+            // Every primary ray is tested against the sphere, some bounce off it
+            assert!(stats.intersection_tests >= stats.primary_rays);
+            assert!(stats.scattered_rays > 0);
+        } else {
+            assert_eq!(stats.primary_rays, 0);
+            assert_eq!(stats.intersection_tests, 0);
+        }
+    }
+
+    #[test]
+    fn test_progressive_render_matches_render() {
+        let config = mirror_sphere_config_with_seed(50, 3);
+        let mut passes_seen = vec![];
+        let progressive = render_progressive(&config, Checkpoint::new(&config), |checkpoint| {
+            assert_eq!(checkpoint.image().width(), 8);
+            passes_seen.push((checkpoint.passes_done(), checkpoint.num_of_passes()));
+        });
+
+        assert_eq!(passes_seen, vec![(1, 4), (2, 4), (3, 4), (4, 4)]);
+        assert_eq!(progressive.image, render(&config, |_, _| ()).image);
+    }
+
+    #[test]
+    fn test_progressive_render_matches_render_with_a_pixel_filter() {
+        let lambertian = || Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.1, 0.1, 0.1),
+            },
+        };
+        let config = |pixel_filter: PixelFilter| {
+            sphere_config_with_options(
+                lambertian(),
+                RenderOptions {
+                    pixel_filter,
+                    ..options(5, 3)
+                },
+            )
+        };
+
+        let gaussian = config(PixelFilter::Gaussian);
+        let image = render(&gaussian, |_, _| ()).image;
+        let progressive = render_progressive(&gaussian, Checkpoint::new(&gaussian), |_| ());
+        assert_eq!(progressive.image, image);
+        assert_ne!(image, render(&config(PixelFilter::Box), |_, _| ()).image);
+        assert!(Checkpoint::new(&gaussian)
+            .check_matches(&config(PixelFilter::Tent))
+            .is_err());
+    }
+
+    #[test]
+    fn test_resumed_render_matches_render() {
+        let config = mirror_sphere_config_with_seed(50, 3);
+
+        // Interrupted after the second pass and written out
+        let mut saved = Vec::new();
+        render_progressive(&config, Checkpoint::new(&config), |checkpoint| {
+            if checkpoint.passes_done() == 2 {
+                checkpoint.write(&mut saved).unwrap();
+            }
+        });
+
+        let checkpoint = Checkpoint::read(&mut saved.as_slice()).unwrap();
+        assert!(checkpoint.check_matches(&config).is_ok());
+        assert!(checkpoint
+            .check_matches(&mirror_sphere_config_with_seed(50, 4))
+            .is_err());
+
+        let mut passes_seen = vec![];
+        let resumed = render_progressive(&config, checkpoint, |checkpoint| {
+            passes_seen.push(checkpoint.passes_done());
+        });
+        assert_eq!(passes_seen, vec![3, 4]);
+        assert_eq!(resumed.image, render(&config, |_, _| ()).image);
+    }
+
+    #[test]
+    fn test_render_passes() {
+        let config = mirror_sphere_config(50);
+
+        // The centre of the image looks straight at the front of the sphere
+        let normal = render_pass(&config, Pass::Normal);
+        let centre = normal.get_pixel(4, 4);
+        assert!(centre.b() > 0.9);
+
+        let albedo = render_pass(&config, Pass::Albedo);
+        assert_approx_eq!(albedo.get_pixel(4, 4).r(), 0.95);
+
+        // The corners miss the sphere, while the centre is the nearest point of it
+        let depth = render_pass(&config, Pass::Depth);
+        assert!(depth.get_pixel(0, 0).r().is_infinite());
+        assert!(depth.get_pixel(4, 4).r().is_finite());
+        let normalised = normalise_depths(&depth, config.depth_range());
+        assert_approx_eq!(normalised.get_pixel(0, 0).r(), 1.0);
+        assert!(normalised.get_pixel(4, 4).r() < 1.0);
+    }
+
+    #[test]
+    fn test_denoiser_buffers() {
+        let config = mirror_sphere_config(50);
+        let image = render(&config, |_, _| ()).image;
+        let buffers = denoiser_buffers(&config, &image);
+
+        assert_eq!((buffers.width, buffers.height), (8, 8));
+        for buffer in &[&buffers.beauty, &buffers.albedo, &buffers.normal] {
+            assert_eq!(buffer.len(), 64);
+        }
+
+        // The first entry is the top left pixel, the last row of the image
+        let top_left = image.get_pixel(7, 0);
+        assert_eq!(
+            buffers.beauty[0],
+            [
+                top_left.r() as f32,
+                top_left.g() as f32,
+                top_left.b() as f32
+            ]
+        );
+
+        // Straight at the front of the sphere the normal points back at the camera
+        let centre = 4 * 8 + 4;
+        assert_approx_eq!(buffers.albedo[centre][0], 0.95, 1e-6);
+        assert!(buffers.normal[centre][2] > 0.9);
+        assert!(buffers.normal[centre][0].abs() < 0.3);
+        // The corners miss, which has no normal
+        assert_eq!(buffers.normal[0], [-1.0, -1.0, -1.0]);
+    }
+
+    #[test]
+    fn test_max_depth_limits_reflections() {
+        let shallow = render(&mirror_sphere_config(1), |_, _| ()).image;
+        let deep = render(&mirror_sphere_config(50), |_, _| ()).image;
+
+        // With a single bounce the reflections on the sphere cannot reach the background
+        assert!(total_brightness(&shallow) < total_brightness(&deep));
+    }
+
+    #[test]
+    fn test_ambient_lights_paths_cut_off_at_the_max_depth() {
+        let grey = || Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.5, 0.5, 0.5),
+            },
+        };
+        let config = |ambient: Option<Float>| {
+            sphere_config_with_options(
+                grey(),
+                RenderOptions {
+                    ambient,
+                    ..options(1, 0)
+                },
+            )
+        };
+
+        // The middle of the image sees the sphere, which only sees the cut off bounce
+        let middle = |image: &Image| *image.get_pixel(4, 4);
+        assert_eq!(
+            middle(&render(&config(None), |_, _| ()).image),
+            Colour::new(0.0, 0.0, 0.0)
+        );
+        let lit = middle(&render(&config(Some(0.8)), |_, _| ()).image);
+        assert_approx_eq!(lit.g(), 0.4);
+    }
+
+    #[test]
+    fn test_rays_pass_through_cutouts() {
+        let cutout = |alpha: Float| Material::Cutout {
+            material: Box::from(Material::Lambertian {
+                albedo: Texture::Constant {
+                    colour: Colour::new(0.5, 0.5, 0.5),
+                },
+            }),
+            alpha: Texture::Constant {
+                colour: Colour::new(alpha, alpha, alpha),
+            },
+            threshold: 0.5,
+        };
+        let ray = Ray::new(Vector::new(0.0, 0.0, 3.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+
+        let solid = sphere_config(cutout(1.0), 5, 0, 0.0);
+        assert_approx_eq!(first_hit(&ray, &solid).unwrap().distance, 2.0);
+        // Through the hole in the front and the one in the back, even with no ray epsilon
+        let hollow = sphere_config(cutout(0.0), 5, 0, 0.0);
+        assert!(first_hit(&ray, &hollow).is_none());
+    }
+
+    #[test]
+    fn test_ray_epsilon_removes_shadow_acne() {
+        let grey = || Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.5, 0.5, 0.5),
+            },
+        };
+        let acne = render(&sphere_config(grey(), 50, 0, 0.0), |_, _| ()).image;
+        let clean = render(&sphere_config(grey(), 50, 0, 1e-3), |_, _| ()).image;
+
+        // Without an epsilon bounced rays keep hitting the point they left, darkening the sphere
+        assert!(total_brightness(&acne) < 0.9 * total_brightness(&clean));
+    }
+
+    #[test]
+    fn test_multiple_importance_sampling() {
+        let (_, material_noise) = brightness_and_noise(LightSampling::Material);
+        let (lights_brightness, lights_noise) = brightness_and_noise(LightSampling::Lights);
+        let (mis_brightness, mis_noise) = brightness_and_noise(LightSampling::Mis);
+
+        // Following the floor's material only rarely finds such a small light
+        assert!(mis_noise < 0.1 * material_noise);
+        assert!(mis_noise < 1.2 * lights_noise);
+        assert_approx_eq!(mis_brightness / lights_brightness, 1.0, 0.05);
+    }
+
+    #[test]
+    fn test_light_linking() {
+        for &light_sampling in &[
+            LightSampling::Material,
+            LightSampling::Lights,
+            LightSampling::Mis,
+        ] {
+            let brightness = |floor_group, light_group| {
+                let config = linked_light_config(light_sampling, 0, floor_group, light_group);
+                total_brightness(&render(&config, |_, _| ()).image)
+            };
+            // Lights outside of any group light everything, those in one only light their group
+            assert!(brightness(Some(1), None) > 0.0);
+            assert!(brightness(Some(1), Some(1)) > 0.0);
+            assert_eq!(brightness(Some(2), Some(1)), 0.0);
+            assert_eq!(brightness(None, Some(1)), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_environment_light_sampling() {
+        let render_sky = |light_sampling, num_of_rays, seed| {
+            render(
+                &sky_patch_config(light_sampling, num_of_rays, seed),
+                |_, _| (),
+            )
+            .image
+        };
+        assert!(sky_patch_config(LightSampling::Mis, 1, 0)
+            .environment_light()
+            .is_some());
+        assert!(sky_patch_config(LightSampling::Material, 1, 0)
+            .environment_light()
+            .is_none());
+
+        let noise = |first: &Image, second: &Image| {
+            iproduct!(0..first.height(), 0..first.width())
+                .map(|(row, col)| {
+                    (first.get_pixel(row, col).luminance() - second.get_pixel(row, col).luminance())
+                        .powi(2)
+                })
+                .sum::<Float>()
+        };
+        let material_noise = noise(
+            &render_sky(LightSampling::Material, 4, 0),
+            &render_sky(LightSampling::Material, 4, 1),
+        );
+        let first = render_sky(LightSampling::Mis, 4, 0);
+        let mis_noise = noise(&first, &render_sky(LightSampling::Mis, 4, 1));
+        // Following the floor's material rarely finds the patch, but it is just as bright after
+        // enough samples
+        assert!(mis_noise < 0.1 * material_noise);
+        let converged = render_sky(LightSampling::Material, 512, 0);
+        assert_approx_eq!(
+            total_brightness(&first) / total_brightness(&converged),
+            1.0,
+            0.1
+        );
+    }
 }
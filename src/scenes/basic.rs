@@ -55,6 +55,8 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         -0.45,
         Material::Dielectric {
             refractive_index: 1.5,
+            absorption: None,
+            roughness: 0.0,
         },
     ));
 
@@ -44,6 +44,9 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         emit: Texture::Constant {
             colour: Colour::new(15.0, 15.0, 15.0),
         },
+        strength: 1.0,
+        two_sided: false,
+        spotlight: None,
     };
 
     geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, green).flip());
@@ -44,6 +44,9 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         emit: Texture::Constant {
             colour: Colour::new(15.0, 15.0, 15.0),
         },
+        strength: 1.0,
+        two_sided: false,
+        spotlight: None,
     };
     let metal = Material::Metal {
         albedo: Colour::new(0.8, 0.85, 0.88),
@@ -4,14 +4,15 @@ use crate::data::colour::Colour;
 use crate::data::vector::Vector;
 use crate::world::background::Background;
 use crate::world::geometry::cube::Cube;
-use crate::world::geometry::medium::ConstantMedium;
+use crate::world::geometry::medium::{ConstantMedium, VariableMedium};
 use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
 use crate::world::geometry::Geometry;
 use crate::world::materials::Material;
+use crate::world::texture::perlin::build_noise_config;
 use crate::world::texture::Texture;
 use crate::world::WorldSave;
 
-pub fn build() -> Result<ConfigSave, anyhow::Error> {
+pub fn build(cloud: bool) -> Result<ConfigSave, anyhow::Error> {
     let aspect = 1.0;
 
     let camera = CameraSave::new(
@@ -45,6 +46,9 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         emit: Texture::Constant {
             colour: Colour::new(7.0, 7.0, 7.0),
         },
+        strength: 1.0,
+        two_sided: false,
+        spotlight: None,
     };
 
     geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, green).flip());
@@ -59,35 +63,63 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
     ));
     geometries.push(XyRect::build((0.0, 555.0), (0.0, 555.0), 555.0, white.clone()).flip());
 
-    let box_boundary_a = Cube::build(
-        Vector::new(0.0, 0.0, 0.0),
-        Vector::new(165.0, 165.0, 165.0),
-        white.clone(),
-    )
-    .rotate_y(-18.0)?
-    .translate(Vector::new(130.0, 0.0, 65.0));
-    let box_boundary_b = Cube::build(
-        Vector::new(0.0, 0.0, 0.0),
-        Vector::new(165.0, 330.0, 165.0),
-        white,
-    )
-    .rotate_y(15.0)?
-    .translate(Vector::new(265.0, 0.0, 295.0));
+    if cloud {
+        let cloud_boundary = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(6.0, 4.0, 6.0),
+            white,
+        );
 
-    geometries.push(ConstantMedium::build(
-        box_boundary_a,
-        0.01,
-        Texture::Constant {
-            colour: Colour::new(1.0, 1.0, 1.0),
-        },
-    ));
-    geometries.push(ConstantMedium::build(
-        box_boundary_b,
-        0.01,
-        Texture::Constant {
-            colour: Colour::new(0.0, 0.0, 0.0),
-        },
-    ));
+        // The cloud is built in a small box and scaled up so its noise has cloud sized features
+        let cloud = VariableMedium::build(
+            cloud_boundary,
+            4.0,
+            Texture::Noise {
+                base_colour: Colour::new(1.0, 1.0, 1.0),
+                scale: 1.0,
+                noisiness: 4.0,
+                noise_config: build_noise_config(),
+            },
+            Texture::Constant {
+                colour: Colour::new(1.0, 1.0, 1.0),
+            },
+        );
+        geometries.push(
+            cloud
+                .scale(Vector::new(55.0, 55.0, 55.0))?
+                .translate(Vector::new(112.0, 120.0, 112.0)),
+        );
+    } else {
+        let box_boundary_a = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(165.0, 165.0, 165.0),
+            white.clone(),
+        )
+        .rotate_y(-18.0)?
+        .translate(Vector::new(130.0, 0.0, 65.0));
+        let box_boundary_b = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(165.0, 330.0, 165.0),
+            white,
+        )
+        .rotate_y(15.0)?
+        .translate(Vector::new(265.0, 0.0, 295.0));
+
+        geometries.push(ConstantMedium::build(
+            box_boundary_a,
+            0.01,
+            Texture::Constant {
+                colour: Colour::new(1.0, 1.0, 1.0),
+            },
+        ));
+        geometries.push(ConstantMedium::build(
+            box_boundary_b,
+            0.01,
+            Texture::Constant {
+                colour: Colour::new(0.0, 0.0, 0.0),
+            },
+        ));
+    }
 
     let black = Colour::new(0.0, 0.0, 0.0);
     let background = Background::new(black, black);
@@ -45,9 +45,14 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         emit: Texture::Constant {
             colour: Colour::new(15.0, 15.0, 15.0),
         },
+        strength: 1.0,
+        two_sided: false,
+        spotlight: None,
     };
     let dielectric = Material::Dielectric {
         refractive_index: 1.5,
+        absorption: None,
+        roughness: 0.0,
     };
 
     geometries.push(YzRect::build((0.0, 555.0), (0.0, 555.0), 555.0, green).flip());
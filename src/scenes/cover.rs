@@ -2,6 +2,7 @@ use crate::camera::{CameraSave, Lens};
 use crate::config::ConfigSave;
 use crate::data::colour::Colour;
 use crate::data::vector::Vector;
+use crate::float::Float;
 use crate::pdf::uniform;
 use crate::world::background::Background;
 use crate::world::geometry::sphere::{MovingSphere, Sphere};
@@ -35,6 +36,7 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
             odd: Box::from(Texture::Constant {
                 colour: Colour::new(0.9, 0.9, 0.9),
             }),
+            scale: 10.0,
         }
     } else {
         Texture::Constant {
@@ -56,6 +58,8 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
         1.0,
         Material::Dielectric {
             refractive_index: 1.5,
+            absorption: None,
+            roughness: 0.0,
         },
     ));
     geometries.push(Sphere::build(
@@ -78,11 +82,11 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
 
     for a in -11..11 {
         for b in -11..11 {
-            let choose_mat: f64 = uniform();
+            let choose_mat: Float = uniform();
             let centre = Vector::new(
-                f64::from(a) + 0.9 * uniform::<f64>(),
+                a as Float + 0.9 * uniform::<Float>(),
                 0.2,
-                f64::from(b) + 0.9 * uniform::<f64>(),
+                b as Float + 0.9 * uniform::<Float>(),
             );
 
             if (centre - Vector::new(4.0, 0.2, 0.0)).len() > 0.9 {
@@ -91,15 +95,15 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
                         geometries.push(MovingSphere::build(
                             centre,
                             0.0,
-                            centre + Vector::new(0.0, 0.5 * uniform::<f64>(), 0.0),
+                            centre + Vector::new(0.0, 0.5 * uniform::<Float>(), 0.0),
                             1.0,
                             0.2,
                             Material::Lambertian {
                                 albedo: Texture::Constant {
                                     colour: Colour::new(
-                                        uniform::<f64>() * uniform::<f64>(),
-                                        uniform::<f64>() * uniform::<f64>(),
-                                        uniform::<f64>() * uniform::<f64>(),
+                                        uniform::<Float>() * uniform::<Float>(),
+                                        uniform::<Float>() * uniform::<Float>(),
+                                        uniform::<Float>() * uniform::<Float>(),
                                     ),
                                 },
                             },
@@ -111,9 +115,9 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
                             Material::Lambertian {
                                 albedo: Texture::Constant {
                                     colour: Colour::new(
-                                        uniform::<f64>() * uniform::<f64>(),
-                                        uniform::<f64>() * uniform::<f64>(),
-                                        uniform::<f64>() * uniform::<f64>(),
+                                        uniform::<Float>() * uniform::<Float>(),
+                                        uniform::<Float>() * uniform::<Float>(),
+                                        uniform::<Float>() * uniform::<Float>(),
                                     ),
                                 },
                             },
@@ -125,11 +129,11 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
                         0.2,
                         Material::Metal {
                             albedo: Colour::new(
-                                0.5 * (1.0 + uniform::<f64>()),
-                                0.5 * (1.0 + uniform::<f64>()),
-                                0.5 * (1.0 + uniform::<f64>()),
+                                0.5 * (1.0 + uniform::<Float>()),
+                                0.5 * (1.0 + uniform::<Float>()),
+                                0.5 * (1.0 + uniform::<Float>()),
                             ),
-                            fuzz: 0.5 * uniform::<f64>(),
+                            fuzz: 0.5 * uniform::<Float>(),
                         },
                     ));
                 } else {
@@ -138,6 +142,8 @@ pub fn build(motion_blur: bool, checker_texture: bool) -> Result<ConfigSave, any
                         0.2,
                         Material::Dielectric {
                             refractive_index: 1.5,
+                            absorption: None,
+                            roughness: 0.0,
                         },
                     ));
                 }
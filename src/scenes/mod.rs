@@ -9,6 +9,7 @@ mod cover;
 mod next_week_final;
 mod perlin;
 mod planets;
+mod primitives;
 mod simple_light;
 
 arg_enum! {
@@ -23,9 +24,11 @@ arg_enum! {
         SimpleLight,
         CornellBox,
         CornellSmoke,
+        CornellCloud,
         CornellMetal,
         CornellSphere,
         NextWeekFinal,
+        Primitives,
     }
 }
 
@@ -39,9 +42,11 @@ pub fn build_scene_config(scene: &Scene) -> Result<ConfigSave, anyhow::Error> {
         Scene::Planets => planets::build(),
         Scene::SimpleLight => simple_light::build(),
         Scene::CornellBox => cornell_box::build(),
-        Scene::CornellSmoke => cornell_smoke::build(),
+        Scene::CornellSmoke => cornell_smoke::build(false),
+        Scene::CornellCloud => cornell_smoke::build(true),
         Scene::CornellMetal => cornell_metal::build(),
         Scene::CornellSphere => cornell_sphere::build(),
         Scene::NextWeekFinal => next_week_final::build(),
+        Scene::Primitives => primitives::build(),
     }
 }
@@ -1,7 +1,8 @@
 use crate::camera::{CameraSave, Lens};
 use crate::config::ConfigSave;
-use crate::data::colour::Colour;
+use crate::data::colour::{Colour, ColourSpace};
 use crate::data::vector::Vector;
+use crate::float::Float;
 use crate::pdf::uniform;
 use crate::world::background::Background;
 use crate::world::geometry::bounding_volume_hierarchy::BoundingVolumeHierarchyNode;
@@ -12,7 +13,7 @@ use crate::world::geometry::sphere::{MovingSphere, Sphere};
 use crate::world::geometry::Geometry;
 use crate::world::materials::Material;
 use crate::world::texture::perlin::build_noise_config;
-use crate::world::texture::Texture;
+use crate::world::texture::{Filter, Texture, WrapMode};
 use crate::world::WorldSave;
 
 pub fn build() -> Result<ConfigSave, anyhow::Error> {
@@ -73,11 +74,11 @@ fn ground_boxes() -> Vec<Geometry> {
     for i in 0..nb {
         for j in 0..nb {
             let w = 100.0;
-            let x0 = -1000.0 + (i as f64) * w;
-            let z0 = -1000.0 + (j as f64) * w;
+            let x0 = -1000.0 + (i as Float) * w;
+            let z0 = -1000.0 + (j as Float) * w;
             let y0 = 0.0;
             let x1 = x0 + w;
-            let y1 = 100.0 * (uniform::<f64>() + 0.01);
+            let y1 = 100.0 * (uniform::<Float>() + 0.01);
             let z1 = z0 + w;
             boxlist.push(Cube::build(
                 Vector::new(x0, y0, z0),
@@ -94,6 +95,9 @@ fn light() -> Geometry {
         emit: Texture::Constant {
             colour: Colour::new(7.0, 7.0, 7.0),
         },
+        strength: 1.0,
+        two_sided: false,
+        spotlight: None,
     };
     XzRect::build((123.0, 423.0), (147.0, 412.0), 554.0, light).flip()
 }
@@ -115,6 +119,8 @@ fn dielectric_a() -> Geometry {
         50.0,
         Material::Dielectric {
             refractive_index: 1.5,
+            absorption: None,
+            roughness: 0.0,
         },
     )
 }
@@ -136,6 +142,8 @@ fn subsurface_material_a() -> (Geometry, Geometry) {
         70.0,
         Material::Dielectric {
             refractive_index: 1.5,
+            absorption: None,
+            roughness: 0.0,
         },
     );
     let medium = ConstantMedium::build(
@@ -154,6 +162,8 @@ fn subsurface_material_b() -> (Geometry, Geometry) {
         5000.0,
         Material::Dielectric {
             refractive_index: 1.5,
+            absorption: None,
+            roughness: 0.0,
         },
     );
     let medium = ConstantMedium::build(
@@ -170,6 +180,9 @@ fn earth() -> Geometry {
     let material = Material::Lambertian {
         albedo: Texture::Image {
             asset_name: String::from("earth.jpg"),
+            filter: Filter::Bilinear,
+            wrap_mode: WrapMode::Clamp,
+            colour_space: ColourSpace::Linear,
         },
     };
     Sphere::build(Vector::new(400.0, 200.0, 400.0), 100.0, material)
@@ -199,9 +212,9 @@ fn sphere_cube() -> Vec<Geometry> {
     for _ in 0..1000 {
         boxlist.push(Sphere::build(
             Vector::new(
-                165.0 * uniform::<f64>(),
-                165.0 * uniform::<f64>(),
-                165.0 * uniform::<f64>(),
+                165.0 * uniform::<Float>(),
+                165.0 * uniform::<Float>(),
+                165.0 * uniform::<Float>(),
             ),
             10.0,
             white.clone(),
@@ -1,12 +1,12 @@
 use crate::camera::{CameraSave, Lens};
 use crate::config::ConfigSave;
-use crate::data::colour::Colour;
+use crate::data::colour::{Colour, ColourSpace};
 use crate::data::vector::Vector;
 use crate::world::background::Background;
 use crate::world::geometry::sphere::Sphere;
 use crate::world::geometry::Geometry;
 use crate::world::materials::Material;
-use crate::world::texture::Texture;
+use crate::world::texture::{Filter, Texture, WrapMode};
 use crate::world::WorldSave;
 
 pub fn build() -> Result<ConfigSave, anyhow::Error> {
@@ -30,6 +30,9 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         Material::Lambertian {
             albedo: Texture::Image {
                 asset_name: String::from("jupiter.jpg"),
+                filter: Filter::Bilinear,
+                wrap_mode: WrapMode::Clamp,
+                colour_space: ColourSpace::Linear,
             },
         },
     ));
@@ -39,6 +42,9 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         Material::Lambertian {
             albedo: Texture::Image {
                 asset_name: String::from("earth.jpg"),
+                filter: Filter::Bilinear,
+                wrap_mode: WrapMode::Clamp,
+                colour_space: ColourSpace::Linear,
             },
         },
     ));
@@ -48,6 +54,9 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         Material::Lambertian {
             albedo: Texture::Image {
                 asset_name: String::from("moon.jpg"),
+                filter: Filter::Bilinear,
+                wrap_mode: WrapMode::Clamp,
+                colour_space: ColourSpace::Linear,
             },
         },
     ));
@@ -57,6 +66,9 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         Material::Lambertian {
             albedo: Texture::Image {
                 asset_name: String::from("mars.jpg"),
+                filter: Filter::Bilinear,
+                wrap_mode: WrapMode::Clamp,
+                colour_space: ColourSpace::Linear,
             },
         },
     ));
@@ -66,6 +78,9 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
         Material::Lambertian {
             albedo: Texture::Image {
                 asset_name: String::from("earth_night.jpg"),
+                filter: Filter::Bilinear,
+                wrap_mode: WrapMode::Clamp,
+                colour_space: ColourSpace::Linear,
             },
         },
     ));
@@ -0,0 +1,215 @@
+use crate::camera::{CameraSave, Lens};
+use crate::config::ConfigSave;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::background::Background;
+use crate::world::geometry::animated::{Animated, TransformKeyframe};
+use crate::world::geometry::bilinear_patch::BilinearPatch;
+use crate::world::geometry::capsule::Capsule;
+use crate::world::geometry::cone::Cone;
+use crate::world::geometry::csg::{Csg, CsgOperation};
+use crate::world::geometry::cube::Cube;
+use crate::world::geometry::cylinder::Cylinder;
+use crate::world::geometry::disk::Disk;
+use crate::world::geometry::ellipsoid::Ellipsoid;
+use crate::world::geometry::instance::Instance;
+use crate::world::geometry::plane::Plane;
+use crate::world::geometry::quad::Quad;
+use crate::world::geometry::sdf::{Sdf, SdfShape};
+use crate::world::geometry::sphere::Sphere;
+use crate::world::geometry::torus::Torus;
+use crate::world::geometry::Geometry;
+use crate::world::materials::{Absorption, Material};
+use crate::world::texture::Texture;
+use crate::world::WorldSave;
+
+fn lambertian(r: Float, g: Float, b: Float) -> Material {
+    Material::Lambertian {
+        albedo: Texture::Constant {
+            colour: Colour::new(r, g, b),
+        },
+    }
+}
+
+fn light(r: Float, g: Float, b: Float) -> Material {
+    Material::DiffuseLight {
+        emit: Texture::Constant {
+            colour: Colour::new(r, g, b),
+        },
+        strength: 1.0,
+        two_sided: false,
+        spotlight: None,
+    }
+}
+
+/// Two rows of the analytic and implicit primitives on the floor, lit by an area light
+/// with a warm fill light that only reaches the torus
+pub fn build() -> Result<ConfigSave, anyhow::Error> {
+    let aspect = 16.0 / 9.0;
+
+    let camera = CameraSave::new(
+        &Vector::new(0.0, 3.5, 10.0),
+        &Vector::new(0.0, 0.7, 0.0),
+        &Vector::new(0.0, 1.0, 0.0),
+        aspect,
+        Lens::new(35.0, 0.0, 10.0),
+        0.0,
+        1.0,
+    );
+
+    let mut geometries: Vec<Geometry> = Vec::with_capacity(16);
+
+    geometries.push(Plane::build(
+        Vector::new(0.0, 0.0, 0.0),
+        Vector::new(0.0, 1.0, 0.0),
+        lambertian(0.4, 0.45, 0.35),
+    ));
+    geometries.push(Quad::build(
+        Vector::new(-4.0, 7.0, -2.0),
+        Vector::new(8.0, 0.0, 0.0),
+        Vector::new(0.0, 0.0, 6.0),
+        light(4.0, 4.0, 4.0),
+    ));
+    geometries.push(
+        Disk::build(
+            Vector::new(6.0, 3.0, 2.0),
+            Vector::new(-1.0, -0.3, -0.3),
+            1.0,
+            light(8.0, 5.0, 2.0),
+        )
+        .in_light_group(1),
+    );
+
+    // The back row
+    geometries.push(Cone::build(
+        Vector::new(-4.0, 0.0, -1.5),
+        0.7,
+        1.6,
+        true,
+        lambertian(0.8, 0.3, 0.1),
+    ));
+    geometries.push(Cylinder::build(
+        Vector::new(-2.0, 0.0, -1.5),
+        0.6,
+        1.4,
+        true,
+        Material::RoughMetal {
+            albedo: Colour::new(0.8, 0.8, 0.85),
+            roughness: 0.2,
+        },
+    ));
+    geometries.push(Capsule::build(
+        Vector::new(-0.4, 0.5, -1.5),
+        Vector::new(0.4, 1.3, -1.5),
+        0.4,
+        lambertian(0.2, 0.4, 0.8),
+    ));
+    geometries.push(Ellipsoid::build(
+        Vector::new(2.0, 0.6, -1.5),
+        Vector::new(0.9, 0.6, 0.5),
+        lambertian(0.7, 0.7, 0.2),
+    ));
+    geometries.push(
+        Torus::build(
+            Vector::new(0.0, 0.0, 0.0),
+            0.6,
+            0.2,
+            lambertian(0.8, 0.8, 0.8),
+        )
+        .rotate_x(70.0)?
+        .translate(Vector::new(4.0, 0.8, -1.5))
+        .in_light_group(1),
+    );
+
+    // The front row
+    let cube = Cube::build(
+        Vector::new(-0.6, -0.6, -0.6),
+        Vector::new(0.6, 0.6, 0.6),
+        lambertian(0.6, 0.1, 0.1),
+    )
+    .rotate_axis(Vector::new(1.0, 1.0, 0.0), 30.0)?;
+    geometries.push(
+        Csg::build(
+            CsgOperation::Difference,
+            cube,
+            Sphere::build(Vector::new(0.0, 0.0, 0.0), 0.75, lambertian(0.9, 0.9, 0.9)),
+        )
+        .translate(Vector::new(-4.0, 0.9, 1.5)),
+    );
+    geometries.push(Sdf::build(
+        SdfShape::SmoothUnion {
+            a: Box::from(SdfShape::Sphere {
+                centre: Vector::new(-2.0, 1.1, 1.5),
+                radius: 0.4,
+            }),
+            b: Box::from(SdfShape::Cuboid {
+                centre: Vector::new(-2.0, 0.4, 1.5),
+                half_size: Vector::new(0.5, 0.4, 0.5),
+                rounding: 0.05,
+            }),
+            smoothness: 0.3,
+        },
+        lambertian(0.3, 0.7, 0.4),
+    ));
+    geometries.push(Sphere::build(
+        Vector::new(0.0, 0.7, 1.5),
+        0.7,
+        Material::Dielectric {
+            refractive_index: 1.5,
+            absorption: Some(Absorption::new(Colour::new(0.4, 0.7, 0.9), 1.0)),
+            roughness: 0.0,
+        },
+    ));
+    geometries.push(BilinearPatch::build(
+        [
+            Vector::new(1.4, 0.2, 0.9),
+            Vector::new(2.6, 1.2, 0.9),
+            Vector::new(2.6, 0.2, 2.1),
+            Vector::new(1.4, 1.2, 2.1),
+        ],
+        lambertian(0.9, 0.5, 0.7),
+    ));
+    geometries.push(Animated::build(
+        Sphere::build(Vector::new(0.0, 0.0, 0.0), 0.4, lambertian(0.9, 0.9, 0.2)),
+        vec![
+            TransformKeyframe::new(
+                0.0,
+                Vector::new(3.7, 0.4, 1.5),
+                Vector::new(0.0, 1.0, 0.0),
+                0.0,
+            ),
+            TransformKeyframe::new(
+                1.0,
+                Vector::new(4.5, 0.4, 1.5),
+                Vector::new(0.0, 1.0, 0.0),
+                0.0,
+            ),
+        ],
+    )?);
+
+    // Small copies of one cylinder lying along the front edge
+    let peg = Cylinder::build(
+        Vector::new(0.0, 0.0, 0.0),
+        0.1,
+        0.5,
+        true,
+        lambertian(0.5, 0.3, 0.2),
+    )
+    .rotate_z(90.0)?;
+    for i in 0..5 {
+        geometries.push(Instance::build(
+            "peg",
+            Vector::new(1.0, 1.0, 1.0),
+            Vector::new(0.0, 1.0, 0.0),
+            20.0 * i as Float,
+            Vector::new(-2.0 + i as Float, 0.1, 3.2),
+        )?);
+    }
+
+    let background = Background::solid(Colour::new(0.02, 0.02, 0.03));
+
+    let world = WorldSave::new(background, geometries).with_prototype("peg", peg);
+
+    Ok(ConfigSave::new(aspect, camera, world))
+}
@@ -57,6 +57,9 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
             emit: Texture::Constant {
                 colour: Colour::new(4.0, 4.0, 4.0),
             },
+            strength: 1.0,
+            two_sided: false,
+            spotlight: None,
         },
     ));
     geometries.push(XyRect::build(
@@ -67,6 +70,9 @@ pub fn build() -> Result<ConfigSave, anyhow::Error> {
             emit: Texture::Constant {
                 colour: Colour::new(4.0, 4.0, 4.0),
             },
+            strength: 1.0,
+            two_sided: false,
+            spotlight: None,
         },
     ));
 
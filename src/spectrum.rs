@@ -0,0 +1,137 @@
+//! Spectral rendering traces every sample at a single random wavelength so materials whose
+//! behaviour changes with wavelength, such as dispersive glass, can split light into colours.
+//!
+//! The scene itself stays RGB. Each sample is tinted by the colour of its wavelength, found from
+//! the CIE 1931 colour matching functions, and the tints are normalised to average to white so a
+//! scene without any dispersion renders the same as it does in RGB.
+
+use crate::data::colour::Colour;
+use crate::float::Float;
+use crate::pdf::uniform;
+use std::sync::OnceLock;
+
+// Nanometres, the range of visible light sampled
+pub const MIN_WAVELENGTH: Float = 380.0;
+pub const MAX_WAVELENGTH: Float = 730.0;
+// Wavelength dispersive materials use when rendering in RGB
+pub const REFERENCE_WAVELENGTH: Float = 550.0;
+
+// hc/k in nanometre Kelvin, for the spectrum of a blackbody
+const PLANCK_SECOND_CONSTANT: Float = 1.438_777e7;
+
+// Converts CIE XYZ into linear sRGB with a D65 white point
+const XYZ_TO_RGB: [[Float; 3]; 3] = [
+    [3.2406, -1.5372, -0.4986],
+    [-0.9689, 1.8758, 0.0415],
+    [0.0557, -0.2040, 1.0570],
+];
+
+pub fn sample_wavelength() -> Float {
+    MIN_WAVELENGTH + (MAX_WAVELENGTH - MIN_WAVELENGTH) * uniform::<Float>()
+}
+
+/// What a sample at `wavelength` adds to each colour channel for every unit of light it carries
+pub fn wavelength_weight(wavelength: Float) -> Colour {
+    let colour = wavelength_colour(wavelength);
+    let average = average_colour();
+    Colour::new(
+        colour.r() / average.r(),
+        colour.g() / average.g(),
+        colour.b() / average.b(),
+    )
+}
+
+/// The average colour of all sampled wavelengths, found once by integrating over them
+fn average_colour() -> &'static Colour {
+    static AVERAGE: OnceLock<Colour> = OnceLock::new();
+    AVERAGE.get_or_init(|| {
+        let steps = (MAX_WAVELENGTH - MIN_WAVELENGTH) as usize;
+        let sum: Colour = (0..steps)
+            .map(|i| wavelength_colour(MIN_WAVELENGTH + i as Float + 0.5))
+            .sum();
+        sum / steps as Float
+    })
+}
+
+/// The linear sRGB colour of the light given off by a blackbody at `temperature` Kelvin, scaled to
+/// a luminance of 1. Colours outside of the sRGB gamut have negative channels
+pub fn blackbody_colour(temperature: Float) -> Colour {
+    // Planck's law, dropping the constant factor that is normalised away at the end
+    let radiance = |wavelength: Float| {
+        1.0 / (wavelength.powi(5)
+            * ((PLANCK_SECOND_CONSTANT / (wavelength * temperature)).exp() - 1.0))
+    };
+    let steps = (MAX_WAVELENGTH - MIN_WAVELENGTH) as usize;
+    let (x, y, z) = (0..steps).map(|i| MIN_WAVELENGTH + i as Float + 0.5).fold(
+        (0.0, 0.0, 0.0),
+        |(x, y, z), wavelength| {
+            let (dx, dy, dz) = colour_matching(wavelength);
+            let radiance = radiance(wavelength);
+            (x + dx * radiance, y + dy * radiance, z + dz * radiance)
+        },
+    );
+    let row = |row: &[Float; 3]| (row[0] * x + row[1] * y + row[2] * z) / y;
+    Colour::new(
+        row(&XYZ_TO_RGB[0]),
+        row(&XYZ_TO_RGB[1]),
+        row(&XYZ_TO_RGB[2]),
+    )
+}
+
+/// The linear sRGB colour of pure light at `wavelength`. Colours outside of the sRGB gamut are
+/// clipped to it
+fn wavelength_colour(wavelength: Float) -> Colour {
+    let (x, y, z) = colour_matching(wavelength);
+    let row = |row: &[Float; 3]| (row[0] * x + row[1] * y + row[2] * z).max(0.0);
+    Colour::new(
+        row(&XYZ_TO_RGB[0]),
+        row(&XYZ_TO_RGB[1]),
+        row(&XYZ_TO_RGB[2]),
+    )
+}
+
+/// The multi-lobe fit of the CIE 1931 standard observer by Wyman, Sloan and Shirley
+fn colour_matching(wavelength: Float) -> (Float, Float, Float) {
+    let lobe = |mean: Float, below: Float, above: Float| {
+        let spread = if wavelength < mean { below } else { above };
+        (-0.5 * ((wavelength - mean) / spread).powi(2)).exp()
+    };
+
+    let x = 1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+        - 0.065 * lobe(501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_wavelength_weights_average_to_white() {
+        let steps = 3500;
+        let step = (MAX_WAVELENGTH - MIN_WAVELENGTH) / steps as Float;
+        let sum: Colour = (0..steps)
+            .map(|i| wavelength_weight(MIN_WAVELENGTH + (i as Float + 0.5) * step))
+            .sum();
+        let average = sum / steps as Float;
+
+        assert_approx_eq!(average.r(), 1.0, 1e-3);
+        assert_approx_eq!(average.g(), 1.0, 1e-3);
+        assert_approx_eq!(average.b(), 1.0, 1e-3);
+    }
+
+    #[test]
+    fn test_wavelength_colours() {
+        let red = wavelength_colour(650.0);
+        assert!(red.r() > red.g() && red.r() > red.b());
+
+        let green = wavelength_colour(530.0);
+        assert!(green.g() > green.r() && green.g() > green.b());
+
+        let blue = wavelength_colour(450.0);
+        assert!(blue.b() > blue.r() && blue.b() > blue.g());
+    }
+}
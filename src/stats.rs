@@ -0,0 +1,129 @@
+//! Counts of the work done by a render, for comparing how fast scenes and BVHs are to trace.
+//!
+//! Only failed rays are always counted. Everything else is only counted when built with the
+//! `stats` feature, otherwise the counters stay at 0 and cost nothing. Intersection tests happen
+//! deep inside the geometry, so they are counted per thread and gathered into the render's
+//! counters after every sample.
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[cfg(feature = "stats")]
+thread_local! {
+    static INTERSECTION_TESTS: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+    static BVH_NODE_TESTS: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+/// Called every time a ray is tested against a primitive
+#[inline]
+pub fn count_intersection_test() {
+    #[cfg(feature = "stats")]
+    INTERSECTION_TESTS.with(|count| count.set(count.get() + 1));
+}
+
+/// Called every time a ray is tested against the bounding box of a BVH node
+#[inline]
+pub fn count_bvh_node_test() {
+    #[cfg(feature = "stats")]
+    BVH_NODE_TESTS.with(|count| count.set(count.get() + 1));
+}
+
+/// Totals for a whole render, shared by every rendering thread
+#[derive(Debug, Default)]
+pub struct RenderCounters {
+    failed_rays: AtomicUsize,
+    primary_rays: AtomicU64,
+    scattered_rays: AtomicU64,
+    intersection_tests: AtomicU64,
+    bvh_node_tests: AtomicU64,
+}
+
+impl RenderCounters {
+    pub fn add_failed_ray(&self) {
+        self.failed_rays.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[inline]
+    pub fn add_primary_ray(&self) {
+        #[cfg(feature = "stats")]
+        self.primary_rays.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn add_scattered_ray(&self) {
+        #[cfg(feature = "stats")]
+        self.scattered_rays.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Moves the tests counted by the current thread into the totals
+    #[inline]
+    pub fn gather_thread_counts(&self) {
+        #[cfg(feature = "stats")]
+        {
+            let intersection_tests = INTERSECTION_TESTS.with(|count| count.replace(0));
+            let bvh_node_tests = BVH_NODE_TESTS.with(|count| count.replace(0));
+            self.intersection_tests
+                .fetch_add(intersection_tests, Ordering::Relaxed);
+            self.bvh_node_tests
+                .fetch_add(bvh_node_tests, Ordering::Relaxed);
+        }
+    }
+
+    pub fn failed_rays(&self) -> usize {
+        self.failed_rays.load(Ordering::SeqCst)
+    }
+
+    pub fn stats(&self, elapsed: Duration) -> RenderStats {
+        RenderStats {
+            primary_rays: self.primary_rays.load(Ordering::SeqCst),
+            scattered_rays: self.scattered_rays.load(Ordering::SeqCst),
+            intersection_tests: self.intersection_tests.load(Ordering::SeqCst),
+            bvh_node_tests: self.bvh_node_tests.load(Ordering::SeqCst),
+            elapsed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderStats {
+    // Rays sent from the camera
+    pub primary_rays: u64,
+    // Rays that carried on after bouncing off or through a surface
+    pub scattered_rays: u64,
+    // Rays tested against a primitive, including those towards the lights
+    pub intersection_tests: u64,
+    // Rays tested against the bounding box of a BVH node
+    pub bvh_node_tests: u64,
+    // Wall clock time spent tracing
+    pub elapsed: Duration,
+}
+
+impl RenderStats {
+    /// Whether the ray and intersection counts were collected
+    pub fn enabled() -> bool {
+        cfg!(feature = "stats")
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        let rays = self.primary_rays + self.scattered_rays;
+        write!(
+            f,
+            "{} primary rays, {} scattered rays, {} intersection tests and {} BVH node tests in \
+             {:.2}s ({:.0} rays/s)",
+            self.primary_rays,
+            self.scattered_rays,
+            self.intersection_tests,
+            self.bvh_node_tests,
+            seconds,
+            if seconds > 0.0 {
+                rays as f64 / seconds
+            } else {
+                0.0
+            }
+        )
+    }
+}
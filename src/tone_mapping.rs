@@ -0,0 +1,212 @@
+use crate::data::colour::Colour;
+use crate::data::image::Image;
+use crate::float::Float;
+use std::str::FromStr;
+
+arg_enum! {
+    /// Compresses the unbounded radiance gathered by the renderer into the displayable `[0, 1]`
+    /// range before gamma correction
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ToneMapping {
+        None,
+        Reinhard,
+        AcesFilmic,
+    }
+}
+
+/// Encodes the tone mapped linear colours for display, channel by channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransferFunction {
+    // Raises each channel to the power of 1 / gamma
+    Gamma(Float),
+    // The piecewise sRGB curve, which is linear close to black instead of a pure power curve
+    Srgb,
+}
+
+impl Default for TransferFunction {
+    fn default() -> Self {
+        TransferFunction::Gamma(2.0)
+    }
+}
+
+impl TransferFunction {
+    pub fn encode(self, channel: Float) -> Float {
+        match self {
+            TransferFunction::Gamma(gamma) => channel.powf(1.0 / gamma),
+            TransferFunction::Srgb if channel <= 0.003_130_8 => 12.92 * channel,
+            TransferFunction::Srgb => 1.055 * channel.powf(1.0 / 2.4) - 0.055,
+        }
+    }
+
+    pub fn apply(self, colour: &Colour) -> Colour {
+        Colour::new(
+            self.encode(colour.r()),
+            self.encode(colour.g()),
+            self.encode(colour.b()),
+        )
+    }
+}
+
+/// Either `srgb` or the gamma to use, such as `2.2`
+impl FromStr for TransferFunction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("srgb") {
+            return Ok(TransferFunction::Srgb);
+        }
+        match s.parse::<Float>() {
+            Ok(gamma) if gamma > 0.0 && gamma.is_finite() => Ok(TransferFunction::Gamma(gamma)),
+            _ => Err(format!("unknown transfer function {}", s)),
+        }
+    }
+}
+
+// Stephen Hill's fit of the ACES reference rendering and output transforms. The input matrix
+// moves from linear sRGB into the space the curve was fitted in, the output matrix moves back
+const ACES_INPUT: [[Float; 3]; 3] = [
+    [0.59719, 0.35458, 0.04823],
+    [0.07600, 0.90834, 0.01566],
+    [0.02840, 0.13383, 0.83777],
+];
+const ACES_OUTPUT: [[Float; 3]; 3] = [
+    [1.60475, -0.53108, -0.07367],
+    [-0.10208, 1.10813, -0.00605],
+    [-0.00327, -0.07276, 1.07602],
+];
+
+impl ToneMapping {
+    pub fn apply(self, colour: &Colour) -> Colour {
+        match self {
+            ToneMapping::None => *colour,
+            ToneMapping::Reinhard => Colour::new(
+                reinhard(colour.r()),
+                reinhard(colour.g()),
+                reinhard(colour.b()),
+            ),
+            ToneMapping::AcesFilmic => aces_filmic(colour),
+        }
+    }
+
+    /// Tone maps and encodes the linear colours of a render so it can be stored in 8 bits
+    pub fn display_image(self, image: &Image, transfer_function: TransferFunction) -> Image {
+        image.map(|colour| transfer_function.apply(&self.apply(colour)))
+    }
+}
+
+/// Scales the linear colours of a render by `exposure` stops, each of which doubles the brightness
+pub fn expose(image: &Image, exposure: Float) -> Image {
+    let scale = Float::powf(2.0, exposure);
+    image.map(|colour| scale * colour)
+}
+
+fn reinhard(channel: Float) -> Float {
+    channel / (1.0 + channel)
+}
+
+fn aces_filmic(colour: &Colour) -> Colour {
+    let colour = transform(&ACES_INPUT, colour);
+    let colour = Colour::new(
+        aces_curve(colour.r()),
+        aces_curve(colour.g()),
+        aces_curve(colour.b()),
+    );
+    let colour = transform(&ACES_OUTPUT, &colour);
+    Colour::new(
+        colour.r().clamp(0.0, 1.0),
+        colour.g().clamp(0.0, 1.0),
+        colour.b().clamp(0.0, 1.0),
+    )
+}
+
+fn aces_curve(channel: Float) -> Float {
+    let numerator = channel * (channel + 0.024_578_6) - 0.000_090_537;
+    let denominator = channel * (0.983_729 * channel + 0.432_951) + 0.238_081;
+    numerator / denominator
+}
+
+fn transform(matrix: &[[Float; 3]; 3], colour: &Colour) -> Colour {
+    let row = |row: &[Float; 3]| row[0] * colour.r() + row[1] * colour.g() + row[2] * colour.b();
+    Colour::new(row(&matrix[0]), row(&matrix[1]), row(&matrix[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::image::Pixel;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_none_is_identity() {
+        let colour = Colour::new(4.0, 0.5, 0.0);
+        assert_eq!(ToneMapping::None.apply(&colour), colour);
+    }
+
+    #[test]
+    fn test_exposure_doubles_per_stop() {
+        let pixels = vec![
+            Pixel::new(0, 0, Colour::new(0.25, 1.0, 3.0)),
+            Pixel::new(0, 1, Colour::new(0.0, 0.5, 8.0)),
+        ];
+        let image = Image::from(&pixels);
+
+        assert_eq!(expose(&image, 0.0), image);
+        let brighter = expose(&image, 1.0);
+        assert_eq!(*brighter.get_pixel(0, 0), Colour::new(0.5, 2.0, 6.0));
+        assert_eq!(*brighter.get_pixel(0, 1), Colour::new(0.0, 1.0, 16.0));
+        let darker = expose(&image, -2.0);
+        assert_eq!(*darker.get_pixel(0, 1), Colour::new(0.0, 0.125, 2.0));
+    }
+
+    #[test]
+    fn test_transfer_functions() {
+        // Gamma 2 is the square root
+        assert_approx_eq!(TransferFunction::default().encode(0.25), 0.5);
+        assert_approx_eq!(TransferFunction::Gamma(2.2).encode(0.5), 0.7297, 1e-4);
+
+        // sRGB is linear close to black, where a power curve is much steeper, and meets it near
+        // the middle
+        assert_approx_eq!(TransferFunction::Srgb.encode(0.002), 12.92 * 0.002);
+        assert!(TransferFunction::Srgb.encode(0.002) < TransferFunction::Gamma(2.2).encode(0.002));
+        assert_approx_eq!(TransferFunction::Srgb.encode(0.214), 0.5, 1e-3);
+        assert_approx_eq!(TransferFunction::Srgb.encode(1.0), 1.0);
+
+        // Both pieces of the sRGB curve meet
+        let knee = 0.003_130_8;
+        assert_approx_eq!(
+            1.055 * Float::powf(knee, 1.0 / 2.4) - 0.055,
+            12.92 * knee,
+            1e-6
+        );
+    }
+
+    #[test]
+    fn test_parse_transfer_function() {
+        assert_eq!("Srgb".parse(), Ok(TransferFunction::Srgb));
+        assert_eq!("2.2".parse(), Ok(TransferFunction::Gamma(2.2)));
+        assert!("0".parse::<TransferFunction>().is_err());
+        assert!("linear".parse::<TransferFunction>().is_err());
+    }
+
+    #[test]
+    fn test_reinhard() {
+        let colour = ToneMapping::Reinhard.apply(&Colour::new(1.0, 3.0, 0.0));
+        assert_approx_eq!(colour.r(), 0.5);
+        assert_approx_eq!(colour.g(), 0.75);
+        assert_approx_eq!(colour.b(), 0.0);
+    }
+
+    #[test]
+    fn test_aces_filmic_rolls_off_highlights() {
+        let black = ToneMapping::AcesFilmic.apply(&Colour::new(0.0, 0.0, 0.0));
+        assert_approx_eq!(black.len(), 0.0, 1e-3);
+
+        let white = ToneMapping::AcesFilmic.apply(&Colour::new(100.0, 100.0, 100.0));
+        assert!(white.r() > 0.95 && white.r() <= 1.0);
+
+        // Bright saturated colours drift towards white instead of clipping to pure red
+        let red = ToneMapping::AcesFilmic.apply(&Colour::new(50.0, 5.0, 5.0));
+        assert!(red.r() <= 1.0);
+        assert!(red.g() / red.r() > 0.1);
+    }
+}
@@ -1,21 +1,277 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
 use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::pdf::EnvironmentDistribution;
+use crate::world::geometry::sphere::sphere_texture_coords;
+use crate::world::texture::Texture;
 
+// Environment maps that are not images are sampled for importance sampling on a grid this size
+const DEFAULT_DISTRIBUTION_ROWS: usize = 128;
+// Larger images are averaged down to at most this many rows of cells, and twice as many columns
+const MAX_DISTRIBUTION_ROWS: usize = 512;
+
+/// What a ray sees when it escapes the scene without hitting anything
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Background {
-    top: Colour,
-    bottom: Colour,
+#[serde(from = "SavedBackground")]
+pub enum Background {
+    /// The same colour in every direction, such as black for product shots
+    SolidColour { colour: Colour },
+    /// Blends vertically from `bottom` to `top` based on the ray direction
+    Gradient { top: Colour, bottom: Colour },
+    /// An equirectangular map, usually an image texture, wrapped around the scene
+    Environment { texture: Texture },
+}
+
+/// Backgrounds are loaded either tagged with their kind or as the bare `top` and `bottom` of a
+/// gradient, which is how they were saved when gradients were the only kind
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedBackground {
+    Gradient { top: Colour, bottom: Colour },
+    Tagged(TaggedBackground),
+}
+
+#[derive(Deserialize)]
+enum TaggedBackground {
+    SolidColour { colour: Colour },
+    Gradient { top: Colour, bottom: Colour },
+    Environment { texture: Texture },
+}
+
+impl From<SavedBackground> for Background {
+    fn from(saved: SavedBackground) -> Self {
+        match saved {
+            SavedBackground::Gradient { top, bottom }
+            | SavedBackground::Tagged(TaggedBackground::Gradient { top, bottom }) => {
+                Background::Gradient { top, bottom }
+            }
+            SavedBackground::Tagged(TaggedBackground::SolidColour { colour }) => {
+                Background::SolidColour { colour }
+            }
+            SavedBackground::Tagged(TaggedBackground::Environment { texture }) => {
+                Background::Environment { texture }
+            }
+        }
+    }
 }
 
 impl Background {
     pub fn new(top: Colour, bottom: Colour) -> Background {
-        Background { top, bottom }
+        Background::Gradient { top, bottom }
+    }
+
+    pub fn solid(colour: Colour) -> Background {
+        Background::SolidColour { colour }
+    }
+
+    pub fn colour(&self, ray: &Ray, assets: &Assets) -> Colour {
+        let unit_direction = ray.direction().unit_vector();
+
+        match self {
+            Background::SolidColour { colour } => *colour,
+            Background::Gradient { top, bottom } => {
+                let t = 0.5 * (unit_direction.y() + 1.0);
+                linear_interpolation(t, bottom, top)
+            }
+            Background::Environment { texture } => {
+                // Map the direction to latitude and longitude exactly like a texture on a sphere
+                let texture_coords =
+                    sphere_texture_coords(&unit_direction, &Vector::new(0.0, 0.0, 0.0), 1.0);
+                texture.value(texture_coords, &unit_direction, assets)
+            }
+        }
+    }
+
+    /// Whether no light at all comes from the background. Environment maps are assumed to be lit
+    pub fn is_black(&self) -> bool {
+        let black = Colour::new(0.0, 0.0, 0.0);
+        match self {
+            Background::SolidColour { colour } => *colour == black,
+            Background::Gradient { top, bottom } => *top == black && *bottom == black,
+            Background::Environment { .. } => false,
+        }
+    }
+
+    /// The distribution that sends rays towards the bright parts of an environment map, so it
+    /// can be sampled like a light. Solid colours and gradients are smooth enough to be found by
+    /// the materials
+    pub fn distribution(&self, assets: &Assets) -> Option<EnvironmentDistribution> {
+        let texture = match self {
+            Background::SolidColour { .. } | Background::Gradient { .. } => return None,
+            Background::Environment { texture } => texture,
+        };
+        let (rows, cols) = match texture {
+            Texture::Image { asset_name, .. } => {
+                let image = assets.get_asset(asset_name);
+                (image.height() as usize, image.width() as usize)
+            }
+            _ => (DEFAULT_DISTRIBUTION_ROWS, 2 * DEFAULT_DISTRIBUTION_ROWS),
+        };
+        let cell_rows = rows.min(MAX_DISTRIBUTION_ROWS);
+        let cell_cols = cols.min(2 * MAX_DISTRIBUTION_ROWS);
+        // Every cell averages enough samples to see each texel it covers, so a small bright sun
+        // is not missed between them
+        let samples = Float::max(
+            (rows as Float / cell_rows as Float).ceil(),
+            (cols as Float / cell_cols as Float).ceil(),
+        ) as usize;
+
+        let origin = Vector::new(0.0, 0.0, 0.0);
+        let luminance: Vec<Float> = iproduct!(0..cell_rows, 0..cell_cols)
+            .map(|(row, col)| {
+                let total: Float = iproduct!(0..samples, 0..samples)
+                    .map(|(i, j)| {
+                        let direction = EnvironmentDistribution::direction(
+                            (row as Float + (i as Float + 0.5) / samples as Float)
+                                / cell_rows as Float,
+                            (col as Float + (j as Float + 0.5) / samples as Float)
+                                / cell_cols as Float,
+                        );
+                        self.colour(&Ray::new(origin, direction, 0.0), assets)
+                            .luminance()
+                    })
+                    .sum();
+                total / (samples * samples) as Float
+            })
+            .collect();
+        EnvironmentDistribution::build(&luminance, cell_rows, cell_cols)
+    }
+
+    pub fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        match self {
+            Background::SolidColour { .. } | Background::Gradient { .. } => Ok(()),
+            Background::Environment { texture } => texture.validate(assets),
+        }
+    }
+}
+
+/// The classic sky, white below the horizon blending to light blue overhead
+impl Default for Background {
+    fn default() -> Self {
+        Background::new(Colour::new(0.5, 0.7, 1.0), Colour::new(1.0, 1.0, 1.0))
+    }
+}
+
+fn linear_interpolation(t: Float, colour_a: &Colour, colour_b: &Colour) -> Colour {
+    (1.0 - t) * colour_a + t * colour_b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::ColourSpace;
+    use crate::world::texture::{Axis, Filter, WrapMode};
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_gradient_colour() {
+        let assets = Assets::new(&[]).unwrap();
+        let background = Background::new(Colour::new(0.0, 0.0, 1.0), Colour::new(1.0, 0.0, 0.0));
+        let origin = Vector::new(0.0, 0.0, 0.0);
+
+        let up = Ray::new(origin, Vector::new(0.0, 2.0, 0.0), 0.0);
+        assert_eq!(background.colour(&up, &assets), Colour::new(0.0, 0.0, 1.0));
+
+        let horizon = Ray::new(origin, Vector::new(1.0, 0.0, 0.0), 0.0);
+        let colour = background.colour(&horizon, &assets);
+        assert_approx_eq!(colour.r(), 0.5);
+        assert_approx_eq!(colour.b(), 0.5);
+    }
+
+    #[test]
+    fn test_untagged_gradient_still_loads() {
+        let bare = "top:\n  r: 0.0\n  g: 0.0\n  b: 1.0\nbottom:\n  r: 1.0\n  g: 0.0\n  b: 0.0";
+        let background: Background = serde_yaml::from_str(bare).unwrap();
+        assert!(matches!(
+            background,
+            Background::Gradient { top, .. } if top == Colour::new(0.0, 0.0, 1.0)
+        ));
+
+        // Saved tagged, and loaded back the same
+        let saved = serde_yaml::to_string(&background).unwrap();
+        assert!(saved.contains("Gradient"));
+        let loaded: Background = serde_yaml::from_str(&saved).unwrap();
+        assert_eq!(serde_yaml::to_string(&loaded).unwrap(), saved);
     }
 
-    pub fn top(&self) -> &Colour {
-        &self.top
+    #[test]
+    fn test_solid_colour() {
+        let assets = Assets::new(&[]).unwrap();
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let background: Background =
+            serde_yaml::from_str("SolidColour:\n  colour:\n    r: 0.0\n    g: 0.0\n    b: 0.0")
+                .unwrap();
+        let ray = Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.3, 1.0, 0.0), 0.0);
+
+        assert_eq!(background.colour(&ray, &assets), black);
+        assert!(background.is_black());
+        assert!(!Background::solid(Colour::new(0.1, 0.1, 0.1)).is_black());
     }
 
-    pub fn bottom(&self) -> &Colour {
-        &self.bottom
+    #[test]
+    fn test_environment_colour() {
+        let assets = Assets::new(&[]).unwrap();
+        let sky = Colour::new(0.2, 0.4, 0.8);
+        let ground = Colour::new(0.3, 0.2, 0.1);
+
+        // Checker on the direction vector splits the sphere of directions into octants
+        let background = Background::Environment {
+            texture: Texture::Checker {
+                even: Box::from(Texture::Constant { colour: sky }),
+                odd: Box::from(Texture::Constant { colour: ground }),
+                scale: 1.0,
+            },
+        };
+        let origin = Vector::new(0.0, 0.0, 0.0);
+
+        let ray = Ray::new(origin, Vector::new(1.0, 1.0, 1.0), 0.0);
+        assert_eq!(background.colour(&ray, &assets), sky);
+
+        let ray = Ray::new(origin, Vector::new(1.0, -1.0, 1.0), 0.0);
+        assert_eq!(background.colour(&ray, &assets), ground);
+    }
+
+    #[test]
+    fn test_environment_validate() {
+        let assets = Assets::new(&[]).unwrap();
+        let background = Background::Environment {
+            texture: Texture::Image {
+                asset_name: String::from("sky.hdr"),
+                filter: Filter::Bilinear,
+                wrap_mode: WrapMode::Clamp,
+                colour_space: ColourSpace::Linear,
+            },
+        };
+
+        assert!(background.validate(&assets).is_err());
+    }
+
+    #[test]
+    fn test_environment_distribution() {
+        let assets = Assets::new(&[]).unwrap();
+        let white = Colour::new(1.0, 1.0, 1.0);
+        assert!(Background::new(white, white)
+            .distribution(&assets)
+            .is_none());
+
+        // Lit only above the horizon
+        let background = Background::Environment {
+            texture: Texture::Transform {
+                scale: (2.0, 1.0),
+                offset: (-1.0, 0.0),
+                rotation: 0.0,
+                inner: Box::from(Texture::Gradient {
+                    start: Colour::new(0.0, 0.0, 0.0),
+                    end: white,
+                    axis: Axis::Row,
+                }),
+            },
+        };
+        let distribution = background.distribution(&assets).unwrap();
+        for _ in 0..100 {
+            assert!(distribution.generate().y() >= 0.0);
+        }
     }
 }
@@ -0,0 +1,263 @@
+use crate::animation::{check_times, segment, AnimationError};
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::quaternion::Quaternion;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+
+/// Where an animated geometry is at `time` on the timeline, rotated by `angle` degrees around
+/// `axis` and then moved by `translation`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TransformKeyframe {
+    time: Float,
+    #[serde(default = "default_translation")]
+    translation: Vector,
+    #[serde(default = "default_axis")]
+    axis: Vector,
+    #[serde(default)]
+    angle: Float,
+}
+
+fn default_translation() -> Vector {
+    Vector::new(0.0, 0.0, 0.0)
+}
+
+fn default_axis() -> Vector {
+    Vector::new(0.0, 1.0, 0.0)
+}
+
+impl TransformKeyframe {
+    pub fn new(time: Float, translation: Vector, axis: Vector, angle: Float) -> TransformKeyframe {
+        TransformKeyframe {
+            time,
+            translation,
+            axis,
+            angle,
+        }
+    }
+}
+
+/// A geometry that moves between keyframes. It is placed by the time of each ray, so it blurs
+/// over the time the shutter is open. Between keyframes it moves in a straight line and turns at
+/// an even rate
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Animated {
+    geometry: Box<Geometry>,
+    keyframes: Vec<TransformKeyframe>,
+}
+
+impl Animated {
+    pub fn build(
+        geometry: Geometry,
+        keyframes: Vec<TransformKeyframe>,
+    ) -> Result<Geometry, AnimationError> {
+        let animated = Animated {
+            geometry: Box::from(geometry),
+            keyframes,
+        };
+        animated.check_keyframes()?;
+        Ok(Geometry::Animated(Box::from(animated)))
+    }
+
+    fn check_keyframes(&self) -> Result<(), AnimationError> {
+        check_times(self.keyframes.iter().map(|keyframe| keyframe.time))?;
+        match self
+            .keyframes
+            .iter()
+            .find(|keyframe| keyframe.axis.len_squared() == 0.0)
+        {
+            Some(keyframe) => Err(AnimationError::ZeroRotationAxis {
+                time: keyframe.time,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The rotation and then translation the geometry has at `time`
+    fn transform_at(&self, time: Float) -> (Quaternion, Vector) {
+        let (before, after, t) = segment(self.keyframes.iter().map(|keyframe| keyframe.time), time);
+        let (before, after) = (&self.keyframes[before], &self.keyframes[after]);
+        let rotation = Quaternion::from_axis_angle(&before.axis, before.angle)
+            .slerp(&Quaternion::from_axis_angle(&after.axis, after.angle), t);
+        let translation = (1.0 - t) * before.translation + t * after.translation;
+        (rotation, translation)
+    }
+}
+
+impl Hittable for Animated {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let (rotation, translation) = self.transform_at(ray.time());
+        let inverse = rotation.conjugate();
+        let object_ray = Ray::new(
+            inverse.rotate_vector(&(ray.origin() - translation)),
+            inverse.rotate_vector(ray.direction()),
+            ray.time(),
+        );
+
+        self.geometry
+            .hit(&object_ray, tmin, tmax)
+            .map(|hit| HitResult {
+                ray: *ray,
+                point: rotation.rotate_vector(&hit.point) + translation,
+                surface_normal: rotation.rotate_vector(&hit.surface_normal),
+                texture_gradients: hit
+                    .texture_gradients
+                    .map(|(row, col)| (rotation.rotate_vector(&row), rotation.rotate_vector(&col))),
+                ..hit
+            })
+    }
+
+    /// Bounds where the geometry is at each end of the time range and at every keyframe in
+    /// between. Moving in straight lines it stays within those, but while it turns its corners
+    /// swing out, so then it is bounded by the sphere they all lie within instead
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        let bbox = self.geometry.bounding_box(time_start, time_end)?;
+        let (min, max) = (bbox.min(), bbox.max());
+        let (xs, ys, zs) = ([min.x(), max.x()], [min.y(), max.y()], [min.z(), max.z()]);
+        let corners: Vec<Vector> = iproduct!(&xs, &ys, &zs)
+            .map(|(&x, &y, &z)| Vector::new(x, y, z))
+            .collect();
+
+        let times = self
+            .keyframes
+            .iter()
+            .map(|keyframe| keyframe.time)
+            .filter(|&time| time_start < time && time < time_end);
+        let transforms: Vec<(Quaternion, Vector)> = std::iter::once(time_start)
+            .chain(times)
+            .chain(std::iter::once(time_end))
+            .map(|time| self.transform_at(time))
+            .collect();
+        let turns = transforms.windows(2).any(|pair| pair[0].0 != pair[1].0);
+
+        let radius = corners
+            .iter()
+            .map(|corner| corner.len())
+            .fold(0.0, Float::max);
+        let points: Vec<Vector> = if turns {
+            let reach = Vector::new(radius, radius, radius);
+            transforms
+                .iter()
+                .flat_map(|(_, translation)| vec![translation - reach, translation + reach])
+                .collect()
+        } else {
+            transforms
+                .iter()
+                .flat_map(|(rotation, translation)| {
+                    corners
+                        .iter()
+                        .map(move |corner| rotation.rotate_vector(corner) + translation)
+                })
+                .collect()
+        };
+        let (min, max) = points.iter().fold(
+            (
+                Vector::new(Float::MAX, Float::MAX, Float::MAX),
+                Vector::new(Float::MIN, Float::MIN, Float::MIN),
+            ),
+            |(min, max), point| (min.min(point), max.max(point)),
+        );
+        Some(AxisAlignedBoundingBox::new(min, max))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.check_keyframes()?;
+        self.geometry.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        // Where it is depends on the time of the ray, which sampling a direction does not know
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.geometry.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::Colour;
+    use crate::world::geometry::sphere::Sphere;
+    use crate::world::materials::Material;
+    use crate::world::texture::Texture;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn ball(keyframes: Vec<TransformKeyframe>) -> Geometry {
+        let material = Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.5, 0.5, 0.5),
+            },
+        };
+        Animated::build(
+            Sphere::build(Vector::new(1.0, 0.0, 0.0), 0.5, material),
+            keyframes,
+        )
+        .unwrap()
+    }
+
+    fn keyframe(time: Float, x: Float, angle: Float) -> TransformKeyframe {
+        TransformKeyframe::new(time, Vector::new(x, 0.0, 0.0), default_axis(), angle)
+    }
+
+    #[test]
+    fn test_animated_hit_follows_the_time_of_the_ray() {
+        // Slides 4 along x between times 0 and 2
+        let ball = ball(vec![keyframe(0.0, 0.0, 0.0), keyframe(2.0, 4.0, 0.0)]);
+        let down = |x: Float, time: Float| {
+            Ray::new(Vector::new(x, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), time)
+        };
+
+        let hit = ball.hit(&down(3.0, 1.0), 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.point.y(), 0.5);
+        assert_approx_eq!(hit.surface_normal.y(), 1.0);
+        assert!(ball.hit(&down(3.0, 0.0), 0.001, Float::MAX).is_none());
+        // Held at the last keyframe after the animation ends
+        assert!(ball.hit(&down(5.0, 10.0), 0.001, Float::MAX).is_some());
+    }
+
+    #[test]
+    fn test_animated_turns_between_keyframes() {
+        let ball = ball(vec![keyframe(0.0, 0.0, 0.0), keyframe(1.0, 0.0, 90.0)]);
+        // Half way through a quarter turn around y the ball is at 45 degrees
+        let towards = Vector::new(1.0, 0.0, -1.0).unit_vector();
+        let ray = Ray::new(5.0 * towards, -towards, 0.5);
+        let hit = ball.hit(&ray, 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.point.len(), 1.5);
+    }
+
+    #[test]
+    fn test_animated_bounding_box() {
+        let sliding = ball(vec![keyframe(0.0, 0.0, 0.0), keyframe(2.0, 4.0, 0.0)]);
+        let bbox = sliding.bounding_box(0.0, 1.0).unwrap();
+        assert_approx_eq!(bbox.min().x(), 0.5);
+        assert_approx_eq!(bbox.max().x(), 3.5);
+
+        // Turning around the origin the ball sweeps out a circle
+        let turning = ball(vec![keyframe(0.0, 0.0, 0.0), keyframe(1.0, 0.0, 180.0)]);
+        let bbox = turning.bounding_box(0.0, 1.0).unwrap();
+        assert!(bbox.min().x() <= -1.5 && bbox.max().z() >= 1.5);
+    }
+
+    #[test]
+    fn test_animated_validate() {
+        let material = Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.5, 0.5, 0.5),
+            },
+        };
+        let sphere = || Sphere::build(Vector::new(0.0, 0.0, 0.0), 1.0, material.clone());
+        assert!(Animated::build(sphere(), vec![]).is_err());
+        assert!(Animated::build(
+            sphere(),
+            vec![keyframe(1.0, 0.0, 0.0), keyframe(0.0, 0.0, 0.0)]
+        )
+        .is_err());
+        let no_axis =
+            TransformKeyframe::new(0.0, default_translation(), default_translation(), 0.0);
+        assert!(Animated::build(sphere(), vec![no_axis]).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::camera::Ray;
 use crate::data::vector::Vector;
+use crate::float::Float;
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct AxisAlignedBoundingBox {
@@ -7,16 +8,33 @@ pub struct AxisAlignedBoundingBox {
     max: Vector,
 }
 
-fn single_axis_hit(min: f64, max: f64, origin: f64, direction: f64) -> (f64, f64) {
-    let inv_direction = 1.0 / direction;
-
-    let t0 = (min - origin) * inv_direction;
-    let t1 = (max - origin) * inv_direction;
+/// A ray with the inverse of its direction worked out once, for testing against many boxes
+#[derive(Debug, Clone)]
+pub struct InverseRay {
+    origin: [Float; 3],
+    inv_direction: [Float; 3],
+    // Whether the ray travels towards -infinity along each axis, and so meets max before min
+    negative: [bool; 3],
+}
 
-    if inv_direction < 0.0 {
-        (t1, t0)
-    } else {
-        (t0, t1)
+impl InverseRay {
+    pub fn new(ray: &Ray) -> InverseRay {
+        let origin = ray.origin();
+        let direction = ray.direction();
+        let inv_direction = [
+            1.0 / direction.x(),
+            1.0 / direction.y(),
+            1.0 / direction.z(),
+        ];
+        InverseRay {
+            origin: [origin.x(), origin.y(), origin.z()],
+            inv_direction,
+            negative: [
+                inv_direction[0] < 0.0,
+                inv_direction[1] < 0.0,
+                inv_direction[2] < 0.0,
+            ],
+        }
     }
 }
 
@@ -25,14 +43,14 @@ fn surrounding(
     box_b: &AxisAlignedBoundingBox,
 ) -> AxisAlignedBoundingBox {
     let small = Vector::new(
-        f64::min(box_a.min().x(), box_b.min().x()),
-        f64::min(box_a.min().y(), box_b.min().y()),
-        f64::min(box_a.min().z(), box_b.min().z()),
+        Float::min(box_a.min().x(), box_b.min().x()),
+        Float::min(box_a.min().y(), box_b.min().y()),
+        Float::min(box_a.min().z(), box_b.min().z()),
     );
     let big = Vector::new(
-        f64::max(box_a.max().x(), box_b.max().x()),
-        f64::max(box_a.max().y(), box_b.max().y()),
-        f64::max(box_a.max().z(), box_b.max().z()),
+        Float::max(box_a.max().x(), box_b.max().x()),
+        Float::max(box_a.max().y(), box_b.max().y()),
+        Float::max(box_a.max().z(), box_b.max().z()),
     );
 
     AxisAlignedBoundingBox {
@@ -70,44 +88,55 @@ impl AxisAlignedBoundingBox {
         &self.max
     }
 
-    pub fn intersection(&self, ray: &Ray, tmin: f64, tmax: f64) -> bool {
-        let (t0, t1) = single_axis_hit(
-            self.min.x(),
-            self.max.x(),
-            ray.origin().x(),
-            ray.direction().x(),
-        );
-        let tmin = if t0 > tmin { t0 } else { tmin };
-        let tmax = if t1 < tmax { t1 } else { tmax };
-        if tmax <= tmin {
-            return false;
-        }
+    pub fn centroid(&self) -> Vector {
+        0.5 * (self.min + self.max)
+    }
 
-        let (t0, t1) = single_axis_hit(
-            self.min.y(),
-            self.max.y(),
-            ray.origin().y(),
-            ray.direction().y(),
-        );
-        let tmin = if t0 > tmin { t0 } else { tmin };
-        let tmax = if t1 < tmax { t1 } else { tmax };
-        if tmax <= tmin {
-            return false;
-        }
+    pub fn surface_area(&self) -> Float {
+        let extent = self.max - self.min;
+        2.0 * (extent.x() * extent.y() + extent.y() * extent.z() + extent.z() * extent.x())
+    }
 
-        let (t0, t1) = single_axis_hit(
-            self.min.z(),
-            self.max.z(),
-            ray.origin().z(),
-            ray.direction().z(),
-        );
-        let tmin = if t0 > tmin { t0 } else { tmin };
-        let tmax = if t1 < tmax { t1 } else { tmax };
-        if tmax <= tmin {
-            return false;
+    pub fn intersection(&self, ray: &Ray, tmin: Float, tmax: Float) -> bool {
+        self.inverse_intersection(&InverseRay::new(ray), tmin, tmax)
+    }
+
+    /// The part of `tmin..tmax` along the ray that is inside the box
+    pub fn clip(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<(Float, Float)> {
+        let (start, end) = self.inverse_range(&InverseRay::new(ray), tmin, tmax);
+        if start < end {
+            Some((start, end))
+        } else {
+            None
         }
+    }
 
-        true
+    /// The slab test without any branches. A NaN, from a ray lying in one of the box's planes,
+    /// fails every comparison and so leaves the range as it was
+    pub fn inverse_intersection(&self, ray: &InverseRay, tmin: Float, tmax: Float) -> bool {
+        let (tmin, tmax) = self.inverse_range(ray, tmin, tmax);
+        tmin < tmax
+    }
+
+    #[inline]
+    fn inverse_range(&self, ray: &InverseRay, tmin: Float, tmax: Float) -> (Float, Float) {
+        let min = [self.min.x(), self.min.y(), self.min.z()];
+        let max = [self.max.x(), self.max.y(), self.max.z()];
+
+        let mut tmin = tmin;
+        let mut tmax = tmax;
+        for axis in 0..3 {
+            let (near, far) = if ray.negative[axis] {
+                (max[axis], min[axis])
+            } else {
+                (min[axis], max[axis])
+            };
+            let t0 = (near - ray.origin[axis]) * ray.inv_direction[axis];
+            let t1 = (far - ray.origin[axis]) * ray.inv_direction[axis];
+            tmin = if t0 > tmin { t0 } else { tmin };
+            tmax = if t1 < tmax { t1 } else { tmax };
+        }
+        (tmin, tmax)
     }
 }
 
@@ -115,6 +144,47 @@ impl AxisAlignedBoundingBox {
 mod tests {
     use super::*;
 
+    fn unit_box() -> AxisAlignedBoundingBox {
+        AxisAlignedBoundingBox::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 1.0, 1.0))
+    }
+
+    #[test]
+    fn test_intersection() {
+        let ray = |origin: Vector, direction: Vector| Ray::new(origin, direction, 0.0);
+        let through = ray(Vector::new(0.5, 0.5, -1.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(unit_box().intersection(&through, 0.0, Float::MAX));
+        // The box is 1 to 2 along the ray so a range ending before it misses
+        assert!(!unit_box().intersection(&through, 0.0, 0.9));
+
+        let backwards = ray(Vector::new(0.5, 0.5, 2.0), Vector::new(0.0, 0.0, -1.0));
+        assert!(unit_box().intersection(&backwards, 0.0, Float::MAX));
+
+        let away = ray(Vector::new(0.5, 0.5, 2.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(!unit_box().intersection(&away, 0.0, Float::MAX));
+
+        let beside = ray(Vector::new(1.5, 0.5, -1.0), Vector::new(0.0, 0.0, 1.0));
+        assert!(!unit_box().intersection(&beside, 0.0, Float::MAX));
+    }
+
+    #[test]
+    fn test_clip() {
+        let ray = Ray::new(Vector::new(0.5, 0.5, -1.0), Vector::new(0.0, 0.0, 2.0), 0.0);
+        assert_eq!(unit_box().clip(&ray, 0.0, Float::MAX), Some((0.5, 1.0)));
+        assert_eq!(unit_box().clip(&ray, 0.75, 0.9), Some((0.75, 0.9)));
+        assert_eq!(unit_box().clip(&ray, 0.0, 0.25), None);
+    }
+
+    #[test]
+    fn test_intersection_of_ray_in_plane_of_box() {
+        // Parallel to x with the origin on the min y face, so the y slab gives 0 * infinity
+        let ray = Ray::new(Vector::new(-1.0, 0.0, 0.5), Vector::new(1.0, 0.0, 0.0), 0.0);
+        assert!(unit_box().intersection(&ray, 0.0, Float::MAX));
+
+        let inverse = InverseRay::new(&ray);
+        assert!(unit_box().inverse_intersection(&inverse, 0.0, Float::MAX));
+        assert!(!unit_box().inverse_intersection(&inverse, 0.0, 0.5));
+    }
+
     #[test]
     fn test_surrounding_bounding_boxes() {
         let box_a =
@@ -131,6 +201,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_centroid_and_surface_area() {
+        let bounding_box =
+            AxisAlignedBoundingBox::new(Vector::new(0.0, 0.0, 0.0), Vector::new(1.0, 2.0, 3.0));
+
+        assert_eq!(bounding_box.centroid(), Vector::new(0.5, 1.0, 1.5));
+        assert_eq!(bounding_box.surface_area(), 22.0);
+    }
+
     #[test]
     fn test_surrounding_bounding_boxes_when_one_is_none() {
         let bounding_box =
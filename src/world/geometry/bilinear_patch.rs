@@ -0,0 +1,295 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::pdf::uniform;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+
+// Keeps the bounding box of a flat patch lying in an axis plane from having no thickness
+const BOUNDING_BOX_PADDING: Float = 0.0001;
+
+/// The surface swept out by blending between four corners, which need not lie in a plane.
+/// Texture coords (u, v) are 0 at the first corner, with u running towards the second corner and v
+/// towards the fourth. Listed counter-clockwise as seen from the front, the normal faces the viewer
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct BilinearPatch {
+    // In order around the edge
+    corners: [Vector; 4],
+    material: Material,
+}
+
+impl BilinearPatch {
+    pub fn build(corners: [Vector; 4], material: Material) -> Geometry {
+        Geometry::BilinearPatch(Box::from(BilinearPatch { corners, material }))
+    }
+
+    fn point(&self, u: Float, v: Float) -> Vector {
+        let [p00, p10, p11, p01] = &self.corners;
+        (1.0 - u) * (1.0 - v) * p00 + u * (1.0 - v) * p10 + u * v * p11 + (1.0 - u) * v * p01
+    }
+
+    /// How the surface moves as u and then v increase
+    fn partials(&self, u: Float, v: Float) -> (Vector, Vector) {
+        let [p00, p10, p11, p01] = &self.corners;
+        (
+            (1.0 - v) * (p10 - p00) + v * (p11 - p01),
+            (1.0 - u) * (p01 - p00) + u * (p11 - p10),
+        )
+    }
+
+    /// The (distance, u, v) of every point the ray passes through the patch. Each u gives a
+    /// straight line across the patch in the v direction, so the ray hits where it is in the same
+    /// plane as one of those lines, which is where a quadratic in u is 0
+    fn intersections(&self, ray: &Ray) -> Vec<(Float, Float, Float)> {
+        let [p00, p10, p11, p01] = &self.corners;
+        let direction = ray.direction();
+        // The line for u starts at start + u * start_step and runs along across + u * across_step
+        let start = p00 - ray.origin();
+        let start_step = p10 - p00;
+        let across = p01 - p00;
+        let across_step = (p11 - p10) - across;
+
+        let coplanar =
+            |lhs: &Vector, rhs: &Vector| Vector::dot(&Vector::cross(lhs, rhs), direction);
+        let a = coplanar(&start, &across);
+        let b = coplanar(&start, &across_step) + coplanar(&start_step, &across);
+        let c = coplanar(&start_step, &across_step);
+
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return vec![];
+        }
+        // Avoids cancellation between b and the root, and copes with the c = 0 of flat patches
+        let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+
+        [q / c, a / q]
+            .iter()
+            .filter(|u| (0.0..=1.0).contains(*u))
+            .filter_map(|&u| {
+                let line_start = start + u * start_step;
+                let line = across + u * across_step;
+                let normal = Vector::cross(direction, &line);
+                let normal_squared = normal.len_squared();
+                if normal_squared == 0.0 {
+                    return None;
+                }
+
+                // Where line_start + v * line = distance * direction
+                let distance =
+                    Vector::dot(&Vector::cross(&line_start, &line), &normal) / normal_squared;
+                let v =
+                    Vector::dot(&Vector::cross(&line_start, direction), &normal) / normal_squared;
+                if (0.0..=1.0).contains(&v) {
+                    Some((distance, u, v))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl Hittable for BilinearPatch {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let (distance, u, v) = self
+            .intersections(ray)
+            .into_iter()
+            .filter(|(distance, _, _)| (tmin..=tmax).contains(distance))
+            .min_by(|a, b| a.0.total_cmp(&b.0))?;
+
+        let (along_u, along_v) = self.partials(u, v);
+        Some(HitResult {
+            distance,
+            ray: *ray,
+            point: ray.point(distance),
+            surface_normal: Vector::cross(&along_u, &along_v).unit_vector(),
+            material: self.material.clone(),
+            texture_coords: (u, v),
+            texture_gradients: Some((along_u, along_v)),
+            light_group: None,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        let padding = Vector::new(
+            BOUNDING_BOX_PADDING,
+            BOUNDING_BOX_PADDING,
+            BOUNDING_BOX_PADDING,
+        );
+        let [p00, p10, p11, p01] = &self.corners;
+        // Every point of the patch is a weighted average of the corners, so it is inside their box
+        Some(AxisAlignedBoundingBox::new(
+            p00.min(p10).min(&p11.min(p01)) - padding,
+            p00.max(p10).max(&p11.max(p01)) + padding,
+        ))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        self.material.is_attractor()
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+
+    /// Points are sampled uniformly in (u, v), which crowds them together wherever the patch is
+    /// squeezed, so the density is that of the nearest hit scaled by how stretched it is there
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        let direction = direction.unit_vector();
+        let hit = self.hit(&Ray::new(*origin, direction, 0.0), 0.001, Float::MAX);
+        match hit {
+            None => 0.0,
+            Some(hit) => {
+                let (u, v) = hit.texture_coords;
+                let (along_u, along_v) = self.partials(u, v);
+                let area = Vector::cross(&along_u, &along_v).len();
+                let distance_squared = hit.distance.powi(2);
+                let cosine = Vector::dot(&direction, &hit.face_normal()).abs();
+
+                distance_squared / (cosine * area)
+            }
+        }
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        self.point(uniform(), uniform()) - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::Colour;
+    use crate::world::texture::Texture;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn material() -> Material {
+        Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.5, 0.5, 0.5),
+            },
+        }
+    }
+
+    fn assert_vectors_eq(left: &Vector, right: &Vector) {
+        assert_approx_eq!(left.x(), right.x());
+        assert_approx_eq!(left.y(), right.y());
+        assert_approx_eq!(left.z(), right.z());
+    }
+
+    fn down_ray(x: Float, z: Float) -> Ray {
+        Ray::new(Vector::new(x, 5.0, z), Vector::new(0.0, -2.0, 0.0), 0.0)
+    }
+
+    #[test]
+    fn test_planar_patch() {
+        // A tilted quad that is not a parallelogram, rising along z and wider at the back
+        let patch = BilinearPatch::build(
+            [
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(0.0, 1.0, 2.0),
+                Vector::new(2.0, 1.0, 2.0),
+                Vector::new(1.0, 0.0, 0.0),
+            ],
+            material(),
+        );
+
+        let hit = patch.hit(&down_ray(0.75, 1.0), 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 2.25);
+        assert_approx_eq!(hit.texture_coords.0, 0.5);
+        assert_approx_eq!(hit.texture_coords.1, 0.5);
+        assert_vectors_eq(
+            &hit.surface_normal,
+            &Vector::new(0.0, 2.0, -1.0).unit_vector(),
+        );
+        assert!(hit.front_face());
+
+        // Outside the front edge, then inside the wider back of the quad
+        assert!(patch.hit(&down_ray(1.2, 0.2), 0.001, Float::MAX).is_none());
+        assert!(patch.hit(&down_ray(1.7, 1.8), 0.001, Float::MAX).is_some());
+    }
+
+    #[test]
+    fn test_saddle_patch() {
+        // The corner at x = z = 1 is raised, giving y = x * z over the unit square
+        let patch = BilinearPatch {
+            corners: [
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(1.0, 1.0, 1.0),
+                Vector::new(0.0, 0.0, 1.0),
+            ],
+            material: material(),
+        };
+
+        for &(x, z) in &[(0.5, 0.5), (0.25, 0.8), (0.9, 0.1), (0.7, 0.6)] {
+            let hit = patch.hit(&down_ray(x, z), 0.001, Float::MAX).unwrap();
+            assert_approx_eq!(hit.point.y(), x * z);
+            assert_approx_eq!(hit.texture_coords.0, x);
+            assert_approx_eq!(hit.texture_coords.1, z);
+            // Listed clockwise seen from above, so the normal is the downwards one of y = xz
+            assert_vectors_eq(
+                &hit.surface_normal,
+                &-Vector::new(-z, 1.0, -x).unit_vector(),
+            );
+        }
+
+        // Along the diagonal the surface curves up as y = s², which this ray crosses at
+        // s² - 0.8s + 0.1 = 0. The nearest of the two is hit
+        let ray = Ray::new(
+            Vector::new(-1.0, -0.9, -1.0),
+            Vector::new(1.0, 0.8, 1.0),
+            0.0,
+        );
+        assert_eq!(patch.intersections(&ray).len(), 2);
+        let hit = patch.hit(&ray, 0.001, Float::MAX).unwrap();
+        let nearest = (0.8 - Float::sqrt(0.24)) / 2.0;
+        assert_approx_eq!(hit.point.x(), nearest);
+        assert_approx_eq!(hit.distance, 1.0 + nearest);
+    }
+
+    #[test]
+    fn test_bilinear_patch_bounding_box() {
+        let patch = BilinearPatch::build(
+            [
+                Vector::new(0.0, 0.0, 0.0),
+                Vector::new(1.0, 0.0, 0.0),
+                Vector::new(1.0, 1.0, 1.0),
+                Vector::new(0.0, 0.0, 1.0),
+            ],
+            material(),
+        );
+        let bounding_box = patch.bounding_box(0.0, 0.0).unwrap();
+        assert_vectors_eq(bounding_box.min(), &Vector::new(-0.0001, -0.0001, -0.0001));
+        assert_vectors_eq(bounding_box.max(), &Vector::new(1.0001, 1.0001, 1.0001));
+    }
+
+    #[test]
+    fn test_bilinear_patch_light_sampling() {
+        let patch = BilinearPatch::build(
+            [
+                Vector::new(-1.0, 2.0, -1.0),
+                Vector::new(-1.0, 2.0, 1.0),
+                Vector::new(1.0, 2.0, 1.0),
+                Vector::new(1.0, 2.0, -1.0),
+            ],
+            material(),
+        );
+        let origin = Vector::new(0.0, 0.0, 0.0);
+
+        // Straight up at the 2x2 square from 2 away
+        let pdf = patch.pdf_value(&origin, &Vector::new(0.0, 1.0, 0.0));
+        assert_approx_eq!(pdf, 4.0 / 4.0);
+        for _ in 0..100 {
+            let direction = patch.random(&origin);
+            assert_approx_eq!(direction.y(), 2.0);
+            assert!(patch.pdf_value(&origin, &direction) > 0.0);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
+use crate::onb::Onb;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CapsuleError {
+    #[error("the radius of a capsule must be positive, got {radius}")]
+    InvalidRadius { radius: Float },
+}
+
+/// Every point within `radius` of the segment from `start` to `end`, a cylinder with a
+/// hemisphere on each end
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Capsule {
+    start: Vector,
+    end: Vector,
+    radius: Float,
+    material: Material,
+}
+
+impl Capsule {
+    pub fn build(start: Vector, end: Vector, radius: Float, material: Material) -> Geometry {
+        Geometry::Capsule(Box::from(Capsule {
+            start,
+            end,
+            radius,
+            material,
+        }))
+    }
+
+    /// The unit direction from `start` to `end` and the distance between them. A capsule with
+    /// both ends in the same place is a sphere pointing up
+    fn axis(&self) -> (Vector, Float) {
+        let segment = self.end - self.start;
+        let length = segment.len();
+        if length > 0.0 {
+            (segment / length, length)
+        } else {
+            (Vector::new(0.0, 1.0, 0.0), 0.0)
+        }
+    }
+
+    /// Distances along the ray to every point on the surface, relative to `start`
+    fn intersections(&self, origin: &Vector, direction: &Vector) -> Vec<Float> {
+        let (axis, length) = self.axis();
+        let along = |v: &Vector| Vector::dot(v, &axis);
+        let along_at = |t: Float| along(&(origin + t * direction));
+
+        // The side is the infinite cylinder around the axis, cut off at the ends of the segment
+        let origin_across = origin - along(origin) * axis;
+        let direction_across = direction - along(direction) * axis;
+        let side = quadratic_roots(&origin_across, &direction_across, self.radius)
+            .into_iter()
+            .filter(|&t| (0.0..=length).contains(&along_at(t)));
+
+        // Each cap is the half of a sphere beyond its end of the segment
+        let start_cap = quadratic_roots(origin, direction, self.radius)
+            .into_iter()
+            .filter(|&t| along_at(t) < 0.0);
+        let end_cap = quadratic_roots(&(origin - length * axis), direction, self.radius)
+            .into_iter()
+            .filter(|&t| along_at(t) > length);
+
+        side.chain(start_cap).chain(end_cap).collect()
+    }
+}
+
+/// Where the ray from `origin` along `direction` is `radius` from the origin
+fn quadratic_roots(origin: &Vector, direction: &Vector, radius: Float) -> Vec<Float> {
+    let a = direction.len_squared();
+    let half_b = Vector::dot(origin, direction);
+    let c = origin.len_squared() - radius * radius;
+    let discriminant = half_b * half_b - a * c;
+    if a == 0.0 || discriminant < 0.0 {
+        return vec![];
+    }
+
+    let root = discriminant.sqrt();
+    vec![(-half_b - root) / a, (-half_b + root) / a]
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let origin = ray.origin() - self.start;
+        let distance = self
+            .intersections(&origin, ray.direction())
+            .into_iter()
+            .filter(|t| (tmin..=tmax).contains(t))
+            .min_by(Float::total_cmp)?;
+
+        // The normal points away from the nearest point on the segment, whichever part is hit
+        let (axis, length) = self.axis();
+        let point = origin + distance * ray.direction();
+        let along = Vector::dot(&point, &axis);
+        let surface_normal = (point - along.clamp(0.0, length) * axis) / self.radius;
+
+        // Rows run from the pole of the start cap to the pole of the end cap by distance over the
+        // surface, columns go around the axis
+        let (texture_coords, texture_gradients) = {
+            let onb = Onb::build_from_w(&axis);
+            let elevation = Vector::dot(&surface_normal, &axis).clamp(-1.0, 1.0).asin();
+            let across = surface_normal - Vector::dot(&surface_normal, &axis) * axis;
+            let phi = Float::atan2(Vector::dot(&across, onb.v()), Vector::dot(&across, onb.u()));
+            let meridian_length = PI * self.radius + length;
+            let row = (PI * self.radius / 2.0 + along.clamp(0.0, length) + self.radius * elevation)
+                / meridian_length;
+            let col = (phi + PI) / (2.0 * PI);
+
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            let outwards = cos_phi * onb.u() + sin_phi * onb.v();
+            let around = -sin_phi * onb.u() + cos_phi * onb.v();
+            let (sin_elevation, cos_elevation) = elevation.sin_cos();
+            let up = -sin_elevation * outwards + cos_elevation * axis;
+            (
+                (row, col),
+                (
+                    meridian_length * up,
+                    2.0 * PI * self.radius * cos_elevation * around,
+                ),
+            )
+        };
+
+        Some(HitResult {
+            distance,
+            ray: *ray,
+            point: ray.point(distance),
+            surface_normal,
+            material: self.material.clone(),
+            texture_coords,
+            texture_gradients: Some(texture_gradients),
+            light_group: None,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        let radius = Vector::new(self.radius, self.radius, self.radius);
+        Some(AxisAlignedBoundingBox::new(
+            self.start.min(&self.end) - radius,
+            self.start.max(&self.end) + radius,
+        ))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        if self.radius <= 0.0 {
+            return Err(CapsuleError::InvalidRadius {
+                radius: self.radius,
+            }
+            .into());
+        }
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn capsule() -> Geometry {
+        // Lying along x from -1 to 1
+        Capsule::build(
+            Vector::new(-1.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            0.5,
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        )
+    }
+
+    fn assert_vectors_eq(left: &Vector, right: &Vector) {
+        assert_approx_eq!(left.x(), right.x());
+        assert_approx_eq!(left.y(), right.y());
+        assert_approx_eq!(left.z(), right.z());
+    }
+
+    #[test]
+    fn test_capsule_side_hit() {
+        let ray = Ray::new(Vector::new(0.3, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let hit = capsule().hit(&ray, 0.001, Float::MAX).unwrap();
+
+        assert_approx_eq!(hit.distance, 4.5);
+        assert_vectors_eq(&hit.surface_normal, &Vector::new(0.0, 1.0, 0.0));
+        assert!(hit.front_face());
+    }
+
+    #[test]
+    fn test_capsule_cap_hits() {
+        // Straight onto the pole of the end cap
+        let ray = Ray::new(Vector::new(5.0, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0), 0.0);
+        let hit = capsule().hit(&ray, 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 3.5);
+        assert_vectors_eq(&hit.surface_normal, &Vector::new(1.0, 0.0, 0.0));
+        assert_approx_eq!(hit.texture_coords.0, 1.0);
+
+        // Down onto the start cap, past the end of the side, where the cap curves away
+        let ray = Ray::new(
+            Vector::new(-1.3, 5.0, 0.0),
+            Vector::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        let hit = capsule().hit(&ray, 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 5.0 - 0.4);
+        assert_vectors_eq(&hit.surface_normal, &Vector::new(-0.6, 0.8, 0.0));
+
+        // Past the caps is a miss
+        let ray = Ray::new(
+            Vector::new(-1.6, 5.0, 0.0),
+            Vector::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        assert!(capsule().hit(&ray, 0.001, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_capsule_hit_from_inside() {
+        let ray = Ray::new(Vector::new(0.9, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
+        let hit = capsule().hit(&ray, 0.001, Float::MAX).unwrap();
+
+        assert_approx_eq!(hit.distance, 0.6);
+        assert!(!hit.front_face());
+    }
+
+    #[test]
+    fn test_capsule_texture_coords_run_pole_to_pole() {
+        let row = |origin: Vector| {
+            let ray = Ray::new(origin, Vector::new(0.0, -1.0, 0.0), 0.0);
+            capsule()
+                .hit(&ray, 0.001, Float::MAX)
+                .unwrap()
+                .texture_coords
+                .0
+        };
+
+        // A quarter circle of cap, then the side and then the other quarter circle
+        let meridian = PI * 0.5 + 2.0;
+        assert_approx_eq!(row(Vector::new(-1.0, 5.0, 0.0)), (PI * 0.25) / meridian);
+        assert_approx_eq!(
+            row(Vector::new(0.0, 5.0, 0.0)),
+            (PI * 0.25 + 1.0) / meridian
+        );
+        assert_approx_eq!(
+            row(Vector::new(1.0, 5.0, 0.0)),
+            (PI * 0.25 + 2.0) / meridian
+        );
+    }
+
+    #[test]
+    fn test_capsule_bounding_box() {
+        let tilted = Capsule::build(
+            Vector::new(1.0, 2.0, 3.0),
+            Vector::new(-1.0, 0.0, 4.0),
+            0.5,
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        );
+        let expected_box =
+            AxisAlignedBoundingBox::new(Vector::new(-1.5, -0.5, 2.5), Vector::new(1.5, 2.5, 4.5));
+        assert_eq!(tilted.bounding_box(0.0, 0.0), Some(expected_box));
+    }
+
+    #[test]
+    fn test_capsule_with_both_ends_together_is_a_sphere() {
+        let sphere = Capsule::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        );
+        let ray = Ray::new(Vector::new(0.6, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let hit = sphere.hit(&ray, 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 4.2);
+        assert_approx_eq!(hit.surface_normal.len(), 1.0);
+    }
+}
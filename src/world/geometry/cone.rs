@@ -0,0 +1,266 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float;
+use crate::float::consts::PI;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+
+// Discriminants this close to 0 come from rays grazing the cone or passing through the apex
+const DISCRIMINANT_EPSILON: Float = 1e-9;
+
+/// A cone standing upright along the y axis on the centre of its base with the apex `height`
+/// above it. Use the rotate wrappers to orient it in any other direction
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Cone {
+    base: Vector,
+    radius: Float,
+    height: Float,
+    // Open at the base when not set
+    #[serde(default)]
+    capped: bool,
+    material: Material,
+}
+
+impl Cone {
+    pub fn build(
+        base: Vector,
+        radius: Float,
+        height: Float,
+        capped: bool,
+        material: Material,
+    ) -> Geometry {
+        Geometry::Cone(Box::from(Cone {
+            base,
+            radius,
+            height,
+            capped,
+            material,
+        }))
+    }
+
+    /// Hits on the slanted side, texture coords wrap around the axis and run up to the apex
+    fn side_hit(
+        &self,
+        origin: &Vector,
+        direction: &Vector,
+        tmin: Float,
+        tmax: Float,
+    ) -> Option<Hit> {
+        // Solve x^2 + z^2 = k^2 * (h - y)^2 where k = radius / height along the ray
+        let k_squared = (self.radius / self.height).powi(2);
+        let below_apex = self.height - origin.y();
+
+        let a = direction.x().powi(2) + direction.z().powi(2) - k_squared * direction.y().powi(2);
+        let b = 2.0
+            * (origin.x() * direction.x()
+                + origin.z() * direction.z()
+                + k_squared * below_apex * direction.y());
+        let c = origin.x().powi(2) + origin.z().powi(2) - k_squared * below_apex.powi(2);
+
+        let roots = if a == 0.0 {
+            // The ray is parallel to the side of the cone so only crosses it once
+            if b == 0.0 {
+                return None;
+            }
+            vec![-c / b]
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < -DISCRIMINANT_EPSILON {
+                return None;
+            }
+            let root = discriminant.max(0.0).sqrt();
+            let (t0, t1) = ((-b - root) / (2.0 * a), (-b + root) / (2.0 * a));
+            // A NaN root is dropped by the range check below
+            vec![float::min(t0, t1), float::max(t0, t1)]
+        };
+
+        roots.into_iter().find_map(|t| {
+            let point = origin + t * direction;
+            if !(t >= tmin && t <= tmax) || point.y() < 0.0 || point.y() > self.height {
+                return None;
+            }
+
+            // Gradient of x^2 + z^2 - k^2 * (h - y)^2, which vanishes at the apex
+            let gradient = Vector::new(point.x(), k_squared * (self.height - point.y()), point.z());
+            let surface_normal = if gradient.len_squared() > 0.0 {
+                gradient.unit_vector()
+            } else {
+                Vector::new(0.0, 1.0, 0.0)
+            };
+
+            let phi = Float::atan2(point.z(), point.x());
+            Some(Hit {
+                distance: t,
+                surface_normal,
+                texture_coords: (point.y() / self.height, (phi + PI) / (2.0 * PI)),
+                texture_gradients: (
+                    Vector::new(0.0, 1.0, 0.0),
+                    Vector::new(-point.z(), 0.0, point.x()),
+                ),
+            })
+        })
+    }
+
+    /// Hits on the base disk, texture coords are a planar projection of the disk
+    fn cap_hit(
+        &self,
+        origin: &Vector,
+        direction: &Vector,
+        tmin: Float,
+        tmax: Float,
+    ) -> Option<Hit> {
+        if direction.y() == 0.0 {
+            return None;
+        }
+
+        let t = -origin.y() / direction.y();
+        let point = origin + t * direction;
+        if !(t >= tmin && t <= tmax) || point.x().powi(2) + point.z().powi(2) > self.radius.powi(2)
+        {
+            return None;
+        }
+
+        Some(Hit {
+            distance: t,
+            surface_normal: Vector::new(0.0, -1.0, 0.0),
+            texture_coords: (
+                (point.z() / self.radius + 1.0) / 2.0,
+                (point.x() / self.radius + 1.0) / 2.0,
+            ),
+            texture_gradients: (Vector::new(0.0, 0.0, 1.0), Vector::new(1.0, 0.0, 0.0)),
+        })
+    }
+}
+
+/// A hit in the local space of the cone
+struct Hit {
+    distance: Float,
+    surface_normal: Vector,
+    texture_coords: (Float, Float),
+    texture_gradients: (Vector, Vector),
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let origin = ray.origin() - self.base;
+        let direction = ray.direction();
+
+        let mut hits = vec![self.side_hit(&origin, direction, tmin, tmax)];
+        if self.capped {
+            hits.push(self.cap_hit(&origin, direction, tmin, tmax));
+        }
+
+        let closest = hits
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))?;
+
+        Some(HitResult {
+            distance: closest.distance,
+            ray: *ray,
+            point: ray.point(closest.distance),
+            surface_normal: closest.surface_normal,
+            material: self.material.clone(),
+            texture_coords: closest.texture_coords,
+            texture_gradients: Some(closest.texture_gradients),
+            light_group: None,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        let radius = self.radius.abs();
+        Some(AxisAlignedBoundingBox::new(
+            self.base - Vector::new(radius, 0.0, radius),
+            self.base + Vector::new(radius, self.height, radius),
+        ))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn cone(capped: bool) -> Cone {
+        Cone {
+            base: Vector::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            height: 2.0,
+            capped,
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_cone_hit_apex() {
+        let ray = Ray::new(Vector::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+
+        let hit_result = cone(false).hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 3.0);
+        assert_eq!(hit_result.point, Vector::new(0.0, 2.0, 0.0));
+    }
+
+    #[test]
+    fn test_cone_side_hit() {
+        // Half way up the cone the radius is 0.5
+        let ray = Ray::new(Vector::new(-3.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
+
+        let hit_result = cone(false).hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 2.5);
+
+        let normal = hit_result.surface_normal;
+        assert_approx_eq!(normal.len(), 1.0);
+        assert!(normal.x() < 0.0 && normal.y() > 0.0);
+        assert_approx_eq!(normal.y() / -normal.x(), 0.5);
+    }
+
+    #[test]
+    fn test_cone_cap() {
+        let ray = Ray::new(Vector::new(0.5, -1.0, 0.0), Vector::new(0.0, 1.0, 0.0), 0.0);
+
+        let hit_result = cone(true).hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 1.0);
+        assert_eq!(hit_result.surface_normal, Vector::new(0.0, -1.0, 0.0));
+
+        // Without the cap the ray enters through the open base hitting the inside of the side
+        let hit_result = cone(false).hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 2.0);
+    }
+
+    #[test]
+    fn test_cone_nan_ray_misses() {
+        let ray = Ray::new(
+            Vector::new(-3.0, 0.5, 0.0),
+            Vector::new(Float::NAN, 0.0, 0.0),
+            0.0,
+        );
+        assert!(cone(true).hit(&ray, 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_cone_bounding_box() {
+        let expected_box =
+            AxisAlignedBoundingBox::new(Vector::new(-1.0, 0.0, -1.0), Vector::new(1.0, 2.0, 1.0));
+
+        assert_eq!(cone(true).bounding_box(0.0, 0.0), Some(expected_box));
+    }
+}
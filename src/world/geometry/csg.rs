@@ -0,0 +1,213 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+
+// How far past a boundary the next hit is searched for so the same boundary isn't found again
+const BOUNDARY_EPSILON: Float = 1e-6;
+// Stops a ray grazing a badly behaved geometry from collecting boundaries forever
+const MAX_BOUNDARIES: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(self, in_first: bool, in_second: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_first || in_second,
+            CsgOperation::Intersection => in_first && in_second,
+            CsgOperation::Difference => in_first && !in_second,
+        }
+    }
+}
+
+/// Combines two closed geometries into a single solid. For a difference the second geometry is
+/// carved out of the first
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Csg {
+    operation: CsgOperation,
+    first: Box<Geometry>,
+    second: Box<Geometry>,
+}
+
+impl Csg {
+    pub fn build(operation: CsgOperation, first: Geometry, second: Geometry) -> Geometry {
+        Geometry::Csg(Box::from(Csg {
+            operation,
+            first: Box::from(first),
+            second: Box::from(second),
+        }))
+    }
+}
+
+/// Every boundary of a closed geometry along the ray in order, along with whether the ray starts
+/// inside it. A ray leaving through its first boundary must have started inside
+fn hit_all(geometry: &Geometry, ray: &Ray, tmin: Float, tmax: Float) -> (bool, Vec<HitResult>) {
+    let mut hits = Vec::new();
+    let mut tmin = tmin;
+
+    while hits.len() < MAX_BOUNDARIES {
+        match geometry.hit(ray, tmin, tmax) {
+            Some(hit) => {
+                tmin = hit.distance + BOUNDARY_EPSILON;
+                hits.push(hit);
+            }
+            None => break,
+        }
+    }
+
+    let starts_inside = hits.first().is_some_and(|hit| !hit.front_face());
+    (starts_inside, hits)
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let (mut in_first, first_hits) = hit_all(&self.first, ray, tmin, tmax);
+        let (mut in_second, second_hits) = hit_all(&self.second, ray, tmin, tmax);
+
+        let mut first_hits = first_hits.into_iter().peekable();
+        let mut second_hits = second_hits.into_iter().peekable();
+        let inside = self.operation.contains(in_first, in_second);
+
+        // Walk the boundaries of both geometries in order until one of them changes whether the
+        // ray is inside the combined solid
+        loop {
+            let from_first = match (first_hits.peek(), second_hits.peek()) {
+                (Some(first), Some(second)) => first.distance <= second.distance,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+
+            let hit = if from_first {
+                in_first = !in_first;
+                first_hits.next()?
+            } else {
+                in_second = !in_second;
+                second_hits.next()?
+            };
+
+            if self.operation.contains(in_first, in_second) == inside {
+                continue;
+            }
+
+            // Surfaces carved out by the second geometry face into the hole it leaves behind
+            if !from_first && self.operation == CsgOperation::Difference {
+                return Some(HitResult {
+                    surface_normal: -hit.surface_normal,
+                    ..hit
+                });
+            }
+            return Some(hit);
+        }
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        // Intersections and differences never reach outside the first geometry
+        match self.operation {
+            CsgOperation::Union => AxisAlignedBoundingBox::surrounding(
+                &self.first.bounding_box(time_start, time_end),
+                &self.second.bounding_box(time_start, time_end),
+            ),
+            CsgOperation::Intersection | CsgOperation::Difference => {
+                self.first.bounding_box(time_start, time_end)
+            }
+        }
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.first.validate(assets)?;
+        self.second.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        // Parts of both geometries are hidden so neither can be sampled as a whole
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.first.emits_light() || self.second.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::vector::Vector;
+    use crate::world::geometry::cube::Cube;
+    use crate::world::geometry::sphere::Sphere;
+    use crate::world::geometry::test_material;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn cube() -> Geometry {
+        Cube::build(
+            Vector::new(-1.0, -1.0, -1.0),
+            Vector::new(1.0, 1.0, 1.0),
+            test_material(),
+        )
+    }
+
+    // Sits on the top face of the cube so half of it sticks out
+    fn sphere() -> Geometry {
+        Sphere::build(Vector::new(0.0, 0.0, 1.0), 0.5, test_material())
+    }
+
+    fn down_ray(x: Float) -> Ray {
+        Ray::new(Vector::new(x, 0.0, 5.0), Vector::new(0.0, 0.0, -1.0), 0.0)
+    }
+
+    #[test]
+    fn test_difference_carves_a_hole() {
+        let carved = Csg::build(CsgOperation::Difference, cube(), sphere());
+
+        // The bottom of the hole faces up, out of the carved cube
+        let hit = carved.hit(&down_ray(0.0), 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 4.5);
+        assert_approx_eq!(hit.surface_normal.z(), 1.0);
+        assert!(hit.front_face());
+
+        let hit = carved.hit(&down_ray(0.9), 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 4.0);
+    }
+
+    #[test]
+    fn test_union_and_intersection() {
+        let union = Csg::build(CsgOperation::Union, cube(), sphere());
+        let hit = union.hit(&down_ray(0.0), 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 3.5);
+
+        let intersection = Csg::build(CsgOperation::Intersection, cube(), sphere());
+        let hit = intersection.hit(&down_ray(0.0), 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 4.0);
+        assert!(intersection
+            .hit(&down_ray(0.9), 0.001, Float::MAX)
+            .is_none());
+    }
+
+    #[test]
+    fn test_difference_from_inside() {
+        let carved = Csg::build(CsgOperation::Difference, cube(), sphere());
+
+        // Starting inside the cube below the hole, the ray leaves the solid through the hole
+        let ray = Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0), 0.0);
+        let hit = carved.hit(&ray, 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 0.5);
+        assert!(!hit.front_face());
+    }
+
+    #[test]
+    fn test_csg_bounding_box() {
+        let union = Csg::build(CsgOperation::Union, cube(), sphere());
+        let bbox = union.bounding_box(0.0, 0.0).unwrap();
+        assert_approx_eq!(bbox.max().z(), 1.5);
+
+        let carved = Csg::build(CsgOperation::Difference, cube(), sphere());
+        let bbox = carved.bounding_box(0.0, 0.0).unwrap();
+        assert_approx_eq!(bbox.max().z(), 1.0);
+    }
+}
@@ -1,6 +1,8 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::pdf::uniform_between;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
 use crate::world::geometry::{Geometry, HitResult, Hittable};
@@ -15,52 +17,52 @@ pub struct Cube {
 
 impl Cube {
     pub fn build(pmin: Vector, pmax: Vector, material: Material) -> Geometry {
+        Cube::build_with_faces(
+            pmin,
+            pmax,
+            [
+                material.clone(),
+                material.clone(),
+                material.clone(),
+                material.clone(),
+                material.clone(),
+                material,
+            ],
+        )
+    }
+
+    /// Builds a cube with its own material on each face. The faces are ordered +x, -x, +y, -y,
+    /// +z and then -z, the first facing along increasing x
+    pub fn build_with_faces(pmin: Vector, pmax: Vector, materials: [Material; 6]) -> Geometry {
+        let [pos_x, neg_x, pos_y, neg_y, pos_z, neg_z] = materials;
         let mut rectangles: Vec<Geometry> = Vec::with_capacity(6);
 
         rectangles.push(XyRect::build(
             (pmin.x(), pmax.x()),
             (pmin.y(), pmax.y()),
             pmax.z(),
-            material.clone(),
+            pos_z,
         ));
         rectangles.push(
-            XyRect::build(
-                (pmin.x(), pmax.x()),
-                (pmin.y(), pmax.y()),
-                pmin.z(),
-                material.clone(),
-            )
-            .flip(),
+            XyRect::build((pmin.x(), pmax.x()), (pmin.y(), pmax.y()), pmin.z(), neg_z).flip(),
         );
         rectangles.push(XzRect::build(
             (pmin.x(), pmax.x()),
             (pmin.z(), pmax.z()),
             pmax.y(),
-            material.clone(),
+            pos_y,
         ));
         rectangles.push(
-            XzRect::build(
-                (pmin.x(), pmax.x()),
-                (pmin.z(), pmax.z()),
-                pmin.y(),
-                material.clone(),
-            )
-            .flip(),
+            XzRect::build((pmin.x(), pmax.x()), (pmin.z(), pmax.z()), pmin.y(), neg_y).flip(),
         );
         rectangles.push(YzRect::build(
             (pmin.y(), pmax.y()),
             (pmin.z(), pmax.z()),
             pmax.x(),
-            material.clone(),
+            pos_x,
         ));
         rectangles.push(
-            YzRect::build(
-                (pmin.y(), pmax.y()),
-                (pmin.z(), pmax.z()),
-                pmin.x(),
-                material,
-            )
-            .flip(),
+            YzRect::build((pmin.y(), pmax.y()), (pmin.z(), pmax.z()), pmin.x(), neg_x).flip(),
         );
 
         Geometry::Cube(Box::from(Cube {
@@ -72,14 +74,14 @@ impl Cube {
 }
 
 impl Hittable for Cube {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
         self.rectangles
             .iter()
             .flat_map(|rect| rect.hit(ray, tmin, tmax))
             .min()
     }
 
-    fn bounding_box(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
         Some(AxisAlignedBoundingBox::new(self.pmin, self.pmax))
     }
 
@@ -91,13 +93,47 @@ impl Hittable for Cube {
     }
 
     fn is_attractor(&self) -> bool {
-        false
+        self.rectangles
+            .iter()
+            .any(|rectangle| rectangle.is_attractor())
+    }
+
+    fn emits_light(&self) -> bool {
+        self.rectangles
+            .iter()
+            .any(|rectangle| rectangle.emits_light())
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        // Each of the attracting faces is equally likely to be sampled
+        let faces = self.attracting_faces();
+        let weight = 1.0 / faces.clone().count() as Float;
+        faces
+            .map(|face| weight * face.pdf_value(origin, direction))
+            .sum()
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        let faces = self.attracting_faces();
+        let choice = uniform_between::<usize>(0, faces.clone().count());
+        faces.clone().nth(choice).unwrap().random(origin)
+    }
+}
+
+impl Cube {
+    /// Faces can have their own materials so only some of them may be worth sampling
+    fn attracting_faces(&self) -> impl Iterator<Item = &Geometry> + Clone {
+        self.rectangles
+            .iter()
+            .filter(|rectangle| rectangle.is_attractor())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::data::colour::Colour;
+    use crate::world::texture::Texture;
     use assert_approx_eq::assert_approx_eq;
 
     #[test]
@@ -107,19 +143,21 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         );
 
         let ray = Ray::new(Vector::new(2.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 1.0);
 
         let ray = Ray::new(Vector::new(-1.0, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 1.0);
 
         let ray = Ray::new(Vector::new(0.5, 2.0, 0.5), Vector::new(0.0, -1.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 1.0);
     }
 
@@ -130,11 +168,13 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         );
 
         let ray = Ray::new(Vector::new(2.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_eq!(hit_result.surface_normal, Vector::new(1.0, 0.0, 0.0));
     }
 
@@ -145,6 +185,8 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         );
 
@@ -161,13 +203,100 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         );
 
         let ray = Ray::new(Vector::new(2.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         let (u, v) = hit_result.texture_coords;
         assert_approx_eq!(u, 0.5);
         assert_approx_eq!(v, 0.5);
     }
+
+    #[test]
+    fn test_cube_face_materials() {
+        let lambertian = |r: Float, g: Float, b: Float| Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(r, g, b),
+            },
+        };
+        let materials = [
+            lambertian(1.0, 0.0, 0.0),
+            lambertian(0.0, 1.0, 0.0),
+            lambertian(0.0, 0.0, 1.0),
+            lambertian(1.0, 1.0, 0.0),
+            lambertian(0.0, 1.0, 1.0),
+            lambertian(1.0, 0.0, 1.0),
+        ];
+        let cube = Cube::build_with_faces(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 1.0),
+            materials.clone(),
+        );
+
+        let rays = [
+            Ray::new(Vector::new(2.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0),
+            Ray::new(Vector::new(-1.0, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0), 0.0),
+            Ray::new(Vector::new(0.5, 2.0, 0.5), Vector::new(0.0, -1.0, 0.0), 0.0),
+            Ray::new(Vector::new(0.5, -1.0, 0.5), Vector::new(0.0, 1.0, 0.0), 0.0),
+            Ray::new(Vector::new(0.5, 0.5, 2.0), Vector::new(0.0, 0.0, -1.0), 0.0),
+            Ray::new(Vector::new(0.5, 0.5, -1.0), Vector::new(0.0, 0.0, 1.0), 0.0),
+        ];
+        for (ray, material) in rays.iter().zip(materials.iter()) {
+            let hit_result = cube.hit(ray, 0.0, Float::MAX).unwrap();
+            assert_eq!(&hit_result.material, material);
+        }
+    }
+
+    #[test]
+    fn test_cube_light_sampling() {
+        use crate::float::consts::PI;
+        use crate::pdf::{random_unit_vector, seed_thread_rng};
+
+        seed_thread_rng(0);
+        let light = Material::DiffuseLight {
+            emit: Texture::Constant {
+                colour: Colour::new(4.0, 4.0, 4.0),
+            },
+            strength: 1.0,
+            two_sided: false,
+            spotlight: None,
+        };
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 2.0, 1.0),
+            light,
+        );
+        assert!(cube.is_attractor());
+
+        let origin = Vector::new(3.0, 2.5, -2.0);
+        for _ in 0..100 {
+            let direction = cube.random(&origin);
+            assert!(cube
+                .hit(&Ray::new(origin, direction, 0.0), 0.0, Float::MAX)
+                .is_some());
+            assert!(cube.pdf_value(&origin, &direction) > 0.0);
+        }
+
+        // Integrating the pdf over every direction with uniformly sampled directions gives 1
+        let num_of_samples = 200_000;
+        let total: Float = (0..num_of_samples)
+            .map(|_| cube.pdf_value(&origin, &random_unit_vector()))
+            .sum();
+        assert_approx_eq!(4.0 * PI * total / num_of_samples as Float, 1.0, 0.05);
+
+        let lambertian = Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.5, 0.5, 0.5),
+            },
+        };
+        let unlit = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 1.0),
+            lambertian,
+        );
+        assert!(!unlit.is_attractor());
+    }
 }
@@ -0,0 +1,243 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+
+/// A cylinder standing upright along the y axis on the centre of its bottom cap. Use the rotate
+/// wrappers to orient it in any other direction
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Cylinder {
+    base: Vector,
+    radius: Float,
+    height: Float,
+    // An open tube when not set
+    #[serde(default)]
+    capped: bool,
+    material: Material,
+}
+
+impl Cylinder {
+    pub fn build(
+        base: Vector,
+        radius: Float,
+        height: Float,
+        capped: bool,
+        material: Material,
+    ) -> Geometry {
+        Geometry::Cylinder(Box::from(Cylinder {
+            base,
+            radius,
+            height,
+            capped,
+            material,
+        }))
+    }
+
+    /// Hits on the curved side, texture coords wrap around the axis and run up the height
+    fn side_hit(
+        &self,
+        origin: &Vector,
+        direction: &Vector,
+        tmin: Float,
+        tmax: Float,
+    ) -> Option<Hit> {
+        // Solve (ox + t * dx)^2 + (oz + t * dz)^2 = r^2
+        let a = direction.x() * direction.x() + direction.z() * direction.z();
+        let b = 2.0 * (origin.x() * direction.x() + origin.z() * direction.z());
+        let c = origin.x() * origin.x() + origin.z() * origin.z() - self.radius * self.radius;
+
+        let discriminant = b * b - 4.0 * a * c;
+        if a == 0.0 || discriminant < 0.0 {
+            return None;
+        }
+
+        let roots = [
+            (-b - discriminant.sqrt()) / (2.0 * a),
+            (-b + discriminant.sqrt()) / (2.0 * a),
+        ];
+        roots.iter().find_map(|&t| {
+            let point = origin + t * direction;
+            // Negated so a NaN root fails the range check as well, here and for the caps
+            if !(t >= tmin && t <= tmax) || point.y() < 0.0 || point.y() > self.height {
+                return None;
+            }
+
+            let phi = Float::atan2(point.z(), point.x());
+            Some(Hit {
+                distance: t,
+                surface_normal: Vector::new(point.x(), 0.0, point.z()) / self.radius,
+                texture_coords: (point.y() / self.height, (phi + PI) / (2.0 * PI)),
+                texture_gradients: (
+                    Vector::new(0.0, 1.0, 0.0),
+                    Vector::new(-point.z(), 0.0, point.x()),
+                ),
+            })
+        })
+    }
+
+    /// Hits on the disk at height `y`, texture coords are a planar projection of the disk
+    fn cap_hit(
+        &self,
+        y: Float,
+        origin: &Vector,
+        direction: &Vector,
+        tmin: Float,
+        tmax: Float,
+    ) -> Option<Hit> {
+        if direction.y() == 0.0 {
+            return None;
+        }
+
+        let t = (y - origin.y()) / direction.y();
+        let point = origin + t * direction;
+        if !(t >= tmin && t <= tmax) || point.x().powi(2) + point.z().powi(2) > self.radius.powi(2)
+        {
+            return None;
+        }
+
+        let normal_y = if y > 0.0 { 1.0 } else { -1.0 };
+        Some(Hit {
+            distance: t,
+            surface_normal: Vector::new(0.0, normal_y, 0.0),
+            texture_coords: (
+                (point.z() / self.radius + 1.0) / 2.0,
+                (point.x() / self.radius + 1.0) / 2.0,
+            ),
+            texture_gradients: (Vector::new(0.0, 0.0, 1.0), Vector::new(1.0, 0.0, 0.0)),
+        })
+    }
+}
+
+/// A hit in the local space of the cylinder
+struct Hit {
+    distance: Float,
+    surface_normal: Vector,
+    texture_coords: (Float, Float),
+    texture_gradients: (Vector, Vector),
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let origin = ray.origin() - self.base;
+        let direction = ray.direction();
+
+        let mut hits = vec![self.side_hit(&origin, direction, tmin, tmax)];
+        if self.capped {
+            hits.push(self.cap_hit(0.0, &origin, direction, tmin, tmax));
+            hits.push(self.cap_hit(self.height, &origin, direction, tmin, tmax));
+        }
+
+        let closest = hits
+            .into_iter()
+            .flatten()
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))?;
+
+        Some(HitResult {
+            distance: closest.distance,
+            ray: *ray,
+            point: ray.point(closest.distance),
+            surface_normal: closest.surface_normal,
+            material: self.material.clone(),
+            texture_coords: closest.texture_coords,
+            texture_gradients: Some(closest.texture_gradients),
+            light_group: None,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        let radius = self.radius.abs();
+        Some(AxisAlignedBoundingBox::new(
+            self.base - Vector::new(radius, 0.0, radius),
+            self.base + Vector::new(radius, self.height, radius),
+        ))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn cylinder(capped: bool) -> Cylinder {
+        Cylinder {
+            base: Vector::new(0.0, -1.0, 0.0),
+            radius: 1.0,
+            height: 2.0,
+            capped,
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_cylinder_side_hit() {
+        let ray = Ray::new(Vector::new(-3.0, 0.5, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
+
+        let hit_result = cylinder(false).hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 2.0);
+        assert_eq!(hit_result.surface_normal, Vector::new(-1.0, 0.0, 0.0));
+        assert_approx_eq!(hit_result.texture_coords.0, 0.75);
+    }
+
+    #[test]
+    fn test_cylinder_miss_beyond_height() {
+        let ray = Ray::new(Vector::new(-3.0, 1.5, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
+
+        assert!(cylinder(true).hit(&ray, 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_cylinder_caps() {
+        let ray = Ray::new(Vector::new(0.5, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+
+        let hit_result = cylinder(true).hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 4.0);
+        assert_eq!(hit_result.surface_normal, Vector::new(0.0, 1.0, 0.0));
+
+        // An open tube is only hit on the inside of its side
+        assert!(cylinder(false).hit(&ray, 0.0, Float::MAX).is_none());
+
+        let ray = Ray::new(Vector::new(0.5, 0.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let hit_result = cylinder(true).hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 1.0);
+        assert_eq!(hit_result.surface_normal, Vector::new(0.0, -1.0, 0.0));
+        assert!(!hit_result.front_face());
+    }
+
+    #[test]
+    fn test_cylinder_nan_ray_misses() {
+        let ray = Ray::new(
+            Vector::new(-3.0, 0.5, 0.0),
+            Vector::new(Float::NAN, 0.0, 0.0),
+            0.0,
+        );
+        assert!(cylinder(true).hit(&ray, 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_cylinder_bounding_box() {
+        let expected_box =
+            AxisAlignedBoundingBox::new(Vector::new(-1.0, -1.0, -1.0), Vector::new(1.0, 1.0, 1.0));
+
+        assert_eq!(cylinder(true).bounding_box(0.0, 0.0), Some(expected_box));
+    }
+}
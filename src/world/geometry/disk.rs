@@ -0,0 +1,192 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
+use crate::onb::Onb;
+use crate::pdf::uniform;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+
+// Rays that are this close to parallel with the disk are treated as misses
+const PARALLEL_EPSILON: Float = 1e-12;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Disk {
+    centre: Vector,
+    normal: Vector,
+    radius: Float,
+    material: Material,
+}
+
+impl Disk {
+    pub fn build(centre: Vector, normal: Vector, radius: Float, material: Material) -> Geometry {
+        Geometry::Disk(Box::from(Disk {
+            centre,
+            normal: normal.unit_vector(),
+            radius,
+            material,
+        }))
+    }
+
+    fn area(&self) -> Float {
+        PI * self.radius * self.radius
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let normal = self.normal.unit_vector();
+        let denominator = Vector::dot(ray.direction(), &normal);
+        if denominator.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+
+        let distance = Vector::dot(&(self.centre - ray.origin()), &normal) / denominator;
+        if distance < tmin || distance > tmax {
+            return None;
+        }
+
+        let point = ray.point(distance);
+        let offset = point - self.centre;
+        if offset.len_squared() > self.radius * self.radius {
+            return None;
+        }
+
+        // Polar coords, the distance from the centre and the angle around it
+        let onb = Onb::build_from_w(&normal);
+        let angle = Float::atan2(Vector::dot(&offset, onb.v()), Vector::dot(&offset, onb.u()));
+        let texture_coords = (offset.len() / self.radius, (angle + PI) / (2.0 * PI));
+
+        Some(HitResult {
+            distance,
+            ray: *ray,
+            point,
+            surface_normal: normal,
+            material: self.material.clone(),
+            texture_coords,
+            texture_gradients: Some((offset, Vector::cross(&normal, &offset))),
+            light_group: None,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        // The disk reaches r * sin(angle between the axis and the normal) along each axis. Pad
+        // the box so that axis aligned disks do not end up with a zero width box
+        let normal = self.normal.unit_vector();
+        let radius = self.radius.abs();
+        let extent = |n: Float| radius * (1.0 - n * n).max(0.0).sqrt() + 0.0001;
+        let extent = Vector::new(extent(normal.x()), extent(normal.y()), extent(normal.z()));
+
+        Some(AxisAlignedBoundingBox::new(
+            self.centre - extent,
+            self.centre + extent,
+        ))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        self.material.is_attractor()
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        let direction = direction.unit_vector();
+        let hit = self.hit(&Ray::new(*origin, direction, 0.0), 0.001, Float::MAX);
+        match hit {
+            None => 0.0,
+            Some(hit) => {
+                let distance_squared = hit.distance.powi(2);
+                let cosine = Vector::dot(&direction, &hit.face_normal()).abs();
+
+                distance_squared / (cosine * self.area())
+            }
+        }
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        // Taking the square root of the radius keeps the samples uniform over the area
+        let onb = Onb::build_from_w(&self.normal);
+        let radius = self.radius * uniform::<Float>().sqrt();
+        let angle = 2.0 * PI * uniform::<Float>();
+
+        let random_point =
+            self.centre + radius * angle.cos() * onb.u() + radius * angle.sin() * onb.v();
+        random_point - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn disk() -> Disk {
+        Disk {
+            centre: Vector::new(0.0, 1.0, 0.0),
+            normal: Vector::new(0.0, -1.0, 0.0),
+            radius: 2.0,
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_disk_hit() {
+        let ray = Ray::new(Vector::new(1.5, 0.0, 0.5), Vector::new(0.0, 1.0, 0.0), 0.0);
+
+        let hit_result = disk().hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 1.0);
+        assert_eq!(hit_result.surface_normal, Vector::new(0.0, -1.0, 0.0));
+        assert!(hit_result.front_face());
+        assert_approx_eq!(hit_result.texture_coords.0, Float::sqrt(2.5) / 2.0);
+    }
+
+    #[test]
+    fn test_disk_miss_outside_radius() {
+        let ray = Ray::new(Vector::new(1.5, 0.0, 1.5), Vector::new(0.0, 1.0, 0.0), 0.0);
+
+        assert!(disk().hit(&ray, 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_disk_bounding_box() {
+        let expected_box = AxisAlignedBoundingBox::new(
+            Vector::new(-2.0001, 0.9999, -2.0001),
+            Vector::new(2.0001, 1.0001, 2.0001),
+        );
+
+        assert_eq!(disk().bounding_box(0.0, 0.0), Some(expected_box));
+    }
+
+    #[test]
+    fn test_disk_pdf_value() {
+        let origin = Vector::new(0.0, 0.0, 0.0);
+        let direction = Vector::new(0.0, 1.0, 0.0);
+
+        // distance^2 / (cosine * area) = 1.0 / (1.0 * 4π)
+        assert_approx_eq!(disk().pdf_value(&origin, &direction), 1.0 / (4.0 * PI));
+    }
+
+    #[test]
+    fn test_disk_random() {
+        let disk = disk();
+        let origin = Vector::new(0.5, -2.0, 0.0);
+        for _ in 0..100 {
+            let direction = disk.random(&origin);
+            assert!(disk
+                .hit(&Ray::new(origin, direction, 0.0), 0.0, Float::MAX)
+                .is_some());
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::sphere::{sphere_hit, sphere_texture_coords, sphere_texture_gradients};
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum EllipsoidError {
+    #[error("the semi axes of an ellipsoid must all be positive, got {semi_axes:?}")]
+    InvalidSemiAxes { semi_axes: Vector },
+}
+
+/// A sphere stretched by a different semi axis length along each of x, y and z
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Ellipsoid {
+    centre: Vector,
+    semi_axes: Vector,
+    material: Material,
+}
+
+impl Ellipsoid {
+    pub fn build(centre: Vector, semi_axes: Vector, material: Material) -> Geometry {
+        Geometry::Ellipsoid(Box::from(Ellipsoid {
+            centre,
+            semi_axes,
+            material,
+        }))
+    }
+
+    /// Squashes a world space vector into the space where the ellipsoid is the unit sphere
+    fn squash(&self, v: &Vector) -> Vector {
+        Vector::new(
+            v.x() / self.semi_axes.x(),
+            v.y() / self.semi_axes.y(),
+            v.z() / self.semi_axes.z(),
+        )
+    }
+
+    fn stretch(&self, v: &Vector) -> Vector {
+        Vector::new(
+            v.x() * self.semi_axes.x(),
+            v.y() * self.semi_axes.y(),
+            v.z() * self.semi_axes.z(),
+        )
+    }
+}
+
+impl Hittable for Ellipsoid {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        // Stretching the ray along with the ellipsoid keeps the distance along it the same
+        let origin = self.squash(&(ray.origin() - self.centre));
+        let direction = self.squash(ray.direction());
+        let unit_ray = Ray::new(origin, direction, ray.time());
+        let centre = Vector::new(0.0, 0.0, 0.0);
+
+        sphere_hit(&unit_ray, &centre, 1.0, tmin, tmax).map(|distance| {
+            let unit_point = unit_ray.point(distance);
+
+            // The gradient of (x/a)^2 + (y/b)^2 + (z/c)^2, rather than the direction from the
+            // centre which is only the normal on a sphere
+            let surface_normal = self.squash(&unit_point).unit_vector();
+
+            let (north, east) = sphere_texture_gradients(&unit_point, &centre);
+            let texture_gradients = (self.stretch(&north), self.stretch(&east));
+
+            HitResult {
+                distance,
+                ray: *ray,
+                point: ray.point(distance),
+                surface_normal,
+                material: self.material.clone(),
+                texture_coords: sphere_texture_coords(&unit_point, &centre, 1.0),
+                texture_gradients: Some(texture_gradients),
+                light_group: None,
+            }
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        Some(AxisAlignedBoundingBox::new(
+            self.centre - self.semi_axes,
+            self.centre + self.semi_axes,
+        ))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        let semi_axes = self.semi_axes;
+        if semi_axes.x() <= 0.0 || semi_axes.y() <= 0.0 || semi_axes.z() <= 0.0 {
+            return Err(EllipsoidError::InvalidSemiAxes { semi_axes }.into());
+        }
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        // There is no simple way of sampling the solid angle an ellipsoid covers
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn ellipsoid() -> Geometry {
+        Ellipsoid::build(
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(3.0, 1.0, 0.5),
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        )
+    }
+
+    #[test]
+    fn test_ellipsoid_hit_longest_axis_tip() {
+        let ray = Ray::new(
+            Vector::new(10.0, 0.0, 0.0),
+            Vector::new(-1.0, 0.0, 0.0),
+            0.0,
+        );
+        let hit = ellipsoid().hit(&ray, 0.001, Float::MAX).unwrap();
+
+        assert_approx_eq!(hit.distance, 6.0);
+        assert_approx_eq!(hit.point.x(), 4.0);
+        assert_approx_eq!(hit.surface_normal.x(), 1.0);
+        assert!(hit.front_face());
+    }
+
+    #[test]
+    fn test_ellipsoid_surface_normal_is_the_gradient() {
+        // Half way out along x the surface of a sphere would have a normal 60° from x, but the
+        // stretched surface is much flatter so the normal leans further towards y
+        let ray = Ray::new(Vector::new(2.5, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let hit = ellipsoid().hit(&ray, 0.001, Float::MAX).unwrap();
+
+        let (x, y) = (1.5, Float::sqrt(0.75));
+        assert_approx_eq!(hit.point.y(), y);
+        let expected = Vector::new(x / 9.0, y, 0.0).unit_vector();
+        assert_approx_eq!(hit.surface_normal.x(), expected.x());
+        assert_approx_eq!(hit.surface_normal.y(), expected.y());
+        assert_approx_eq!(hit.surface_normal.len(), 1.0);
+    }
+
+    #[test]
+    fn test_ellipsoid_bounding_box() {
+        let expected_box =
+            AxisAlignedBoundingBox::new(Vector::new(-2.0, -1.0, -0.5), Vector::new(4.0, 1.0, 0.5));
+        assert_eq!(ellipsoid().bounding_box(0.0, 0.0), Some(expected_box));
+    }
+
+    #[test]
+    fn test_ellipsoid_validate_semi_axes() {
+        let assets = Assets::new(&[]).unwrap();
+        assert!(ellipsoid().validate(&assets).is_ok());
+
+        let flat = Ellipsoid::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 0.0, 1.0),
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        );
+        assert!(flat.validate(&assets).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::vector::Vector;
+use crate::float::Float;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
 
@@ -18,14 +19,14 @@ impl FlipNormals {
 }
 
 impl Hittable for FlipNormals {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
         self.geometry.hit(ray, tmin, tmax).map(|hit| HitResult {
             surface_normal: -hit.surface_normal,
             ..hit
         })
     }
 
-    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
         self.geometry.bounding_box(time_start, time_end)
     }
 
@@ -37,7 +38,11 @@ impl Hittable for FlipNormals {
         self.geometry.is_attractor()
     }
 
-    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+    fn emits_light(&self) -> bool {
+        self.geometry.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
         self.geometry.pdf_value(origin, direction)
     }
 
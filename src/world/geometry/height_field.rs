@@ -0,0 +1,572 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::triangle::intersect;
+use crate::world::geometry::{HitResult, Hittable};
+use crate::world::materials::Material;
+use serde::{Serialize, Serializer};
+use std::convert::TryFrom;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum HeightFieldError {
+    #[error("{source_name} needs at least 2x2 heights but has {cols}x{rows}")]
+    TooSmall {
+        source_name: String,
+        cols: usize,
+        rows: usize,
+    },
+    #[error("{source_name} has {len} heights but {cols}x{rows} of them were expected")]
+    WrongSize {
+        source_name: String,
+        len: usize,
+        cols: usize,
+        rows: usize,
+    },
+    #[error("{source_name} could not be found")]
+    MissingFile { source_name: String },
+}
+
+/// Terrain over a region of the xz plane, with a grid of heights spread evenly across it. Each
+/// cell of the grid is split into two triangles that are shaded smoothly with normals from the
+/// slope of the heights around each corner. Height fields are saved as the path of their image
+/// and the regions it covers, and the image is read again when the height field is loaded
+#[derive(Deserialize, Clone, Debug)]
+#[serde(try_from = "SavedHeightField")]
+pub struct HeightField {
+    source: String,
+    // The number of heights along x and along z
+    cols: usize,
+    rows: usize,
+    // Row by row from z0, each row running along x from x0
+    heights: Vec<Float>,
+    x0: Float,
+    x1: Float,
+    z0: Float,
+    z1: Float,
+    // The heights of black and white in the image
+    y0: Float,
+    y1: Float,
+    // The lowest and highest of the heights, which bound the terrain in y
+    min_height: Float,
+    max_height: Float,
+    material: Material,
+}
+
+#[derive(Deserialize)]
+struct SavedHeightField {
+    image: String,
+    x: (Float, Float),
+    z: (Float, Float),
+    y: (Float, Float),
+    material: Material,
+}
+
+#[derive(Serialize)]
+struct SavedHeightFieldRef<'a> {
+    image: &'a str,
+    x: (Float, Float),
+    z: (Float, Float),
+    y: (Float, Float),
+    material: &'a Material,
+}
+
+impl TryFrom<SavedHeightField> for HeightField {
+    type Error = anyhow::Error;
+
+    fn try_from(saved: SavedHeightField) -> Result<HeightField, anyhow::Error> {
+        HeightField::load_image(saved.image, saved.x, saved.z, saved.y, saved.material)
+    }
+}
+
+impl Serialize for HeightField {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SavedHeightFieldRef {
+            image: &self.source,
+            x: (self.x0, self.x1),
+            z: (self.z0, self.z1),
+            y: (self.y0, self.y1),
+            material: &self.material,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl HeightField {
+    #[allow(clippy::too_many_arguments)]
+    fn build(
+        source: &str,
+        cols: usize,
+        rows: usize,
+        heights: Vec<Float>,
+        (x0, x1): (Float, Float),
+        (z0, z1): (Float, Float),
+        (y0, y1): (Float, Float),
+        material: Material,
+    ) -> Result<HeightField, HeightFieldError> {
+        let height_field = HeightField {
+            source: source.to_string(),
+            cols,
+            rows,
+            min_height: heights.iter().cloned().fold(Float::INFINITY, Float::min),
+            max_height: heights
+                .iter()
+                .cloned()
+                .fold(Float::NEG_INFINITY, Float::max),
+            heights,
+            x0,
+            x1,
+            z0,
+            z1,
+            y0,
+            y1,
+            material,
+        };
+        height_field.check_size()?;
+        Ok(height_field)
+    }
+
+    /// Loads a grayscale image, usually a digital elevation model, with each pixel giving the
+    /// height of one point of the grid. Black is at `y0` and white at `y1`, and the top row of
+    /// the image is at `z0`
+    pub fn load_image<P: AsRef<Path>>(
+        path: P,
+        x: (Float, Float),
+        z: (Float, Float),
+        (y0, y1): (Float, Float),
+        material: Material,
+    ) -> Result<HeightField, anyhow::Error> {
+        let path = path.as_ref();
+        let image = image::open(path)?.to_luma();
+        let heights = image
+            .pixels()
+            .map(|pixel| y0 + (y1 - y0) * Float::from(pixel.data[0]) / 255.0)
+            .collect();
+        let height_field = HeightField::build(
+            &path.display().to_string(),
+            image.width() as usize,
+            image.height() as usize,
+            heights,
+            x,
+            z,
+            (y0, y1),
+            material,
+        )?;
+        Ok(height_field)
+    }
+
+    fn check_size(&self) -> Result<(), HeightFieldError> {
+        if self.cols < 2 || self.rows < 2 {
+            return Err(HeightFieldError::TooSmall {
+                source_name: self.source.clone(),
+                cols: self.cols,
+                rows: self.rows,
+            });
+        }
+        if self.heights.len() != self.cols * self.rows {
+            return Err(HeightFieldError::WrongSize {
+                source_name: self.source.clone(),
+                len: self.heights.len(),
+                cols: self.cols,
+                rows: self.rows,
+            });
+        }
+        Ok(())
+    }
+
+    fn cell_size(&self) -> (Float, Float) {
+        (
+            (self.x1 - self.x0) / (self.cols - 1) as Float,
+            (self.z1 - self.z0) / (self.rows - 1) as Float,
+        )
+    }
+
+    fn height(&self, col: usize, row: usize) -> Float {
+        self.heights[row * self.cols + col]
+    }
+
+    fn vertex(&self, col: usize, row: usize) -> Vector {
+        let (dx, dz) = self.cell_size();
+        Vector::new(
+            self.x0 + col as Float * dx,
+            self.height(col, row),
+            self.z0 + row as Float * dz,
+        )
+    }
+
+    /// The normal at a point of the grid from the slope between its neighbours, or between it
+    /// and its only neighbour along the edges
+    fn vertex_normal(&self, col: usize, row: usize) -> Vector {
+        let (dx, dz) = self.cell_size();
+        let (left, right) = (col.saturating_sub(1), (col + 1).min(self.cols - 1));
+        let (back, front) = (row.saturating_sub(1), (row + 1).min(self.rows - 1));
+        let slope_x =
+            (self.height(right, row) - self.height(left, row)) / ((right - left) as Float * dx);
+        let slope_z =
+            (self.height(col, front) - self.height(col, back)) / ((front - back) as Float * dz);
+        Vector::new(-slope_x, 1.0, -slope_z).unit_vector()
+    }
+
+    /// The closest hit on the two triangles of the cell starting at `col` and `row`
+    fn cell_hit(
+        &self,
+        col: usize,
+        row: usize,
+        ray: &Ray,
+        tmin: Float,
+        tmax: Float,
+    ) -> Option<HitResult> {
+        let corners = [
+            (col, row),
+            (col + 1, row + 1),
+            (col + 1, row),
+            (col, row + 1),
+        ];
+        let [a, b, c, d] = corners;
+        [[a, b, c], [a, d, b]]
+            .iter()
+            .filter_map(|triangle| {
+                let [v0, v1, v2] = triangle.map(|(col, row)| self.vertex(col, row));
+                intersect(&v0, &v1, &v2, ray, tmin, tmax)
+                    .map(|(distance, u, v)| (triangle, [v0, v1, v2], distance, u, v))
+            })
+            .min_by(|left, right| left.2.total_cmp(&right.2))
+            .map(|(triangle, [v0, v1, v2], distance, u, v)| {
+                let [n0, n1, n2] = triangle.map(|(col, row)| self.vertex_normal(col, row));
+                // Interpolating unit vectors does not produce a unit vector so renormalise
+                let surface_normal = ((1.0 - u - v) * n0 + u * n1 + v * n2).unit_vector();
+
+                // How the point moves across the flat triangle as each texture coord increases
+                let flat = Vector::cross(&(v1 - v0), &(v2 - v0));
+                let (width, depth) = (self.x1 - self.x0, self.z1 - self.z0);
+                let texture_gradients = (
+                    Vector::new(width, -width * flat.x() / flat.y(), 0.0),
+                    Vector::new(0.0, -depth * flat.z() / flat.y(), depth),
+                );
+
+                let point = ray.point(distance);
+                HitResult {
+                    distance,
+                    ray: *ray,
+                    point,
+                    surface_normal,
+                    material: self.material.clone(),
+                    texture_coords: ((point.x() - self.x0) / width, (point.z() - self.z0) / depth),
+                    texture_gradients: Some(texture_gradients),
+                    light_group: None,
+                }
+            })
+    }
+
+    /// The range of distances along the ray that are inside the bounding box of the terrain
+    fn clip(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<(Float, Float)> {
+        let slabs = [
+            (ray.origin().x(), ray.direction().x(), self.x0, self.x1),
+            (
+                ray.origin().y(),
+                ray.direction().y(),
+                self.min_height,
+                self.max_height,
+            ),
+            (ray.origin().z(), ray.direction().z(), self.z0, self.z1),
+        ];
+        let (mut start, mut end) = (tmin, tmax);
+        for &(origin, direction, low, high) in &slabs {
+            if direction == 0.0 {
+                if origin < low || origin > high {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = ((low - origin) / direction, (high - origin) / direction);
+            start = start.max(t0.min(t1));
+            end = end.min(t0.max(t1));
+        }
+        if start <= end {
+            Some((start, end))
+        } else {
+            None
+        }
+    }
+}
+
+impl Hittable for HeightField {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let (start, end) = self.clip(ray, tmin, tmax)?;
+        let (dx, dz) = self.cell_size();
+        let origin = ray.origin();
+        let direction = ray.direction();
+
+        // Walk the cells under the ray in the order it crosses them, so the first cell with a
+        // hit holds the closest one
+        let entry = ray.point(start);
+        let first_cell = |position: Float, low: Float, size: Float, count: usize| {
+            (((position - low) / size).floor().max(0.0) as usize).min(count - 2)
+        };
+        let mut col = first_cell(entry.x(), self.x0, dx, self.cols);
+        let mut row = first_cell(entry.z(), self.z0, dz, self.rows);
+
+        // The distance to the next cell boundary along each axis and between boundaries
+        let crossing = |cell: usize, low: Float, size: Float, origin: Float, direction: Float| {
+            if direction == 0.0 {
+                return (Float::INFINITY, Float::INFINITY);
+            }
+            let boundary = if direction > 0.0 { cell + 1 } else { cell };
+            let next = (low + boundary as Float * size - origin) / direction;
+            (next, size / direction.abs())
+        };
+        let (mut next_col, col_step) = crossing(col, self.x0, dx, origin.x(), direction.x());
+        let (mut next_row, row_step) = crossing(row, self.z0, dz, origin.z(), direction.z());
+
+        let mut cell_start = start;
+        loop {
+            let cell_end = next_col.min(next_row).min(end);
+
+            // Skip cells the ray passes entirely above
+            let highest = [
+                (col, row),
+                (col + 1, row),
+                (col, row + 1),
+                (col + 1, row + 1),
+            ]
+            .iter()
+            .map(|&(col, row)| self.height(col, row))
+            .fold(Float::NEG_INFINITY, Float::max);
+            let ray_low = ray.point(cell_start).y().min(ray.point(cell_end).y());
+            if ray_low <= highest {
+                if let Some(hit) = self.cell_hit(col, row, ray, tmin, tmax) {
+                    return Some(hit);
+                }
+            }
+
+            if cell_end >= end {
+                return None;
+            }
+            if next_col < next_row {
+                if direction.x() > 0.0 && col + 2 < self.cols {
+                    col += 1;
+                } else if direction.x() < 0.0 && col > 0 {
+                    col -= 1;
+                } else {
+                    return None;
+                }
+                next_col += col_step;
+            } else {
+                if direction.z() > 0.0 && row + 2 < self.rows {
+                    row += 1;
+                } else if direction.z() < 0.0 && row > 0 {
+                    row -= 1;
+                } else {
+                    return None;
+                }
+                next_row += row_step;
+            }
+            cell_start = cell_end;
+        }
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        // Pad the box so that flat terrain does not end up with a zero height box
+        let padding = Vector::new(0.0001, 0.0001, 0.0001);
+        Some(AxisAlignedBoundingBox::new(
+            Vector::new(self.x0, self.min_height, self.z0) - padding,
+            Vector::new(self.x1, self.max_height, self.z1) + padding,
+        ))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.check_size()?;
+        // The image is read again whenever the height field is loaded, so it has to stay put
+        if !Path::new(&self.source).is_file() {
+            return Err(HeightFieldError::MissingFile {
+                source_name: self.source.clone(),
+            }
+            .into());
+        }
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::{seed_thread_rng, uniform};
+    use crate::world::geometry::test_material;
+    use crate::world::geometry::Geometry;
+    use assert_approx_eq::assert_approx_eq;
+
+    /// A single peak of height 1 in the middle of a 3x3 grid over [-1, 1] in x and z
+    fn peak() -> Geometry {
+        #[rustfmt::skip]
+        let heights = vec![
+            0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0,
+        ];
+        let height_field = HeightField::build(
+            "peak",
+            3,
+            3,
+            heights,
+            (-1.0, 1.0),
+            (-1.0, 1.0),
+            (0.0, 1.0),
+            test_material(),
+        );
+        Geometry::HeightField(Box::from(height_field.unwrap()))
+    }
+
+    #[test]
+    fn test_height_field_hit() {
+        let height_field = peak();
+
+        let down = Ray::new(Vector::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let hit = height_field.hit(&down, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 4.0);
+        assert_approx_eq!(hit.surface_normal.y(), 1.0);
+        assert_approx_eq!(hit.texture_coords.0, 0.5);
+        assert_approx_eq!(hit.texture_coords.1, 0.5);
+
+        // Halfway down the slope towards +x, where the smooth normal leans towards +x
+        let down = Ray::new(Vector::new(0.5, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let hit = height_field.hit(&down, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit.point.y(), 0.5);
+        assert!(hit.surface_normal.x() > 0.0);
+        assert_approx_eq!(hit.surface_normal.z(), 0.0);
+        assert_approx_eq!(hit.surface_normal.len(), 1.0);
+
+        let above = Ray::new(Vector::new(-2.0, 1.5, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
+        assert!(height_field.hit(&above, 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_height_field_matches_every_cell() {
+        seed_thread_rng(0);
+        #[rustfmt::skip]
+        let heights = vec![
+            0.1, 0.4, 0.3, 0.0, 0.2,
+            0.5, 0.9, 0.6, 0.3, 0.1,
+            0.2, 0.7, 1.0, 0.8, 0.4,
+            0.0, 0.3, 0.5, 0.2, 0.6,
+        ];
+        let height_field = HeightField::build(
+            "hills",
+            5,
+            4,
+            heights,
+            (0.0, 4.0),
+            (-1.0, 2.0),
+            (0.0, 1.0),
+            test_material(),
+        )
+        .unwrap();
+
+        for _ in 0..2000 {
+            let origin = Vector::new(
+                6.0 * uniform::<Float>() - 1.0,
+                2.0 * uniform::<Float>(),
+                4.0 * uniform::<Float>() - 1.5,
+            );
+            let target = Vector::new(
+                4.0 * uniform::<Float>(),
+                0.5,
+                3.0 * uniform::<Float>() - 1.0,
+            );
+            let ray = Ray::new(origin, target - origin, 0.0);
+
+            let brute_force = (0..3)
+                .flat_map(|row| (0..4).map(move |col| (col, row)))
+                .filter_map(|(col, row)| height_field.cell_hit(col, row, &ray, 0.0, Float::MAX))
+                .map(|hit| hit.distance)
+                .min_by(Float::total_cmp);
+            let hit = height_field
+                .hit(&ray, 0.0, Float::MAX)
+                .map(|hit| hit.distance);
+            match (hit, brute_force) {
+                (Some(hit), Some(expected)) => assert_approx_eq!(hit, expected),
+                (hit, expected) => assert_eq!(hit, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_height_field_size_is_checked() {
+        let result = HeightField::build(
+            "small",
+            1,
+            3,
+            vec![0.0; 3],
+            (0.0, 1.0),
+            (0.0, 1.0),
+            (0.0, 1.0),
+            test_material(),
+        );
+        assert!(matches!(result, Err(HeightFieldError::TooSmall { .. })));
+
+        let result = HeightField::build(
+            "short",
+            2,
+            2,
+            vec![0.0; 3],
+            (0.0, 1.0),
+            (0.0, 1.0),
+            (0.0, 1.0),
+            test_material(),
+        );
+        assert!(matches!(
+            result,
+            Err(HeightFieldError::WrongSize { len: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_height_field_loads_from_an_image() {
+        let path = std::env::temp_dir().join(format!("rayt_terrain_{}.png", std::process::id()));
+        // Black all round with white in the bottom right corner
+        image::GrayImage::from_fn(2, 2, |col, row| image::Luma([255 * (col * row) as u8]))
+            .save(&path)
+            .unwrap();
+
+        let height_field = Geometry::HeightField(Box::from(
+            HeightField::load_image(&path, (0.0, 1.0), (0.0, 1.0), (2.0, 4.0), test_material())
+                .unwrap(),
+        ));
+        let saved = serde_yaml::to_string(&height_field).unwrap();
+        assert!(!saved.contains("heights"));
+
+        let loaded: Geometry = serde_yaml::from_str(&saved).unwrap();
+        let down =
+            |x: Float, z: Float| Ray::new(Vector::new(x, 5.0, z), Vector::new(0.0, -1.0, 0.0), 0.0);
+        assert_approx_eq!(
+            loaded
+                .hit(&down(0.0, 0.0), 0.0, Float::MAX)
+                .unwrap()
+                .point
+                .y(),
+            2.0
+        );
+        assert_approx_eq!(
+            loaded
+                .hit(&down(1.0, 1.0), 0.0, Float::MAX)
+                .unwrap()
+                .point
+                .y(),
+            4.0
+        );
+        assert!(loaded.validate(&Assets::default()).is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.validate(&Assets::default()).is_err());
+    }
+}
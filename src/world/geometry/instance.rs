@@ -0,0 +1,360 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::quaternion::Quaternion;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum InstanceError {
+    #[error("there is no prototype named {prototype} to make an instance of")]
+    UnknownPrototype { prototype: String },
+    #[error("the instance of {prototype} must be listed directly in the world's geometries")]
+    NotInWorld { prototype: String },
+    #[error("instance scale {scale:?} can not have a zero component")]
+    ZeroScale { scale: Vector },
+    #[error("the rotation axis of an instance can not be zero")]
+    ZeroRotationAxis(),
+    #[error("the prototype {prototype} has no bounding box so it can not be instanced")]
+    Unbounded { prototype: String },
+}
+
+/// A copy of one of the world's prototype geometries, scaled, then rotated by `angle` degrees
+/// around `axis` and then moved by `translation`. Every instance of a prototype shares the one
+/// geometry so a mesh and its BVH are only stored once however many times it appears
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Instance {
+    // The name of the prototype in the world
+    prototype: String,
+    #[serde(default = "default_scale")]
+    scale: Vector,
+    #[serde(default = "default_axis")]
+    axis: Vector,
+    #[serde(default)]
+    angle: Float,
+    #[serde(default = "default_translation")]
+    translation: Vector,
+    // Filled in from the world's prototypes when the config is built
+    #[serde(skip)]
+    geometry: Option<Arc<Geometry>>,
+}
+
+fn default_scale() -> Vector {
+    Vector::new(1.0, 1.0, 1.0)
+}
+
+fn default_axis() -> Vector {
+    Vector::new(0.0, 1.0, 0.0)
+}
+
+fn default_translation() -> Vector {
+    Vector::new(0.0, 0.0, 0.0)
+}
+
+impl Instance {
+    pub fn build(
+        prototype: &str,
+        scale: Vector,
+        axis: Vector,
+        angle: Float,
+        translation: Vector,
+    ) -> Result<Geometry, InstanceError> {
+        let instance = Instance {
+            prototype: prototype.to_string(),
+            scale,
+            axis,
+            angle,
+            translation,
+            geometry: None,
+        };
+        instance.check_transform()?;
+        Ok(Geometry::Instance(Box::from(instance)))
+    }
+
+    /// Shares the geometry of the prototype this is an instance of
+    pub fn link(
+        &mut self,
+        prototypes: &HashMap<String, Arc<Geometry>>,
+    ) -> Result<(), InstanceError> {
+        let geometry =
+            prototypes
+                .get(&self.prototype)
+                .ok_or_else(|| InstanceError::UnknownPrototype {
+                    prototype: self.prototype.clone(),
+                })?;
+        self.geometry = Some(Arc::clone(geometry));
+        Ok(())
+    }
+
+    /// Checks the instance against the prototypes before it is linked
+    pub fn validate_with(
+        &self,
+        prototypes: &HashMap<String, Geometry>,
+        assets: &Assets,
+    ) -> Result<(), anyhow::Error> {
+        self.check_transform()?;
+        let geometry =
+            prototypes
+                .get(&self.prototype)
+                .ok_or_else(|| InstanceError::UnknownPrototype {
+                    prototype: self.prototype.clone(),
+                })?;
+        if geometry.bounding_box(0.0, 0.0).is_none() {
+            return Err(InstanceError::Unbounded {
+                prototype: self.prototype.clone(),
+            }
+            .into());
+        }
+        geometry.validate(assets)
+    }
+
+    fn check_transform(&self) -> Result<(), InstanceError> {
+        if self.scale.x() == 0.0 || self.scale.y() == 0.0 || self.scale.z() == 0.0 {
+            return Err(InstanceError::ZeroScale { scale: self.scale });
+        }
+        if self.axis.len_squared() == 0.0 {
+            return Err(InstanceError::ZeroRotationAxis());
+        }
+        Ok(())
+    }
+
+    fn geometry(&self) -> &Geometry {
+        self.geometry
+            .as_ref()
+            .unwrap_or_else(|| panic!("the instance of {} was never linked", self.prototype))
+    }
+
+    fn rotation(&self) -> Quaternion {
+        Quaternion::from_axis_angle(&self.axis, self.angle)
+    }
+
+    fn to_object(&self, rotation: &Quaternion, v: &Vector) -> Vector {
+        let v = rotation.conjugate().rotate_vector(v);
+        Vector::new(
+            v.x() / self.scale.x(),
+            v.y() / self.scale.y(),
+            v.z() / self.scale.z(),
+        )
+    }
+
+    fn to_world(&self, rotation: &Quaternion, v: &Vector) -> Vector {
+        rotation.rotate_vector(&Vector::new(
+            v.x() * self.scale.x(),
+            v.y() * self.scale.y(),
+            v.z() * self.scale.z(),
+        ))
+    }
+
+    /// Normals transform by the inverse transpose, which divides by the scale instead of
+    /// multiplying
+    fn normal_to_world(&self, rotation: &Quaternion, normal: &Vector) -> Vector {
+        rotation
+            .rotate_vector(&Vector::new(
+                normal.x() / self.scale.x(),
+                normal.y() / self.scale.y(),
+                normal.z() / self.scale.z(),
+            ))
+            .unit_vector()
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let rotation = self.rotation();
+        // The direction is transformed along with the origin so distances along the ray are
+        // unchanged
+        let object_ray = Ray::new(
+            self.to_object(&rotation, &(ray.origin() - self.translation)),
+            self.to_object(&rotation, ray.direction()),
+            ray.time(),
+        );
+
+        self.geometry()
+            .hit(&object_ray, tmin, tmax)
+            .map(|hit| HitResult {
+                ray: *ray,
+                point: self.to_world(&rotation, &hit.point) + self.translation,
+                surface_normal: self.normal_to_world(&rotation, &hit.surface_normal),
+                texture_gradients: hit.texture_gradients.map(|(row, col)| {
+                    (
+                        self.to_world(&rotation, &row),
+                        self.to_world(&rotation, &col),
+                    )
+                }),
+                ..hit
+            })
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        let bbox = self.geometry().bounding_box(time_start, time_end)?;
+        let rotation = self.rotation();
+        let (min, max) = (bbox.min(), bbox.max());
+        let (xs, ys, zs) = ([min.x(), max.x()], [min.y(), max.y()], [min.z(), max.z()]);
+        let corners = iproduct!(&xs, &ys, &zs)
+            .map(|(&x, &y, &z)| self.to_world(&rotation, &Vector::new(x, y, z)) + self.translation);
+        let (min, max) = corners.fold(
+            (
+                Vector::new(Float::MAX, Float::MAX, Float::MAX),
+                Vector::new(Float::MIN, Float::MIN, Float::MIN),
+            ),
+            |(min, max), corner| (min.min(&corner), max.max(&corner)),
+        );
+        Some(AxisAlignedBoundingBox::new(min, max))
+    }
+
+    /// Instances are validated by the world against its prototypes before they are linked, so one
+    /// reached here is nested inside another geometry where it would never be linked
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        match &self.geometry {
+            Some(geometry) => geometry.validate(assets),
+            None => Err(InstanceError::NotInWorld {
+                prototype: self.prototype.clone(),
+            }
+            .into()),
+        }
+    }
+
+    fn is_attractor(&self) -> bool {
+        // Like a scale, the transform changes the solid angle the prototype covers in a way it
+        // can not account for, so instances are never sampled directly
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.geometry
+            .as_ref()
+            .is_some_and(|geometry| geometry.emits_light())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::Colour;
+    use crate::world::geometry::cube::Cube;
+    use crate::world::materials::Material;
+    use crate::world::texture::Texture;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn prototypes() -> HashMap<String, Arc<Geometry>> {
+        let material = Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.5, 0.5, 0.5),
+            },
+        };
+        let cube = Cube::build(
+            Vector::new(-1.0, -1.0, -1.0),
+            Vector::new(1.0, 1.0, 1.0),
+            material,
+        );
+        let mut prototypes = HashMap::new();
+        prototypes.insert("cube".to_string(), Arc::new(cube));
+        prototypes
+    }
+
+    fn instance(scale: Vector, angle: Float, translation: Vector) -> Instance {
+        let mut instance = Instance {
+            prototype: "cube".to_string(),
+            scale,
+            axis: Vector::new(0.0, 1.0, 0.0),
+            angle,
+            translation,
+            geometry: None,
+        };
+        instance.link(&prototypes()).unwrap();
+        instance
+    }
+
+    #[test]
+    fn test_instance_hit() {
+        // Stretched to 4 wide along x, turned a quarter so that is along z, then moved up
+        let instance = instance(Vector::new(2.0, 1.0, 1.0), 90.0, Vector::new(0.0, 3.0, 0.0));
+
+        let ray = Ray::new(
+            Vector::new(0.0, 3.0, 10.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let hit = instance.hit(&ray, 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 8.0);
+        assert_approx_eq!(hit.point.z(), 2.0);
+        assert_approx_eq!(hit.surface_normal.z(), 1.0);
+        assert_approx_eq!(hit.surface_normal.len(), 1.0);
+
+        let ray = Ray::new(
+            Vector::new(10.0, 3.0, 0.0),
+            Vector::new(-1.0, 0.0, 0.0),
+            0.0,
+        );
+        let hit = instance.hit(&ray, 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.point.x(), 1.0);
+
+        // Below where the cube was moved to
+        let ray = Ray::new(
+            Vector::new(0.0, 0.0, 10.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        assert!(instance.hit(&ray, 0.001, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_instance_bounding_box() {
+        let instance = instance(Vector::new(2.0, 1.0, 1.0), 90.0, Vector::new(0.0, 3.0, 0.0));
+        let bbox = instance.bounding_box(0.0, 0.0).unwrap();
+        assert_approx_eq!(bbox.min().x(), -1.0);
+        assert_approx_eq!(bbox.max().y(), 4.0);
+        assert_approx_eq!(bbox.max().z(), 2.0);
+    }
+
+    #[test]
+    fn test_instances_share_the_prototype() {
+        let prototypes = prototypes();
+        let mut first = Instance::build(
+            "cube",
+            default_scale(),
+            default_axis(),
+            0.0,
+            default_translation(),
+        )
+        .unwrap();
+        let mut second = first.clone();
+        for geometry in &mut [&mut first, &mut second] {
+            match geometry {
+                Geometry::Instance(instance) => instance.link(&prototypes).unwrap(),
+                _ => panic!("expected an instance"),
+            }
+        }
+        assert_eq!(Arc::strong_count(&prototypes["cube"]), 3);
+
+        let mut missing = instance(default_scale(), 0.0, default_translation());
+        missing.prototype = "tree".to_string();
+        assert!(missing.link(&prototypes).is_err());
+    }
+
+    #[test]
+    fn test_nested_instances_are_invalid() {
+        let assets = Assets::new(&[]).unwrap();
+        let unlinked = Instance::build(
+            "cube",
+            default_scale(),
+            default_axis(),
+            0.0,
+            default_translation(),
+        )
+        .unwrap();
+        assert!(unlinked.validate(&assets).is_err());
+        assert!(Instance::build(
+            "cube",
+            Vector::new(1.0, 0.0, 1.0),
+            default_axis(),
+            0.0,
+            default_translation()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+
+/// Puts a geometry in a light group for light linking. A light in a group only lights the
+/// geometries in the same group, so a fill light can be kept off the backdrop, while lights
+/// outside of any group light everything. Geometries in a group are lit by both. A group given
+/// inside another one takes precedence
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LightGroup {
+    group: u32,
+    geometry: Box<Geometry>,
+}
+
+impl LightGroup {
+    pub fn build(group: u32, geometry: Geometry) -> Geometry {
+        Geometry::LightGroup(Box::from(LightGroup {
+            group,
+            geometry: Box::from(geometry),
+        }))
+    }
+
+    pub fn group(&self) -> u32 {
+        self.group
+    }
+}
+
+/// Whether light from a geometry in `light_group` reaches a surface in `surface_group`
+pub fn illuminates(light_group: Option<u32>, surface_group: Option<u32>) -> bool {
+    light_group.is_none() || light_group == surface_group
+}
+
+impl Hittable for LightGroup {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        self.geometry.hit(ray, tmin, tmax).map(|hit| HitResult {
+            light_group: hit.light_group.or(Some(self.group)),
+            ..hit
+        })
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        self.geometry.bounding_box(time_start, time_end)
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.geometry.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        self.geometry.is_attractor()
+    }
+
+    fn emits_light(&self) -> bool {
+        self.geometry.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        self.geometry.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        self.geometry.random(origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::Colour;
+    use crate::world::geometry::sphere::Sphere;
+    use crate::world::materials::Material;
+    use crate::world::texture::Texture;
+
+    #[test]
+    fn test_light_group_tags_hits() {
+        let sphere = Sphere::build(
+            Vector::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::Lambertian {
+                albedo: Texture::Constant {
+                    colour: Colour::new(0.5, 0.5, 0.5),
+                },
+            },
+        );
+        let grouped = LightGroup::build(2, LightGroup::build(1, sphere));
+        let ray = Ray::new(Vector::new(0.0, 0.0, 5.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        assert_eq!(
+            grouped.hit(&ray, 0.001, Float::MAX).unwrap().light_group,
+            Some(1)
+        );
+        assert_eq!(grouped.light_group(), Some(2));
+
+        assert!(illuminates(None, Some(1)));
+        assert!(illuminates(Some(1), Some(1)));
+        assert!(!illuminates(Some(1), Some(2)));
+        assert!(!illuminates(Some(1), None));
+    }
+}
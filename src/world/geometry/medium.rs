@@ -2,21 +2,29 @@ use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::vector::Vector;
 use crate::float;
+use crate::float::Float;
 use crate::pdf::uniform;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
 use crate::world::materials::Material;
 use crate::world::texture::Texture;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MediumError {
+    #[error("the density of a medium can not be read from an image")]
+    ImageDensity(),
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ConstantMedium {
     boundary: Box<Geometry>,
-    density: f64,
+    density: Float,
     material: Material,
 }
 
 impl ConstantMedium {
-    pub fn build(boundary: Geometry, density: f64, albedo: Texture) -> Geometry {
+    pub fn build(boundary: Geometry, density: Float, albedo: Texture) -> Geometry {
         Geometry::ConstantMedium(Box::from(ConstantMedium {
             boundary: Box::from(boundary),
             density,
@@ -26,47 +34,21 @@ impl ConstantMedium {
 }
 
 impl Hittable for ConstantMedium {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
-        self.boundary
-            .hit(ray, std::f64::MIN, std::f64::MAX)
-            .and_then(|first_hit| {
-                self.boundary
-                    .hit(ray, &first_hit.distance + 0.0001, std::f64::MAX)
-                    .map(|second_hit| (first_hit, second_hit))
-            })
-            .and_then(|(first_hit, second_hit)| {
-                let mut d1 = first_hit.distance;
-                let mut d2 = second_hit.distance;
-
-                d1 = float::max(d1, tmin);
-                d2 = float::min(d2, tmax);
-
-                if d1 >= d2 {
-                    return None;
-                }
-                d1 = float::max(d1, 0.0);
-
-                let distance_inside_boundary = (d2 - d1) * ray.direction().len();
-                let hit_distance = -(1.0 / self.density) * uniform::<f64>().ln();
-
-                if hit_distance >= distance_inside_boundary {
-                    return None;
-                }
-
-                let distance = d1 + hit_distance / ray.direction().len();
-
-                Some(HitResult {
-                    distance,
-                    ray: *ray,
-                    point: ray.point(distance),
-                    surface_normal: Vector::new(1.0, 0.0, 0.0), // Arbitrary,
-                    material: self.material.clone(),
-                    ..first_hit
-                })
-            })
-    }
-
-    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let (first_hit, d1, d2) = boundary_interval(&self.boundary, ray, tmin, tmax)?;
+
+        let distance_inside_boundary = (d2 - d1) * ray.direction().len();
+        let hit_distance = -(1.0 / self.density) * uniform::<Float>().ln();
+
+        if hit_distance >= distance_inside_boundary {
+            return None;
+        }
+
+        let distance = d1 + hit_distance / ray.direction().len();
+        Some(scatter_hit(ray, distance, &self.material, first_hit))
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
         self.boundary.bounding_box(time_start, time_end)
     }
 
@@ -79,4 +61,195 @@ impl Hittable for ConstantMedium {
     fn is_attractor(&self) -> bool {
         false
     }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+}
+
+/// A medium that is thicker in some places than others. The density at a point is `max_density`
+/// scaled by the brightness of `density` there, which is clamped to `[0, 1]`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VariableMedium {
+    boundary: Box<Geometry>,
+    max_density: Float,
+    density: Texture,
+    material: Material,
+}
+
+impl VariableMedium {
+    pub fn build(
+        boundary: Geometry,
+        max_density: Float,
+        density: Texture,
+        albedo: Texture,
+    ) -> Geometry {
+        Geometry::VariableMedium(Box::from(VariableMedium {
+            boundary: Box::from(boundary),
+            max_density,
+            density,
+            material: Material::Isotropic { albedo },
+        }))
+    }
+
+    fn density_fraction(&self, point: &Vector) -> Float {
+        // Image textures are rejected by `validate` so no assets are ever needed here
+        let fraction = self
+            .density
+            .value((0.0, 0.0), point, &Assets::default())
+            .luminance();
+        fraction.clamp(0.0, 1.0)
+    }
+}
+
+impl Hittable for VariableMedium {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let (first_hit, d1, d2) = boundary_interval(&self.boundary, ray, tmin, tmax)?;
+        let distance_inside_boundary = (d2 - d1) * ray.direction().len();
+
+        // Woodcock tracking. Collisions are sampled as if the whole medium had the maximum density
+        // and each one is kept in proportion to the real density there, otherwise it is a null
+        // collision and tracking carries on from it. This leaves the scattering distance unbiased
+        let mut travelled = 0.0;
+        loop {
+            travelled -= (1.0 / self.max_density) * uniform::<Float>().ln();
+            if travelled >= distance_inside_boundary {
+                return None;
+            }
+
+            let distance = d1 + travelled / ray.direction().len();
+            if uniform::<Float>() < self.density_fraction(&ray.point(distance)) {
+                return Some(scatter_hit(ray, distance, &self.material, first_hit));
+            }
+        }
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        self.boundary.bounding_box(time_start, time_end)
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.density
+            .validate(&Assets::default())
+            .map_err(|_| MediumError::ImageDensity())?;
+        self.material.validate(assets)?;
+        self.boundary.validate(assets)?;
+        Ok(())
+    }
+
+    fn is_attractor(&self) -> bool {
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+}
+
+/// The distances along the ray where it enters and leaves the boundary, limited to the range
+/// being searched, along with the hit where it enters
+fn boundary_interval(
+    boundary: &Geometry,
+    ray: &Ray,
+    tmin: Float,
+    tmax: Float,
+) -> Option<(HitResult, Float, Float)> {
+    let first_hit = boundary.hit(ray, Float::MIN, Float::MAX)?;
+    let second_hit = boundary.hit(ray, first_hit.distance + 0.0001, Float::MAX)?;
+
+    let d1 = float::max(first_hit.distance, tmin);
+    let d2 = float::min(second_hit.distance, tmax);
+
+    if d1 >= d2 {
+        return None;
+    }
+    let d1 = float::max(d1, 0.0);
+
+    Some((first_hit, d1, d2))
+}
+
+fn scatter_hit(ray: &Ray, distance: Float, material: &Material, first_hit: HitResult) -> HitResult {
+    HitResult {
+        distance,
+        ray: *ray,
+        point: ray.point(distance),
+        surface_normal: Vector::new(1.0, 0.0, 0.0), // Arbitrary,
+        material: material.clone(),
+        texture_gradients: None,
+        ..first_hit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::{Colour, ColourSpace};
+    use crate::world::geometry::cube::Cube;
+    use crate::world::texture::{Filter, WrapMode};
+
+    fn boundary() -> Geometry {
+        Cube::build(
+            Vector::new(0.0, -1.0, -1.0),
+            Vector::new(10.0, 1.0, 1.0),
+            Material::Isotropic {
+                albedo: constant(1.0),
+            },
+        )
+    }
+
+    fn constant(value: Float) -> Texture {
+        Texture::Constant {
+            colour: Colour::new(value, value, value),
+        }
+    }
+
+    // The fraction of rays along the length of the boundary that scatter before leaving it
+    fn scattered_fraction(medium: &Geometry) -> Float {
+        let ray = Ray::new(Vector::new(-1.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
+        let samples = 20_000;
+        let scattered = (0..samples)
+            .filter(|_| medium.hit(&ray, 0.001, Float::MAX).is_some())
+            .count();
+        scattered as Float / samples as Float
+    }
+
+    #[test]
+    fn test_constant_density_matches_constant_medium() {
+        // Both should scatter 1 - e^-1 of the rays crossing 10 units at a density of 0.1
+        let expected = 1.0 - Float::exp(-1.0);
+
+        let constant_medium = ConstantMedium::build(boundary(), 0.1, constant(1.0));
+        let variable_medium = VariableMedium::build(boundary(), 0.1, constant(1.0), constant(1.0));
+
+        assert!((scattered_fraction(&constant_medium) - expected).abs() < 0.02);
+        assert!((scattered_fraction(&variable_medium) - expected).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_variable_density_scales_the_maximum() {
+        // Half the density everywhere is the same as a constant medium at half the density
+        let expected = 1.0 - Float::exp(-0.5);
+        let medium = VariableMedium::build(boundary(), 0.1, constant(0.5), constant(1.0));
+        assert!((scattered_fraction(&medium) - expected).abs() < 0.02);
+
+        let empty = VariableMedium::build(boundary(), 0.1, constant(0.0), constant(1.0));
+        assert_eq!(scattered_fraction(&empty), 0.0);
+    }
+
+    #[test]
+    fn test_variable_density_rejects_images() {
+        let assets = Assets::default();
+        let medium = VariableMedium::build(
+            boundary(),
+            0.1,
+            Texture::Image {
+                asset_name: String::from("clouds.png"),
+                filter: Filter::Bilinear,
+                wrap_mode: WrapMode::Clamp,
+                colour_space: ColourSpace::Linear,
+            },
+            constant(1.0),
+        );
+        assert!(medium.validate(&assets).is_err());
+    }
 }
@@ -0,0 +1,397 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::bounding_volume_hierarchy::BoundingVolumeHierarchyNode;
+use crate::world::geometry::triangle::Triangle;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+use serde::{Serialize, Serializer};
+use std::convert::TryFrom;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum MeshError {
+    #[error("{source_name}:{line_number}: could not parse `{line}`")]
+    InvalidLine {
+        source_name: String,
+        line_number: usize,
+        line: String,
+    },
+    #[error("{source_name}:{line_number}: vertex index {index} is out of range")]
+    InvalidIndex {
+        source_name: String,
+        line_number: usize,
+        index: i64,
+    },
+    #[error("{source_name} does not contain any triangles")]
+    Empty { source_name: String },
+    #[error("{source_name} could not be found")]
+    MissingFile { source_name: String },
+}
+
+/// Triangles loaded from a Wavefront OBJ file. Meshes are saved as the path of the file and the
+/// material of its triangles, and the file is read again when the mesh is loaded
+#[derive(Deserialize, Clone, Debug)]
+#[serde(try_from = "SavedMesh")]
+pub struct TriangleMesh {
+    source: String,
+    num_of_triangles: usize,
+    triangles: Box<Geometry>,
+    material: Material,
+}
+
+#[derive(Deserialize)]
+struct SavedMesh {
+    path: String,
+    material: Material,
+}
+
+#[derive(Serialize)]
+struct SavedMeshRef<'a> {
+    path: &'a str,
+    material: &'a Material,
+}
+
+impl TryFrom<SavedMesh> for TriangleMesh {
+    type Error = anyhow::Error;
+
+    fn try_from(saved: SavedMesh) -> Result<TriangleMesh, anyhow::Error> {
+        TriangleMesh::read_obj(saved.path, saved.material)
+    }
+}
+
+impl Serialize for TriangleMesh {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SavedMeshRef {
+            path: &self.source,
+            material: &self.material,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl TriangleMesh {
+    /// Loads a Wavefront OBJ file as a geometry, with its triangles in a bounding volume hierarchy.
+    /// Only `v`, `vn` and `f` directives are used, anything else in the file is skipped. Faces
+    /// with more than 3 vertices are split into a triangle fan and faces that reference a normal
+    /// for every vertex are smooth shaded
+    pub fn load_obj<P: AsRef<Path>>(
+        path: P,
+        material: Material,
+    ) -> Result<Geometry, anyhow::Error> {
+        let mesh = TriangleMesh::read_obj(path, material)?;
+        Ok(Geometry::Mesh(Box::from(mesh)))
+    }
+
+    fn read_obj<P: AsRef<Path>>(
+        path: P,
+        material: Material,
+    ) -> Result<TriangleMesh, anyhow::Error> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        let mesh = TriangleMesh::parse_obj(&path.display().to_string(), &contents, material)?;
+        Ok(mesh)
+    }
+
+    fn parse_obj(
+        source: &str,
+        contents: &str,
+        material: Material,
+    ) -> Result<TriangleMesh, MeshError> {
+        let invalid_line = |line_number: usize, line: &str| MeshError::InvalidLine {
+            source_name: source.to_string(),
+            line_number,
+            line: line.to_string(),
+        };
+
+        let mut vertices: Vec<Vector> = Vec::new();
+        // A zero normal has no direction, so faces using it are shaded flat
+        let mut normals: Vec<Option<Vector>> = Vec::new();
+        let mut triangles: Vec<Geometry> = Vec::new();
+
+        for (line_index, line) in contents.lines().enumerate() {
+            let line_number = line_index + 1;
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("v") => {
+                    let vertex =
+                        parse_vector(tokens).ok_or_else(|| invalid_line(line_number, line))?;
+                    vertices.push(vertex);
+                }
+                Some("vn") => {
+                    let normal =
+                        parse_vector(tokens).ok_or_else(|| invalid_line(line_number, line))?;
+                    normals.push(if normal.len_squared() > 0.0 {
+                        Some(normal.unit_vector())
+                    } else {
+                        None
+                    });
+                }
+                Some("f") => {
+                    let invalid_index = |index: i64| MeshError::InvalidIndex {
+                        source_name: source.to_string(),
+                        line_number,
+                        index,
+                    };
+
+                    let face = tokens
+                        .map(|token| {
+                            // Faces can be written as `v`, `v/vt`, `v//vn` or `v/vt/vn`
+                            let mut indices = token.split('/');
+                            let vertex = indices
+                                .next()
+                                .and_then(|index| index.parse::<i64>().ok())
+                                .ok_or_else(|| invalid_line(line_number, line))?;
+                            let vertex = resolve_index(vertex, vertices.len())
+                                .ok_or_else(|| invalid_index(vertex))?;
+
+                            let normal = match indices.nth(1) {
+                                None | Some("") => None,
+                                Some(normal) => {
+                                    let normal = normal
+                                        .parse::<i64>()
+                                        .map_err(|_| invalid_line(line_number, line))?;
+                                    let normal = resolve_index(normal, normals.len())
+                                        .ok_or_else(|| invalid_index(normal))?;
+                                    normals[normal]
+                                }
+                            };
+
+                            Ok((vertex, normal))
+                        })
+                        .collect::<Result<Vec<(usize, Option<Vector>)>, MeshError>>()?;
+                    if face.len() < 3 {
+                        return Err(invalid_line(line_number, line));
+                    }
+
+                    let smooth = face.iter().all(|(_, normal)| normal.is_some());
+                    for i in 1..face.len() - 1 {
+                        let (v0, n0) = face[0];
+                        let (v1, n1) = face[i];
+                        let (v2, n2) = face[i + 1];
+
+                        let triangle = match (n0, n1, n2) {
+                            (Some(n0), Some(n1), Some(n2)) if smooth => Triangle::build_smooth(
+                                vertices[v0],
+                                vertices[v1],
+                                vertices[v2],
+                                [n0, n1, n2],
+                                material.clone(),
+                            ),
+                            _ => Triangle::build(
+                                vertices[v0],
+                                vertices[v1],
+                                vertices[v2],
+                                material.clone(),
+                            ),
+                        };
+                        triangles.push(triangle);
+                    }
+                }
+                _ => continue,
+            }
+        }
+
+        if triangles.is_empty() {
+            return Err(MeshError::Empty {
+                source_name: source.to_string(),
+            });
+        }
+
+        let num_of_triangles = triangles.len();
+        Ok(TriangleMesh {
+            source: source.to_string(),
+            num_of_triangles,
+            triangles: Box::from(BoundingVolumeHierarchyNode::build(triangles, 0.0, 0.0)),
+            material,
+        })
+    }
+}
+
+fn parse_vector<'a, I: Iterator<Item = &'a str>>(tokens: I) -> Option<Vector> {
+    let coords = tokens
+        .take(3)
+        .map(|token| token.parse::<Float>().ok())
+        .collect::<Option<Vec<Float>>>()?;
+    if coords.len() != 3 {
+        return None;
+    }
+    Some(Vector::new(coords[0], coords[1], coords[2]))
+}
+
+/// OBJ indices start at 1 and negative indices count backwards from the last vertex read so far
+fn resolve_index(index: i64, num_of_vertices: usize) -> Option<usize> {
+    let num_of_vertices = num_of_vertices as i64;
+    let resolved = if index < 0 {
+        num_of_vertices + index
+    } else {
+        index - 1
+    };
+
+    if resolved >= 0 && resolved < num_of_vertices {
+        Some(resolved as usize)
+    } else {
+        None
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        self.triangles.hit(ray, tmin, tmax)
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        self.triangles.bounding_box(time_start, time_end)
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        if self.num_of_triangles == 0 {
+            return Err(MeshError::Empty {
+                source_name: self.source.clone(),
+            }
+            .into());
+        }
+        // The file is read again whenever the mesh is loaded, so it has to stay where it was
+        if !Path::new(&self.source).is_file() {
+            return Err(MeshError::MissingFile {
+                source_name: self.source.clone(),
+            }
+            .into());
+        }
+        self.triangles.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.triangles.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::geometry::test_material;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_parse_obj_triangulates_quads() {
+        let obj = "# unit square\n\
+                   o square\n\
+                   v 0.0 0.0 0.0\n\
+                   v 1.0 0.0 0.0\n\
+                   v 1.0 1.0 0.0\n\
+                   v 0.0 1.0 0.0\n\
+                   vt 0.0 0.0\n\
+                   s off\n\
+                   f 1/1 2/1 3/1 4/1\n";
+
+        let mesh = TriangleMesh::parse_obj("square.obj", obj, test_material()).unwrap();
+        assert_eq!(mesh.num_of_triangles, 2);
+
+        let ray = Ray::new(Vector::new(0.9, 0.1, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        assert_approx_eq!(mesh.hit(&ray, 0.0, Float::MAX).unwrap().distance, 1.0);
+
+        let ray = Ray::new(Vector::new(0.1, 0.9, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        assert_approx_eq!(mesh.hit(&ray, 0.0, Float::MAX).unwrap().distance, 1.0);
+    }
+
+    #[test]
+    fn test_parse_obj_negative_indices() {
+        let obj = "v 0.0 0.0 0.0\nv 1.0 0.0 0.0\nv 0.0 1.0 0.0\nf -3 -2 -1\n";
+
+        let mesh = TriangleMesh::parse_obj("triangle.obj", obj, test_material()).unwrap();
+        assert_eq!(mesh.num_of_triangles, 1);
+    }
+
+    #[test]
+    fn test_parse_obj_vertex_normals() {
+        let obj = "v 0.0 0.0 0.0\n\
+                   v 1.0 0.0 0.0\n\
+                   v 0.0 1.0 0.0\n\
+                   vn 0.0 0.0 2.0\n\
+                   vn 1.0 0.0 1.0\n\
+                   vn 0.0 1.0 1.0\n\
+                   f 1//1 2//2 3//3\n";
+
+        let mesh = TriangleMesh::parse_obj("smooth.obj", obj, test_material()).unwrap();
+
+        let ray = Ray::new(Vector::new(0.0, 0.0, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        let hit_result = mesh.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_eq!(hit_result.surface_normal, Vector::new(0.0, 0.0, 1.0));
+
+        let ray = Ray::new(Vector::new(0.5, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        let hit_result = mesh.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert!(hit_result.surface_normal.x() > 0.0);
+        assert!(hit_result.surface_normal.y() > 0.0);
+    }
+
+    #[test]
+    fn test_parse_obj_zero_normal_is_shaded_flat() {
+        let obj = "v 0.0 0.0 0.0\n\
+                   v 1.0 0.0 0.0\n\
+                   v 0.0 1.0 0.0\n\
+                   vn 0.0 0.0 1.0\n\
+                   vn 0.0 0.0 0.0\n\
+                   f 1//1 2//2 3//1\n";
+
+        let mesh = TriangleMesh::parse_obj("zero.obj", obj, test_material()).unwrap();
+
+        let ray = Ray::new(Vector::new(0.2, 0.2, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        let hit_result = mesh.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_eq!(hit_result.surface_normal, Vector::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn test_parse_obj_errors() {
+        let out_of_range = "v 0.0 0.0 0.0\nv 1.0 0.0 0.0\nf 1 2 3\n";
+        match TriangleMesh::parse_obj("bad.obj", out_of_range, test_material()) {
+            Err(MeshError::InvalidIndex {
+                line_number, index, ..
+            }) => {
+                assert_eq!(line_number, 3);
+                assert_eq!(index, 3);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let bad_vertex = "v 0.0 zero 0.0\n";
+        assert!(TriangleMesh::parse_obj("bad.obj", bad_vertex, test_material()).is_err());
+
+        let no_faces = "v 0.0 0.0 0.0\n";
+        match TriangleMesh::parse_obj("empty.obj", no_faces, test_material()) {
+            Err(MeshError::Empty { .. }) => (),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_mesh_loads_from_path() {
+        let path = std::env::temp_dir().join(format!("rayt_mesh_{}.obj", std::process::id()));
+        std::fs::write(
+            &path,
+            "v 0.0 0.0 0.0\nv 1.0 0.0 0.0\nv 0.0 1.0 0.0\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let mesh = TriangleMesh::load_obj(&path, test_material()).unwrap();
+        let saved = serde_yaml::to_string(&mesh).unwrap();
+        assert!(saved.contains(&path.display().to_string()));
+        assert!(!saved.contains("Triangle"));
+
+        let loaded: Geometry = serde_yaml::from_str(&saved).unwrap();
+        let ray = Ray::new(Vector::new(0.2, 0.2, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        assert_approx_eq!(loaded.hit(&ray, 0.0, Float::MAX).unwrap().distance, 1.0);
+        assert!(loaded.validate(&Assets::default()).is_ok());
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.validate(&Assets::default()).is_err());
+        assert!(serde_yaml::from_str::<Geometry>(&saved).is_err());
+    }
+}
@@ -1,25 +1,62 @@
+pub mod animated;
 pub mod axis_aligned_bounding_box;
+pub mod bilinear_patch;
 pub mod bounding_volume_hierarchy;
+pub mod capsule;
+pub mod cone;
+pub mod csg;
 pub mod cube;
+pub mod cylinder;
+pub mod disk;
+pub mod ellipsoid;
 pub mod flip_normals;
+pub mod height_field;
+pub mod instance;
+pub mod light_group;
 pub mod medium;
+pub mod mesh;
+pub mod plane;
+pub mod quad;
 pub mod rectangle;
 pub mod rotate;
+pub mod scale;
+pub mod sdf;
 pub mod sphere;
+pub mod torus;
 pub mod translate;
+pub mod triangle;
 
 use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::geometry::animated::Animated;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::bilinear_patch::BilinearPatch;
 use crate::world::geometry::bounding_volume_hierarchy::BoundingVolumeHierarchyNode;
+use crate::world::geometry::capsule::Capsule;
+use crate::world::geometry::cone::Cone;
+use crate::world::geometry::csg::Csg;
 use crate::world::geometry::cube::Cube;
+use crate::world::geometry::cylinder::Cylinder;
+use crate::world::geometry::disk::Disk;
+use crate::world::geometry::ellipsoid::Ellipsoid;
 use crate::world::geometry::flip_normals::FlipNormals;
-use crate::world::geometry::medium::ConstantMedium;
+use crate::world::geometry::height_field::HeightField;
+use crate::world::geometry::instance::Instance;
+use crate::world::geometry::light_group::LightGroup;
+use crate::world::geometry::medium::{ConstantMedium, VariableMedium};
+use crate::world::geometry::mesh::TriangleMesh;
+use crate::world::geometry::plane::Plane;
+use crate::world::geometry::quad::Quad;
 use crate::world::geometry::rectangle::{XyRect, XzRect, YzRect};
-use crate::world::geometry::rotate::RotateY;
+use crate::world::geometry::rotate::{RotateAxis, RotateX, RotateY, RotateZ};
+use crate::world::geometry::scale::Scale;
+use crate::world::geometry::sdf::Sdf;
 use crate::world::geometry::sphere::{MovingSphere, Sphere};
+use crate::world::geometry::torus::Torus;
 use crate::world::geometry::translate::Translate;
+use crate::world::geometry::triangle::Triangle;
 use crate::world::materials::Material;
 use anyhow::Error;
 use std::cmp::Ordering;
@@ -37,11 +74,33 @@ pub enum Geometry {
     YzRect(Box<YzRect>),
     Flip(Box<FlipNormals>),
     Translate(Box<Translate>),
+    RotateX(Box<RotateX>),
     RotateY(Box<RotateY>),
+    RotateZ(Box<RotateZ>),
+    Triangle(Box<Triangle>),
+    Mesh(Box<TriangleMesh>),
+    Cylinder(Box<Cylinder>),
+    Cone(Box<Cone>),
+    Torus(Box<Torus>),
+    Plane(Box<Plane>),
+    Disk(Box<Disk>),
+    Scale(Box<Scale>),
+    RotateAxis(Box<RotateAxis>),
+    Csg(Box<Csg>),
+    VariableMedium(Box<VariableMedium>),
+    Ellipsoid(Box<Ellipsoid>),
+    HeightField(Box<HeightField>),
+    BilinearPatch(Box<BilinearPatch>),
+    Capsule(Box<Capsule>),
+    Sdf(Box<Sdf>),
+    Quad(Box<Quad>),
+    Instance(Box<Instance>),
+    Animated(Box<Animated>),
+    LightGroup(Box<LightGroup>),
 }
 
 impl Hittable for Geometry {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
         match self {
             Geometry::Bvh(inner) => inner.hit(ray, tmin, tmax),
             Geometry::Sphere(inner) => inner.hit(ray, tmin, tmax),
@@ -53,11 +112,33 @@ impl Hittable for Geometry {
             Geometry::YzRect(inner) => inner.hit(ray, tmin, tmax),
             Geometry::Flip(inner) => inner.hit(ray, tmin, tmax),
             Geometry::Translate(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::RotateX(inner) => inner.hit(ray, tmin, tmax),
             Geometry::RotateY(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::RotateZ(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Triangle(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Mesh(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Cylinder(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Cone(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Torus(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Plane(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Disk(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Scale(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::RotateAxis(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Csg(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::VariableMedium(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Ellipsoid(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::HeightField(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::BilinearPatch(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Capsule(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Sdf(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Quad(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Instance(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::Animated(inner) => inner.hit(ray, tmin, tmax),
+            Geometry::LightGroup(inner) => inner.hit(ray, tmin, tmax),
         }
     }
 
-    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
         match self {
             Geometry::Bvh(inner) => inner.bounding_box(time_start, time_end),
             Geometry::Sphere(inner) => inner.bounding_box(time_start, time_end),
@@ -69,7 +150,29 @@ impl Hittable for Geometry {
             Geometry::YzRect(inner) => inner.bounding_box(time_start, time_end),
             Geometry::Flip(inner) => inner.bounding_box(time_start, time_end),
             Geometry::Translate(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::RotateX(inner) => inner.bounding_box(time_start, time_end),
             Geometry::RotateY(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::RotateZ(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Triangle(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Mesh(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Cylinder(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Cone(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Torus(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Plane(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Disk(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Scale(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::RotateAxis(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Csg(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::VariableMedium(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Ellipsoid(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::HeightField(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::BilinearPatch(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Capsule(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Sdf(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Quad(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Instance(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::Animated(inner) => inner.bounding_box(time_start, time_end),
+            Geometry::LightGroup(inner) => inner.bounding_box(time_start, time_end),
         }
     }
 
@@ -85,7 +188,29 @@ impl Hittable for Geometry {
             Geometry::YzRect(inner) => inner.validate(assets),
             Geometry::Flip(inner) => inner.validate(assets),
             Geometry::Translate(inner) => inner.validate(assets),
+            Geometry::RotateX(inner) => inner.validate(assets),
             Geometry::RotateY(inner) => inner.validate(assets),
+            Geometry::RotateZ(inner) => inner.validate(assets),
+            Geometry::Triangle(inner) => inner.validate(assets),
+            Geometry::Mesh(inner) => inner.validate(assets),
+            Geometry::Cylinder(inner) => inner.validate(assets),
+            Geometry::Cone(inner) => inner.validate(assets),
+            Geometry::Torus(inner) => inner.validate(assets),
+            Geometry::Plane(inner) => inner.validate(assets),
+            Geometry::Disk(inner) => inner.validate(assets),
+            Geometry::Scale(inner) => inner.validate(assets),
+            Geometry::RotateAxis(inner) => inner.validate(assets),
+            Geometry::Csg(inner) => inner.validate(assets),
+            Geometry::VariableMedium(inner) => inner.validate(assets),
+            Geometry::Ellipsoid(inner) => inner.validate(assets),
+            Geometry::HeightField(inner) => inner.validate(assets),
+            Geometry::BilinearPatch(inner) => inner.validate(assets),
+            Geometry::Capsule(inner) => inner.validate(assets),
+            Geometry::Sdf(inner) => inner.validate(assets),
+            Geometry::Quad(inner) => inner.validate(assets),
+            Geometry::Instance(inner) => inner.validate(assets),
+            Geometry::Animated(inner) => inner.validate(assets),
+            Geometry::LightGroup(inner) => inner.validate(assets),
         }
     }
 
@@ -101,11 +226,71 @@ impl Hittable for Geometry {
             Geometry::YzRect(inner) => inner.is_attractor(),
             Geometry::Flip(inner) => inner.is_attractor(),
             Geometry::Translate(inner) => inner.is_attractor(),
+            Geometry::RotateX(inner) => inner.is_attractor(),
             Geometry::RotateY(inner) => inner.is_attractor(),
+            Geometry::RotateZ(inner) => inner.is_attractor(),
+            Geometry::Triangle(inner) => inner.is_attractor(),
+            Geometry::Mesh(inner) => inner.is_attractor(),
+            Geometry::Cylinder(inner) => inner.is_attractor(),
+            Geometry::Cone(inner) => inner.is_attractor(),
+            Geometry::Torus(inner) => inner.is_attractor(),
+            Geometry::Plane(inner) => inner.is_attractor(),
+            Geometry::Disk(inner) => inner.is_attractor(),
+            Geometry::Scale(inner) => inner.is_attractor(),
+            Geometry::RotateAxis(inner) => inner.is_attractor(),
+            Geometry::Csg(inner) => inner.is_attractor(),
+            Geometry::VariableMedium(inner) => inner.is_attractor(),
+            Geometry::Ellipsoid(inner) => inner.is_attractor(),
+            Geometry::HeightField(inner) => inner.is_attractor(),
+            Geometry::BilinearPatch(inner) => inner.is_attractor(),
+            Geometry::Capsule(inner) => inner.is_attractor(),
+            Geometry::Sdf(inner) => inner.is_attractor(),
+            Geometry::Quad(inner) => inner.is_attractor(),
+            Geometry::Instance(inner) => inner.is_attractor(),
+            Geometry::Animated(inner) => inner.is_attractor(),
+            Geometry::LightGroup(inner) => inner.is_attractor(),
         }
     }
 
-    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+    fn emits_light(&self) -> bool {
+        match self {
+            Geometry::Bvh(inner) => inner.emits_light(),
+            Geometry::Sphere(inner) => inner.emits_light(),
+            Geometry::MovingSphere(inner) => inner.emits_light(),
+            Geometry::Cube(inner) => inner.emits_light(),
+            Geometry::ConstantMedium(inner) => inner.emits_light(),
+            Geometry::XyRect(inner) => inner.emits_light(),
+            Geometry::XzRect(inner) => inner.emits_light(),
+            Geometry::YzRect(inner) => inner.emits_light(),
+            Geometry::Flip(inner) => inner.emits_light(),
+            Geometry::Translate(inner) => inner.emits_light(),
+            Geometry::RotateX(inner) => inner.emits_light(),
+            Geometry::RotateY(inner) => inner.emits_light(),
+            Geometry::RotateZ(inner) => inner.emits_light(),
+            Geometry::Triangle(inner) => inner.emits_light(),
+            Geometry::Mesh(inner) => inner.emits_light(),
+            Geometry::Cylinder(inner) => inner.emits_light(),
+            Geometry::Cone(inner) => inner.emits_light(),
+            Geometry::Torus(inner) => inner.emits_light(),
+            Geometry::Plane(inner) => inner.emits_light(),
+            Geometry::Disk(inner) => inner.emits_light(),
+            Geometry::Scale(inner) => inner.emits_light(),
+            Geometry::RotateAxis(inner) => inner.emits_light(),
+            Geometry::Csg(inner) => inner.emits_light(),
+            Geometry::VariableMedium(inner) => inner.emits_light(),
+            Geometry::Ellipsoid(inner) => inner.emits_light(),
+            Geometry::HeightField(inner) => inner.emits_light(),
+            Geometry::BilinearPatch(inner) => inner.emits_light(),
+            Geometry::Capsule(inner) => inner.emits_light(),
+            Geometry::Sdf(inner) => inner.emits_light(),
+            Geometry::Quad(inner) => inner.emits_light(),
+            Geometry::Instance(inner) => inner.emits_light(),
+            Geometry::Animated(inner) => inner.emits_light(),
+            Geometry::LightGroup(inner) => inner.emits_light(),
+        }
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
         match self {
             Geometry::Bvh(inner) => inner.pdf_value(origin, direction),
             Geometry::Sphere(inner) => inner.pdf_value(origin, direction),
@@ -117,7 +302,29 @@ impl Hittable for Geometry {
             Geometry::YzRect(inner) => inner.pdf_value(origin, direction),
             Geometry::Flip(inner) => inner.pdf_value(origin, direction),
             Geometry::Translate(inner) => inner.pdf_value(origin, direction),
+            Geometry::RotateX(inner) => inner.pdf_value(origin, direction),
             Geometry::RotateY(inner) => inner.pdf_value(origin, direction),
+            Geometry::RotateZ(inner) => inner.pdf_value(origin, direction),
+            Geometry::Triangle(inner) => inner.pdf_value(origin, direction),
+            Geometry::Mesh(inner) => inner.pdf_value(origin, direction),
+            Geometry::Cylinder(inner) => inner.pdf_value(origin, direction),
+            Geometry::Cone(inner) => inner.pdf_value(origin, direction),
+            Geometry::Torus(inner) => inner.pdf_value(origin, direction),
+            Geometry::Plane(inner) => inner.pdf_value(origin, direction),
+            Geometry::Disk(inner) => inner.pdf_value(origin, direction),
+            Geometry::Scale(inner) => inner.pdf_value(origin, direction),
+            Geometry::RotateAxis(inner) => inner.pdf_value(origin, direction),
+            Geometry::Csg(inner) => inner.pdf_value(origin, direction),
+            Geometry::VariableMedium(inner) => inner.pdf_value(origin, direction),
+            Geometry::Ellipsoid(inner) => inner.pdf_value(origin, direction),
+            Geometry::HeightField(inner) => inner.pdf_value(origin, direction),
+            Geometry::BilinearPatch(inner) => inner.pdf_value(origin, direction),
+            Geometry::Capsule(inner) => inner.pdf_value(origin, direction),
+            Geometry::Sdf(inner) => inner.pdf_value(origin, direction),
+            Geometry::Quad(inner) => inner.pdf_value(origin, direction),
+            Geometry::Instance(inner) => inner.pdf_value(origin, direction),
+            Geometry::Animated(inner) => inner.pdf_value(origin, direction),
+            Geometry::LightGroup(inner) => inner.pdf_value(origin, direction),
         }
     }
 
@@ -133,7 +340,29 @@ impl Hittable for Geometry {
             Geometry::YzRect(inner) => inner.random(origin),
             Geometry::Flip(inner) => inner.random(origin),
             Geometry::Translate(inner) => inner.random(origin),
+            Geometry::RotateX(inner) => inner.random(origin),
             Geometry::RotateY(inner) => inner.random(origin),
+            Geometry::RotateZ(inner) => inner.random(origin),
+            Geometry::Triangle(inner) => inner.random(origin),
+            Geometry::Mesh(inner) => inner.random(origin),
+            Geometry::Cylinder(inner) => inner.random(origin),
+            Geometry::Cone(inner) => inner.random(origin),
+            Geometry::Torus(inner) => inner.random(origin),
+            Geometry::Plane(inner) => inner.random(origin),
+            Geometry::Disk(inner) => inner.random(origin),
+            Geometry::Scale(inner) => inner.random(origin),
+            Geometry::RotateAxis(inner) => inner.random(origin),
+            Geometry::Csg(inner) => inner.random(origin),
+            Geometry::VariableMedium(inner) => inner.random(origin),
+            Geometry::Ellipsoid(inner) => inner.random(origin),
+            Geometry::HeightField(inner) => inner.random(origin),
+            Geometry::BilinearPatch(inner) => inner.random(origin),
+            Geometry::Capsule(inner) => inner.random(origin),
+            Geometry::Sdf(inner) => inner.random(origin),
+            Geometry::Quad(inner) => inner.random(origin),
+            Geometry::Instance(inner) => inner.random(origin),
+            Geometry::Animated(inner) => inner.random(origin),
+            Geometry::LightGroup(inner) => inner.random(origin),
         }
     }
 }
@@ -147,22 +376,57 @@ impl Geometry {
         Translate::build(self, offset)
     }
 
-    pub fn rotate_y(self, angle: f64) -> Result<Geometry, anyhow::Error> {
+    pub fn rotate_x(self, angle: Float) -> Result<Geometry, anyhow::Error> {
+        let rotate = RotateX::build(self, angle)?;
+        Ok(rotate)
+    }
+
+    pub fn rotate_y(self, angle: Float) -> Result<Geometry, anyhow::Error> {
         let rotate = RotateY::build(self, angle)?;
         Ok(rotate)
     }
+
+    pub fn rotate_z(self, angle: Float) -> Result<Geometry, anyhow::Error> {
+        let rotate = RotateZ::build(self, angle)?;
+        Ok(rotate)
+    }
+
+    pub fn rotate_axis(self, axis: Vector, angle: Float) -> Result<Geometry, anyhow::Error> {
+        let rotate = RotateAxis::build(self, axis, angle)?;
+        Ok(rotate)
+    }
+
+    pub fn scale(self, factor: Vector) -> Result<Geometry, anyhow::Error> {
+        let scale = Scale::build(self, factor)?;
+        Ok(scale)
+    }
+
+    pub fn in_light_group(self, group: u32) -> Geometry {
+        LightGroup::build(group, self)
+    }
+
+    /// The light group the whole geometry is in, if it is put in one at its top
+    pub fn light_group(&self) -> Option<u32> {
+        match self {
+            Geometry::LightGroup(inner) => Some(inner.group()),
+            _ => None,
+        }
+    }
 }
 
 pub trait Hittable: Debug {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult>;
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult>;
 
-    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox>;
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox>;
 
     fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error>;
 
     fn is_attractor(&self) -> bool;
 
-    fn pdf_value(&self, _origin: &Vector, _direction: &Vector) -> f64 {
+    /// Whether any part of the geometry gives off light
+    fn emits_light(&self) -> bool;
+
+    fn pdf_value(&self, _origin: &Vector, _direction: &Vector) -> Float {
         unimplemented!("{:?} is not implemented as an attractor", self)
     }
 
@@ -173,12 +437,16 @@ pub trait Hittable: Debug {
 
 #[derive(Debug, Clone)]
 pub struct HitResult {
-    pub distance: f64,
+    pub distance: Float,
     pub ray: Ray,
     pub point: Vector,
     pub surface_normal: Vector,
     pub material: Material,
-    pub texture_coords: (f64, f64),
+    pub texture_coords: (Float, Float),
+    // How the hit point moves as each of the texture coords increases, when the geometry knows
+    pub texture_gradients: Option<(Vector, Vector)>,
+    // The light group of the geometry that was hit, if it is in one
+    pub light_group: Option<u32>,
 }
 
 impl HitResult {
@@ -197,8 +465,16 @@ impl HitResult {
 
 impl Ord for HitResult {
     fn cmp(&self, other: &HitResult) -> Ordering {
-        // We should never get a NaN here. Panic if we do
-        self.distance.partial_cmp(&other.distance).unwrap()
+        // A NaN distance from a degenerate primitive sorts after every real distance so it is never
+        // picked as the closest hit, and all NaNs are equal so the ordering stays total
+        self.distance
+            .is_nan()
+            .cmp(&other.distance.is_nan())
+            .then_with(|| {
+                self.distance
+                    .partial_cmp(&other.distance)
+                    .unwrap_or(Ordering::Equal)
+            })
     }
 }
 
@@ -216,6 +492,16 @@ impl PartialEq for HitResult {
 
 impl Eq for HitResult {}
 
+/// A clear glass for the tests of geometries that do not depend on the material
+#[cfg(test)]
+pub(crate) fn test_material() -> Material {
+    Material::Dielectric {
+        refractive_index: 1.5,
+        absorption: None,
+        roughness: 0.0,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -229,8 +515,12 @@ mod tests {
             surface_normal: Vector::new(0.0, 0.0, 0.0),
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
             texture_coords: (1.0, 0.5),
+            texture_gradients: None,
+            light_group: None,
         };
         assert_eq!(hit_result.clone(), hit_result.clone());
 
@@ -241,8 +531,12 @@ mod tests {
             surface_normal: Vector::new(0.0, 0.0, 0.0),
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
             texture_coords: (1.0, 0.5),
+            texture_gradients: None,
+            light_group: None,
         };
         assert_ne!(hit_result, other_hit_result);
     }
@@ -256,8 +550,12 @@ mod tests {
             surface_normal: Vector::new(0.0, 0.0, 0.0),
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
             texture_coords: (1.0, 0.5),
+            texture_gradients: None,
+            light_group: None,
         };
         let other_hit_result = HitResult {
             distance: 1.0,
@@ -266,10 +564,47 @@ mod tests {
             surface_normal: Vector::new(0.0, 0.0, 0.0),
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
             texture_coords: (1.0, 0.5),
+            texture_gradients: None,
+            light_group: None,
         };
         assert!(other_hit_result > hit_result);
         assert!(hit_result < other_hit_result);
     }
+
+    #[test]
+    fn test_hit_result_nan_sorts_last() {
+        let hit_at = |distance| HitResult {
+            distance,
+            ray: Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 0.0), 0.0),
+            point: Vector::new(0.0, 0.0, 0.0),
+            surface_normal: Vector::new(0.0, 0.0, 0.0),
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+            texture_coords: (1.0, 0.5),
+            texture_gradients: None,
+            light_group: None,
+        };
+
+        let mut hits = vec![
+            hit_at(Float::NAN),
+            hit_at(2.0),
+            hit_at(-Float::NAN),
+            hit_at(Float::INFINITY),
+            hit_at(0.5),
+        ];
+        hits.sort();
+        let distances: Vec<Float> = hits.iter().map(|hit| hit.distance).collect();
+        assert_eq!(&distances[..3], &[0.5, 2.0, Float::INFINITY]);
+        assert!(distances[3].is_nan() && distances[4].is_nan());
+
+        assert_eq!(hit_at(Float::NAN), hit_at(-Float::NAN));
+        assert_eq!(hits.into_iter().min().unwrap().distance, 0.5);
+    }
 }
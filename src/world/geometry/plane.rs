@@ -0,0 +1,130 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::onb::Onb;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+
+// Rays that are this close to parallel with the plane are treated as misses
+const PARALLEL_EPSILON: Float = 1e-12;
+
+/// An infinite plane through `point`. It has no bounding box so it can only be used at the top
+/// level of a scene and not inside a BVH
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Plane {
+    point: Vector,
+    normal: Vector,
+    material: Material,
+}
+
+impl Plane {
+    pub fn build(point: Vector, normal: Vector, material: Material) -> Geometry {
+        Geometry::Plane(Box::from(Plane {
+            point,
+            normal: normal.unit_vector(),
+            material,
+        }))
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let normal = self.normal.unit_vector();
+        let denominator = Vector::dot(ray.direction(), &normal);
+        if denominator.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+
+        let distance = Vector::dot(&(self.point - ray.origin()), &normal) / denominator;
+        if distance < tmin || distance > tmax {
+            return None;
+        }
+
+        // Texture coords repeat every unit along two directions in the plane
+        let point = ray.point(distance);
+        let onb = Onb::build_from_w(&normal);
+        let offset = point - self.point;
+        let texture_coords = (
+            Vector::dot(&offset, onb.v()).rem_euclid(1.0),
+            Vector::dot(&offset, onb.u()).rem_euclid(1.0),
+        );
+
+        Some(HitResult {
+            distance,
+            ray: *ray,
+            point,
+            surface_normal: normal,
+            material: self.material.clone(),
+            texture_coords,
+            texture_gradients: Some((*onb.v(), *onb.u())),
+            light_group: None,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        None
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        // An infinite plane can not be sampled uniformly
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn plane() -> Plane {
+        Plane {
+            point: Vector::new(0.0, -1.0, 0.0),
+            normal: Vector::new(0.0, 1.0, 0.0),
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_plane_hit() {
+        let ray = Ray::new(
+            Vector::new(1000.0, 1.0, -500.0),
+            Vector::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+
+        let hit_result = plane().hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 2.0);
+        assert!(hit_result.front_face());
+
+        let (u, v) = hit_result.texture_coords;
+        assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+    }
+
+    #[test]
+    fn test_plane_miss() {
+        let parallel = Ray::new(Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
+        assert!(plane().hit(&parallel, 0.0, Float::MAX).is_none());
+
+        let away = Ray::new(Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, 1.0, 0.0), 0.0);
+        assert!(plane().hit(&away, 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_plane_has_no_bounding_box() {
+        assert_eq!(plane().bounding_box(0.0, 1.0), None);
+    }
+}
@@ -0,0 +1,252 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::pdf::uniform;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+use thiserror::Error;
+
+// Keeps the bounding box of a quad lying in an axis plane from having no thickness
+const BOUNDING_BOX_PADDING: Float = 0.0001;
+
+#[derive(Debug, Error)]
+pub enum QuadError {
+    #[error("the edges of a quad must not be parallel, got {u:?} and {v:?}")]
+    ParallelEdges { u: Vector, v: Vector },
+}
+
+/// The parallelogram with a corner at `corner` and edges `u` and `v` leaving it, in any
+/// orientation. The normal is `u × v`, and texture coords run from 0 to 1 along each edge
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Quad {
+    corner: Vector,
+    u: Vector,
+    v: Vector,
+    material: Material,
+}
+
+impl Quad {
+    pub fn build(corner: Vector, u: Vector, v: Vector, material: Material) -> Geometry {
+        Geometry::Quad(Box::from(Quad {
+            corner,
+            u,
+            v,
+            material,
+        }))
+    }
+
+    fn area(&self) -> Float {
+        Vector::cross(&self.u, &self.v).len()
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let n = Vector::cross(&self.u, &self.v);
+        let normal = n.unit_vector();
+        let denominator = Vector::dot(&normal, ray.direction());
+        // Parallel to the plane of the quad
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let distance = Vector::dot(&normal, &(self.corner - ray.origin())) / denominator;
+        if distance < tmin || distance > tmax {
+            return None;
+        }
+
+        // How far along each edge the point is, found by splitting it into the two edges
+        let point = ray.point(distance);
+        let planar = point - self.corner;
+        let w = n / Vector::dot(&n, &n);
+        let alpha = Vector::dot(&w, &Vector::cross(&planar, &self.v));
+        let beta = Vector::dot(&w, &Vector::cross(&self.u, &planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(HitResult {
+            distance,
+            ray: *ray,
+            point,
+            surface_normal: normal,
+            material: self.material.clone(),
+            texture_coords: (alpha, beta),
+            texture_gradients: Some((self.u, self.v)),
+            light_group: None,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        let padding = Vector::new(
+            BOUNDING_BOX_PADDING,
+            BOUNDING_BOX_PADDING,
+            BOUNDING_BOX_PADDING,
+        );
+        let corners = [
+            self.corner,
+            self.corner + self.u,
+            self.corner + self.v,
+            self.corner + self.u + self.v,
+        ];
+        let min = corners
+            .iter()
+            .fold(corners[0], |min, corner| min.min(corner));
+        let max = corners
+            .iter()
+            .fold(corners[0], |max, corner| max.max(corner));
+        Some(AxisAlignedBoundingBox::new(min - padding, max + padding))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        if self.area() == 0.0 {
+            return Err(QuadError::ParallelEdges {
+                u: self.u,
+                v: self.v,
+            }
+            .into());
+        }
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        self.material.is_attractor()
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        let direction = direction.unit_vector();
+        let hit = self.hit(&Ray::new(*origin, direction, 0.0), 0.001, Float::MAX);
+        match hit {
+            None => 0.0,
+            Some(hit) => {
+                let distance_squared = hit.distance.powi(2);
+                let cosine = Vector::dot(&direction, &hit.face_normal()).abs();
+
+                distance_squared / (cosine * self.area())
+            }
+        }
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        let random_point = self.corner + uniform::<Float>() * self.u + uniform::<Float>() * self.v;
+        random_point - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::Colour;
+    use crate::world::texture::Texture;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn material() -> Material {
+        Material::DiffuseLight {
+            emit: Texture::Constant {
+                colour: Colour::new(1.0, 1.0, 1.0),
+            },
+            strength: 1.0,
+            two_sided: false,
+            spotlight: None,
+        }
+    }
+
+    /// A 2x1 quad tilted 45 degrees about the x axis, facing up and towards +z
+    fn tilted_quad() -> Geometry {
+        Quad::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 0.0, 0.0),
+            Vector::new(0.0, 1.0, -1.0).unit_vector(),
+            material(),
+        )
+    }
+
+    #[test]
+    fn test_quad_hit() {
+        let quad = tilted_quad();
+        let ray = Ray::new(
+            Vector::new(1.5, 5.0, -0.5),
+            Vector::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        let hit = quad.hit(&ray, 0.001, Float::MAX).unwrap();
+
+        assert_approx_eq!(hit.distance, 4.5);
+        assert_approx_eq!(hit.point.y(), 0.5);
+        assert_approx_eq!(hit.texture_coords.0, 0.75);
+        assert_approx_eq!(hit.texture_coords.1, Float::sqrt(0.5));
+        let expected_normal = Vector::new(0.0, 1.0, 1.0).unit_vector();
+        assert_approx_eq!(hit.surface_normal.y(), expected_normal.y());
+        assert_approx_eq!(hit.surface_normal.z(), expected_normal.z());
+        assert!(hit.front_face());
+    }
+
+    #[test]
+    fn test_quad_misses_outside_edges() {
+        let quad = tilted_quad();
+        for origin in &[
+            Vector::new(2.5, 5.0, -0.5),
+            Vector::new(1.0, 5.0, -0.8),
+            Vector::new(1.0, 5.0, 0.2),
+        ] {
+            let ray = Ray::new(*origin, Vector::new(0.0, -1.0, 0.0), 0.0);
+            assert!(quad.hit(&ray, 0.001, Float::MAX).is_none());
+        }
+
+        // Along the plane of the quad
+        let ray = Ray::new(
+            Vector::new(-1.0, 0.1, -0.1),
+            Vector::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+        assert!(quad.hit(&ray, 0.001, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_quad_light_sampling() {
+        let quad = Quad::build(
+            Vector::new(-1.0, 2.0, -1.0),
+            Vector::new(0.0, 0.0, 2.0),
+            Vector::new(2.0, 0.0, 0.0),
+            material(),
+        );
+        let origin = Vector::new(0.0, 0.0, 0.0);
+
+        assert!(quad.is_attractor());
+        assert_approx_eq!(quad.pdf_value(&origin, &Vector::new(0.0, 1.0, 0.0)), 1.0);
+        for _ in 0..100 {
+            let direction = quad.random(&origin);
+            assert_approx_eq!(direction.y(), 2.0);
+            assert!(direction.x().abs() <= 1.0 && direction.z().abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_quad_bounding_box() {
+        let bounding_box = tilted_quad().bounding_box(0.0, 0.0).unwrap();
+        let side = Float::sqrt(0.5);
+        assert_approx_eq!(bounding_box.min().z(), -side - 0.0001);
+        assert_approx_eq!(bounding_box.max().x(), 2.0001);
+        assert_approx_eq!(bounding_box.max().y(), side + 0.0001);
+    }
+
+    #[test]
+    fn test_quad_with_parallel_edges_is_invalid() {
+        let assets = Assets::new(&[]).unwrap();
+        assert!(tilted_quad().validate(&assets).is_ok());
+
+        let flat = Quad::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 0.0),
+            Vector::new(2.0, 2.0, 0.0),
+            material(),
+        );
+        assert!(flat.validate(&assets).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::vector::Vector;
+use crate::float::Float;
 use crate::pdf::uniform_between;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
@@ -8,19 +9,19 @@ use crate::world::materials::Material;
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct XyRect {
-    x0: f64,
-    x1: f64,
-    y0: f64,
-    y1: f64,
-    k: f64,
+    x0: Float,
+    x1: Float,
+    y0: Float,
+    y1: Float,
+    k: Float,
     material: Material,
 }
 
 impl XyRect {
     pub fn build(
-        x_lines: (f64, f64),
-        y_lines: (f64, f64),
-        z_plane: f64,
+        x_lines: (Float, Float),
+        y_lines: (Float, Float),
+        z_plane: Float,
         material: Material,
     ) -> Geometry {
         let (x0, x1) = x_lines;
@@ -37,7 +38,7 @@ impl XyRect {
 }
 
 impl Hittable for XyRect {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
         let distance = (self.k - ray.origin().z()) / ray.direction().z();
 
         if distance.is_nan() || distance < tmin || distance > tmax {
@@ -61,10 +62,12 @@ impl Hittable for XyRect {
                 (x - self.x0) / (self.x1 - self.x0),
                 (y - self.y0) / (self.y1 - self.y0),
             ),
+            texture_gradients: Some((Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0))),
+            light_group: None,
         })
     }
 
-    fn bounding_box(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
         Some(AxisAlignedBoundingBox::new(
             Vector::new(self.x0, self.y0, self.k - 0.0001),
             Vector::new(self.x1, self.y1, self.k + 0.0001),
@@ -79,9 +82,13 @@ impl Hittable for XyRect {
         self.material.is_attractor()
     }
 
-    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
         let direction = direction.unit_vector();
-        let hit = self.hit(&Ray::new(*origin, direction, 0.0), 0.001, std::f64::MAX);
+        let hit = self.hit(&Ray::new(*origin, direction, 0.0), 0.001, Float::MAX);
         match hit {
             None => 0.0,
             Some(hit) => {
@@ -106,19 +113,19 @@ impl Hittable for XyRect {
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct XzRect {
-    x0: f64,
-    x1: f64,
-    z0: f64,
-    z1: f64,
-    k: f64,
+    x0: Float,
+    x1: Float,
+    z0: Float,
+    z1: Float,
+    k: Float,
     material: Material,
 }
 
 impl XzRect {
     pub fn build(
-        x_lines: (f64, f64),
-        z_lines: (f64, f64),
-        y_plane: f64,
+        x_lines: (Float, Float),
+        z_lines: (Float, Float),
+        y_plane: Float,
         material: Material,
     ) -> Geometry {
         let (x0, x1) = x_lines;
@@ -135,7 +142,7 @@ impl XzRect {
 }
 
 impl Hittable for XzRect {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
         let distance = (self.k - ray.origin().y()) / ray.direction().y();
 
         if distance.is_nan() || distance < tmin || distance > tmax {
@@ -159,10 +166,12 @@ impl Hittable for XzRect {
                 (x - self.x0) / (self.x1 - self.x0),
                 (z - self.z0) / (self.z1 - self.z0),
             ),
+            texture_gradients: Some((Vector::new(1.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0))),
+            light_group: None,
         })
     }
 
-    fn bounding_box(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
         Some(AxisAlignedBoundingBox::new(
             Vector::new(self.x0, self.k - 0.0001, self.z0),
             Vector::new(self.x1, self.k + 0.0001, self.z1),
@@ -177,9 +186,13 @@ impl Hittable for XzRect {
         self.material.is_attractor()
     }
 
-    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
         let direction = direction.unit_vector();
-        let hit = self.hit(&Ray::new(*origin, direction, 0.0), 0.001, std::f64::MAX);
+        let hit = self.hit(&Ray::new(*origin, direction, 0.0), 0.001, Float::MAX);
         match hit {
             None => 0.0,
             Some(hit) => {
@@ -204,19 +217,19 @@ impl Hittable for XzRect {
 
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct YzRect {
-    y0: f64,
-    y1: f64,
-    z0: f64,
-    z1: f64,
-    k: f64,
+    y0: Float,
+    y1: Float,
+    z0: Float,
+    z1: Float,
+    k: Float,
     material: Material,
 }
 
 impl YzRect {
     pub fn build(
-        y_lines: (f64, f64),
-        z_lines: (f64, f64),
-        x_plane: f64,
+        y_lines: (Float, Float),
+        z_lines: (Float, Float),
+        x_plane: Float,
         material: Material,
     ) -> Geometry {
         let (y0, y1) = y_lines;
@@ -233,7 +246,7 @@ impl YzRect {
 }
 
 impl Hittable for YzRect {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
         let distance = (self.k - ray.origin().x()) / ray.direction().x();
 
         if distance.is_nan() || distance < tmin || distance > tmax {
@@ -257,10 +270,12 @@ impl Hittable for YzRect {
                 (y - self.y0) / (self.y1 - self.y0),
                 (z - self.z0) / (self.z1 - self.z0),
             ),
+            texture_gradients: Some((Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, 0.0, 1.0))),
+            light_group: None,
         })
     }
 
-    fn bounding_box(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
         Some(AxisAlignedBoundingBox::new(
             Vector::new(self.k - 0.0001, self.y0, self.z0),
             Vector::new(self.k + 0.0001, self.y1, self.z1),
@@ -275,9 +290,13 @@ impl Hittable for YzRect {
         self.material.is_attractor()
     }
 
-    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
         let direction = direction.unit_vector();
-        let hit = self.hit(&Ray::new(*origin, direction, 0.0), 0.001, std::f64::MAX);
+        let hit = self.hit(&Ray::new(*origin, direction, 0.0), 0.001, Float::MAX);
         match hit {
             None => 0.0,
             Some(hit) => {
@@ -315,11 +334,13 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
 
-        let hit_result = rect.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rect.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 1.0);
     }
 
@@ -333,11 +354,13 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
 
-        let hit_result = rect.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rect.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_eq!(hit_result.surface_normal, Vector::new(0.0, 0.0, 1.0));
     }
 
@@ -351,6 +374,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
 
@@ -372,11 +397,13 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
 
-        let hit_result = rect.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rect.hit(&ray, 0.0, Float::MAX).unwrap();
         let (u, v) = hit_result.texture_coords;
         assert_approx_eq!(u, 0.5);
         assert_approx_eq!(v, 0.5);
@@ -392,11 +419,13 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 1.0, 0.5), Vector::new(0.0, -1.0, 0.0), 0.0);
 
-        let hit_result = rect.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rect.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 1.0);
     }
 
@@ -410,11 +439,13 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 1.0, 0.5), Vector::new(0.0, -1.0, 0.0), 0.0);
 
-        let hit_result = rect.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rect.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_eq!(hit_result.surface_normal, Vector::new(0.0, 1.0, 0.0));
     }
 
@@ -428,6 +459,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
 
@@ -449,11 +482,13 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(0.5, 1.0, 0.5), Vector::new(0.0, -1.0, 0.0), 0.0);
 
-        let hit_result = rect.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rect.hit(&ray, 0.0, Float::MAX).unwrap();
         let (u, v) = hit_result.texture_coords;
         assert_approx_eq!(u, 0.5);
         assert_approx_eq!(v, 0.5);
@@ -469,11 +504,13 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(1.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
 
-        let hit_result = rect.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rect.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 1.0);
     }
 
@@ -487,11 +524,13 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(1.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
 
-        let hit_result = rect.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rect.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_eq!(hit_result.surface_normal, Vector::new(1.0, 0.0, 0.0));
     }
 
@@ -505,6 +544,8 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
 
@@ -526,11 +567,13 @@ mod tests {
             k: 0.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(1.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
 
-        let hit_result = rect.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rect.hit(&ray, 0.0, Float::MAX).unwrap();
         let (u, v) = hit_result.texture_coords;
         assert_approx_eq!(u, 0.5);
         assert_approx_eq!(v, 0.5);
@@ -1,6 +1,8 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
+use crate::data::quaternion::Quaternion;
 use crate::data::vector::Vector;
+use crate::float::Float;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
 use thiserror::Error;
@@ -9,16 +11,168 @@ use thiserror::Error;
 pub enum GeometryError {
     #[error("rotation is only supported for geometries have have bounding boxes")]
     RotationUnsupported(),
+    #[error("the axis of a rotation can not be zero")]
+    ZeroRotationAxis(),
+}
+
+/// Hits `geometry` in its own frame by taking the ray there with `to_object` and bringing the
+/// hit point and surface normal back out with `to_world`
+fn rotated_hit<F, G>(
+    geometry: &Geometry,
+    ray: &Ray,
+    tmin: Float,
+    tmax: Float,
+    to_object: F,
+    to_world: G,
+) -> Option<HitResult>
+where
+    F: Fn(&Vector) -> Vector,
+    G: Fn(&Vector) -> Vector,
+{
+    let origin = to_object(ray.origin());
+    let direction = to_object(ray.direction());
+    let rotated_ray = Ray::new(origin, direction, ray.time());
+
+    geometry.hit(&rotated_ray, tmin, tmax).map(|hit| {
+        let point = to_world(&hit.point);
+        let surface_normal = to_world(&hit.surface_normal);
+        let texture_gradients = hit
+            .texture_gradients
+            .map(|(row, col)| (to_world(&row), to_world(&col)));
+        HitResult {
+            // Materials scatter against the world space surface normal so they also need the
+            // world space ray rather than the one we rotated into object space
+            ray: *ray,
+            point,
+            surface_normal,
+            texture_gradients,
+            ..hit
+        }
+    })
+}
+
+/// The pdf of sampling `direction` from `origin` towards `geometry` is the same as sampling both
+/// rotated into its frame, as rotations do not change solid angles
+fn rotated_pdf_value<F>(
+    geometry: &Geometry,
+    origin: &Vector,
+    direction: &Vector,
+    to_object: F,
+) -> Float
+where
+    F: Fn(&Vector) -> Vector,
+{
+    geometry.pdf_value(&to_object(origin), &to_object(direction))
+}
+
+fn rotated_random<F, G>(geometry: &Geometry, origin: &Vector, to_object: F, to_world: G) -> Vector
+where
+    F: Fn(&Vector) -> Vector,
+    G: Fn(&Vector) -> Vector,
+{
+    to_world(&geometry.random(&to_object(origin)))
+}
+
+fn rotated_bounding_box<F>(
+    bbox: Option<AxisAlignedBoundingBox>,
+    to_world: F,
+) -> Option<AxisAlignedBoundingBox>
+where
+    F: Fn(&Vector) -> Vector,
+{
+    let bbox = bbox?;
+    let mut min = Vector::new(Float::MAX, Float::MAX, Float::MAX);
+    let mut max = Vector::new(Float::MIN, Float::MIN, Float::MIN);
+
+    for i in 0..2 {
+        for j in 0..2 {
+            for k in 0..2 {
+                let tester = to_world(&Vector::new(
+                    (i as Float) * bbox.max().x() + ((1 - i) as Float) * bbox.min().x(),
+                    (j as Float) * bbox.max().y() + ((1 - j) as Float) * bbox.min().y(),
+                    (k as Float) * bbox.max().z() + ((1 - k) as Float) * bbox.min().z(),
+                ));
+
+                min = min.min(&tester);
+                max = max.max(&tester);
+            }
+        }
+    }
+    Some(AxisAlignedBoundingBox::new(min, max))
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RotateX {
+    geometry: Box<Geometry>,
+    angle: Float,
+}
+
+impl RotateX {
+    pub fn build(geometry: Geometry, angle: Float) -> Result<Geometry, GeometryError> {
+        if geometry.bounding_box(0.0, 0.0).is_none() {
+            return Err(GeometryError::RotationUnsupported());
+        }
+        Ok(Geometry::RotateX(Box::from(RotateX {
+            geometry: Box::from(geometry),
+            angle,
+        })))
+    }
+}
+
+impl Hittable for RotateX {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        rotated_hit(
+            &self.geometry,
+            ray,
+            tmin,
+            tmax,
+            |v| v.rotate_x(-self.angle),
+            |v| v.rotate_x(self.angle),
+        )
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        rotated_bounding_box(self.geometry.bounding_box(time_start, time_end), |v| {
+            v.rotate_x(self.angle)
+        })
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.geometry.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        self.geometry.is_attractor()
+    }
+
+    fn emits_light(&self) -> bool {
+        self.geometry.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        rotated_pdf_value(&self.geometry, origin, direction, |v| {
+            v.rotate_x(-self.angle)
+        })
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        rotated_random(
+            &self.geometry,
+            origin,
+            |v| v.rotate_x(-self.angle),
+            |v| v.rotate_x(self.angle),
+        )
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct RotateY {
     geometry: Box<Geometry>,
-    angle: f64,
+    angle: Float,
 }
 
 impl RotateY {
-    pub fn build(geometry: Geometry, angle: f64) -> Result<Geometry, GeometryError> {
+    pub fn build(geometry: Geometry, angle: Float) -> Result<Geometry, GeometryError> {
         if geometry.bounding_box(0.0, 0.0).is_none() {
             return Err(GeometryError::RotationUnsupported());
         }
@@ -30,47 +184,85 @@ impl RotateY {
 }
 
 impl Hittable for RotateY {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
-        let origin = ray.origin().rotate_y(-self.angle);
-        let direction = ray.direction().rotate_y(-self.angle);
-        let rotated_ray = Ray::new(origin, direction, ray.time());
-
-        self.geometry.hit(&rotated_ray, tmin, tmax).map(|hit| {
-            let point = hit.point.rotate_y(self.angle);
-            let surface_normal = hit.surface_normal.rotate_y(self.angle);
-            HitResult {
-                point,
-                surface_normal,
-                ..hit
-            }
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        rotated_hit(
+            &self.geometry,
+            ray,
+            tmin,
+            tmax,
+            |v| v.rotate_y(-self.angle),
+            |v| v.rotate_y(self.angle),
+        )
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        rotated_bounding_box(self.geometry.bounding_box(time_start, time_end), |v| {
+            v.rotate_y(self.angle)
         })
     }
 
-    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
-        match self.geometry.bounding_box(time_start, time_end) {
-            None => None,
-            Some(bbox) => {
-                let mut min = Vector::new(std::f64::MAX, std::f64::MAX, std::f64::MAX);
-                let mut max = Vector::new(std::f64::MIN, std::f64::MIN, std::f64::MIN);
-
-                for i in 0..2 {
-                    for j in 0..2 {
-                        for k in 0..2 {
-                            let tester = Vector::new(
-                                (i as f64) * bbox.max().x() + ((1 - i) as f64) * bbox.min().x(),
-                                (j as f64) * bbox.max().y() + ((1 - j) as f64) * bbox.min().y(),
-                                (k as f64) * bbox.max().z() + ((1 - k) as f64) * bbox.min().z(),
-                            )
-                            .rotate_y(self.angle);
-
-                            min = min.min(&tester);
-                            max = max.max(&tester);
-                        }
-                    }
-                }
-                Some(AxisAlignedBoundingBox::new(min, max))
-            }
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.geometry.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        self.geometry.is_attractor()
+    }
+
+    fn emits_light(&self) -> bool {
+        self.geometry.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        rotated_pdf_value(&self.geometry, origin, direction, |v| {
+            v.rotate_y(-self.angle)
+        })
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        rotated_random(
+            &self.geometry,
+            origin,
+            |v| v.rotate_y(-self.angle),
+            |v| v.rotate_y(self.angle),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RotateZ {
+    geometry: Box<Geometry>,
+    angle: Float,
+}
+
+impl RotateZ {
+    pub fn build(geometry: Geometry, angle: Float) -> Result<Geometry, GeometryError> {
+        if geometry.bounding_box(0.0, 0.0).is_none() {
+            return Err(GeometryError::RotationUnsupported());
         }
+        Ok(Geometry::RotateZ(Box::from(RotateZ {
+            geometry: Box::from(geometry),
+            angle,
+        })))
+    }
+}
+
+impl Hittable for RotateZ {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        rotated_hit(
+            &self.geometry,
+            ray,
+            tmin,
+            tmax,
+            |v| v.rotate_z(-self.angle),
+            |v| v.rotate_z(self.angle),
+        )
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        rotated_bounding_box(self.geometry.bounding_box(time_start, time_end), |v| {
+            v.rotate_z(self.angle)
+        })
     }
 
     fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
@@ -81,12 +273,111 @@ impl Hittable for RotateY {
         self.geometry.is_attractor()
     }
 
-    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
-        self.geometry.pdf_value(origin, direction)
+    fn emits_light(&self) -> bool {
+        self.geometry.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        rotated_pdf_value(&self.geometry, origin, direction, |v| {
+            v.rotate_z(-self.angle)
+        })
     }
 
     fn random(&self, origin: &Vector) -> Vector {
-        self.geometry.random(origin)
+        rotated_random(
+            &self.geometry,
+            origin,
+            |v| v.rotate_z(-self.angle),
+            |v| v.rotate_z(self.angle),
+        )
+    }
+}
+
+/// Rotates `angle` degrees about an arbitrary axis through the origin, turning anticlockwise
+/// when looking back down the axis like the other rotations
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RotateAxis {
+    geometry: Box<Geometry>,
+    axis: Vector,
+    angle: Float,
+}
+
+impl RotateAxis {
+    pub fn build(
+        geometry: Geometry,
+        axis: Vector,
+        angle: Float,
+    ) -> Result<Geometry, GeometryError> {
+        if geometry.bounding_box(0.0, 0.0).is_none() {
+            return Err(GeometryError::RotationUnsupported());
+        }
+        if axis.len_squared() == 0.0 {
+            return Err(GeometryError::ZeroRotationAxis());
+        }
+        Ok(Geometry::RotateAxis(Box::from(RotateAxis {
+            geometry: Box::from(geometry),
+            axis: axis.unit_vector(),
+            angle,
+        })))
+    }
+
+    fn rotation(&self) -> Quaternion {
+        Quaternion::from_axis_angle(&self.axis, self.angle)
+    }
+}
+
+impl Hittable for RotateAxis {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let rotation = self.rotation();
+        let inverse = rotation.conjugate();
+        rotated_hit(
+            &self.geometry,
+            ray,
+            tmin,
+            tmax,
+            |v| inverse.rotate_vector(v),
+            |v| rotation.rotate_vector(v),
+        )
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        let rotation = self.rotation();
+        rotated_bounding_box(self.geometry.bounding_box(time_start, time_end), |v| {
+            rotation.rotate_vector(v)
+        })
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        if self.axis.len_squared() == 0.0 {
+            return Err(GeometryError::ZeroRotationAxis().into());
+        }
+        self.geometry.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        self.geometry.is_attractor()
+    }
+
+    fn emits_light(&self) -> bool {
+        self.geometry.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        let inverse = self.rotation().conjugate();
+        rotated_pdf_value(&self.geometry, origin, direction, |v| {
+            inverse.rotate_vector(v)
+        })
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        let rotation = self.rotation();
+        let inverse = rotation.conjugate();
+        rotated_random(
+            &self.geometry,
+            origin,
+            |v| inverse.rotate_vector(v),
+            |v| rotation.rotate_vector(v),
+        )
     }
 }
 
@@ -104,16 +395,18 @@ mod tests {
             Vector::new(2.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         );
 
         let ray = Ray::new(Vector::new(3.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 1.0);
 
         let rotated_cube = cube.rotate_y(-90.0).unwrap();
 
-        let hit_result = rotated_cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rotated_cube.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 3.0);
     }
 
@@ -124,18 +417,20 @@ mod tests {
             Vector::new(2.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         );
 
         let ray = Ray::new(Vector::new(3.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.surface_normal.x(), 1.0);
         assert_approx_eq!(hit_result.surface_normal.y(), 0.0);
         assert_approx_eq!(hit_result.surface_normal.z(), 0.0);
 
         let rotated_cube = cube.rotate_y(-90.0).unwrap();
 
-        let hit_result = rotated_cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rotated_cube.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.surface_normal.x(), 1.0);
         assert_approx_eq!(hit_result.surface_normal.y(), 0.0);
         assert_approx_eq!(hit_result.surface_normal.z(), 0.0);
@@ -148,6 +443,8 @@ mod tests {
             Vector::new(2.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         );
 
@@ -178,20 +475,237 @@ mod tests {
             Vector::new(2.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         );
 
         let ray = Ray::new(Vector::new(3.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         let (u, v) = hit_result.texture_coords;
         assert_approx_eq!(u, 0.5);
         assert_approx_eq!(v, 0.5);
 
         let rotated_cube = cube.rotate_y(-90.0).unwrap();
 
-        let hit_result = rotated_cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = rotated_cube.hit(&ray, 0.0, Float::MAX).unwrap();
         let (u, v) = hit_result.texture_coords;
         assert_approx_eq!(u, 0.25);
         assert_approx_eq!(v, 0.5);
     }
+
+    #[test]
+    fn test_rotate_x_hit() {
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 2.0, 1.0),
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        );
+
+        let ray = Ray::new(Vector::new(0.5, 0.5, 3.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 2.0);
+
+        // The cube now lies along the z axis, from z = 0 to z = 2
+        let rotated_cube = cube.rotate_x(90.0).unwrap();
+
+        let ray = Ray::new(
+            Vector::new(0.5, -0.5, 3.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let hit_result = rotated_cube.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 1.0);
+        assert_approx_eq!(hit_result.surface_normal.z(), 1.0);
+        assert!(hit_result.front_face());
+    }
+
+    #[test]
+    fn test_rotate_x_bounding_box() {
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 2.0, 1.0),
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        );
+
+        let bbox = cube.rotate_x(90.0).unwrap().bounding_box(0.0, 0.0).unwrap();
+
+        assert_approx_eq!(bbox.min().x(), 0.0);
+        assert_approx_eq!(bbox.min().y(), -1.0);
+        assert_approx_eq!(bbox.min().z(), 0.0);
+
+        assert_approx_eq!(bbox.max().x(), 1.0);
+        assert_approx_eq!(bbox.max().y(), 0.0);
+        assert_approx_eq!(bbox.max().z(), 2.0);
+    }
+
+    #[test]
+    fn test_rotate_z_hit() {
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 1.0, 1.0),
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        );
+
+        // The cube now lies along the y axis, from y = 0 to y = 2
+        let rotated_cube = cube.rotate_z(90.0).unwrap();
+
+        let ray = Ray::new(
+            Vector::new(-0.5, 3.0, 0.5),
+            Vector::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        let hit_result = rotated_cube.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 1.0);
+        assert_approx_eq!(hit_result.surface_normal.y(), 1.0);
+        assert!(hit_result.front_face());
+    }
+
+    #[test]
+    fn test_rotate_z_bounding_box() {
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 1.0, 1.0),
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        );
+
+        let bbox = cube.rotate_z(90.0).unwrap().bounding_box(0.0, 0.0).unwrap();
+
+        assert_approx_eq!(bbox.min().x(), -1.0);
+        assert_approx_eq!(bbox.min().y(), 0.0);
+        assert_approx_eq!(bbox.min().z(), 0.0);
+
+        assert_approx_eq!(bbox.max().x(), 0.0);
+        assert_approx_eq!(bbox.max().y(), 2.0);
+        assert_approx_eq!(bbox.max().z(), 1.0);
+    }
+
+    #[test]
+    fn test_rotate_hit_keeps_world_ray() {
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 1.0, 1.0),
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        );
+        let rotated_cube = cube.rotate_y(-90.0).unwrap();
+
+        let ray = Ray::new(Vector::new(3.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
+        let hit_result = rotated_cube.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_eq!(hit_result.ray.direction(), ray.direction());
+        assert!(hit_result.front_face());
+    }
+
+    #[test]
+    fn test_rotate_axis_matches_rotate_y() {
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(2.0, 1.0, 1.0),
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        );
+        let rotated_y = cube.clone().rotate_y(90.0).unwrap();
+        let rotated_axis = cube.rotate_axis(Vector::new(0.0, 2.0, 0.0), 90.0).unwrap();
+
+        let expected_box = rotated_y.bounding_box(0.0, 0.0).unwrap();
+        let bbox = rotated_axis.bounding_box(0.0, 0.0).unwrap();
+        assert_approx_eq!(bbox.min().x(), expected_box.min().x());
+        assert_approx_eq!(bbox.min().z(), expected_box.min().z());
+        assert_approx_eq!(bbox.max().x(), expected_box.max().x());
+        assert_approx_eq!(bbox.max().z(), expected_box.max().z());
+
+        for origin in &[
+            Vector::new(0.5, 0.5, -3.0),
+            Vector::new(3.0, 0.5, -0.5),
+            Vector::new(0.5, 3.0, -1.5),
+        ] {
+            let ray = Ray::new(*origin, Vector::new(0.5, 0.5, -1.5) - origin, 0.0);
+            let expected = rotated_y.hit(&ray, 0.0, Float::MAX).unwrap();
+            let hit_result = rotated_axis.hit(&ray, 0.0, Float::MAX).unwrap();
+
+            assert_approx_eq!(hit_result.distance, expected.distance);
+            assert_approx_eq!(hit_result.surface_normal.x(), expected.surface_normal.x());
+            assert_approx_eq!(hit_result.surface_normal.y(), expected.surface_normal.y());
+            assert_approx_eq!(hit_result.surface_normal.z(), expected.surface_normal.z());
+            assert_approx_eq!(hit_result.texture_coords.0, expected.texture_coords.0);
+            assert_approx_eq!(hit_result.texture_coords.1, expected.texture_coords.1);
+        }
+    }
+
+    #[test]
+    fn test_rotate_axis_rejects_zero_axis() {
+        let sphere = crate::world::geometry::sphere::Sphere::build(
+            Vector::new(0.0, 0.0, 0.0),
+            1.0,
+            Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        );
+        assert!(sphere
+            .rotate_axis(Vector::new(0.0, 0.0, 0.0), 45.0)
+            .is_err());
+    }
+
+    #[test]
+    fn test_rotate_light_sampling() {
+        use crate::world::geometry::rectangle::XyRect;
+
+        let light = Material::Dielectric {
+            refractive_index: 1.5,
+            absorption: None,
+            roughness: 0.0,
+        };
+        // Turning a rectangle facing z a quarter turn about y leaves it facing x, so both
+        // rotations have to sample and weigh directions exactly like the unrotated one would
+        let rectangle = XyRect::build((-1.0, 1.0), (0.0, 1.0), 2.0, light);
+        let rotations = [
+            rectangle.clone().rotate_y(90.0).unwrap(),
+            rectangle
+                .clone()
+                .rotate_axis(Vector::new(0.0, 3.0, 0.0), 90.0)
+                .unwrap(),
+        ];
+
+        let origin = Vector::new(0.0, 0.5, 0.0);
+        let direction = Vector::new(0.3, 0.2, 1.0);
+        let expected = rectangle.pdf_value(&origin, &direction);
+        assert!(expected > 0.0);
+
+        for rotated in &rotations {
+            assert!(rotated.is_attractor());
+            let turned = direction.rotate_y(90.0);
+            assert_approx_eq!(rotated.pdf_value(&origin, &turned), expected);
+            assert_approx_eq!(rotated.pdf_value(&origin, &direction), 0.0);
+
+            for _ in 0..100 {
+                let sampled = rotated.random(&origin);
+                let hit = rotated.hit(&Ray::new(origin, sampled, 0.0), 0.0, Float::MAX);
+                assert_approx_eq!(hit.unwrap().distance, 1.0);
+            }
+        }
+    }
 }
@@ -0,0 +1,175 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ScaleError {
+    #[error("scale factor {factor:?} can not have a zero component")]
+    ZeroFactor { factor: Vector },
+}
+
+/// Stretches a geometry by a separate factor along each axis. Negative factors mirror it
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Scale {
+    geometry: Box<Geometry>,
+    factor: Vector,
+}
+
+impl Scale {
+    pub fn build(geometry: Geometry, factor: Vector) -> Result<Geometry, ScaleError> {
+        check_factor(&factor)?;
+        Ok(Geometry::Scale(Box::from(Scale {
+            geometry: Box::from(geometry),
+            factor,
+        })))
+    }
+
+    fn to_object(&self, v: &Vector) -> Vector {
+        Vector::new(
+            v.x() / self.factor.x(),
+            v.y() / self.factor.y(),
+            v.z() / self.factor.z(),
+        )
+    }
+
+    fn to_world(&self, v: &Vector) -> Vector {
+        Vector::new(
+            v.x() * self.factor.x(),
+            v.y() * self.factor.y(),
+            v.z() * self.factor.z(),
+        )
+    }
+}
+
+fn check_factor(factor: &Vector) -> Result<(), ScaleError> {
+    if factor.x() == 0.0 || factor.y() == 0.0 || factor.z() == 0.0 {
+        return Err(ScaleError::ZeroFactor { factor: *factor });
+    }
+    Ok(())
+}
+
+impl Hittable for Scale {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        // The direction is scaled along with the origin so distances along the ray are unchanged
+        let scaled_ray = Ray::new(
+            self.to_object(ray.origin()),
+            self.to_object(ray.direction()),
+            ray.time(),
+        );
+
+        self.geometry
+            .hit(&scaled_ray, tmin, tmax)
+            .map(|hit| HitResult {
+                ray: *ray,
+                point: self.to_world(&hit.point),
+                // Normals transform by the inverse transpose, which for a scale is dividing by
+                // the factor. This also keeps normals facing out of mirrored geometry
+                surface_normal: self.to_object(&hit.surface_normal).unit_vector(),
+                texture_gradients: hit
+                    .texture_gradients
+                    .map(|(row, col)| (self.to_world(&row), self.to_world(&col))),
+                ..hit
+            })
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        // Mirroring swaps which corner is the minimum so both corners are compared
+        let bbox = self.geometry.bounding_box(time_start, time_end)?;
+        let min = self.to_world(bbox.min());
+        let max = self.to_world(bbox.max());
+        Some(AxisAlignedBoundingBox::new(min.min(&max), min.max(&max)))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        check_factor(&self.factor)?;
+        self.geometry.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        // Stretching changes the solid angle a geometry covers in a way the inner geometry can not
+        // account for, so scaled geometries are never sampled directly
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.geometry.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::geometry::cube::Cube;
+    use crate::world::geometry::sphere::Sphere;
+    use crate::world::geometry::test_material;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn unit_sphere() -> Geometry {
+        Sphere::build(Vector::new(0.0, 0.0, 0.0), 1.0, test_material())
+    }
+
+    #[test]
+    fn test_scale_hit() {
+        let ellipsoid = unit_sphere().scale(Vector::new(2.0, 1.0, 1.0)).unwrap();
+
+        let ray = Ray::new(Vector::new(5.0, 0.0, 0.0), Vector::new(-1.0, 0.0, 0.0), 0.0);
+        let hit = ellipsoid.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 3.0);
+        assert_approx_eq!(hit.point.x(), 2.0);
+        assert_eq!(hit.surface_normal, Vector::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_scale_surface_normal() {
+        let ellipsoid = unit_sphere().scale(Vector::new(2.0, 1.0, 1.0)).unwrap();
+
+        // Halfway along the long axis the normal leans much more towards y than x
+        let ray = Ray::new(Vector::new(1.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        let hit = ellipsoid.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit.surface_normal.len(), 1.0);
+        assert_approx_eq!(hit.point.y(), Float::sqrt(0.75));
+        assert!(hit.surface_normal.y() > 3.0 * hit.surface_normal.x());
+        assert!(hit.surface_normal.x() > 0.0);
+    }
+
+    #[test]
+    fn test_mirrored_scale_keeps_normals_outward() {
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 1.0),
+            test_material(),
+        )
+        .scale(Vector::new(-1.0, 1.0, 1.0))
+        .unwrap();
+
+        let ray = Ray::new(Vector::new(-3.0, 0.5, 0.5), Vector::new(1.0, 0.0, 0.0), 0.0);
+        let hit = cube.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit.distance, 2.0);
+        assert_eq!(hit.surface_normal, Vector::new(-1.0, 0.0, 0.0));
+        assert!(hit.front_face());
+    }
+
+    #[test]
+    fn test_scale_bounding_box() {
+        let cube = Cube::build(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(1.0, 1.0, 1.0),
+            test_material(),
+        )
+        .scale(Vector::new(-2.0, 3.0, 0.5))
+        .unwrap();
+
+        let expected_box =
+            AxisAlignedBoundingBox::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(0.0, 3.0, 0.5));
+        assert_eq!(cube.bounding_box(0.0, 0.0), Some(expected_box));
+    }
+
+    #[test]
+    fn test_scale_rejects_zero_factor() {
+        assert!(unit_sphere().scale(Vector::new(1.0, 0.0, 1.0)).is_err());
+    }
+}
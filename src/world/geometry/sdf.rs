@@ -0,0 +1,508 @@
+//! Shapes given by a signed distance function, which is negative inside the shape, positive
+//! outside and never more than the distance to the surface.
+//!
+//! The surface is found by sphere tracing: nothing can be closer than the distance at a point, so
+//! the ray can safely step that far forward, until the distance is small enough to call a hit.
+//! This works for shapes such as smoothly blended solids and fractals that have no closed form
+//! intersection, but takes many more steps than an analytic primitive.
+
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::sphere::{sphere_texture_coords, sphere_texture_gradients};
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+use thiserror::Error;
+
+// A point this close to the surface counts as a hit. Single precision can not get as close
+#[cfg(not(feature = "f32"))]
+const SURFACE_DISTANCE: Float = 1e-5;
+#[cfg(feature = "f32")]
+const SURFACE_DISTANCE: Float = 1e-4;
+// Rays that have not reached the surface after this many steps, such as those grazing it, miss
+const MAX_STEPS: u32 = 1000;
+// Distance either side of a point the gradient is measured over, wider in single precision so
+// the difference between the distances is not lost to rounding
+#[cfg(not(feature = "f32"))]
+const NORMAL_STEP: Float = 1e-5;
+#[cfg(feature = "f32")]
+const NORMAL_STEP: Float = 1e-3;
+// No point of a mandelbulb of any power is further than this from its centre before scaling
+const MANDELBULB_RADIUS: Float = 2.0;
+
+#[derive(Debug, Error)]
+pub enum SdfError {
+    #[error("the sizes of a signed distance field shape must be positive, got {size}")]
+    InvalidSize { size: Float },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum SdfShape {
+    Sphere {
+        centre: Vector,
+        radius: Float,
+    },
+    // The box with corners at `centre ± half_size`, with its edges rounded off by `rounding`
+    Cuboid {
+        centre: Vector,
+        half_size: Vector,
+        #[serde(default)]
+        rounding: Float,
+    },
+    // Lying flat around the y axis
+    Torus {
+        centre: Vector,
+        major_radius: Float,
+        minor_radius: Float,
+    },
+    // The power 8 fractal fits in a sphere of about `scale` around `centre`. More iterations give
+    // finer detail
+    Mandelbulb {
+        centre: Vector,
+        scale: Float,
+        #[serde(default = "default_mandelbulb_power")]
+        power: Float,
+        #[serde(default = "default_mandelbulb_iterations")]
+        iterations: u32,
+    },
+    Union {
+        a: Box<SdfShape>,
+        b: Box<SdfShape>,
+    },
+    // A union that blends the shapes together where they come within `smoothness` of each other
+    SmoothUnion {
+        a: Box<SdfShape>,
+        b: Box<SdfShape>,
+        smoothness: Float,
+    },
+    // `a` with `b` carved out of it
+    Subtraction {
+        a: Box<SdfShape>,
+        b: Box<SdfShape>,
+    },
+    Intersection {
+        a: Box<SdfShape>,
+        b: Box<SdfShape>,
+    },
+}
+
+fn default_mandelbulb_power() -> Float {
+    8.0
+}
+
+fn default_mandelbulb_iterations() -> u32 {
+    12
+}
+
+impl SdfShape {
+    pub fn distance(&self, point: &Vector) -> Float {
+        match self {
+            SdfShape::Sphere { centre, radius } => (point - centre).len() - radius,
+            SdfShape::Cuboid {
+                centre,
+                half_size,
+                rounding,
+            } => cuboid_distance(&(point - centre), half_size) - rounding,
+            SdfShape::Torus {
+                centre,
+                major_radius,
+                minor_radius,
+            } => {
+                let p = point - centre;
+                let around = Float::hypot(p.x(), p.z()) - major_radius;
+                Float::hypot(around, p.y()) - minor_radius
+            }
+            SdfShape::Mandelbulb {
+                centre,
+                scale,
+                power,
+                iterations,
+            } => scale * mandelbulb_distance(&((point - centre) / *scale), *power, *iterations),
+            SdfShape::Union { a, b } => a.distance(point).min(b.distance(point)),
+            SdfShape::SmoothUnion { a, b, smoothness } => {
+                smooth_min(a.distance(point), b.distance(point), *smoothness)
+            }
+            SdfShape::Subtraction { a, b } => a.distance(point).max(-b.distance(point)),
+            SdfShape::Intersection { a, b } => a.distance(point).max(b.distance(point)),
+        }
+    }
+
+    pub fn bounding_box(&self) -> AxisAlignedBoundingBox {
+        let around = |centre: &Vector, half_size: Vector| {
+            AxisAlignedBoundingBox::new(centre - half_size, centre + half_size)
+        };
+        let all = |size: Float| Vector::new(size, size, size);
+
+        match self {
+            SdfShape::Sphere { centre, radius } => around(centre, all(*radius)),
+            SdfShape::Cuboid {
+                centre,
+                half_size,
+                rounding,
+            } => around(centre, half_size + all(*rounding)),
+            SdfShape::Torus {
+                centre,
+                major_radius,
+                minor_radius,
+            } => {
+                let across = major_radius + minor_radius;
+                around(centre, Vector::new(across, *minor_radius, across))
+            }
+            SdfShape::Mandelbulb { centre, scale, .. } => {
+                around(centre, all(MANDELBULB_RADIUS * scale))
+            }
+            SdfShape::Union { a, b } => surrounding(a, b, 0.0),
+            // The blend only ever adds a quarter of the smoothness onto the nearest shape
+            SdfShape::SmoothUnion { a, b, smoothness } => surrounding(a, b, smoothness / 4.0),
+            SdfShape::Subtraction { a, .. } | SdfShape::Intersection { a, .. } => a.bounding_box(),
+        }
+    }
+
+    fn validate(&self) -> Result<(), SdfError> {
+        let positive = |size: Float| {
+            if size > 0.0 {
+                Ok(())
+            } else {
+                Err(SdfError::InvalidSize { size })
+            }
+        };
+
+        match self {
+            SdfShape::Sphere { radius, .. } => positive(*radius),
+            SdfShape::Cuboid {
+                half_size,
+                rounding,
+                ..
+            } => {
+                positive(half_size.x())?;
+                positive(half_size.y())?;
+                positive(half_size.z())?;
+                if *rounding < 0.0 {
+                    return Err(SdfError::InvalidSize { size: *rounding });
+                }
+                Ok(())
+            }
+            SdfShape::Torus {
+                major_radius,
+                minor_radius,
+                ..
+            } => {
+                positive(*major_radius)?;
+                positive(*minor_radius)
+            }
+            SdfShape::Mandelbulb { scale, power, .. } => {
+                positive(*scale)?;
+                positive(*power)
+            }
+            SdfShape::SmoothUnion { a, b, smoothness } => {
+                positive(*smoothness)?;
+                a.validate()?;
+                b.validate()
+            }
+            SdfShape::Union { a, b }
+            | SdfShape::Subtraction { a, b }
+            | SdfShape::Intersection { a, b } => {
+                a.validate()?;
+                b.validate()
+            }
+        }
+    }
+}
+
+fn surrounding(a: &SdfShape, b: &SdfShape, margin: Float) -> AxisAlignedBoundingBox {
+    let (a, b) = (a.bounding_box(), b.bounding_box());
+    let margin = Vector::new(margin, margin, margin);
+    AxisAlignedBoundingBox::new(a.min().min(b.min()) - margin, a.max().max(b.max()) + margin)
+}
+
+fn cuboid_distance(point: &Vector, half_size: &Vector) -> Float {
+    let q = Vector::new(
+        point.x().abs() - half_size.x(),
+        point.y().abs() - half_size.y(),
+        point.z().abs() - half_size.z(),
+    );
+    let outside = q.max(&Vector::new(0.0, 0.0, 0.0)).len();
+    let inside = q.x().max(q.y()).max(q.z()).min(0.0);
+    outside + inside
+}
+
+/// The polynomial smooth minimum, equal to the minimum wherever `a` and `b` are more than
+/// `smoothness` apart
+fn smooth_min(a: Float, b: Float, smoothness: Float) -> Float {
+    let h = (0.5 + 0.5 * (b - a) / smoothness).clamp(0.0, 1.0);
+    b + h * (a - b) - smoothness * h * (1.0 - h)
+}
+
+/// An estimate of the distance to the mandelbulb centred on the origin. It can be a little
+/// generous close to the surface, which only makes sphere tracing take a few more steps
+fn mandelbulb_distance(point: &Vector, power: Float, iterations: u32) -> Float {
+    let mut z = *point;
+    let mut derivative = 1.0;
+    let mut radius = z.len();
+
+    for _ in 0..iterations {
+        if radius > MANDELBULB_RADIUS || radius == 0.0 {
+            break;
+        }
+
+        // Raise z to the power in spherical coordinates and add the starting point back
+        let theta = (z.z() / radius).acos() * power;
+        let phi = Float::atan2(z.y(), z.x()) * power;
+        derivative = power * radius.powf(power - 1.0) * derivative + 1.0;
+        let scaled = radius.powf(power);
+        z = scaled
+            * Vector::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            )
+            + point;
+        radius = z.len();
+    }
+
+    if radius == 0.0 {
+        return 0.0;
+    }
+    0.5 * radius.ln() * radius / derivative
+}
+
+/// A geometry whose surface is where the distance function of `shape` is 0
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Sdf {
+    shape: SdfShape,
+    material: Material,
+}
+
+impl Sdf {
+    pub fn build(shape: SdfShape, material: Material) -> Geometry {
+        Geometry::Sdf(Box::from(Sdf { shape, material }))
+    }
+
+    /// Points down the steepest increase in distance, found with central differences
+    fn normal(&self, point: &Vector) -> Vector {
+        let difference = |offset: Vector| {
+            self.shape.distance(&(point + offset)) - self.shape.distance(&(point - offset))
+        };
+        Vector::new(
+            difference(Vector::new(NORMAL_STEP, 0.0, 0.0)),
+            difference(Vector::new(0.0, NORMAL_STEP, 0.0)),
+            difference(Vector::new(0.0, 0.0, NORMAL_STEP)),
+        )
+        .unit_vector()
+    }
+}
+
+impl Hittable for Sdf {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let bounding_box = self.shape.bounding_box();
+        let (start, end) = bounding_box.clip(ray, tmin, tmax)?;
+        let speed = ray.direction().len();
+
+        // Rays starting inside the shape, such as those refracted into it, trace the distance to
+        // the surface from the inside
+        let side = self.shape.distance(&ray.point(start)).signum();
+        let mut distance = start;
+        for _ in 0..MAX_STEPS {
+            let point = ray.point(distance);
+            let to_surface = side * self.shape.distance(&point);
+            if to_surface < SURFACE_DISTANCE {
+                let surface_normal = self.normal(&point);
+                // Textured as if projected outwards from the middle of the bounds onto a sphere
+                let centre = bounding_box.centroid();
+                let radius = (point - centre).len();
+                return Some(HitResult {
+                    distance,
+                    ray: *ray,
+                    point,
+                    surface_normal,
+                    material: self.material.clone(),
+                    texture_coords: sphere_texture_coords(&point, &centre, radius),
+                    texture_gradients: Some(sphere_texture_gradients(&point, &centre)),
+                    light_group: None,
+                });
+            }
+
+            distance += to_surface / speed;
+            if distance > end {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        Some(self.shape.bounding_box())
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.shape.validate()?;
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::geometry::test_material;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn sphere(x: Float) -> Box<SdfShape> {
+        Box::from(SdfShape::Sphere {
+            centre: Vector::new(x, 0.0, 0.0),
+            radius: 1.0,
+        })
+    }
+
+    fn down_ray(x: Float) -> Ray {
+        Ray::new(Vector::new(x, 5.0, 0.0), Vector::new(0.0, -2.0, 0.0), 0.0)
+    }
+
+    #[test]
+    fn test_sdf_sphere_matches_analytic_sphere() {
+        let sdf = Sdf::build(*sphere(0.0), test_material());
+        let hit = sdf.hit(&down_ray(0.6), 0.001, Float::MAX).unwrap();
+
+        // Hits are only found to within the surface distance
+        let tolerance = 10.0 * SURFACE_DISTANCE;
+        assert_approx_eq!(hit.distance, (5.0 - 0.8) / 2.0, tolerance);
+        assert_approx_eq!(hit.surface_normal.x(), 0.6, tolerance);
+        assert_approx_eq!(hit.surface_normal.y(), 0.8, tolerance);
+        assert!(hit.front_face());
+
+        assert!(sdf.hit(&down_ray(1.1), 0.001, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_sdf_hit_from_inside() {
+        let sdf = Sdf::build(*sphere(0.0), test_material());
+        let ray = Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 0.0, 1.0), 0.0);
+        let hit = sdf.hit(&ray, 0.001, Float::MAX).unwrap();
+
+        assert_approx_eq!(hit.distance, 1.0, 1e-4);
+        assert!(!hit.front_face());
+    }
+
+    #[test]
+    fn test_smooth_union_blends_spheres() {
+        let union = Sdf::build(
+            SdfShape::Union {
+                a: sphere(-1.1),
+                b: sphere(1.1),
+            },
+            test_material(),
+        );
+        let blended = Sdf::build(
+            SdfShape::SmoothUnion {
+                a: sphere(-1.1),
+                b: sphere(1.1),
+                smoothness: 0.5,
+            },
+            test_material(),
+        );
+
+        // Between the spheres the blend fills in the gap a plain union leaves
+        assert!(union.hit(&down_ray(0.0), 0.001, Float::MAX).is_none());
+        let bridge = blended.hit(&down_ray(0.0), 0.001, Float::MAX).unwrap();
+        assert!(bridge.point.y() > 0.0);
+        assert_approx_eq!(bridge.surface_normal.y(), 1.0, 1e-4);
+
+        // Far from where they meet the spheres are untouched
+        let top = blended.hit(&down_ray(-1.6), 0.001, Float::MAX).unwrap();
+        let expected = union.hit(&down_ray(-1.6), 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(top.distance, expected.distance, 1e-4);
+    }
+
+    #[test]
+    fn test_subtraction_carves_a_hole() {
+        let carved = Sdf::build(
+            SdfShape::Subtraction {
+                a: Box::from(SdfShape::Cuboid {
+                    centre: Vector::new(0.0, 0.0, 0.0),
+                    half_size: Vector::new(1.0, 1.0, 1.0),
+                    rounding: 0.0,
+                }),
+                b: Box::from(SdfShape::Sphere {
+                    centre: Vector::new(0.0, 1.0, 0.0),
+                    radius: 0.5,
+                }),
+            },
+            test_material(),
+        );
+
+        let hit = carved.hit(&down_ray(0.0), 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.point.y(), 0.5, 1e-4);
+        let hit = carved.hit(&down_ray(0.8), 0.001, Float::MAX).unwrap();
+        assert_approx_eq!(hit.point.y(), 1.0, 1e-4);
+    }
+
+    #[test]
+    fn test_mandelbulb_is_hit_near_its_centre() {
+        let bulb = Sdf::build(
+            SdfShape::Mandelbulb {
+                centre: Vector::new(0.0, 0.0, 0.0),
+                scale: 1.0,
+                power: 8.0,
+                iterations: 12,
+            },
+            test_material(),
+        );
+        let hit = bulb.hit(&down_ray(0.0), 0.001, Float::MAX).unwrap();
+        assert!(hit.point.y() > 0.5 && hit.point.y() < 1.3);
+        assert!(bulb.hit(&down_ray(2.5), 0.001, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_sdf_bounding_box() {
+        let shape = SdfShape::SmoothUnion {
+            a: sphere(-1.0),
+            b: Box::from(SdfShape::Torus {
+                centre: Vector::new(2.0, 0.0, 0.0),
+                major_radius: 1.0,
+                minor_radius: 0.5,
+            }),
+            smoothness: 0.4,
+        };
+        let expected =
+            AxisAlignedBoundingBox::new(Vector::new(-2.1, -1.1, -1.6), Vector::new(3.6, 1.1, 1.6));
+        let bounding_box = shape.bounding_box();
+        for (actual, expected) in [
+            (bounding_box.min(), expected.min()),
+            (bounding_box.max(), expected.max()),
+        ] {
+            assert_approx_eq!(actual.x(), expected.x());
+            assert_approx_eq!(actual.y(), expected.y());
+            assert_approx_eq!(actual.z(), expected.z());
+        }
+    }
+
+    #[test]
+    fn test_sdf_validate() {
+        let assets = Assets::new(&[]).unwrap();
+        assert!(Sdf::build(*sphere(0.0), test_material())
+            .validate(&assets)
+            .is_ok());
+
+        let flat = SdfShape::Cuboid {
+            centre: Vector::new(0.0, 0.0, 0.0),
+            half_size: Vector::new(1.0, 0.0, 1.0),
+            rounding: 0.0,
+        };
+        let shape = SdfShape::Union {
+            a: sphere(0.0),
+            b: Box::from(flat),
+        };
+        assert!(Sdf::build(shape, test_material())
+            .validate(&assets)
+            .is_err());
+    }
+}
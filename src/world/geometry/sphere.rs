@@ -1,14 +1,21 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
 use crate::onb::Onb;
-use crate::pdf::uniform;
+use crate::pdf::{random_unit_vector, uniform};
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
 use crate::world::materials::Material;
-use std::f64::consts::PI;
 
-fn sphere_hit(ray: &Ray, centre: &Vector, radius: f64, tmin: f64, tmax: f64) -> Option<f64> {
+pub fn sphere_hit(
+    ray: &Ray,
+    centre: &Vector,
+    radius: Float,
+    tmin: Float,
+    tmax: Float,
+) -> Option<Float> {
     // p(t) = ray
     // c = sphere_centre
     // R = sphere_radius
@@ -39,7 +46,7 @@ fn sphere_hit(ray: &Ray, centre: &Vector, radius: f64, tmin: f64, tmax: f64) ->
     None
 }
 
-fn sphere_bounding_box(centre: &Vector, radius: f64) -> Option<AxisAlignedBoundingBox> {
+fn sphere_bounding_box(centre: &Vector, radius: Float) -> Option<AxisAlignedBoundingBox> {
     let radius = radius.abs();
     Some(AxisAlignedBoundingBox::new(
         centre - Vector::new(radius, radius, radius),
@@ -47,11 +54,19 @@ fn sphere_bounding_box(centre: &Vector, radius: f64) -> Option<AxisAlignedBoundi
     ))
 }
 
-fn sphere_texture_coords(hit_point: &Vector, centre: &Vector, radius: f64) -> (f64, f64) {
+/// Rows move towards the north pole and columns move east around the y axis
+pub fn sphere_texture_gradients(hit_point: &Vector, centre: &Vector) -> (Vector, Vector) {
+    let point = (hit_point - centre).unit_vector();
+    let north = Vector::new(0.0, 1.0, 0.0) - point.y() * point;
+    let east = Vector::new(point.z(), 0.0, -point.x());
+    (north, east)
+}
+
+pub fn sphere_texture_coords(hit_point: &Vector, centre: &Vector, radius: Float) -> (Float, Float) {
     let point = (hit_point - centre) / radius;
 
-    let theta = PI - f64::acos(point.y());
-    let phi = f64::atan2(point.x(), point.z());
+    let theta = PI - Float::acos(point.y());
+    let phi = Float::atan2(point.x(), point.z());
 
     let row = theta / PI;
     let mut col = phi / (2.0 * PI) + 0.25;
@@ -68,12 +83,12 @@ fn sphere_texture_coords(hit_point: &Vector, centre: &Vector, radius: f64) -> (f
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct Sphere {
     centre: Vector,
-    radius: f64,
+    radius: Float,
     material: Material,
 }
 
 impl Sphere {
-    pub fn build(centre: Vector, radius: f64, material: Material) -> Geometry {
+    pub fn build(centre: Vector, radius: Float, material: Material) -> Geometry {
         Geometry::Sphere(Box::from(Sphere {
             centre,
             radius,
@@ -81,7 +96,7 @@ impl Sphere {
         }))
     }
 
-    fn surface_normal(&self, ray: &Ray, distance: f64) -> Vector {
+    fn surface_normal(&self, ray: &Ray, distance: Float) -> Vector {
         // We divide by radius instead of taking the unit vector so that a negative
         // radius sphere will have a surface normal that points inward
         (ray.point(distance) - self.centre) / self.radius
@@ -89,12 +104,13 @@ impl Sphere {
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
         sphere_hit(ray, &self.centre, self.radius, tmin, tmax).map(|distance| {
             let point = ray.point(distance);
             let surface_normal = self.surface_normal(&ray, distance);
 
             let texture_coords = sphere_texture_coords(&point, &self.centre, self.radius);
+            let texture_gradients = sphere_texture_gradients(&point, &self.centre);
 
             HitResult {
                 distance,
@@ -103,11 +119,13 @@ impl Hittable for Sphere {
                 surface_normal,
                 material: self.material.clone(),
                 texture_coords,
+                texture_gradients: Some(texture_gradients),
+                light_group: None,
             }
         })
     }
 
-    fn bounding_box(&self, _time_start: f64, _time_end: f64) -> Option<AxisAlignedBoundingBox> {
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
         sphere_bounding_box(&self.centre, self.radius)
     }
 
@@ -119,13 +137,17 @@ impl Hittable for Sphere {
         self.material.is_attractor()
     }
 
-    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
         // If origin is on the sphere itself or very close to it then some directions that were
         // generated by this sphere's `random` method will result n rays that intersect the sphere
         // in less than the `tmin` passed to `hit`. Therefore this method will return a pdf value
         // of 0 which the calling code needs to handle
 
-        let hit = self.hit(&Ray::new(*origin, *direction, 0.0), 0.001, std::f64::MAX);
+        let hit = self.hit(&Ray::new(*origin, *direction, 0.0), 0.001, Float::MAX);
         match hit {
             None => 0.0,
             Some(_hit) => {
@@ -137,7 +159,7 @@ impl Hittable for Sphere {
                     return 1.0 / (4.0 * PI);
                 }
 
-                let cos_theta_max = f64::sqrt(1.0 - distance_ratio);
+                let cos_theta_max = Float::sqrt(1.0 - distance_ratio);
                 let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
 
                 1.0 / solid_angle
@@ -149,8 +171,9 @@ impl Hittable for Sphere {
         let cp = self.centre - origin;
         let distance_ratio = self.radius.powi(2) / cp.len_squared();
         if distance_ratio > 1.0 {
-            // This means origin is inside the sphere. Any ray will hit the sphere
-            return Vector::new(uniform(), uniform(), uniform());
+            // This means origin is inside the sphere. Any ray will hit the sphere so sample every
+            // direction uniformly to match the pdf value
+            return random_unit_vector();
         }
 
         let onb = Onb::build_from_w(&cp);
@@ -158,16 +181,16 @@ impl Hittable for Sphere {
     }
 }
 
-pub fn random_to_sphere(distance_ratio: f64) -> Vector {
-    let r1 = uniform::<f64>();
-    let r2 = uniform::<f64>();
+pub fn random_to_sphere(distance_ratio: Float) -> Vector {
+    let r1 = uniform::<Float>();
+    let r2 = uniform::<Float>();
 
-    let cos_theta_max = f64::sqrt(1.0 - distance_ratio);
+    let cos_theta_max = Float::sqrt(1.0 - distance_ratio);
     let z = 1.0 + r2 * (cos_theta_max - 1.0);
 
     let phi = 2.0 * PI * r1;
-    let x = f64::cos(phi) * f64::sqrt(1.0 - z.powi(2));
-    let y = f64::sin(phi) * f64::sqrt(1.0 - z.powi(2));
+    let x = Float::cos(phi) * Float::sqrt(1.0 - z.powi(2));
+    let y = Float::sin(phi) * Float::sqrt(1.0 - z.powi(2));
 
     Vector::new(x, y, z)
 }
@@ -175,20 +198,20 @@ pub fn random_to_sphere(distance_ratio: f64) -> Vector {
 #[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
 pub struct MovingSphere {
     centre_start: Vector,
-    time_start: f64,
+    time_start: Float,
     centre_end: Vector,
-    time_end: f64,
-    radius: f64,
+    time_end: Float,
+    radius: Float,
     material: Material,
 }
 
 impl MovingSphere {
     pub fn build(
         centre_start: Vector,
-        time_start: f64,
+        time_start: Float,
         centre_end: Vector,
-        time_end: f64,
-        radius: f64,
+        time_end: Float,
+        radius: Float,
         material: Material,
     ) -> Geometry {
         Geometry::MovingSphere(Box::from(MovingSphere {
@@ -201,12 +224,12 @@ impl MovingSphere {
         }))
     }
 
-    fn centre(&self, time: f64) -> Vector {
+    fn centre(&self, time: Float) -> Vector {
         let time_fraction = (time - self.time_start) / (self.time_end - self.time_start);
         self.centre_start + time_fraction * (self.centre_end - self.centre_start)
     }
 
-    fn surface_normal(&self, ray: &Ray, distance: f64) -> Vector {
+    fn surface_normal(&self, ray: &Ray, distance: Float) -> Vector {
         // We divide by radius instead of taking the unit vector so that a negative
         // radius sphere will have a surface normal that points inward
         let centre = self.centre(ray.time());
@@ -215,13 +238,14 @@ impl MovingSphere {
 }
 
 impl Hittable for MovingSphere {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
         let centre = self.centre(ray.time());
         sphere_hit(ray, &centre, self.radius, tmin, tmax).map(|distance| {
             let point = ray.point(distance);
             let surface_normal = self.surface_normal(&ray, distance);
 
             let texture_coords = sphere_texture_coords(&point, &centre, self.radius);
+            let texture_gradients = sphere_texture_gradients(&point, &centre);
 
             HitResult {
                 distance,
@@ -230,11 +254,13 @@ impl Hittable for MovingSphere {
                 surface_normal,
                 material: self.material.clone(),
                 texture_coords,
+                texture_gradients: Some(texture_gradients),
+                light_group: None,
             }
         })
     }
 
-    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
         let box_start = sphere_bounding_box(&self.centre(time_start), self.radius);
         let box_end = sphere_bounding_box(&self.centre(time_end), self.radius);
 
@@ -248,6 +274,10 @@ impl Hittable for MovingSphere {
     fn is_attractor(&self) -> bool {
         false
     }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
 }
 
 #[cfg(test)]
@@ -262,11 +292,13 @@ mod tests {
             radius: 1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
 
-        let hit_result = sphere.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = sphere.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 1.0);
     }
 
@@ -277,11 +309,13 @@ mod tests {
             radius: 1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
 
-        let hit_result = sphere.hit(&ray, 1.0001, core::f64::MAX).unwrap();
+        let hit_result = sphere.hit(&ray, 1.0001, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 3.0);
     }
 
@@ -292,11 +326,13 @@ mod tests {
             radius: -1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
 
-        let hit_result = sphere.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = sphere.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 1.0);
     }
 
@@ -307,11 +343,13 @@ mod tests {
             radius: 1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
 
-        let hit_result = sphere.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = sphere.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_eq!(hit_result.surface_normal, Vector::new(-1.0, 0.0, 0.0));
     }
 
@@ -322,11 +360,13 @@ mod tests {
             radius: -1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
         let ray = Ray::new(Vector::new(-2.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
 
-        let hit_result = sphere.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = sphere.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_eq!(hit_result.surface_normal, Vector::new(1.0, 0.0, 0.0));
     }
 
@@ -337,6 +377,8 @@ mod tests {
             radius: 1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
 
@@ -356,6 +398,8 @@ mod tests {
             radius: 1.0,
             material: Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         };
 
@@ -390,4 +434,53 @@ mod tests {
         assert_approx_eq!(row, 0.0);
         assert_approx_eq!(col, 0.5);
     }
+
+    fn light() -> Sphere {
+        Sphere {
+            centre: Vector::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_sphere_pdf_value() {
+        let sphere = light();
+        let origin = Vector::new(0.0, 0.0, 2.0);
+
+        // sin(theta_max) = 1 / 2 so the cone of directions towards the sphere has a half angle of 30°
+        let solid_angle = 2.0 * PI * (1.0 - Float::sqrt(0.75));
+        let towards = Vector::new(0.0, 0.0, -1.0);
+        assert_approx_eq!(sphere.pdf_value(&origin, &towards), 1.0 / solid_angle);
+
+        let away = Vector::new(0.0, 1.0, 0.0);
+        assert_eq!(sphere.pdf_value(&origin, &away), 0.0);
+    }
+
+    #[test]
+    fn test_sphere_random_hits_sphere() {
+        let sphere = light();
+        let origin = Vector::new(3.0, 1.0, 2.0);
+
+        for _ in 0..100 {
+            let direction = sphere.random(&origin);
+            assert!(sphere.pdf_value(&origin, &direction) > 0.0);
+        }
+    }
+
+    #[test]
+    fn test_sphere_random_from_inside_covers_all_directions() {
+        let sphere = light();
+        let origin = Vector::new(0.0, 0.0, 0.5);
+
+        let directions: Vec<Vector> = (0..100).map(|_| sphere.random(&origin)).collect();
+        assert!(directions.iter().any(|direction| direction.x() < 0.0));
+        assert!(directions.iter().any(|direction| direction.y() < 0.0));
+        assert!(directions.iter().any(|direction| direction.z() < 0.0));
+        assert_approx_eq!(sphere.pdf_value(&origin, &directions[0]), 1.0 / (4.0 * PI));
+    }
 }
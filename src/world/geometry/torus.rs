@@ -0,0 +1,318 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float;
+use crate::float::consts::PI;
+use crate::float::Float;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+
+// Coefficients smaller than this are treated as 0 when solving polynomials
+const SOLVER_EPSILON: Float = 1e-12;
+// Newton iterations used to refine the roots from the closed form solution
+const POLISH_ITERATIONS: usize = 3;
+
+/// A ring around the y axis. `major_radius` is the distance from the centre to the middle of the
+/// tube and `minor_radius` the radius of the tube itself
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Torus {
+    centre: Vector,
+    major_radius: Float,
+    minor_radius: Float,
+    material: Material,
+}
+
+impl Torus {
+    pub fn build(
+        centre: Vector,
+        major_radius: Float,
+        minor_radius: Float,
+        material: Material,
+    ) -> Geometry {
+        Geometry::Torus(Box::from(Torus {
+            centre,
+            major_radius,
+            minor_radius,
+            material,
+        }))
+    }
+
+    /// Gradient of the implicit function (|p|^2 + R^2 - r^2)^2 - 4 * R^2 * (x^2 + z^2)
+    fn surface_normal(&self, point: &Vector) -> Vector {
+        let major_squared = self.major_radius * self.major_radius;
+        let e = point.len_squared() - major_squared - self.minor_radius * self.minor_radius;
+        Vector::new(
+            point.x() * e,
+            point.y() * (e + 2.0 * major_squared),
+            point.z() * e,
+        )
+        .unit_vector()
+    }
+
+    fn texture_coords(&self, point: &Vector) -> (Float, Float) {
+        let around_ring = Float::atan2(point.z(), point.x());
+        let from_ring = point.x().hypot(point.z()) - self.major_radius;
+        let around_tube = Float::atan2(point.y(), from_ring);
+
+        (
+            (around_tube + PI) / (2.0 * PI),
+            (around_ring + PI) / (2.0 * PI),
+        )
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let direction_len = ray.direction().len();
+        let direction = ray.direction() / direction_len;
+        let origin = ray.origin() - self.centre;
+
+        // Far away origins make the quartic badly conditioned so solve from where the ray enters
+        // the bounding sphere of the torus instead
+        let bounding_radius = self.major_radius + self.minor_radius.abs();
+        let b = Vector::dot(&origin, &direction);
+        let c = origin.len_squared() - bounding_radius * bounding_radius;
+        let discriminant = b * b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let start = (-b - discriminant.sqrt()).max(0.0);
+        let origin = origin + start * direction;
+
+        let major_squared = self.major_radius * self.major_radius;
+        let minor_squared = self.minor_radius * self.minor_radius;
+        let f = Vector::dot(&origin, &direction);
+        let e = origin.len_squared() - major_squared - minor_squared;
+
+        // |direction| = 1 so the leading coefficient is 1
+        let roots = solve_quartic(
+            4.0 * f,
+            4.0 * f * f + 2.0 * e + 4.0 * major_squared * direction.y() * direction.y(),
+            4.0 * e * f + 8.0 * major_squared * origin.y() * direction.y(),
+            e * e - 4.0 * major_squared * (minor_squared - origin.y() * origin.y()),
+        );
+
+        let distance = roots
+            .into_iter()
+            .map(|root| (start + root) / direction_len)
+            // Also drops any NaN root
+            .filter(|distance| *distance >= tmin && *distance <= tmax)
+            .reduce(float::min)?;
+
+        let point = ray.point(distance);
+        let local_point = point - self.centre;
+        Some(HitResult {
+            distance,
+            ray: *ray,
+            point,
+            surface_normal: self.surface_normal(&local_point),
+            material: self.material.clone(),
+            texture_coords: self.texture_coords(&local_point),
+            texture_gradients: None,
+            light_group: None,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        let minor_radius = self.minor_radius.abs();
+        let outer_radius = self.major_radius.abs() + minor_radius;
+        let extent = Vector::new(outer_radius, minor_radius, outer_radius);
+        Some(AxisAlignedBoundingBox::new(
+            self.centre - extent,
+            self.centre + extent,
+        ))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        false
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+}
+
+/// Real roots of x^4 + a * x^3 + b * x^2 + c * x + d using Ferrari's method
+fn solve_quartic(a: Float, b: Float, c: Float, d: Float) -> Vec<Float> {
+    // Substitute x = y - a / 4 to get the depressed quartic y^4 + p * y^2 + q * y + r
+    let a_squared = a * a;
+    let p = b - 3.0 * a_squared / 8.0;
+    let q = c - a * b / 2.0 + a_squared * a / 8.0;
+    let r = d - a * c / 4.0 + a_squared * b / 16.0 - 3.0 * a_squared * a_squared / 256.0;
+
+    let depressed_roots = if q.abs() < SOLVER_EPSILON {
+        // Biquadratic, solve for y^2
+        solve_quadratic(p, r)
+            .into_iter()
+            .filter(|z| *z >= 0.0)
+            .flat_map(|z| vec![z.sqrt(), -z.sqrt()])
+            .collect()
+    } else {
+        // Any positive root m of the resolvent cubic splits the quartic into two quadratics
+        // (y^2 + p / 2 + m)^2 = 2 * m * (y - q / (4 * m))^2
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .fold(Float::MIN, Float::max);
+        if m <= 0.0 {
+            return vec![];
+        }
+
+        let s = (2.0 * m).sqrt();
+        let mut roots = solve_quadratic(-s, p / 2.0 + m + q / (2.0 * s));
+        roots.extend(solve_quadratic(s, p / 2.0 + m - q / (2.0 * s)));
+        roots
+    };
+
+    depressed_roots
+        .into_iter()
+        .map(|y| polish_root(y - a / 4.0, a, b, c, d))
+        .collect()
+}
+
+/// Real roots of x^2 + b * x + c
+fn solve_quadratic(b: Float, c: Float) -> Vec<Float> {
+    let discriminant = b * b - 4.0 * c;
+    if discriminant < 0.0 {
+        return vec![];
+    }
+
+    let root = discriminant.sqrt();
+    vec![(-b - root) / 2.0, (-b + root) / 2.0]
+}
+
+/// Real roots of x^3 + a * x^2 + b * x + c using Cardano's method
+fn solve_cubic(a: Float, b: Float, c: Float) -> Vec<Float> {
+    // Substitute x = s - a / 3 to get the depressed cubic s^3 + p * s + q
+    let p = b - a * a / 3.0;
+    let q = 2.0 * a * a * a / 27.0 - a * b / 3.0 + c;
+    let shift = a / 3.0;
+
+    let discriminant = q * q / 4.0 + p * p * p / 27.0;
+    if p.abs() < SOLVER_EPSILON {
+        return vec![(-q).cbrt() - shift];
+    }
+    if discriminant > 0.0 {
+        let root = discriminant.sqrt();
+        return vec![(-q / 2.0 + root).cbrt() + (-q / 2.0 - root).cbrt() - shift];
+    }
+
+    // Three real roots
+    let amplitude = 2.0 * (-p / 3.0).sqrt();
+    let angle = ((3.0 * q / (2.0 * p)) * (-3.0 / p).sqrt())
+        .clamp(-1.0, 1.0)
+        .acos()
+        / 3.0;
+    (0..3)
+        .map(|k| amplitude * (angle - 2.0 * PI * k as Float / 3.0).cos() - shift)
+        .collect()
+}
+
+fn polish_root(mut x: Float, a: Float, b: Float, c: Float, d: Float) -> Float {
+    for _ in 0..POLISH_ITERATIONS {
+        let value = (((x + a) * x + b) * x + c) * x + d;
+        let derivative = ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+        if derivative.abs() < SOLVER_EPSILON {
+            break;
+        }
+        x -= value / derivative;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn torus() -> Torus {
+        Torus {
+            centre: Vector::new(0.0, 0.0, 0.0),
+            major_radius: 2.0,
+            minor_radius: 0.5,
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_solve_quartic() {
+        // (x - 1)(x - 2)(x + 3)(x - 4) = x^4 - 4x^3 - 7x^2 + 34x - 24
+        let mut roots = solve_quartic(-4.0, -7.0, 34.0, -24.0);
+        roots.sort_by(Float::total_cmp);
+
+        assert_eq!(roots.len(), 4);
+        for (root, expected) in roots.iter().zip(&[-3.0, 1.0, 2.0, 4.0]) {
+            assert_approx_eq!(root, expected);
+        }
+
+        // x^4 + 1 has no real roots
+        assert!(solve_quartic(0.0, 0.0, 0.0, 1.0).is_empty());
+    }
+
+    #[test]
+    fn test_torus_hit_through_ring() {
+        let ray = Ray::new(Vector::new(-5.0, 0.0, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
+
+        let hit_result = torus().hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 2.5);
+        assert_approx_eq!(hit_result.surface_normal.x(), -1.0);
+
+        // Starting inside the tube the next surface is the inner side of the same tube
+        let hit_result = torus().hit(&ray, 3.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 3.5);
+    }
+
+    #[test]
+    fn test_torus_hit_from_above() {
+        let ray = Ray::new(Vector::new(0.0, 5.0, 2.0), Vector::new(0.0, -2.0, 0.0), 0.0);
+
+        let hit_result = torus().hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 2.25);
+        assert_approx_eq!(hit_result.surface_normal.y(), 1.0);
+    }
+
+    #[test]
+    fn test_torus_miss() {
+        // Straight through the hole
+        let ray = Ray::new(Vector::new(0.0, 5.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        assert!(torus().hit(&ray, 0.0, Float::MAX).is_none());
+
+        // Passing above the ring
+        let ray = Ray::new(Vector::new(-5.0, 0.6, 0.0), Vector::new(1.0, 0.0, 0.0), 0.0);
+        assert!(torus().hit(&ray, 0.0, Float::MAX).is_none());
+
+        // Pointing away
+        let ray = Ray::new(
+            Vector::new(-5.0, 0.0, 0.0),
+            Vector::new(-1.0, 0.0, 0.0),
+            0.0,
+        );
+        assert!(torus().hit(&ray, 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_torus_nan_ray_misses() {
+        let ray = Ray::new(
+            Vector::new(-3.0, 0.0, 0.0),
+            Vector::new(1.0, Float::NAN, 0.0),
+            0.0,
+        );
+        assert!(torus().hit(&ray, 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_torus_bounding_box() {
+        let expected_box =
+            AxisAlignedBoundingBox::new(Vector::new(-2.5, -0.5, -2.5), Vector::new(2.5, 0.5, 2.5));
+
+        assert_eq!(torus().bounding_box(0.0, 0.0), Some(expected_box));
+    }
+}
@@ -1,6 +1,7 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::vector::Vector;
+use crate::float::Float;
 use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
 use crate::world::geometry::{Geometry, HitResult, Hittable};
 
@@ -20,7 +21,7 @@ impl Translate {
 }
 
 impl Hittable for Translate {
-    fn hit(&self, ray: &Ray, tmin: f64, tmax: f64) -> Option<HitResult> {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
         let moved_ray = ray.offset(self.offset);
         self.geometry
             .hit(&moved_ray, tmin, tmax)
@@ -30,7 +31,7 @@ impl Hittable for Translate {
             })
     }
 
-    fn bounding_box(&self, time_start: f64, time_end: f64) -> Option<AxisAlignedBoundingBox> {
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<AxisAlignedBoundingBox> {
         match self.geometry.bounding_box(time_start, time_end) {
             None => None,
             Some(bounding_box) => Some(AxisAlignedBoundingBox::new(
@@ -48,12 +49,17 @@ impl Hittable for Translate {
         self.geometry.is_attractor()
     }
 
-    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> f64 {
-        self.geometry.pdf_value(origin, direction)
+    fn emits_light(&self) -> bool {
+        self.geometry.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        self.geometry.pdf_value(&(origin - self.offset), direction)
     }
 
     fn random(&self, origin: &Vector) -> Vector {
-        self.geometry.random(origin)
+        // Directions are the same with or without the offset, only the origin moves
+        self.geometry.random(&(origin - self.offset))
     }
 }
 
@@ -71,12 +77,14 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         )
         .translate(Vector::new(1.0, 0.2, 0.0));
 
         let ray = Ray::new(Vector::new(3.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_approx_eq!(hit_result.distance, 1.0);
     }
 
@@ -87,12 +95,14 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         )
         .translate(Vector::new(1.0, 0.2, 0.0));
 
         let ray = Ray::new(Vector::new(3.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         assert_eq!(hit_result.surface_normal, Vector::new(1.0, 0.0, 0.0));
     }
 
@@ -103,6 +113,8 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         )
         .translate(Vector::new(1.0, 0.2, 0.0));
@@ -120,14 +132,44 @@ mod tests {
             Vector::new(1.0, 1.0, 1.0),
             Material::Dielectric {
                 refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
             },
         )
         .translate(Vector::new(1.0, 0.2, 0.0));
 
         let ray = Ray::new(Vector::new(3.0, 0.5, 0.5), Vector::new(-1.0, 0.0, 0.0), 0.0);
-        let hit_result = cube.hit(&ray, 0.0, core::f64::MAX).unwrap();
+        let hit_result = cube.hit(&ray, 0.0, Float::MAX).unwrap();
         let (u, v) = hit_result.texture_coords;
         assert_approx_eq!(u, 0.3);
         assert_approx_eq!(v, 0.5);
     }
+
+    #[test]
+    fn test_translate_light_sampling() {
+        use crate::world::geometry::rectangle::XzRect;
+
+        let light = Material::Dielectric {
+            refractive_index: 1.5,
+            absorption: None,
+            roughness: 0.0,
+        };
+        let moved = XzRect::build((0.0, 1.0), (0.0, 1.0), 2.0, light.clone())
+            .translate(Vector::new(1.0, 0.5, 0.0));
+        let expected = XzRect::build((1.0, 2.0), (0.0, 1.0), 2.5, light);
+
+        let origin = Vector::new(0.0, 0.0, 0.0);
+        let direction = Vector::new(1.5, 2.5, 0.5);
+        assert!(moved.is_attractor());
+        assert_approx_eq!(
+            moved.pdf_value(&origin, &direction),
+            expected.pdf_value(&origin, &direction)
+        );
+
+        for _ in 0..100 {
+            let direction = moved.random(&origin);
+            assert_approx_eq!(direction.y(), 2.5);
+            assert!(direction.x() >= 1.0 && direction.x() <= 2.0);
+        }
+    }
 }
@@ -0,0 +1,292 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::pdf::uniform;
+use crate::world::geometry::axis_aligned_bounding_box::AxisAlignedBoundingBox;
+use crate::world::geometry::{Geometry, HitResult, Hittable};
+use crate::world::materials::Material;
+
+// Rays that are this close to parallel with the plane of the triangle are treated as misses
+const PARALLEL_EPSILON: Float = 1e-12;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Triangle {
+    v0: Vector,
+    v1: Vector,
+    v2: Vector,
+    #[serde(default)]
+    normals: Option<[Vector; 3]>,
+    material: Material,
+}
+
+impl Triangle {
+    /// The surface normal follows the right hand rule for the winding `v0 -> v1 -> v2`
+    pub fn build(v0: Vector, v1: Vector, v2: Vector, material: Material) -> Geometry {
+        Geometry::Triangle(Box::from(Triangle {
+            v0,
+            v1,
+            v2,
+            normals: None,
+            material,
+        }))
+    }
+
+    /// Builds a triangle that shades smoothly by interpolating the given per vertex normals
+    /// across its face instead of using the flat face normal
+    pub fn build_smooth(
+        v0: Vector,
+        v1: Vector,
+        v2: Vector,
+        normals: [Vector; 3],
+        material: Material,
+    ) -> Geometry {
+        Geometry::Triangle(Box::from(Triangle {
+            v0,
+            v1,
+            v2,
+            normals: Some(normals),
+            material,
+        }))
+    }
+
+    fn area(&self) -> Float {
+        Vector::cross(&(self.v1 - self.v0), &(self.v2 - self.v0)).len() / 2.0
+    }
+}
+
+/// Möller–Trumbore: solves o + t * d = (1 - u - v) * v0 + u * v1 + v * v2 for (t, u, v) where the
+/// ray crosses the triangle between `tmin` and `tmax`
+pub fn intersect(
+    v0: &Vector,
+    v1: &Vector,
+    v2: &Vector,
+    ray: &Ray,
+    tmin: Float,
+    tmax: Float,
+) -> Option<(Float, Float, Float)> {
+    let edge1 = v1 - v0;
+    let edge2 = v2 - v0;
+
+    let p = Vector::cross(ray.direction(), &edge2);
+    let determinant = Vector::dot(&edge1, &p);
+    if determinant.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+
+    let s = ray.origin() - v0;
+    let u = Vector::dot(&s, &p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = Vector::cross(&s, &edge1);
+    let v = Vector::dot(ray.direction(), &q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let distance = Vector::dot(&edge2, &q) * inverse_determinant;
+    if distance.is_nan() || distance < tmin || distance > tmax {
+        return None;
+    }
+    Some((distance, u, v))
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, tmin: Float, tmax: Float) -> Option<HitResult> {
+        let (distance, u, v) = intersect(&self.v0, &self.v1, &self.v2, ray, tmin, tmax)?;
+        let edge1 = self.v1 - self.v0;
+        let edge2 = self.v2 - self.v0;
+
+        let surface_normal = match &self.normals {
+            // Interpolating unit vectors does not produce a unit vector so renormalise
+            Some([n0, n1, n2]) => ((1.0 - u - v) * n0 + u * n1 + v * n2).unit_vector(),
+            None => Vector::cross(&edge1, &edge2).unit_vector(),
+        };
+
+        Some(HitResult {
+            distance,
+            ray: *ray,
+            point: ray.point(distance),
+            surface_normal,
+            material: self.material.clone(),
+            texture_coords: (u, v),
+            texture_gradients: Some((edge1, edge2)),
+            light_group: None,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<AxisAlignedBoundingBox> {
+        // Pad the box so that axis aligned triangles do not end up with a zero width box
+        let padding = Vector::new(0.0001, 0.0001, 0.0001);
+        Some(AxisAlignedBoundingBox::new(
+            self.v0.min(&self.v1).min(&self.v2) - padding,
+            self.v0.max(&self.v1).max(&self.v2) + padding,
+        ))
+    }
+
+    fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
+        self.material.validate(assets)
+    }
+
+    fn is_attractor(&self) -> bool {
+        self.material.is_attractor()
+    }
+
+    fn emits_light(&self) -> bool {
+        self.material.emits_light()
+    }
+
+    fn pdf_value(&self, origin: &Vector, direction: &Vector) -> Float {
+        let direction = direction.unit_vector();
+        let hit = self.hit(&Ray::new(*origin, direction, 0.0), 0.001, Float::MAX);
+        match hit {
+            None => 0.0,
+            Some(hit) => {
+                let distance_squared = hit.distance.powi(2);
+                let cosine = Vector::dot(&direction, &hit.face_normal()).abs();
+
+                distance_squared / (cosine * self.area())
+            }
+        }
+    }
+
+    fn random(&self, origin: &Vector) -> Vector {
+        // Folding the unit square onto the triangle keeps the samples uniform over its area
+        let r1 = uniform::<Float>().sqrt();
+        let r2 = uniform::<Float>();
+        let random_point = (1.0 - r1) * self.v0 + (r1 * (1.0 - r2)) * self.v1 + (r1 * r2) * self.v2;
+        random_point - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn triangle() -> Triangle {
+        Triangle {
+            v0: Vector::new(0.0, 0.0, 0.0),
+            v1: Vector::new(1.0, 0.0, 0.0),
+            v2: Vector::new(0.0, 1.0, 0.0),
+            normals: None,
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: None,
+                roughness: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_triangle_hit() {
+        let ray = Ray::new(
+            Vector::new(0.25, 0.25, 1.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+
+        let hit_result = triangle().hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.distance, 1.0);
+        assert_eq!(hit_result.point, Vector::new(0.25, 0.25, 0.0));
+    }
+
+    #[test]
+    fn test_triangle_miss() {
+        let ray = Ray::new(
+            Vector::new(0.75, 0.75, 1.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        assert!(triangle().hit(&ray, 0.0, Float::MAX).is_none());
+
+        let parallel_ray = Ray::new(
+            Vector::new(-1.0, 0.25, 0.0),
+            Vector::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+        assert!(triangle().hit(&parallel_ray, 0.0, Float::MAX).is_none());
+    }
+
+    #[test]
+    fn test_triangle_surface_normal() {
+        let ray = Ray::new(
+            Vector::new(0.25, 0.25, -1.0),
+            Vector::new(0.0, 0.0, 1.0),
+            0.0,
+        );
+
+        let hit_result = triangle().hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_eq!(hit_result.surface_normal, Vector::new(0.0, 0.0, 1.0));
+        assert!(!hit_result.front_face());
+    }
+
+    #[test]
+    fn test_triangle_smooth_surface_normal() {
+        let triangle = Triangle {
+            normals: Some([
+                Vector::new(0.0, 0.0, 1.0),
+                Vector::new(1.0, 0.0, 1.0).unit_vector(),
+                Vector::new(0.0, 1.0, 1.0).unit_vector(),
+            ]),
+            ..triangle()
+        };
+
+        let ray = Ray::new(Vector::new(0.0, 0.0, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        let hit_result = triangle.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_eq!(hit_result.surface_normal, Vector::new(0.0, 0.0, 1.0));
+
+        let ray = Ray::new(Vector::new(0.5, 0.5, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+        let hit_result = triangle.hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.surface_normal.len(), 1.0);
+        assert_approx_eq!(hit_result.surface_normal.x(), hit_result.surface_normal.y());
+        assert!(hit_result.surface_normal.x() > 0.0);
+    }
+
+    #[test]
+    fn test_triangle_bounding_box() {
+        let expected_box = AxisAlignedBoundingBox::new(
+            Vector::new(-0.0001, -0.0001, -0.0001),
+            Vector::new(1.0001, 1.0001, 0.0001),
+        );
+
+        assert_eq!(triangle().bounding_box(0.0, 0.0), Some(expected_box));
+    }
+
+    #[test]
+    fn test_triangle_texture_coords() {
+        let ray = Ray::new(
+            Vector::new(0.25, 0.5, 1.0),
+            Vector::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+
+        let hit_result = triangle().hit(&ray, 0.0, Float::MAX).unwrap();
+        assert_approx_eq!(hit_result.texture_coords.0, 0.25);
+        assert_approx_eq!(hit_result.texture_coords.1, 0.5);
+    }
+
+    #[test]
+    fn test_triangle_pdf_value() {
+        let origin = Vector::new(0.25, 0.25, 1.0);
+        let direction = Vector::new(0.0, 0.0, -1.0);
+
+        // distance^2 / (cosine * area) = 1.0 / (1.0 * 0.5)
+        assert_approx_eq!(triangle().pdf_value(&origin, &direction), 2.0);
+    }
+
+    #[test]
+    fn test_triangle_random() {
+        let triangle = triangle();
+        let origin = Vector::new(0.0, 0.0, 1.0);
+        for _ in 0..100 {
+            let direction = triangle.random(&origin);
+            assert!(triangle
+                .hit(&Ray::new(origin, direction, 0.0), 0.0, Float::MAX)
+                .is_some());
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! A thin smooth dielectric layer such as lacquer or car paint clearcoat over another material
+//!
+//! Each ray either reflects off the coat, with the probability given by its Fresnel reflectance,
+//! or passes through it to scatter off the base. Picking one in proportion to how much light it
+//! carries keeps the throughput of each pick unweighted. The coat is thin enough that light
+//! leaving the base is not dimmed on the way back out.
+
+use crate::data::assets::Assets;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::pdf::uniform;
+use crate::world::geometry::HitResult;
+use crate::world::materials::dielectric::reflectivity_schlick_approx;
+use crate::world::materials::{microfacet, Material, ScatterResult};
+
+const REFRACTIVE_INDEX_OF_AIR: Float = 1.0;
+
+pub fn scatter(
+    base: &Material,
+    roughness: Float,
+    refractive_index: Float,
+    hit: &HitResult,
+    assets: &Assets,
+) -> Option<ScatterResult> {
+    let cosine = (-Vector::dot(&hit.ray.direction().unit_vector(), &hit.face_normal())).max(0.0);
+    if uniform::<Float>() < reflectance(cosine, refractive_index) {
+        // A white microfacet reflection is weighted by the masking alone, the Fresnel term
+        // having been accounted for by the pick
+        microfacet::scatter(&Colour::new(1.0, 1.0, 1.0), roughness, hit)
+    } else {
+        base.scatter(hit, assets)
+    }
+}
+
+/// How much of the light arriving at `cosine` to the normal reflects off the coat
+pub fn reflectance(cosine: Float, refractive_index: Float) -> Float {
+    reflectivity_schlick_approx(cosine, REFRACTIVE_INDEX_OF_AIR, refractive_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Ray;
+    use crate::pdf::seed_thread_rng;
+    use crate::world::texture::Texture;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn hit(direction: Vector) -> HitResult {
+        HitResult {
+            distance: 1.0,
+            ray: Ray::new(Vector::new(0.0, 1.0, 0.0) - direction, direction, 0.0),
+            point: Vector::new(0.0, 0.0, 0.0),
+            surface_normal: Vector::new(0.0, 1.0, 0.0),
+            material: Material::Lambertian {
+                albedo: Texture::Constant {
+                    colour: Colour::new(0.8, 0.1, 0.1),
+                },
+            },
+            texture_coords: (0.0, 0.0),
+            texture_gradients: None,
+            light_group: None,
+        }
+    }
+
+    /// The fraction of rays at `direction` that reflect off the coat
+    fn coat_fraction(direction: Vector) -> Float {
+        seed_thread_rng(0);
+        let hit = hit(direction);
+        let assets = Assets::default();
+        let num_of_samples = 20_000;
+        let coated = (0..num_of_samples)
+            .filter(|_| {
+                matches!(
+                    scatter(&hit.material, 0.0, 1.5, &hit, &assets),
+                    Some(ScatterResult::Specular { .. })
+                )
+            })
+            .count();
+        coated as Float / num_of_samples as Float
+    }
+
+    #[test]
+    fn test_clearcoat_reflects_more_at_grazing_angles() {
+        // Glass reflects 4% head on
+        assert_approx_eq!(reflectance(1.0, 1.5), 0.04);
+        assert_approx_eq!(coat_fraction(Vector::new(0.0, -1.0, 0.0)), 0.04, 0.01);
+        assert!(coat_fraction(Vector::new(1.0, -0.1, 0.0).unit_vector()) > 0.3);
+    }
+
+    #[test]
+    fn test_smooth_clearcoat_is_a_mirror() {
+        seed_thread_rng(0);
+        let hit = hit(Vector::new(1.0, -1.0, 0.0).unit_vector());
+        let assets = Assets::default();
+        for _ in 0..100 {
+            if let Some(ScatterResult::Specular { attenuation, ray }) =
+                scatter(&hit.material, 0.0, 1.5, &hit, &assets)
+            {
+                assert_approx_eq!(attenuation.r(), 1.0, 0.01);
+                assert_approx_eq!(
+                    ray.direction().unit_vector().y(),
+                    (0.5 as Float).sqrt(),
+                    0.001
+                );
+            }
+        }
+    }
+}
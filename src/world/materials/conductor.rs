@@ -0,0 +1,154 @@
+use crate::data::colour::Colour;
+use crate::float::Float;
+use serde::{Deserialize, Deserializer};
+
+/// Measured complex indices of refraction for common metals, sampled at the red, green and blue
+/// wavelengths 650nm, 550nm and 450nm
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ConductorPreset {
+    Gold,
+    Silver,
+    Copper,
+    Aluminium,
+}
+
+impl ConductorPreset {
+    pub fn eta(self) -> Colour {
+        match self {
+            ConductorPreset::Gold => Colour::new(0.143, 0.374, 1.442),
+            ConductorPreset::Silver => Colour::new(0.155, 0.117, 0.138),
+            ConductorPreset::Copper => Colour::new(0.200, 0.924, 1.102),
+            ConductorPreset::Aluminium => Colour::new(1.657, 0.880, 0.521),
+        }
+    }
+
+    pub fn k(self) -> Colour {
+        match self {
+            ConductorPreset::Gold => Colour::new(3.983, 2.385, 1.603),
+            ConductorPreset::Silver => Colour::new(4.828, 3.122, 2.147),
+            ConductorPreset::Copper => Colour::new(3.912, 2.452, 2.142),
+            ConductorPreset::Aluminium => Colour::new(9.224, 6.270, 4.837),
+        }
+    }
+}
+
+/// Conductors are written either with their eta and k or with the name of a preset
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedConductor {
+    Measured {
+        eta: Colour,
+        k: Colour,
+        #[serde(default)]
+        roughness: Float,
+    },
+    Preset {
+        preset: ConductorPreset,
+        #[serde(default)]
+        roughness: Float,
+    },
+}
+
+/// Loads the eta, k and roughness of a `Material::Conductor`
+pub fn deserialize_conductor<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<(Colour, Colour, Float), D::Error> {
+    Ok(match SavedConductor::deserialize(deserializer)? {
+        SavedConductor::Measured { eta, k, roughness } => (eta, k, roughness),
+        SavedConductor::Preset { preset, roughness } => (preset.eta(), preset.k(), roughness),
+    })
+}
+
+/// The unpolarised Fresnel reflectance of each colour channel for light arriving at `cosine` to
+/// the surface normal
+pub fn fresnel(eta: &Colour, k: &Colour, cosine: Float) -> Colour {
+    Colour::new(
+        fresnel_channel(eta.r(), k.r(), cosine),
+        fresnel_channel(eta.g(), k.g(), cosine),
+        fresnel_channel(eta.b(), k.b(), cosine),
+    )
+}
+
+fn fresnel_channel(eta: Float, k: Float, cosine: Float) -> Float {
+    let cos_squared = cosine.clamp(0.0, 1.0).powi(2);
+    let sin_squared = 1.0 - cos_squared;
+    let eta_squared = eta * eta;
+    let k_squared = k * k;
+
+    let t0 = eta_squared - k_squared - sin_squared;
+    let a_squared_plus_b_squared = (t0 * t0 + 4.0 * eta_squared * k_squared).sqrt();
+    let a = (0.5 * (a_squared_plus_b_squared + t0)).max(0.0).sqrt();
+
+    let t1 = a_squared_plus_b_squared + cos_squared;
+    let t2 = 2.0 * cosine.clamp(0.0, 1.0) * a;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos_squared * a_squared_plus_b_squared + sin_squared * sin_squared;
+    let t4 = t2 * sin_squared;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+
+    0.5 * (perpendicular + parallel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::materials::Material;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_fresnel_at_normal_incidence() {
+        let eta = ConductorPreset::Gold.eta();
+        let k = ConductorPreset::Gold.k();
+        let reflectance = fresnel(&eta, &k, 1.0);
+
+        let expected =
+            |eta: Float, k: Float| ((eta - 1.0).powi(2) + k * k) / ((eta + 1.0).powi(2) + k * k);
+        assert_approx_eq!(reflectance.r(), expected(eta.r(), k.r()));
+        assert_approx_eq!(reflectance.b(), expected(eta.b(), k.b()));
+
+        // Gold reflects far more red than blue head on
+        assert!(reflectance.r() > 0.9);
+        assert!(reflectance.b() < 0.4);
+    }
+
+    #[test]
+    fn test_fresnel_tends_to_white_at_grazing_angles() {
+        let eta = ConductorPreset::Copper.eta();
+        let k = ConductorPreset::Copper.k();
+
+        let grazing = fresnel(&eta, &k, 0.0);
+        assert_approx_eq!(grazing.r(), 1.0);
+        assert_approx_eq!(grazing.g(), 1.0);
+        assert_approx_eq!(grazing.b(), 1.0);
+
+        assert!(fresnel(&eta, &k, 0.1).b() > fresnel(&eta, &k, 1.0).b());
+    }
+
+    #[test]
+    fn test_conductor_loads_from_a_preset() {
+        let gold: Material = serde_yaml::from_str("Conductor:\n  preset: Gold\n").unwrap();
+        match gold {
+            Material::Conductor { eta, k, roughness } => {
+                assert_eq!(eta, ConductorPreset::Gold.eta());
+                assert_eq!(k, ConductorPreset::Gold.k());
+                assert_eq!(roughness, 0.0);
+            }
+            other => panic!("expected a conductor, got {:?}", other),
+        }
+
+        // Saved with the values of the preset, which load the same
+        let saved = serde_yaml::to_string(&gold).unwrap();
+        assert!(!saved.contains("preset"));
+        let loaded: Material = serde_yaml::from_str(&saved).unwrap();
+        assert!(
+            matches!(loaded, Material::Conductor { eta, .. } if eta == ConductorPreset::Gold.eta())
+        );
+
+        let rough: Material =
+            serde_yaml::from_str("Conductor:\n  preset: Copper\n  roughness: 0.3\n").unwrap();
+        assert!(matches!(rough, Material::Conductor { roughness, .. } if roughness == 0.3));
+
+        assert!(serde_yaml::from_str::<Material>("Conductor:\n  preset: Tin\n").is_err());
+    }
+}
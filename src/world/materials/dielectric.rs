@@ -1,16 +1,23 @@
 use crate::camera::Ray;
 use crate::data::colour::Colour;
 use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::onb::Onb;
 use crate::pdf::uniform;
 use crate::world::geometry::HitResult;
-use crate::world::materials::ScatterResult;
+use crate::world::materials::microfacet;
+use crate::world::materials::{Absorption, ScatterResult};
 
-const REFRACTIVE_INDEX_OF_AIR: f64 = 1.0;
-const DIELECTRIC_ATTENUATION: [f64; 3] = [1.0, 1.0, 1.0];
+const REFRACTIVE_INDEX_OF_AIR: Float = 1.0;
+const DIELECTRIC_ATTENUATION: [Float; 3] = [1.0, 1.0, 1.0];
 
-pub fn scatter(refractive_index: f64, hit: &HitResult) -> Option<ScatterResult> {
+pub fn scatter(
+    refractive_index: Float,
+    absorption: &Option<Absorption>,
+    roughness: Float,
+    hit: &HitResult,
+) -> Option<ScatterResult> {
     let unit_vector = hit.ray.direction().unit_vector();
-    let reflected = reflect(&unit_vector, &hit.surface_normal);
 
     let uvn = Vector::dot(&unit_vector, &hit.surface_normal);
 
@@ -22,33 +29,76 @@ pub fn scatter(refractive_index: f64, hit: &HitResult) -> Option<ScatterResult>
         (1.0, REFRACTIVE_INDEX_OF_AIR, refractive_index)
     };
 
-    let cosine = -sign * uvn;
+    // Frosted glass reflects and refracts off a randomly oriented microfacet instead of the
+    // surface itself
+    let normal = sign * hit.surface_normal;
+    let microfacet_normal = microfacet_normal(&unit_vector, &normal, roughness);
+
+    let cosine = -Vector::dot(&unit_vector, &microfacet_normal);
     let reflect_prob = reflectivity_schlick_approx(cosine, n_i, n_t);
-    let reflect_rand: f64 = uniform();
+    let reflect_rand: Float = uniform();
     let should_reflect = reflect_rand < reflect_prob;
 
     let maybe_refracted = if should_reflect {
         None
     } else {
-        refract(&unit_vector, &(sign * hit.surface_normal), n_i / n_t)
+        refract(&unit_vector, &microfacet_normal, n_i / n_t)
+            .map(|refracted| keep_on_side(refracted, &(-normal)))
     };
+    let reflected = keep_on_side(reflect(&unit_vector, &microfacet_normal), &normal);
 
     let ray = match maybe_refracted {
         Some(refracted) => Ray::new(hit.point, refracted, hit.ray.time()),
         None => Ray::new(hit.point, reflected, hit.ray.time()),
     };
 
-    Some(ScatterResult::specular(
-        Colour::new(
-            DIELECTRIC_ATTENUATION[0],
-            DIELECTRIC_ATTENUATION[1],
-            DIELECTRIC_ATTENUATION[2],
-        ),
-        ray,
-    ))
+    let attenuation = Colour::new(
+        DIELECTRIC_ATTENUATION[0],
+        DIELECTRIC_ATTENUATION[1],
+        DIELECTRIC_ATTENUATION[2],
+    );
+
+    // A ray hitting the geometry from the inside has travelled through it since it last scattered
+    // so that is the segment that gets absorbed
+    let attenuation = match absorption {
+        Some(absorption) if uvn > 0.0 => {
+            let distance_inside = hit.distance * hit.ray.direction().len();
+            attenuation * absorption.transmittance(distance_inside)
+        }
+        _ => attenuation,
+    };
+
+    Some(ScatterResult::specular(attenuation, ray))
+}
+
+/// The surface normal facing the incoming ray for smooth glass, otherwise a GGX sampled
+/// microfacet normal around it
+fn microfacet_normal(unit_vector: &Vector, normal: &Vector, roughness: Float) -> Vector {
+    if roughness <= 0.0 {
+        return *normal;
+    }
+
+    let sampled =
+        microfacet::sample_half_vector(microfacet::alpha(roughness), &Onb::build_from_w(normal));
+    if Vector::dot(unit_vector, &sampled) < 0.0 {
+        sampled
+    } else {
+        // The incoming ray cannot hit the back of a microfacet
+        *normal
+    }
 }
 
-fn reflect(unit_vector: &Vector, surface_normal: &Vector) -> Vector {
+/// Mirrors `direction` through the surface if a tilted microfacet sent it to the wrong side
+fn keep_on_side(direction: Vector, side: &Vector) -> Vector {
+    let dot = Vector::dot(&direction, side);
+    if dot < 0.0 {
+        direction - 2.0 * dot * side
+    } else {
+        direction
+    }
+}
+
+pub fn reflect(unit_vector: &Vector, surface_normal: &Vector) -> Vector {
     let uv = unit_vector;
     let n = surface_normal;
 
@@ -57,10 +107,10 @@ fn reflect(unit_vector: &Vector, surface_normal: &Vector) -> Vector {
     uv - 2.0 * b
 }
 
-fn refract(
+pub fn refract(
     unit_vector: &Vector,
     surface_normal: &Vector,
-    refractive_index_ratio: f64,
+    refractive_index_ratio: Float,
 ) -> Option<Vector> {
     let uv = unit_vector;
     let n = surface_normal;
@@ -68,7 +118,7 @@ fn refract(
     let dt = Vector::dot(uv, n);
 
     let ni_over_nt = refractive_index_ratio;
-    let discriminant = 2.0 - ni_over_nt * ni_over_nt * (1.0 - dt * dt);
+    let discriminant = 1.0 - ni_over_nt * ni_over_nt * (1.0 - dt * dt);
 
     if discriminant > 0.0 {
         let refracted = ni_over_nt * (uv - n * dt) - n * discriminant.sqrt();
@@ -78,8 +128,141 @@ fn refract(
     None
 }
 
-fn reflectivity_schlick_approx(cosine: f64, n_i: f64, n_t: f64) -> f64 {
+/// The refractive index at `wavelength` nanometres by Cauchy's equation
+pub fn cauchy(a: Float, b: Float, wavelength: Float) -> Float {
+    let micrometres = wavelength / 1000.0;
+    a + b / (micrometres * micrometres)
+}
+
+pub fn reflectivity_schlick_approx(cosine: Float, n_i: Float, n_t: Float) -> Float {
     let r0 = (n_i - n_t) / (n_i + n_t);
     let r0 = r0 * r0;
-    r0 + (1.0 - r0) * f64::powi(1.0 - cosine, 5)
+    r0 + (1.0 - r0) * Float::powi(1.0 - cosine, 5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::materials::Material;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_refract() {
+        // 45 degrees incidence going from air into glass
+        let unit_vector = Vector::new(1.0, -1.0, 0.0).unit_vector();
+        let surface_normal = Vector::new(0.0, 1.0, 0.0);
+
+        let refracted = refract(&unit_vector, &surface_normal, 1.0 / 1.5).unwrap();
+
+        // Snell's law: sin(theta_t) = sin(45) / 1.5
+        let sin_theta_t = Float::sqrt(0.5) / 1.5;
+        let cos_theta_t = Float::sqrt(1.0 - sin_theta_t * sin_theta_t);
+        assert_approx_eq!(refracted.x(), sin_theta_t);
+        assert_approx_eq!(refracted.y(), -cos_theta_t);
+        assert_approx_eq!(refracted.z(), 0.0);
+        assert_approx_eq!(refracted.len(), 1.0);
+    }
+
+    #[test]
+    fn test_cauchy_bends_blue_more_than_red() {
+        // Crown glass
+        assert_approx_eq!(cauchy(1.5046, 0.0042, 550.0), 1.5185, 1e-4);
+        assert!(cauchy(1.5046, 0.0042, 450.0) > cauchy(1.5046, 0.0042, 650.0));
+    }
+
+    #[test]
+    fn test_absorption() {
+        let absorption = Absorption::new(Colour::new(0.25, 0.5, 1.0), 2.0);
+
+        let transmittance = absorption.transmittance(2.0);
+        assert_approx_eq!(transmittance.r(), 0.25);
+        assert_approx_eq!(transmittance.g(), 0.5);
+        assert_approx_eq!(transmittance.b(), 1.0);
+
+        let transmittance = absorption.transmittance(4.0);
+        assert_approx_eq!(transmittance.r(), 0.0625);
+        assert_approx_eq!(transmittance.g(), 0.25);
+        assert_approx_eq!(transmittance.b(), 1.0);
+
+        let transmittance = absorption.transmittance(0.0);
+        assert_approx_eq!(transmittance.r(), 1.0);
+    }
+
+    #[test]
+    fn test_scatter_absorbs_inside_only() {
+        let absorption = Some(Absorption::new(Colour::new(0.5, 0.5, 0.5), 1.0));
+        let hit = |direction: Vector| HitResult {
+            distance: 2.0,
+            ray: Ray::new(Vector::new(0.0, 0.0, 0.0), direction, 0.0),
+            point: Vector::new(0.0, 0.0, 0.0),
+            surface_normal: Vector::new(0.0, 1.0, 0.0),
+            material: Material::Dielectric {
+                refractive_index: 1.5,
+                absorption: absorption.clone(),
+                roughness: 0.0,
+            },
+            texture_coords: (0.0, 0.0),
+            texture_gradients: None,
+            light_group: None,
+        };
+
+        // Entering the glass
+        match scatter(1.5, &absorption, 0.0, &hit(Vector::new(0.0, -1.0, 0.0))) {
+            Some(ScatterResult::Specular { attenuation, .. }) => {
+                assert_approx_eq!(attenuation.r(), 1.0)
+            }
+            _ => panic!("expected a specular scatter"),
+        }
+
+        // Leaving the glass after travelling 2 units through it
+        match scatter(1.5, &absorption, 0.0, &hit(Vector::new(0.0, 1.0, 0.0))) {
+            Some(ScatterResult::Specular { attenuation, .. }) => {
+                assert_approx_eq!(attenuation.r(), 0.25)
+            }
+            _ => panic!("expected a specular scatter"),
+        }
+    }
+
+    #[test]
+    fn test_refract_total_internal_reflection() {
+        // The critical angle going from glass into air is asin(1 / 1.5) ~= 41.8 degrees
+        let surface_normal = Vector::new(0.0, 1.0, 0.0);
+
+        let below_critical = Vector::new(0.5, -Float::sqrt(0.75), 0.0);
+        assert!(refract(&below_critical, &surface_normal, 1.5).is_some());
+
+        let above_critical = Vector::new(1.0, -1.0, 0.0).unit_vector();
+        assert!(refract(&above_critical, &surface_normal, 1.5).is_none());
+    }
+
+    #[test]
+    fn test_rough_scatter_stays_on_correct_side() {
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let unit_vector = Vector::new(1.0, -0.2, 0.0).unit_vector();
+
+        for _ in 0..1000 {
+            let microfacet_normal = microfacet_normal(&unit_vector, &normal, 0.8);
+            assert!(Vector::dot(&unit_vector, &microfacet_normal) < 0.0);
+
+            let reflected = keep_on_side(reflect(&unit_vector, &microfacet_normal), &normal);
+            assert!(Vector::dot(&reflected, &normal) >= 0.0);
+
+            if let Some(refracted) = refract(&unit_vector, &microfacet_normal, 1.0 / 1.5) {
+                let refracted = keep_on_side(refracted, &(-normal));
+                assert!(Vector::dot(&refracted, &normal) <= 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_roughness_uses_surface_normal() {
+        let normal = Vector::new(0.0, 1.0, 0.0);
+        let unit_vector = Vector::new(1.0, -1.0, 0.0).unit_vector();
+
+        assert_eq!(microfacet_normal(&unit_vector, &normal, 0.0), normal);
+        assert_eq!(
+            keep_on_side(unit_vector, &normal),
+            Vector::new(1.0, 1.0, 0.0).unit_vector()
+        );
+    }
 }
@@ -1,14 +1,18 @@
 use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
 use crate::onb::Onb;
 use crate::pdf::Pdf;
 use crate::world::geometry::HitResult;
 use crate::world::materials::ScatterResult;
 use crate::world::texture::Texture;
-use std::f64::consts::PI;
 
-pub fn scattering_pdf(surface_normal: &Vector, scattered: &Ray) -> f64 {
+// Squared length below which a scatter direction is treated as the zero vector
+const NEAR_ZERO: Float = 1e-16;
+
+pub fn scattering_pdf(surface_normal: &Vector, scattered: &Ray) -> Float {
     // Using s(direction) = cos(θ) / π, where θ is the angle relative to the surface normal
     let mut cosine = Vector::dot(surface_normal, &scattered.direction().unit_vector());
     if cosine < 0.0 {
@@ -23,3 +27,34 @@ pub fn scatter(albedo: &Texture, hit: &HitResult, assets: &Assets) -> Option<Sca
 
     Some(ScatterResult::diffuse(albedo, pdf))
 }
+
+/// Sampled directions can come out as (nearly) the zero vector, e.g. when a light is sampled from a
+/// point that lies on the light itself. Normalising one of those produces NaNs so scatter along
+/// the surface normal instead
+pub fn non_degenerate_direction(direction: Vector, surface_normal: &Vector) -> Vector {
+    if direction.len_squared() < NEAR_ZERO {
+        *surface_normal
+    } else {
+        direction
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_non_degenerate_direction() {
+        let surface_normal = Vector::new(0.0, 1.0, 0.0);
+
+        let random_point = -surface_normal;
+        let direction = non_degenerate_direction(surface_normal + random_point, &surface_normal);
+        assert_eq!(direction, surface_normal);
+
+        let direction = Vector::new(1.0, 1.0, 0.0);
+        assert_eq!(
+            non_degenerate_direction(direction, &surface_normal),
+            direction
+        );
+    }
+}
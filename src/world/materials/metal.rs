@@ -1,11 +1,13 @@
 use crate::camera::Ray;
 use crate::data::colour::Colour;
 use crate::data::vector::Vector;
+use crate::float::Float;
 use crate::pdf::random_point_in_unit_sphere;
 use crate::world::geometry::HitResult;
 use crate::world::materials::ScatterResult;
 
-pub fn scatter(albedo: &Colour, fuzz: f64, hit: &HitResult) -> Option<ScatterResult> {
+pub fn scatter(albedo: &Colour, fuzz: Float, hit: &HitResult) -> Option<ScatterResult> {
+    let fuzz = clamp_fuzz(fuzz);
     let unit_vector = hit.ray.direction().unit_vector();
     let reflected = reflect(&unit_vector, &hit.face_normal());
     let ray = Ray::new(
@@ -17,6 +19,11 @@ pub fn scatter(albedo: &Colour, fuzz: f64, hit: &HitResult) -> Option<ScatterRes
     Some(ScatterResult::specular(*albedo, ray))
 }
 
+/// Fuzz above 1 scatters reflections far enough to go below the surface
+fn clamp_fuzz(fuzz: Float) -> Float {
+    fuzz.clamp(0.0, 1.0)
+}
+
 fn reflect(unit_vector: &Vector, surface_normal: &Vector) -> Vector {
     let uv = unit_vector;
     let n = surface_normal;
@@ -25,3 +32,48 @@ fn reflect(unit_vector: &Vector, surface_normal: &Vector) -> Vector {
 
     uv - 2.0 * b
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::materials::Material;
+
+    #[test]
+    fn test_clamp_fuzz() {
+        assert_eq!(clamp_fuzz(5.0), 1.0);
+        assert_eq!(clamp_fuzz(0.3), 0.3);
+        assert_eq!(clamp_fuzz(-1.0), 0.0);
+    }
+
+    #[test]
+    fn test_scatter_large_fuzz() {
+        let hit = HitResult {
+            distance: 1.0,
+            ray: Ray::new(
+                Vector::new(-1.0, 1.0, 0.0),
+                Vector::new(1.0, -1.0, 0.0).unit_vector(),
+                0.0,
+            ),
+            point: Vector::new(0.0, 0.0, 0.0),
+            surface_normal: Vector::new(0.0, 1.0, 0.0),
+            material: Material::Metal {
+                albedo: Colour::new(1.0, 1.0, 1.0),
+                fuzz: 5.0,
+            },
+            texture_coords: (0.0, 0.0),
+            texture_gradients: None,
+            light_group: None,
+        };
+        let reflected = Vector::new(1.0, 1.0, 0.0).unit_vector();
+
+        for _ in 0..1000 {
+            match scatter(&Colour::new(1.0, 1.0, 1.0), 5.0, &hit) {
+                Some(ScatterResult::Specular { ray, .. }) => {
+                    // A fuzz of 1.0 moves the reflection at most 1.0 away from the mirror direction
+                    assert!((ray.direction() - reflected).len() <= 1.0);
+                }
+                _ => panic!("expected a specular scatter"),
+            }
+        }
+    }
+}
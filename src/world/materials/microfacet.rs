@@ -0,0 +1,262 @@
+//! Rough metal using the GGX (Trowbridge-Reitz) microfacet distribution
+//!
+//! The half vector h is importance sampled from D(h) * cos(θh) so the throughput of a sample
+//! simplifies to F * G * (v·h) / ((n·v) * (n·h)). The anisotropic version stretches the
+//! distribution by a separate alpha along the tangent and bitangent of the texture coords.
+//! Conductors swap the Schlick approximation for the exact Fresnel term of their complex IOR.
+
+use crate::camera::Ray;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
+use crate::onb::Onb;
+use crate::pdf::uniform;
+use crate::world::geometry::HitResult;
+use crate::world::materials::conductor;
+use crate::world::materials::normal_map::tangent_frame;
+use crate::world::materials::ScatterResult;
+
+// Below this alpha the distribution is numerically a mirror
+const MIN_ALPHA: Float = 1e-4;
+
+pub fn scatter(albedo: &Colour, roughness: Float, hit: &HitResult) -> Option<ScatterResult> {
+    let normal = hit.face_normal();
+    let alpha = alpha(roughness);
+
+    let half_vector = sample_half_vector(alpha, &Onb::build_from_w(&normal));
+    reflect(
+        |cosine| schlick_fresnel(albedo, cosine),
+        hit,
+        &normal,
+        &half_vector,
+        |_| alpha,
+    )
+}
+
+/// A rough metal whose reflectance comes from its complex index of refraction `eta + ik`
+pub fn scatter_conductor(
+    eta: &Colour,
+    k: &Colour,
+    roughness: Float,
+    hit: &HitResult,
+) -> Option<ScatterResult> {
+    let normal = hit.face_normal();
+    let alpha = alpha(roughness);
+
+    let half_vector = sample_half_vector(alpha, &Onb::build_from_w(&normal));
+    reflect(
+        |cosine| conductor::fresnel(eta, k, cosine),
+        hit,
+        &normal,
+        &half_vector,
+        |_| alpha,
+    )
+}
+
+/// `roughness_u` applies along increasing texture columns and `roughness_v` along increasing
+/// texture rows. Highlights stretch out along the rougher direction
+pub fn scatter_anisotropic(
+    albedo: &Colour,
+    roughness_u: Float,
+    roughness_v: Float,
+    hit: &HitResult,
+) -> Option<ScatterResult> {
+    let normal = hit.face_normal().unit_vector();
+    let (tangent, bitangent) = tangent_frame(&normal, hit.texture_gradients);
+    let alpha_u = alpha(roughness_u);
+    let alpha_v = alpha(roughness_v);
+
+    let r1 = uniform::<Float>();
+    let r2 = uniform::<Float>();
+
+    let phi = Float::atan2(
+        alpha_v * (2.0 * PI * r2).sin(),
+        alpha_u * (2.0 * PI * r2).cos(),
+    );
+    let slope = (phi.cos() / alpha_u).powi(2) + (phi.sin() / alpha_v).powi(2);
+    let tan_theta_squared = r1 / ((1.0 - r1) * slope);
+    let cos_theta = 1.0 / (1.0 + tan_theta_squared).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+    let half_vector =
+        phi.cos() * sin_theta * tangent + phi.sin() * sin_theta * bitangent + cos_theta * normal;
+
+    // The masking of a direction depends on the alpha in the plane it leaves the surface in
+    let projected_alpha = |direction: &Vector| {
+        let x = Vector::dot(direction, &tangent);
+        let y = Vector::dot(direction, &bitangent);
+        let sin_squared = x * x + y * y;
+        if sin_squared <= 0.0 {
+            return alpha_u;
+        }
+        ((x * x * alpha_u * alpha_u + y * y * alpha_v * alpha_v) / sin_squared).sqrt()
+    };
+    reflect(
+        |cosine| schlick_fresnel(albedo, cosine),
+        hit,
+        &normal,
+        &half_vector,
+        projected_alpha,
+    )
+}
+
+/// Mirrors the view direction about the sampled microfacet normal. `fresnel` gives the
+/// reflectance for the cosine to the microfacet and `alpha_along` gives the roughness seen by a
+/// direction for the masking term
+fn reflect<R, F>(
+    fresnel: R,
+    hit: &HitResult,
+    normal: &Vector,
+    half_vector: &Vector,
+    alpha_along: F,
+) -> Option<ScatterResult>
+where
+    R: Fn(Float) -> Colour,
+    F: Fn(&Vector) -> Float,
+{
+    let view = -hit.ray.direction().unit_vector();
+    let view_dot_half = Vector::dot(&view, half_vector);
+    let scattered = 2.0 * view_dot_half * half_vector - view;
+
+    let normal_dot_view = Vector::dot(normal, &view);
+    let normal_dot_scattered = Vector::dot(normal, &scattered);
+    let normal_dot_half = Vector::dot(normal, half_vector);
+    if normal_dot_view <= 0.0 || normal_dot_scattered <= 0.0 || view_dot_half <= 0.0 {
+        // The microfacet reflected the ray into the surface, treat it as absorbed
+        return None;
+    }
+
+    let geometry_term = smith_g1(alpha_along(&view), normal_dot_view)
+        * smith_g1(alpha_along(&scattered), normal_dot_scattered);
+    let weight = geometry_term * view_dot_half / (normal_dot_view * normal_dot_half);
+    let attenuation = weight * fresnel(view_dot_half);
+
+    let ray = Ray::new(hit.point, scattered, hit.ray.time());
+    Some(ScatterResult::specular(attenuation, ray))
+}
+
+/// Squaring the user facing roughness makes it perceptually closer to linear
+pub fn alpha(roughness: Float) -> Float {
+    (roughness * roughness).max(MIN_ALPHA)
+}
+
+/// Samples a microfacet normal proportional to D(h) * cos(θh) around the w axis of `onb`
+pub fn sample_half_vector(alpha: Float, onb: &Onb) -> Vector {
+    let r1 = uniform::<Float>();
+    let r2 = uniform::<Float>();
+
+    let phi = 2.0 * PI * r2;
+    let cos_theta = ((1.0 - r1) / (1.0 + (alpha * alpha - 1.0) * r1)).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+    onb.local_from_vec(&Vector::new(
+        phi.cos() * sin_theta,
+        phi.sin() * sin_theta,
+        cos_theta,
+    ))
+}
+
+/// The Smith masking function for a single direction with cosine `cosine` to the normal
+fn smith_g1(alpha: Float, cosine: Float) -> Float {
+    let alpha_squared = alpha * alpha;
+    2.0 * cosine / (cosine + (alpha_squared + (1.0 - alpha_squared) * cosine * cosine).sqrt())
+}
+
+/// Metals reflect their albedo head on and tend towards white at grazing angles
+fn schlick_fresnel(albedo: &Colour, cosine: Float) -> Colour {
+    let grazing = (1.0 - cosine).powi(5);
+    (1.0 - grazing) * albedo + grazing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::seed_thread_rng;
+    use crate::world::materials::Material;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn hit(roughness: Float) -> HitResult {
+        HitResult {
+            distance: 1.0,
+            ray: Ray::new(
+                Vector::new(-1.0, 1.0, 0.0),
+                Vector::new(1.0, -1.0, 0.0).unit_vector(),
+                0.0,
+            ),
+            point: Vector::new(0.0, 0.0, 0.0),
+            surface_normal: Vector::new(0.0, 1.0, 0.0),
+            material: Material::RoughMetal {
+                albedo: Colour::new(0.9, 0.6, 0.2),
+                roughness,
+            },
+            texture_coords: (0.0, 0.0),
+            texture_gradients: None,
+            light_group: None,
+        }
+    }
+
+    #[test]
+    fn test_scatter_stays_above_surface() {
+        let hit = hit(1.0);
+        for _ in 0..1000 {
+            match scatter(&Colour::new(0.9, 0.6, 0.2), 1.0, &hit) {
+                Some(ScatterResult::Specular { ray, .. }) => {
+                    assert!(Vector::dot(ray.direction(), &hit.surface_normal) > 0.0);
+                }
+                None => (),
+                _ => panic!("expected a specular scatter"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_roughness_is_a_mirror() {
+        seed_thread_rng(0);
+        let hit = hit(0.0);
+        let reflected = Vector::new(1.0, 1.0, 0.0).unit_vector();
+
+        for _ in 0..100 {
+            match scatter(&Colour::new(0.9, 0.6, 0.2), 0.0, &hit) {
+                Some(ScatterResult::Specular { ray, attenuation }) => {
+                    assert!((ray.direction().unit_vector() - reflected).len() < 0.01);
+                    assert_approx_eq!(attenuation.r(), 0.9, 0.01);
+                }
+                _ => panic!("expected a specular scatter"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_smith_g1() {
+        // A smooth surface does not mask anything
+        assert_approx_eq!(smith_g1(MIN_ALPHA, 0.5), 1.0);
+        assert!(smith_g1(1.0, 0.5) < smith_g1(0.5, 0.5));
+    }
+
+    #[test]
+    fn test_anisotropic_scatter_follows_the_rough_direction() {
+        seed_thread_rng(0);
+        let mut hit = hit(0.0);
+        hit.ray = Ray::new(Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0);
+        // Columns run along x and rows along z
+        hit.texture_gradients = Some((Vector::new(0.0, 0.0, 1.0), Vector::new(1.0, 0.0, 0.0)));
+
+        let albedo = Colour::new(0.9, 0.9, 0.9);
+        let mut spread_x: Float = 0.0;
+        let mut spread_z: Float = 0.0;
+        for _ in 0..1000 {
+            if let Some(ScatterResult::Specular { ray, .. }) =
+                scatter_anisotropic(&albedo, 0.05, 0.8, &hit)
+            {
+                let direction = ray.direction().unit_vector();
+                assert!(direction.y() > 0.0);
+                spread_x = spread_x.max(direction.x().abs());
+                spread_z = spread_z.max(direction.z().abs());
+            }
+        }
+
+        assert!(spread_x < 0.1);
+        assert!(spread_z > 0.5);
+    }
+}
@@ -2,14 +2,67 @@ use crate::camera::Ray;
 use crate::data::assets::Assets;
 use crate::data::colour::Colour;
 use crate::data::vector::Vector;
-use crate::pdf::Pdf;
+use crate::float::Float;
+use crate::pdf::{uniform, Pdf};
+use crate::spectrum::REFERENCE_WAVELENGTH;
 use crate::world::geometry::HitResult;
 use crate::world::texture::Texture;
+pub use thin_film::FilmBase;
 
+use thiserror::Error;
+
+mod clearcoat;
+pub mod conductor;
 mod dielectric;
 mod isotropic;
-mod lambertian;
+pub mod lambertian;
 mod metal;
+mod microfacet;
+mod normal_map;
+mod oren_nayar;
+mod subsurface;
+mod thin_film;
+
+#[derive(Debug, Error)]
+pub enum MaterialError {
+    #[error("dielectric absorption distance must be positive, got {distance}")]
+    InvalidAbsorptionDistance { distance: Float },
+    #[error("metal fuzz must not be negative, got {fuzz}")]
+    NegativeFuzz { fuzz: Float },
+    #[error("rough metal roughness must be between 0 and 1, got {roughness}")]
+    InvalidRoughness { roughness: Float },
+    #[error("conductor eta must be positive, got {eta:?}")]
+    InvalidConductorEta { eta: Colour },
+    #[error("conductor k must not be negative, got {k:?}")]
+    NegativeConductorK { k: Colour },
+    #[error("thin film thickness must not be negative, got {thickness_nm}nm")]
+    NegativeFilmThickness { thickness_nm: Float },
+    #[error("thin film refractive indices must be positive, got {film_index}")]
+    InvalidFilmIndex { film_index: Float },
+    #[error("mix factor must be between 0 and 1, got {factor}")]
+    InvalidMixFactor { factor: Float },
+    #[error("cauchy coefficients must be positive, got {cauchy_a} and {cauchy_b}")]
+    InvalidCauchy { cauchy_a: Float, cauchy_b: Float },
+    #[error("light strength must not be negative, got {strength}")]
+    NegativeStrength { strength: Float },
+    #[error("oren nayar roughness must not be negative, got {roughness}")]
+    NegativeOrenNayarRoughness { roughness: Float },
+    #[error("spotlight cone angles must satisfy 0 <= inner <= outer <= 180, got {inner_angle} and {outer_angle}")]
+    InvalidSpotlightAngles {
+        inner_angle: Float,
+        outer_angle: Float,
+    },
+    #[error("spotlight direction must not be the zero vector")]
+    ZeroSpotlightDirection(),
+    #[error("subsurface mean free path must be positive, got {mean_free_path}")]
+    InvalidMeanFreePath { mean_free_path: Float },
+    #[error("subsurface refractive index must be positive, got {refractive_index}")]
+    InvalidSubsurfaceIndex { refractive_index: Float },
+    #[error("cutout threshold must be between 0 and 1, got {threshold}")]
+    InvalidCutoutThreshold { threshold: Float },
+    #[error("clearcoat refractive index must be positive, got {clearcoat_ior}")]
+    InvalidClearcoatIndex { clearcoat_ior: Float },
+}
 
 pub enum ScatterResult {
     Specular {
@@ -32,31 +85,285 @@ impl ScatterResult {
     }
 }
 
+/// Beer-Lambert absorption for coloured glass: light that travels `distance` through the glass is
+/// tinted to `attenuation`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Absorption {
+    attenuation: Colour,
+    distance: Float,
+}
+
+impl Absorption {
+    pub fn new(attenuation: Colour, distance: Float) -> Absorption {
+        Absorption {
+            attenuation,
+            distance,
+        }
+    }
+
+    /// The fraction of light in each channel that survives travelling `distance` through the glass
+    pub fn transmittance(&self, distance: Float) -> Colour {
+        // exp(-absorbance * distance) where absorbance = -ln(attenuation) / self.distance
+        let exponent = distance / self.distance;
+        Colour::new(
+            self.attenuation.r().powf(exponent),
+            self.attenuation.g().powf(exponent),
+            self.attenuation.b().powf(exponent),
+        )
+    }
+}
+
+/// Limits a light to a cone around `direction`. The light is full strength within `inner_angle`
+/// degrees of it and fades out smoothly to nothing at `outer_angle`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Spotlight {
+    direction: Vector,
+    inner_angle: Float,
+    outer_angle: Float,
+}
+
+impl Spotlight {
+    /// How much of the light leaves in the `outgoing` direction
+    pub fn falloff(&self, outgoing: &Vector) -> Float {
+        let cosine = Vector::dot(&outgoing.unit_vector(), &self.direction.unit_vector());
+        let cos_inner = self.inner_angle.to_radians().cos();
+        let cos_outer = self.outer_angle.to_radians().cos();
+        if cosine >= cos_inner {
+            return 1.0;
+        }
+        if cosine <= cos_outer {
+            return 0.0;
+        }
+
+        let t = (cosine - cos_outer) / (cos_inner - cos_outer);
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    fn validate(&self) -> Result<(), MaterialError> {
+        if !(0.0 <= self.inner_angle
+            && self.inner_angle <= self.outer_angle
+            && self.outer_angle <= 180.0)
+        {
+            return Err(MaterialError::InvalidSpotlightAngles {
+                inner_angle: self.inner_angle,
+                outer_angle: self.outer_angle,
+            });
+        }
+        if self.direction.len_squared() == 0.0 {
+            return Err(MaterialError::ZeroSpotlightDirection());
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Material {
     Lambertian {
         albedo: Texture,
     },
+    OrenNayar {
+        albedo: Texture,
+        // Standard deviation of the microfacet angles in radians, 0 is lambertian
+        roughness: Float,
+    },
     Metal {
         albedo: Colour,
-        fuzz: f64,
+        // Clamped to [0, 1] when scattering
+        fuzz: Float,
+    },
+    RoughMetal {
+        albedo: Colour,
+        // 0 is a perfect mirror, 1 is very rough
+        roughness: Float,
+    },
+    // A rough metal with the complex index of refraction eta + ik given per colour channel. A
+    // `preset` such as `Gold` can be given in place of eta and k, see `ConductorPreset`
+    #[serde(deserialize_with = "conductor::deserialize_conductor")]
+    Conductor {
+        eta: Colour,
+        k: Colour,
+        // Between 0 and 1 like rough metal, 0 if not given
+        roughness: Float,
+    },
+    // Brushed metal, rough along one direction of the texture coords and smooth along the other
+    AnisotropicMetal {
+        albedo: Colour,
+        // Along increasing texture columns, between 0 and 1 like rough metal
+        roughness_u: Float,
+        // Along increasing texture rows
+        roughness_v: Float,
     },
     Dielectric {
         // Air: 1.0, Glass: 1.3-1.7, Diamond: 2.4
-        refractive_index: f64,
+        refractive_index: Float,
+        // Clear glass when not set
+        #[serde(default)]
+        absorption: Option<Absorption>,
+        // 0 is smooth glass, higher values give a frosted look
+        #[serde(default)]
+        roughness: Float,
+    },
+    // Glass whose refractive index changes with wavelength by Cauchy's equation
+    // n = cauchy_a + cauchy_b / λ² with λ in micrometres. Crown glass: 1.5046 and 0.0042,
+    // Flint glass: 1.6700 and 0.00743. Light is only split into colours by spectral renders
+    DispersiveDielectric {
+        cauchy_a: Float,
+        cauchy_b: Float,
+        #[serde(default)]
+        absorption: Option<Absorption>,
+        #[serde(default)]
+        roughness: Float,
+    },
+    // An iridescent film such as soap or oil coated onto a smooth base
+    ThinFilm {
+        // Visible colours appear for films up to a micrometre or so thick
+        thickness_nm: Float,
+        // Soap and water: 1.33, Oil: 1.4-1.5
+        film_index: Float,
+        base: FilmBase,
     },
     DiffuseLight {
         emit: Texture,
+        // Scales the emitted colour so the power of a light can be set independently of its colour
+        #[serde(default = "default_strength")]
+        strength: Float,
+        // Only the front face emits light unless set
+        #[serde(default)]
+        two_sided: bool,
+        // Emits in every direction when not set
+        #[serde(default)]
+        spotlight: Option<Spotlight>,
     },
     Isotropic {
         albedo: Texture,
     },
+    // Translucent materials such as wax, marble and skin that light travels a little way into
+    // before coming back out, tinted by `albedo` every time it scatters inside
+    Subsurface {
+        albedo: Texture,
+        // The average distance light travels inside before scattering
+        mean_free_path: Float,
+        // Wax and skin: about 1.4
+        #[serde(default = "default_subsurface_index")]
+        refractive_index: Float,
+    },
+    // Bends the surface normal seen by another material with a tangent space normal map
+    NormalMapped {
+        material: Box<Material>,
+        normal_map: Texture,
+    },
+    // Each ray uses `b` with probability `factor` and `a` otherwise, such as a dusty metal
+    Mix {
+        a: Box<Material>,
+        b: Box<Material>,
+        factor: Float,
+    },
+    // Holes are cut out of another material wherever the brightness of `alpha` is below
+    // `threshold` and rays pass straight through them, such as the gaps around leaves. Images
+    // with an alpha channel provide it as an asset named like `leaf.png:alpha`
+    Cutout {
+        material: Box<Material>,
+        alpha: Texture,
+        #[serde(default = "default_cutout_threshold")]
+        threshold: Float,
+    },
+    // A thin varnish over another material, such as car paint or lacquered wood, that reflects
+    // more and more of the light towards grazing angles
+    Coated {
+        base: Box<Material>,
+        // Between 0 and 1 like rough metal, 0 is a smooth gloss
+        #[serde(default)]
+        clearcoat_roughness: Float,
+        // Lacquer and varnish: about 1.5
+        #[serde(default = "default_clearcoat_ior")]
+        clearcoat_ior: Float,
+    },
+}
+
+fn default_strength() -> Float {
+    1.0
+}
+
+fn default_cutout_threshold() -> Float {
+    0.5
+}
+
+fn default_clearcoat_ior() -> Float {
+    1.5
+}
+
+fn default_subsurface_index() -> Float {
+    1.4
 }
 
 impl Material {
-    pub fn scattering_pdf(&self, surface_normal: &Vector, scattered: &Ray) -> f64 {
+    /// Mixed materials pick one of their sub-materials for each ray. The scatter and its
+    /// scattering pdf have to come from the same pick, so the renderer selects once per hit
+    pub fn select(self) -> Material {
         match self {
-            Material::Lambertian { .. } => lambertian::scattering_pdf(surface_normal, scattered),
+            Material::Mix { a, b, factor } => {
+                if uniform::<Float>() < factor {
+                    b.select()
+                } else {
+                    a.select()
+                }
+            }
+            // Only hits that are not in a hole get as far as scattering
+            Material::Cutout { material, .. } => material.select(),
+            // Whether the coat reflects depends on the angle of the ray, so that is picked when
+            // scattering
+            Material::Coated {
+                base,
+                clearcoat_roughness,
+                clearcoat_ior,
+            } => Material::Coated {
+                base: Box::from(base.select()),
+                clearcoat_roughness,
+                clearcoat_ior,
+            },
+            material => material,
+        }
+    }
+
+    /// Whether the hit is in a hole cut out of the surface, which rays carry on through
+    pub fn is_cut_out(&self, hit: &HitResult, assets: &Assets) -> bool {
+        match self {
+            Material::Cutout {
+                alpha, threshold, ..
+            } => {
+                alpha
+                    .value(hit.texture_coords, &hit.point, assets)
+                    .luminance()
+                    < *threshold
+            }
+            _ => false,
+        }
+    }
+
+    pub fn scattering_pdf(&self, hit: &HitResult, scattered: &Ray, assets: &Assets) -> Float {
+        match self {
+            Material::Lambertian { .. } => {
+                lambertian::scattering_pdf(&hit.face_normal(), scattered)
+            }
+            Material::OrenNayar { roughness, .. } => {
+                oren_nayar::scattering_pdf(*roughness, hit, scattered)
+            }
+            Material::Subsurface { .. } => subsurface::scattering_pdf(hit, scattered),
+            Material::NormalMapped {
+                material,
+                normal_map,
+            } => {
+                let hit = normal_map::perturbed_hit(normal_map, hit, assets);
+                material.scattering_pdf(&hit, scattered, assets)
+            }
+            // Without a selected sub-material the best that can be done is the average
+            Material::Mix { a, b, factor } => {
+                (1.0 - factor) * a.scattering_pdf(hit, scattered, assets)
+                    + factor * b.scattering_pdf(hit, scattered, assets)
+            }
+            Material::Cutout { material, .. } => material.scattering_pdf(hit, scattered, assets),
+            // The coat only reflects specularly, so diffuse scattering always comes from the base
+            Material::Coated { base, .. } => base.scattering_pdf(hit, scattered, assets),
             _ => 1.0,
         }
     }
@@ -64,28 +371,126 @@ impl Material {
     pub fn scatter(&self, hit: &HitResult, assets: &Assets) -> Option<ScatterResult> {
         match self {
             Material::Lambertian { albedo } => lambertian::scatter(&albedo, hit, assets),
+            Material::OrenNayar { albedo, .. } => lambertian::scatter(albedo, hit, assets),
             Material::Metal { albedo, fuzz } => metal::scatter(&albedo, *fuzz, hit),
-            Material::Dielectric { refractive_index } => {
-                dielectric::scatter(*refractive_index, hit)
+            Material::RoughMetal { albedo, roughness } => {
+                microfacet::scatter(albedo, *roughness, hit)
+            }
+            Material::Conductor { eta, k, roughness } => {
+                microfacet::scatter_conductor(eta, k, *roughness, hit)
+            }
+            Material::AnisotropicMetal {
+                albedo,
+                roughness_u,
+                roughness_v,
+            } => microfacet::scatter_anisotropic(albedo, *roughness_u, *roughness_v, hit),
+            Material::Dielectric {
+                refractive_index,
+                absorption,
+                roughness,
+            } => dielectric::scatter(*refractive_index, absorption, *roughness, hit),
+            Material::DispersiveDielectric {
+                cauchy_a,
+                cauchy_b,
+                absorption,
+                roughness,
+            } => {
+                let wavelength = hit.ray.wavelength().unwrap_or(REFERENCE_WAVELENGTH);
+                let refractive_index = dielectric::cauchy(*cauchy_a, *cauchy_b, wavelength);
+                dielectric::scatter(refractive_index, absorption, *roughness, hit)
             }
+            Material::ThinFilm {
+                thickness_nm,
+                film_index,
+                base,
+            } => thin_film::scatter(*thickness_nm, *film_index, base, hit),
             Material::DiffuseLight { .. } => None,
             Material::Isotropic { albedo } => isotropic::scatter(&albedo, hit, assets),
+            Material::Subsurface {
+                albedo,
+                mean_free_path,
+                refractive_index,
+            } => subsurface::scatter(albedo, *mean_free_path, *refractive_index, hit, assets),
+            Material::NormalMapped {
+                material,
+                normal_map,
+            } => {
+                let hit = normal_map::perturbed_hit(normal_map, hit, assets);
+                material.scatter(&hit, assets)
+            }
+            Material::Mix { a, b, factor } => pick(a, b, *factor).scatter(hit, assets),
+            Material::Cutout { material, .. } => material.scatter(hit, assets),
+            Material::Coated {
+                base,
+                clearcoat_roughness,
+                clearcoat_ior,
+            } => clearcoat::scatter(base, *clearcoat_roughness, *clearcoat_ior, hit, assets),
+        }
+    }
+
+    /// The base colour of the surface, used for the albedo pass. Glass is white so it does not
+    /// darken whatever is seen through it
+    pub fn albedo(&self, hit: &HitResult, assets: &Assets) -> Colour {
+        match self {
+            Material::Lambertian { albedo }
+            | Material::OrenNayar { albedo, .. }
+            | Material::Isotropic { albedo }
+            | Material::Subsurface { albedo, .. } => {
+                albedo.value(hit.texture_coords, &hit.point, assets)
+            }
+            Material::Metal { albedo, .. }
+            | Material::RoughMetal { albedo, .. }
+            | Material::AnisotropicMetal { albedo, .. } => *albedo,
+            Material::Conductor { eta, k, .. } => conductor::fresnel(eta, k, 1.0),
+            Material::Dielectric { .. } | Material::DispersiveDielectric { .. } => {
+                Colour::new(1.0, 1.0, 1.0)
+            }
+            Material::ThinFilm { base, .. } => base.albedo(),
+            Material::DiffuseLight { emit, .. } => {
+                emit.value(hit.texture_coords, &hit.point, assets)
+            }
+            Material::NormalMapped { material, .. } => material.albedo(hit, assets),
+            Material::Mix { a, b, factor } => {
+                (1.0 - factor) * a.albedo(hit, assets) + *factor * b.albedo(hit, assets)
+            }
+            Material::Cutout { material, .. } => material.albedo(hit, assets),
+            Material::Coated { base, .. } => base.albedo(hit, assets),
         }
     }
 
+    /// The light given off at `point` in the `outgoing` direction, back along the ray that hit it
     pub fn emitted(
         &self,
         front_face: bool,
-        texture_coords: (f64, f64),
+        texture_coords: (Float, Float),
         point: &Vector,
+        outgoing: &Vector,
         assets: &Assets,
     ) -> Colour {
-        if !front_face {
-            return Colour::new(0.0, 0.0, 0.0);
-        }
-
         match self {
-            Material::DiffuseLight { emit } => emit.value(texture_coords, point, assets),
+            Material::DiffuseLight {
+                emit,
+                strength,
+                two_sided,
+                spotlight,
+            } if front_face || *two_sided => {
+                let falloff = spotlight
+                    .as_ref()
+                    .map_or(1.0, |spotlight| spotlight.falloff(outgoing));
+                (strength * falloff) * emit.value(texture_coords, point, assets)
+            }
+            Material::NormalMapped { material, .. } => {
+                material.emitted(front_face, texture_coords, point, outgoing, assets)
+            }
+            Material::Mix { a, b, factor } => {
+                pick(a, b, *factor).emitted(front_face, texture_coords, point, outgoing, assets)
+            }
+            Material::Cutout { material, .. } => {
+                material.emitted(front_face, texture_coords, point, outgoing, assets)
+            }
+            Material::Coated { base, .. } => {
+                base.emitted(front_face, texture_coords, point, outgoing, assets)
+            }
             _ => Colour::new(0.0, 0.0, 0.0),
         }
     }
@@ -96,6 +501,193 @@ impl Material {
                 albedo.validate(assets)?;
                 Ok(())
             }
+            Material::OrenNayar { albedo, roughness } => {
+                if *roughness < 0.0 {
+                    return Err(MaterialError::NegativeOrenNayarRoughness {
+                        roughness: *roughness,
+                    }
+                    .into());
+                }
+                albedo.validate(assets)
+            }
+            Material::DiffuseLight {
+                emit,
+                strength,
+                spotlight,
+                ..
+            } => {
+                if *strength < 0.0 {
+                    return Err(MaterialError::NegativeStrength {
+                        strength: *strength,
+                    }
+                    .into());
+                }
+                if let Some(spotlight) = spotlight {
+                    spotlight.validate()?;
+                }
+                emit.validate(assets)
+            }
+            Material::Metal { fuzz, .. } => {
+                if *fuzz < 0.0 {
+                    return Err(MaterialError::NegativeFuzz { fuzz: *fuzz }.into());
+                }
+                Ok(())
+            }
+            Material::RoughMetal { roughness, .. } => {
+                if !(0.0..=1.0).contains(roughness) {
+                    return Err(MaterialError::InvalidRoughness {
+                        roughness: *roughness,
+                    }
+                    .into());
+                }
+                Ok(())
+            }
+            Material::Conductor { eta, k, roughness } => {
+                if !(0.0..=1.0).contains(roughness) {
+                    return Err(MaterialError::InvalidRoughness {
+                        roughness: *roughness,
+                    }
+                    .into());
+                }
+                if eta.r() <= 0.0 || eta.g() <= 0.0 || eta.b() <= 0.0 {
+                    return Err(MaterialError::InvalidConductorEta { eta: *eta }.into());
+                }
+                if k.r() < 0.0 || k.g() < 0.0 || k.b() < 0.0 {
+                    return Err(MaterialError::NegativeConductorK { k: *k }.into());
+                }
+                Ok(())
+            }
+            Material::AnisotropicMetal {
+                roughness_u,
+                roughness_v,
+                ..
+            } => {
+                for roughness in &[roughness_u, roughness_v] {
+                    if !(0.0..=1.0).contains(*roughness) {
+                        return Err(MaterialError::InvalidRoughness {
+                            roughness: **roughness,
+                        }
+                        .into());
+                    }
+                }
+                Ok(())
+            }
+            Material::DispersiveDielectric {
+                cauchy_a, cauchy_b, ..
+            } if *cauchy_a <= 0.0 || *cauchy_b < 0.0 => Err(MaterialError::InvalidCauchy {
+                cauchy_a: *cauchy_a,
+                cauchy_b: *cauchy_b,
+            }
+            .into()),
+            Material::Dielectric {
+                absorption: Some(absorption),
+                ..
+            }
+            | Material::DispersiveDielectric {
+                absorption: Some(absorption),
+                ..
+            } => {
+                if absorption.distance <= 0.0 {
+                    return Err(MaterialError::InvalidAbsorptionDistance {
+                        distance: absorption.distance,
+                    }
+                    .into());
+                }
+                Ok(())
+            }
+            Material::ThinFilm {
+                thickness_nm,
+                film_index,
+                base,
+            } => {
+                if *thickness_nm < 0.0 {
+                    return Err(MaterialError::NegativeFilmThickness {
+                        thickness_nm: *thickness_nm,
+                    }
+                    .into());
+                }
+                if *film_index <= 0.0 {
+                    return Err(MaterialError::InvalidFilmIndex {
+                        film_index: *film_index,
+                    }
+                    .into());
+                }
+                match base {
+                    FilmBase::Dielectric { refractive_index } if *refractive_index <= 0.0 => {
+                        Err(MaterialError::InvalidFilmIndex {
+                            film_index: *refractive_index,
+                        }
+                        .into())
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Material::Subsurface {
+                albedo,
+                mean_free_path,
+                refractive_index,
+            } => {
+                if *mean_free_path <= 0.0 {
+                    return Err(MaterialError::InvalidMeanFreePath {
+                        mean_free_path: *mean_free_path,
+                    }
+                    .into());
+                }
+                if *refractive_index <= 0.0 {
+                    return Err(MaterialError::InvalidSubsurfaceIndex {
+                        refractive_index: *refractive_index,
+                    }
+                    .into());
+                }
+                albedo.validate(assets)
+            }
+            Material::NormalMapped {
+                material,
+                normal_map,
+            } => {
+                normal_map.validate(assets)?;
+                material.validate(assets)
+            }
+            Material::Mix { a, b, factor } => {
+                if !(0.0..=1.0).contains(factor) {
+                    return Err(MaterialError::InvalidMixFactor { factor: *factor }.into());
+                }
+                a.validate(assets)?;
+                b.validate(assets)
+            }
+            Material::Cutout {
+                material,
+                alpha,
+                threshold,
+            } => {
+                if !(0.0..=1.0).contains(threshold) {
+                    return Err(MaterialError::InvalidCutoutThreshold {
+                        threshold: *threshold,
+                    }
+                    .into());
+                }
+                alpha.validate(assets)?;
+                material.validate(assets)
+            }
+            Material::Coated {
+                base,
+                clearcoat_roughness,
+                clearcoat_ior,
+            } => {
+                if !(0.0..=1.0).contains(clearcoat_roughness) {
+                    return Err(MaterialError::InvalidRoughness {
+                        roughness: *clearcoat_roughness,
+                    }
+                    .into());
+                }
+                if *clearcoat_ior <= 0.0 {
+                    return Err(MaterialError::InvalidClearcoatIndex {
+                        clearcoat_ior: *clearcoat_ior,
+                    }
+                    .into());
+                }
+                base.validate(assets)
+            }
             _ => Ok(()),
         }
     }
@@ -104,7 +696,258 @@ impl Material {
         match self {
             Material::DiffuseLight { .. } => true,
             Material::Dielectric { .. } => true,
+            Material::DispersiveDielectric { .. } => true,
+            Material::ThinFilm { base, .. } => matches!(base, FilmBase::Dielectric { .. }),
+            Material::NormalMapped { material, .. } => material.is_attractor(),
+            Material::Mix { a, b, .. } => a.is_attractor() || b.is_attractor(),
+            Material::Cutout { material, .. } => material.is_attractor(),
+            Material::Coated { base, .. } => base.is_attractor(),
+            _ => false,
+        }
+    }
+
+    pub fn emits_light(&self) -> bool {
+        match self {
+            Material::DiffuseLight { .. } => true,
+            Material::NormalMapped { material, .. } => material.emits_light(),
+            Material::Mix { a, b, .. } => a.emits_light() || b.emits_light(),
+            Material::Cutout { material, .. } => material.emits_light(),
+            Material::Coated { base, .. } => base.emits_light(),
             _ => false,
         }
     }
 }
+
+fn pick<'a>(a: &'a Material, b: &'a Material, factor: Float) -> &'a Material {
+    if uniform::<Float>() < factor {
+        b
+    } else {
+        a
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn light(strength: Float, two_sided: bool) -> Material {
+        Material::DiffuseLight {
+            emit: Texture::Constant {
+                colour: Colour::new(1.0, 0.5, 0.25),
+            },
+            strength,
+            two_sided,
+            spotlight: None,
+        }
+    }
+
+    fn spotlight(inner_angle: Float, outer_angle: Float) -> Material {
+        Material::DiffuseLight {
+            emit: Texture::Constant {
+                colour: Colour::new(1.0, 1.0, 1.0),
+            },
+            strength: 2.0,
+            two_sided: false,
+            spotlight: Some(Spotlight {
+                direction: Vector::new(0.0, -3.0, 0.0),
+                inner_angle,
+                outer_angle,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_emitted_strength() {
+        let assets = Assets::new(&[]).unwrap();
+        let point = Vector::new(0.0, 0.0, 0.0);
+        let up = Vector::new(0.0, 1.0, 0.0);
+
+        let emitted = light(4.0, false).emitted(true, (0.0, 0.0), &point, &up, &assets);
+        assert_eq!(emitted, Colour::new(4.0, 2.0, 1.0));
+    }
+
+    #[test]
+    fn test_emitted_two_sided() {
+        let assets = Assets::new(&[]).unwrap();
+        let point = Vector::new(0.0, 0.0, 0.0);
+        let up = Vector::new(0.0, 1.0, 0.0);
+
+        let emitted = light(1.0, false).emitted(false, (0.0, 0.0), &point, &up, &assets);
+        assert_eq!(emitted, Colour::new(0.0, 0.0, 0.0));
+
+        let emitted = light(1.0, true).emitted(false, (0.0, 0.0), &point, &up, &assets);
+        assert_eq!(emitted, Colour::new(1.0, 0.5, 0.25));
+    }
+
+    #[test]
+    fn test_light_defaults() {
+        let light: Material = serde_yaml::from_str(
+            "DiffuseLight:\n  emit:\n    Constant:\n      colour:\n        r: 1.0\n        g: 1.0\n        b: 1.0\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            light,
+            Material::DiffuseLight {
+                emit: Texture::Constant {
+                    colour: Colour::new(1.0, 1.0, 1.0),
+                },
+                strength: 1.0,
+                two_sided: false,
+                spotlight: None,
+            }
+        );
+        assert!(Material::DiffuseLight {
+            emit: Texture::Constant {
+                colour: Colour::new(1.0, 1.0, 1.0),
+            },
+            strength: -1.0,
+            two_sided: false,
+            spotlight: None,
+        }
+        .validate(&Assets::new(&[]).unwrap())
+        .is_err());
+    }
+
+    #[test]
+    fn test_spotlight_cone() {
+        let assets = Assets::new(&[]).unwrap();
+        let point = Vector::new(0.0, 0.0, 0.0);
+        let light = spotlight(20.0, 40.0);
+        let brightness = |degrees: Float| {
+            let radians = degrees.to_radians();
+            let outgoing = Vector::new(radians.sin(), -radians.cos(), 0.0);
+            light
+                .emitted(true, (0.0, 0.0), &point, &outgoing, &assets)
+                .r()
+        };
+
+        assert_approx_eq!(brightness(0.0), 2.0);
+        assert_approx_eq!(brightness(19.0), 2.0);
+        // Fades smoothly between the cones
+        assert!(brightness(25.0) < 2.0 && brightness(25.0) > brightness(35.0));
+        assert!(brightness(35.0) > 0.0);
+        assert_approx_eq!(brightness(41.0), 0.0);
+        assert_approx_eq!(brightness(120.0), 0.0);
+    }
+
+    #[test]
+    fn test_spotlight_validation() {
+        let assets = Assets::new(&[]).unwrap();
+        assert!(spotlight(20.0, 40.0).validate(&assets).is_ok());
+        assert!(spotlight(30.0, 30.0).validate(&assets).is_ok());
+        assert!(spotlight(40.0, 20.0).validate(&assets).is_err());
+        assert!(spotlight(-5.0, 20.0).validate(&assets).is_err());
+        assert!(spotlight(20.0, 200.0).validate(&assets).is_err());
+    }
+
+    #[test]
+    fn test_cutout() {
+        let assets = Assets::new(&[]).unwrap();
+        let cutout = |threshold: Float| Material::Cutout {
+            material: Box::from(light(2.0, false)),
+            alpha: Texture::Constant {
+                colour: Colour::new(0.3, 0.3, 0.3),
+            },
+            threshold,
+        };
+        let hit = HitResult {
+            distance: 1.0,
+            ray: Ray::new(Vector::new(0.0, 1.0, 0.0), Vector::new(0.0, -1.0, 0.0), 0.0),
+            point: Vector::new(0.0, 0.0, 0.0),
+            surface_normal: Vector::new(0.0, 1.0, 0.0),
+            material: cutout(0.5),
+            texture_coords: (0.0, 0.0),
+            texture_gradients: None,
+            light_group: None,
+        };
+
+        assert!(cutout(0.5).is_cut_out(&hit, &assets));
+        assert!(!cutout(0.2).is_cut_out(&hit, &assets));
+        assert!(!light(2.0, false).is_cut_out(&hit, &assets));
+        assert_eq!(cutout(0.2).select(), light(2.0, false));
+        assert!(cutout(0.2).emits_light());
+
+        assert!(cutout(0.2).validate(&assets).is_ok());
+        assert!(cutout(1.5).validate(&assets).is_err());
+    }
+
+    #[test]
+    fn test_coated_validates_its_base() {
+        let assets = Assets::new(&[]).unwrap();
+        let coated =
+            |base: Material, clearcoat_roughness: Float, clearcoat_ior: Float| Material::Coated {
+                base: Box::from(base),
+                clearcoat_roughness,
+                clearcoat_ior,
+            };
+        let red = Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.8, 0.1, 0.1),
+            },
+        };
+        let fuzzed = Material::Metal {
+            albedo: Colour::new(0.9, 0.9, 0.9),
+            fuzz: -1.0,
+        };
+
+        assert!(coated(red.clone(), 0.1, 1.5).validate(&assets).is_ok());
+        assert!(coated(red.clone(), 1.5, 1.5).validate(&assets).is_err());
+        assert!(coated(red, 0.1, 0.0).validate(&assets).is_err());
+        assert!(coated(fuzzed, 0.1, 1.5).validate(&assets).is_err());
+
+        // A smooth varnish by default
+        let saved = "
+Coated:
+  base:
+    Metal:
+      albedo: {r: 1, g: 1, b: 1}
+      fuzz: 0
+";
+        let defaults: Material = serde_yaml::from_str(saved).unwrap();
+        assert_eq!(
+            defaults,
+            coated(
+                Material::Metal {
+                    albedo: Colour::new(1.0, 1.0, 1.0),
+                    fuzz: 0.0,
+                },
+                0.0,
+                1.5
+            )
+        );
+    }
+
+    #[test]
+    fn test_mix_factor_zero_is_the_first_material() {
+        let assets = Assets::new(&[]).unwrap();
+        let point = Vector::new(0.0, 0.0, 0.0);
+        let up = Vector::new(0.0, 1.0, 0.0);
+        let mix = Material::Mix {
+            a: Box::from(light(2.0, false)),
+            b: Box::from(Material::Metal {
+                albedo: Colour::new(1.0, 1.0, 1.0),
+                fuzz: 0.0,
+            }),
+            factor: 0.0,
+        };
+
+        for _ in 0..100 {
+            assert_eq!(mix.clone().select(), light(2.0, false));
+            assert_eq!(
+                mix.emitted(true, (0.0, 0.0), &point, &up, &assets),
+                Colour::new(2.0, 1.0, 0.5)
+            );
+        }
+        assert!(mix.is_attractor());
+        assert!(mix.validate(&assets).is_ok());
+
+        let invalid = Material::Mix {
+            a: Box::from(light(1.0, false)),
+            b: Box::from(light(1.0, true)),
+            factor: 1.5,
+        };
+        assert!(invalid.validate(&assets).is_err());
+    }
+}
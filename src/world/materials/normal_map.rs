@@ -0,0 +1,137 @@
+use crate::data::assets::Assets;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::onb::Onb;
+use crate::world::geometry::HitResult;
+use crate::world::texture::Texture;
+
+// Texture gradients shorter than this are too close to the normal to build a tangent from
+const DEGENERATE_TANGENT: Float = 1e-12;
+
+/// Replaces the surface normal of the hit with the one read from the normal map. Texels are
+/// tangent space normals with red pointing along increasing texture columns, green along
+/// increasing texture rows and blue out of the surface
+pub fn perturbed_hit(normal_map: &Texture, hit: &HitResult, assets: &Assets) -> HitResult {
+    let normal = hit.surface_normal.unit_vector();
+    let texel = normal_map.value(hit.texture_coords, &hit.point, assets);
+    let local = Vector::new(
+        2.0 * texel.r() - 1.0,
+        2.0 * texel.g() - 1.0,
+        2.0 * texel.b() - 1.0,
+    );
+
+    let (tangent, bitangent) = tangent_frame(&normal, hit.texture_gradients);
+    let perturbed = local.x() * tangent + local.y() * bitangent + local.z() * normal;
+
+    // A normal bent past the ray would flip which side of the surface was hit
+    let side = Vector::dot(hit.ray.direction(), &normal);
+    let perturbed_side = Vector::dot(hit.ray.direction(), &perturbed);
+    if perturbed.len_squared() == 0.0 || side * perturbed_side <= 0.0 {
+        return hit.clone();
+    }
+
+    HitResult {
+        surface_normal: perturbed.unit_vector(),
+        ..hit.clone()
+    }
+}
+
+/// An orthonormal tangent and bitangent around the normal that follow the texture columns and
+/// rows. Without texture gradients the frame is arbitrary
+pub fn tangent_frame(
+    normal: &Vector,
+    texture_gradients: Option<(Vector, Vector)>,
+) -> (Vector, Vector) {
+    let arbitrary = || {
+        let onb = Onb::build_from_w(normal);
+        (*onb.u(), *onb.v())
+    };
+
+    let (row_gradient, col_gradient) = match texture_gradients {
+        Some(gradients) => gradients,
+        None => return arbitrary(),
+    };
+
+    let tangent = col_gradient - Vector::dot(&col_gradient, normal) * normal;
+    if tangent.len_squared() < DEGENERATE_TANGENT {
+        return arbitrary();
+    }
+    let tangent = tangent.unit_vector();
+
+    // Mirrored texture coords run the rows the other way around the normal
+    let bitangent = Vector::cross(normal, &tangent);
+    if Vector::dot(&bitangent, &row_gradient) < 0.0 {
+        (tangent, -bitangent)
+    } else {
+        (tangent, bitangent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Ray;
+    use crate::data::colour::Colour;
+    use crate::world::materials::Material;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn hit(texture_gradients: Option<(Vector, Vector)>) -> HitResult {
+        HitResult {
+            distance: 1.0,
+            ray: Ray::new(Vector::new(0.0, 0.0, 1.0), Vector::new(0.0, 0.0, -1.0), 0.0),
+            point: Vector::new(0.0, 0.0, 0.0),
+            surface_normal: Vector::new(0.0, 0.0, 1.0),
+            material: Material::Metal {
+                albedo: Colour::new(1.0, 1.0, 1.0),
+                fuzz: 0.0,
+            },
+            texture_coords: (0.5, 0.5),
+            texture_gradients,
+            light_group: None,
+        }
+    }
+
+    fn normal_map(r: Float, g: Float, b: Float) -> Texture {
+        Texture::Constant {
+            colour: Colour::new(r, g, b),
+        }
+    }
+
+    #[test]
+    fn test_flat_normal_map_keeps_the_normal() {
+        let assets = Assets::new(&[]).unwrap();
+        let gradients = Some((Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0)));
+
+        let perturbed = perturbed_hit(&normal_map(0.5, 0.5, 1.0), &hit(gradients), &assets);
+        assert_approx_eq!(perturbed.surface_normal.z(), 1.0);
+    }
+
+    #[test]
+    fn test_normal_map_follows_texture_gradients() {
+        let assets = Assets::new(&[]).unwrap();
+        // Tilted towards increasing texture columns
+        let tilted = normal_map(1.0, 0.5, 1.0);
+
+        let gradients = Some((Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0)));
+        let perturbed = perturbed_hit(&tilted, &hit(gradients), &assets);
+        assert_approx_eq!(perturbed.surface_normal.len(), 1.0);
+        assert!(perturbed.surface_normal.x() > 0.5);
+        assert_approx_eq!(perturbed.surface_normal.y(), 0.0);
+
+        // Tilted towards increasing texture rows, which run down -x here
+        let tilted = normal_map(0.5, 1.0, 1.0);
+        let gradients = Some((Vector::new(-1.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0)));
+        let perturbed = perturbed_hit(&tilted, &hit(gradients), &assets);
+        assert!(perturbed.surface_normal.x() < -0.5);
+    }
+
+    #[test]
+    fn test_normal_map_never_flips_the_side_hit() {
+        let assets = Assets::new(&[]).unwrap();
+        let gradients = Some((Vector::new(0.0, 1.0, 0.0), Vector::new(1.0, 0.0, 0.0)));
+
+        // Points straight along the tangent, into the plane of the surface
+        let perturbed = perturbed_hit(&normal_map(1.0, 0.5, 0.5), &hit(gradients), &assets);
+        assert_eq!(perturbed.surface_normal, Vector::new(0.0, 0.0, 1.0));
+    }
+}
@@ -0,0 +1,107 @@
+//! Rough diffuse surfaces using the Oren-Nayar reflectance model
+//!
+//! Rays are generated exactly like for a lambertian surface. Only the scattering pdf changes with
+//! the angles to the viewer and to the light so rough surfaces reflect more light back towards
+//! the light source.
+
+use crate::camera::Ray;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::world::geometry::HitResult;
+use crate::world::materials::lambertian;
+
+pub fn scattering_pdf(roughness: Float, hit: &HitResult, scattered: &Ray) -> Float {
+    let normal = hit.face_normal();
+    let lambertian_pdf = lambertian::scattering_pdf(&normal, scattered);
+    if lambertian_pdf <= 0.0 {
+        return 0.0;
+    }
+
+    let view = -hit.ray.direction().unit_vector();
+    let light = scattered.direction().unit_vector();
+
+    let (a, b) = coefficients(roughness);
+    let cos_view = Vector::dot(&normal, &view).clamp(-1.0, 1.0);
+    let cos_light = Vector::dot(&normal, &light).clamp(-1.0, 1.0);
+    let theta_view = cos_view.acos();
+    let theta_light = cos_light.acos();
+    let alpha = theta_view.max(theta_light);
+    let beta = theta_view.min(theta_light);
+
+    // Cosine of the azimuthal angle between the two directions projected onto the surface
+    let view_tangent = view - cos_view * normal;
+    let light_tangent = light - cos_light * normal;
+    let tangent_lengths = view_tangent.len() * light_tangent.len();
+    let cos_phi = if tangent_lengths > 0.0 {
+        Vector::dot(&view_tangent, &light_tangent) / tangent_lengths
+    } else {
+        0.0
+    };
+
+    lambertian_pdf * (a + b * cos_phi.max(0.0) * alpha.sin() * beta.tan())
+}
+
+/// The A and B terms of the qualitative Oren-Nayar model for a roughness of sigma radians
+fn coefficients(roughness: Float) -> (Float, Float) {
+    let sigma_squared = roughness * roughness;
+    let a = 1.0 - 0.5 * sigma_squared / (sigma_squared + 0.33);
+    let b = 0.45 * sigma_squared / (sigma_squared + 0.09);
+    (a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::Colour;
+    use crate::world::materials::Material;
+    use crate::world::texture::Texture;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn hit(roughness: Float) -> HitResult {
+        HitResult {
+            distance: 1.0,
+            ray: Ray::new(
+                Vector::new(-1.0, 1.0, 0.0),
+                Vector::new(1.0, -1.0, 0.0).unit_vector(),
+                0.0,
+            ),
+            point: Vector::new(0.0, 0.0, 0.0),
+            surface_normal: Vector::new(0.0, 1.0, 0.0),
+            material: Material::OrenNayar {
+                albedo: Texture::Constant {
+                    colour: Colour::new(0.5, 0.5, 0.5),
+                },
+                roughness,
+            },
+            texture_coords: (0.0, 0.0),
+            texture_gradients: None,
+            light_group: None,
+        }
+    }
+
+    #[test]
+    fn test_zero_roughness_is_lambertian() {
+        let hit = hit(0.0);
+        let scattered = Ray::new(hit.point, Vector::new(1.0, 2.0, 0.5), 0.0);
+
+        assert_approx_eq!(
+            scattering_pdf(0.0, &hit, &scattered),
+            lambertian::scattering_pdf(&hit.surface_normal, &scattered)
+        );
+    }
+
+    #[test]
+    fn test_rough_surface_reflects_towards_light() {
+        let hit = hit(0.5);
+
+        // Light coming from behind the viewer is reflected back more than by a lambertian surface
+        let back_towards_viewer = Ray::new(hit.point, Vector::new(-1.0, 1.0, 0.0), 0.0);
+        assert!(
+            scattering_pdf(0.5, &hit, &back_towards_viewer)
+                > lambertian::scattering_pdf(&hit.surface_normal, &back_towards_viewer)
+        );
+
+        let below_surface = Ray::new(hit.point, Vector::new(0.0, -1.0, 0.0), 0.0);
+        assert_eq!(scattering_pdf(0.5, &hit, &below_surface), 0.0);
+    }
+}
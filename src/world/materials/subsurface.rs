@@ -0,0 +1,179 @@
+use crate::camera::Ray;
+use crate::data::assets::Assets;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::float::Float;
+use crate::onb::Onb;
+use crate::pdf::{random_point_in_unit_sphere, uniform, Pdf};
+use crate::world::geometry::HitResult;
+use crate::world::materials::dielectric;
+use crate::world::materials::{lambertian, ScatterResult};
+use crate::world::texture::Texture;
+
+const REFRACTIVE_INDEX_OF_AIR: Float = 1.0;
+
+/// Light enters or reflects off the surface like smooth glass, then random walks through the
+/// inside of the geometry the same way it would through a medium. Each ray that hits the surface
+/// from the inside is checked for a scattering event on the way: if one comes first the walk
+/// carries on from there in a random direction, tinted by the albedo, otherwise it reaches the
+/// surface and either reflects back in or leaves. Every step of the walk counts towards the
+/// maximum depth, so a mean free path much smaller than the geometry needs a larger depth
+pub fn scatter(
+    albedo: &Texture,
+    mean_free_path: Float,
+    refractive_index: Float,
+    hit: &HitResult,
+    assets: &Assets,
+) -> Option<ScatterResult> {
+    if hit.front_face() {
+        return dielectric::scatter(refractive_index, &None, 0.0, hit);
+    }
+
+    let speed = hit.ray.direction().len();
+    let free_path = -mean_free_path * uniform::<Float>().ln();
+    if free_path < hit.distance * speed {
+        let point = hit.ray.point(free_path / speed);
+        let scattered = Ray::new(point, random_point_in_unit_sphere(), hit.ray.time());
+        let attenuation = albedo.value(hit.texture_coords, &point, assets);
+        return Some(ScatterResult::specular(attenuation, scattered));
+    }
+
+    let unit_vector = hit.ray.direction().unit_vector();
+    let inwards = hit.face_normal();
+    let reflect_prob = match dielectric::refract(
+        &unit_vector,
+        &inwards,
+        refractive_index / REFRACTIVE_INDEX_OF_AIR,
+    ) {
+        Some(_) => dielectric::reflectivity_schlick_approx(
+            -Vector::dot(&unit_vector, &inwards),
+            refractive_index,
+            REFRACTIVE_INDEX_OF_AIR,
+        ),
+        None => 1.0,
+    };
+    if uniform::<Float>() < reflect_prob {
+        let reflected = dielectric::reflect(&unit_vector, &inwards);
+        let ray = Ray::new(hit.point, reflected, hit.ray.time());
+        return Some(ScatterResult::specular(Colour::new(1.0, 1.0, 1.0), ray));
+    }
+
+    // The walk has lost track of the direction light came in from by the time it leaves, so it
+    // leaves diffusely, which also lets the renderer sample the lights from where it comes out
+    let pdf = Pdf::Cosine(Onb::build_from_w(&(-inwards)));
+    Some(ScatterResult::diffuse(Colour::new(1.0, 1.0, 1.0), pdf))
+}
+
+/// Only rays leaving the surface scatter diffusely, outwards from the side they hit it on
+pub fn scattering_pdf(hit: &HitResult, scattered: &Ray) -> Float {
+    lambertian::scattering_pdf(&(-hit.face_normal()), scattered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::colour::Colour;
+    use crate::data::vector::Vector;
+    use crate::world::materials::Material;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn wax() -> Texture {
+        Texture::Constant {
+            colour: Colour::new(0.9, 0.7, 0.4),
+        }
+    }
+
+    fn hit(direction: Vector) -> HitResult {
+        // Travelling 4 units from the origin to the top of the surface
+        HitResult {
+            distance: 2.0,
+            ray: Ray::new(Vector::new(0.0, 0.0, 0.0), direction, 0.0),
+            point: Vector::new(0.0, 4.0, 0.0),
+            surface_normal: Vector::new(0.0, 1.0, 0.0),
+            material: Material::Subsurface {
+                albedo: wax(),
+                mean_free_path: 1.0,
+                refractive_index: 1.4,
+            },
+            texture_coords: (0.0, 0.0),
+            texture_gradients: None,
+            light_group: None,
+        }
+    }
+
+    #[test]
+    fn test_short_mean_free_path_scatters_inside() {
+        let assets = Assets::new(&[]).unwrap();
+        let hit = hit(Vector::new(0.0, 2.0, 0.0));
+
+        for _ in 0..100 {
+            match scatter(&wax(), 1e-6, 1.4, &hit, &assets) {
+                Some(ScatterResult::Specular { attenuation, ray }) => {
+                    assert_eq!(attenuation, Colour::new(0.9, 0.7, 0.4));
+                    assert!(ray.origin().y() < 0.001);
+                }
+                _ => panic!("expected a specular scatter"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_mean_free_path_reaches_the_surface() {
+        let assets = Assets::new(&[]).unwrap();
+        let hit = hit(Vector::new(0.0, 2.0, 0.0));
+
+        let mut left = 0;
+        for _ in 0..1000 {
+            match scatter(&wax(), 1e9, 1.4, &hit, &assets) {
+                Some(ScatterResult::Specular { attenuation, ray }) => {
+                    // Reflected back in off the inside of the surface
+                    assert_approx_eq!(attenuation.r(), 1.0);
+                    assert_eq!(ray.origin(), &Vector::new(0.0, 4.0, 0.0));
+                    assert!(ray.direction().y() < 0.0);
+                }
+                Some(ScatterResult::Diffuse { attenuation, .. }) => {
+                    assert_approx_eq!(attenuation.r(), 1.0);
+                    left += 1;
+                }
+                None => panic!("expected a scatter"),
+            }
+        }
+        // Schlick's approximation reflects about 3% straight on
+        assert!(left > 940 && left < 995);
+
+        // Leaving diffusely only sends light out of the surface
+        let out = Ray::new(hit.point, Vector::new(0.0, 1.0, 0.0), 0.0);
+        let back_in = Ray::new(hit.point, Vector::new(0.0, -1.0, 0.0), 0.0);
+        assert_approx_eq!(scattering_pdf(&hit, &out), 1.0 / crate::float::consts::PI);
+        assert_approx_eq!(scattering_pdf(&hit, &back_in), 0.0);
+    }
+
+    #[test]
+    fn test_total_internal_reflection_keeps_the_walk_inside() {
+        let assets = Assets::new(&[]).unwrap();
+        let hit = hit(Vector::new(2.0, 1.0, 0.0));
+
+        for _ in 0..100 {
+            match scatter(&wax(), 1e9, 1.4, &hit, &assets) {
+                Some(ScatterResult::Specular { ray, .. }) => assert!(ray.direction().y() < 0.0),
+                _ => panic!("expected a specular scatter"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_entering_never_scatters_inside() {
+        let assets = Assets::new(&[]).unwrap();
+        let hit = hit(Vector::new(0.0, -2.0, 0.0));
+
+        for _ in 0..100 {
+            match scatter(&wax(), 1e-6, 1.4, &hit, &assets) {
+                Some(ScatterResult::Specular { attenuation, ray }) => {
+                    assert_approx_eq!(attenuation.r(), 1.0);
+                    assert_eq!(ray.origin(), &Vector::new(0.0, 4.0, 0.0));
+                }
+                _ => panic!("expected a specular scatter"),
+            }
+        }
+    }
+}
@@ -0,0 +1,295 @@
+//! Iridescent coatings such as soap bubbles and oil slicks
+//!
+//! Light reflected off the top of the film interferes with light reflected off the base below
+//! it. The Airy formula sums every bounce inside the film for the s and p polarisations, which is
+//! evaluated at a single wavelength for each colour channel.
+
+use crate::camera::Ray;
+use crate::data::colour::Colour;
+use crate::data::vector::Vector;
+use crate::float::consts::PI;
+use crate::float::Float;
+use crate::pdf::uniform;
+use crate::world::geometry::HitResult;
+use crate::world::materials::conductor;
+use crate::world::materials::dielectric::{reflect, refract};
+use crate::world::materials::ScatterResult;
+use std::ops::{Add, Div, Mul, Sub};
+
+const REFRACTIVE_INDEX_OF_AIR: Float = 1.0;
+// Nanometres, the same wavelengths as the conductor presets
+const WAVELENGTHS: [Float; 3] = [650.0, 550.0, 450.0];
+
+/// What the film is coated onto
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FilmBase {
+    // Transmits whatever the film does not reflect. A refractive index of 1 gives a soap bubble
+    Dielectric { refractive_index: Float },
+    // Opaque, with the complex index of refraction of a conductor material
+    Conductor { eta: Colour, k: Colour },
+}
+
+impl FilmBase {
+    pub fn albedo(&self) -> Colour {
+        match self {
+            FilmBase::Dielectric { .. } => Colour::new(1.0, 1.0, 1.0),
+            FilmBase::Conductor { eta, k } => conductor::fresnel(eta, k, 1.0),
+        }
+    }
+}
+
+pub fn scatter(
+    thickness_nm: Float,
+    film_index: Float,
+    base: &FilmBase,
+    hit: &HitResult,
+) -> Option<ScatterResult> {
+    let unit_vector = hit.ray.direction().unit_vector();
+    let normal = hit.face_normal();
+    let cosine = (-Vector::dot(&unit_vector, &normal)).clamp(0.0, 1.0);
+    let reflected = Ray::new(hit.point, reflect(&unit_vector, &normal), hit.ray.time());
+
+    let channel = |i: usize, incident: Float, substrate: Complex| {
+        film_reflectance(
+            incident,
+            film_index,
+            substrate,
+            cosine,
+            thickness_nm,
+            WAVELENGTHS[i],
+        )
+    };
+
+    match base {
+        FilmBase::Conductor { eta, k } => {
+            let reflectance = Colour::new(
+                channel(0, REFRACTIVE_INDEX_OF_AIR, Complex::new(eta.r(), k.r())),
+                channel(1, REFRACTIVE_INDEX_OF_AIR, Complex::new(eta.g(), k.g())),
+                channel(2, REFRACTIVE_INDEX_OF_AIR, Complex::new(eta.b(), k.b())),
+            );
+            Some(ScatterResult::specular(reflectance, reflected))
+        }
+        FilmBase::Dielectric { refractive_index } => {
+            // The film sits between the base and the air on whichever side the ray comes from
+            let (n_i, n_t) = if hit.front_face() {
+                (REFRACTIVE_INDEX_OF_AIR, *refractive_index)
+            } else {
+                (*refractive_index, REFRACTIVE_INDEX_OF_AIR)
+            };
+            let reflectance = Colour::new(
+                channel(0, n_i, Complex::real(n_t)),
+                channel(1, n_i, Complex::real(n_t)),
+                channel(2, n_i, Complex::real(n_t)),
+            );
+
+            let refracted = match refract(&unit_vector, &normal, n_i / n_t) {
+                Some(refracted) => refracted,
+                None => return Some(ScatterResult::specular(reflectance, reflected)),
+            };
+
+            // Reflect or refract with the average probability, the attenuation then tints the
+            // ray by how far each channel is from that average
+            let reflect_prob = (reflectance.r() + reflectance.g() + reflectance.b()) / 3.0;
+            if uniform::<Float>() < reflect_prob {
+                Some(ScatterResult::specular(
+                    reflectance / reflect_prob,
+                    reflected,
+                ))
+            } else {
+                let transmittance = Colour::new(
+                    1.0 - reflectance.r(),
+                    1.0 - reflectance.g(),
+                    1.0 - reflectance.b(),
+                );
+                let ray = Ray::new(hit.point, refracted, hit.ray.time());
+                Some(ScatterResult::specular(
+                    transmittance / (1.0 - reflect_prob),
+                    ray,
+                ))
+            }
+        }
+    }
+}
+
+/// The fraction of light of `wavelength` reflected by a film of `film_index` between the
+/// incident medium and the substrate, arriving at `cosine` to the normal
+fn film_reflectance(
+    incident: Float,
+    film_index: Float,
+    substrate: Complex,
+    cosine: Float,
+    thickness_nm: Float,
+    wavelength: Float,
+) -> Float {
+    let incident = Complex::real(incident);
+    let film = Complex::real(film_index);
+    let cos_incident = Complex::real(cosine);
+
+    // n sin(θ) is the same in every layer by Snell's law. Past the critical angle the cosine is
+    // imaginary and the Fresnel terms become total internal reflection
+    let sin_squared = Complex::real(1.0 - cosine * cosine);
+    let cos_in =
+        |n: Complex| (Complex::real(1.0) - incident * incident * sin_squared / (n * n)).sqrt();
+    let cos_film = cos_in(film);
+    let cos_substrate = cos_in(substrate);
+
+    // The phase a wave picks up crossing the film and coming back
+    let phase = Complex::new(0.0, 4.0 * PI * thickness_nm / wavelength) * film * cos_film;
+    let delay = phase.exp();
+
+    let airy = |top: Complex, bottom: Complex| {
+        let bottom = bottom * delay;
+        ((top + bottom) / (Complex::real(1.0) + top * bottom)).norm_squared()
+    };
+    let s = airy(
+        fresnel_s(incident, cos_incident, film, cos_film),
+        fresnel_s(film, cos_film, substrate, cos_substrate),
+    );
+    let p = airy(
+        fresnel_p(incident, cos_incident, film, cos_film),
+        fresnel_p(film, cos_film, substrate, cos_substrate),
+    );
+
+    (0.5 * (s + p)).clamp(0.0, 1.0)
+}
+
+/// Amplitude reflected going from `n1` into `n2` for light polarised perpendicular to the plane
+/// of incidence
+fn fresnel_s(n1: Complex, cos1: Complex, n2: Complex, cos2: Complex) -> Complex {
+    (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2)
+}
+
+/// Amplitude reflected going from `n1` into `n2` for light polarised parallel to the plane of
+/// incidence
+fn fresnel_p(n1: Complex, cos1: Complex, n2: Complex, cos2: Complex) -> Complex {
+    (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Complex {
+    re: Float,
+    im: Float,
+}
+
+impl Complex {
+    fn new(re: Float, im: Float) -> Complex {
+        Complex { re, im }
+    }
+
+    fn real(re: Float) -> Complex {
+        Complex::new(re, 0.0)
+    }
+
+    fn norm_squared(self) -> Float {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// The root with a non-negative real part
+    fn sqrt(self) -> Complex {
+        let norm = self.norm_squared().sqrt();
+        let re = (0.5 * (norm + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (norm - self.re)).max(0.0).sqrt();
+        Complex::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    fn exp(self) -> Complex {
+        let magnitude = self.re.exp();
+        Complex::new(magnitude * self.im.cos(), magnitude * self.im.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let denominator = other.norm_squared();
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / denominator,
+            (self.im * other.re - self.re * other.im) / denominator,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_approx_eq::assert_approx_eq;
+
+    #[test]
+    fn test_no_film_is_a_bare_interface() {
+        // Head on from air into glass 4% is reflected whatever the film is made of
+        let reflectance = film_reflectance(1.0, 1.33, Complex::real(1.5), 1.0, 0.0, 550.0);
+        assert_approx_eq!(reflectance, 0.04);
+    }
+
+    #[test]
+    fn test_quarter_wave_film_cancels_reflection() {
+        let film_index = Float::sqrt(1.5);
+        let thickness = 550.0 / (4.0 * film_index);
+
+        let green = film_reflectance(1.0, film_index, Complex::real(1.5), 1.0, thickness, 550.0);
+        assert_approx_eq!(green, 0.0);
+
+        // Other wavelengths are not cancelled so coated glass takes on a tint
+        let blue = film_reflectance(1.0, film_index, Complex::real(1.5), 1.0, thickness, 450.0);
+        assert!(blue > 0.001);
+    }
+
+    #[test]
+    fn test_soap_film_colour_changes_with_angle() {
+        let soap = |cosine: Float| {
+            let channel = |wavelength| {
+                film_reflectance(1.0, 1.33, Complex::real(1.0), cosine, 400.0, wavelength)
+            };
+            Colour::new(channel(650.0), channel(550.0), channel(450.0))
+        };
+
+        let head_on = soap(1.0);
+        let grazing = soap(0.3);
+        assert!(
+            (head_on.r() - grazing.r()).abs() > 0.01 || (head_on.b() - grazing.b()).abs() > 0.01
+        );
+
+        // A film with air on both sides reflects every channel fully at grazing angles
+        let edge = soap(0.0);
+        assert_approx_eq!(edge.g(), 1.0);
+    }
+
+    #[test]
+    fn test_complex_sqrt() {
+        let root = Complex::real(-4.0).sqrt();
+        assert_approx_eq!(root.re, 0.0);
+        assert_approx_eq!(root.im, 2.0);
+
+        let root = Complex::new(3.0, 4.0).sqrt();
+        assert_approx_eq!(root.re, 2.0);
+        assert_approx_eq!(root.im, 1.0);
+    }
+}
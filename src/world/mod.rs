@@ -1,16 +1,35 @@
 use crate::data::assets::Assets;
 use crate::world::background::Background;
 use crate::world::geometry::{Geometry, Hittable};
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use thiserror::Error;
 
 pub mod background;
 pub mod geometry;
 pub mod materials;
 pub mod texture;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Error)]
+pub enum WorldError {
+    #[error("geometry {index} ({kind}) has a non-finite number at {path}")]
+    NonFinite {
+        index: usize,
+        kind: String,
+        path: String,
+    },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct WorldSave {
+    // Scenes without one have the classic sky
+    #[serde(default)]
     background: Background,
     geometries: Vec<Geometry>,
+    // Geometries that are only rendered through the instances of them in `geometries`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    prototypes: HashMap<String, Geometry>,
 }
 
 impl WorldSave {
@@ -18,9 +37,16 @@ impl WorldSave {
         WorldSave {
             background,
             geometries,
+            prototypes: HashMap::new(),
         }
     }
 
+    /// Adds a geometry that is only rendered through the instances of it named `name`
+    pub fn with_prototype(mut self, name: &str, geometry: Geometry) -> WorldSave {
+        self.prototypes.insert(String::from(name), geometry);
+        self
+    }
+
     pub fn background(&self) -> &Background {
         &self.background
     }
@@ -29,14 +55,218 @@ impl WorldSave {
         if self.geometries.is_empty() {
             panic!("Geometries have already been drained")
         }
+        let prototypes: HashMap<String, Arc<Geometry>> = self
+            .prototypes
+            .drain()
+            .map(|(name, geometry)| (name, Arc::new(geometry)))
+            .collect();
         let range = 0..self.geometries.len();
-        self.geometries.drain(range).collect()
+        self.geometries
+            .drain(range)
+            .map(|geometry| match geometry {
+                Geometry::Instance(mut instance) => {
+                    instance
+                        .link(&prototypes)
+                        .expect("instances were validated");
+                    Geometry::Instance(instance)
+                }
+                geometry => geometry,
+            })
+            .collect()
     }
 
     pub fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
-        for geometry in &self.geometries {
-            geometry.validate(assets)?
+        self.background.validate(assets)?;
+        for (index, geometry) in self.geometries.iter().enumerate() {
+            check_finite(index, geometry)?;
+            match geometry {
+                Geometry::Instance(instance) => instance.validate_with(&self.prototypes, assets)?,
+                geometry => geometry.validate(assets)?,
+            }
         }
         Ok(())
     }
+
+    /// Nothing in the scene gives off light and the background is black, so every pixel would
+    /// come out black
+    pub fn is_unlit(&self) -> bool {
+        self.background.is_black()
+            && !self.geometries.iter().any(Hittable::emits_light)
+            && !self.prototypes.values().any(Hittable::emits_light)
+    }
+}
+
+/// NaN or infinite coordinates make hits impossible to order and boxes impossible to build, so
+/// they are rejected up front with the field they were found in
+fn check_finite(index: usize, geometry: &Geometry) -> Result<(), WorldError> {
+    // Going through the saved form covers every number of every geometry, however deeply nested
+    let value = match serde_yaml::to_value(geometry) {
+        Ok(value) => value,
+        Err(_) => return Ok(()),
+    };
+    let kind = match &value {
+        Value::Mapping(mapping) => mapping
+            .iter()
+            .next()
+            .and_then(|(key, _)| key.as_str())
+            .unwrap_or("Geometry")
+            .to_string(),
+        _ => "Geometry".to_string(),
+    };
+    match non_finite_path(&value) {
+        Some(path) => Err(WorldError::NonFinite {
+            index,
+            kind,
+            path: path.join("."),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// The keys leading to the first non-finite number in `value`
+fn non_finite_path(value: &Value) -> Option<Vec<String>> {
+    match value {
+        Value::Number(number) => match number.as_f64() {
+            Some(number) if !number.is_finite() => Some(Vec::new()),
+            _ => None,
+        },
+        Value::Sequence(sequence) => sequence.iter().enumerate().find_map(|(i, item)| {
+            non_finite_path(item).map(|mut path| {
+                path.insert(0, i.to_string());
+                path
+            })
+        }),
+        Value::Mapping(mapping) => mapping.iter().find_map(|(key, item)| {
+            non_finite_path(item).map(|mut path| {
+                let key = match key {
+                    Value::String(key) => key.clone(),
+                    _ => "?".to_string(),
+                };
+                path.insert(0, key);
+                path
+            })
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Ray;
+    use crate::data::colour::Colour;
+    use crate::data::vector::Vector;
+    use crate::float::Float;
+    use crate::world::geometry::instance::Instance;
+    use crate::world::geometry::sphere::Sphere;
+    use crate::world::geometry::translate::Translate;
+    use crate::world::materials::Material;
+    use crate::world::texture::Texture;
+
+    fn sphere(centre: Vector, material: Material) -> Geometry {
+        Sphere::build(centre, 1.0, material)
+    }
+
+    fn lambertian() -> Material {
+        Material::Lambertian {
+            albedo: Texture::Constant {
+                colour: Colour::new(0.5, 0.5, 0.5),
+            },
+        }
+    }
+
+    #[test]
+    fn test_rejects_non_finite_coordinates() {
+        let assets = Assets::new(&[]).unwrap();
+        let centre = Vector::new(0.0, Float::NAN, 0.0);
+        let world = WorldSave::new(
+            Background::new(Colour::new(1.0, 1.0, 1.0), Colour::new(1.0, 1.0, 1.0)),
+            vec![
+                sphere(Vector::new(0.0, 0.0, 0.0), lambertian()),
+                Translate::build(
+                    sphere(centre, lambertian()),
+                    Vector::new(Float::INFINITY, 0.0, 0.0),
+                ),
+            ],
+        );
+
+        let message = world.validate(&assets).unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "geometry 1 (Translate) has a non-finite number at Translate.geometry.Sphere.centre.y"
+        );
+    }
+
+    #[test]
+    fn test_is_unlit() {
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let dark = |geometry| WorldSave::new(Background::new(black, black), vec![geometry]);
+
+        assert!(dark(sphere(Vector::new(0.0, 0.0, 0.0), lambertian())).is_unlit());
+
+        let light = Material::DiffuseLight {
+            emit: Texture::Constant {
+                colour: Colour::new(4.0, 4.0, 4.0),
+            },
+            strength: 1.0,
+            two_sided: false,
+            spotlight: None,
+        };
+        assert!(!dark(sphere(Vector::new(0.0, 0.0, 0.0), light)).is_unlit());
+
+        let sky = WorldSave::new(
+            Background::new(Colour::new(0.5, 0.7, 1.0), black),
+            vec![sphere(Vector::new(0.0, 0.0, 0.0), lambertian())],
+        );
+        assert!(!sky.is_unlit());
+    }
+
+    #[test]
+    fn test_instances_are_linked_to_prototypes() {
+        let assets = Assets::new(&[]).unwrap();
+        let black = Colour::new(0.0, 0.0, 0.0);
+        let instance = |prototype: &str, x: Float| {
+            Instance::build(
+                prototype,
+                Vector::new(1.0, 1.0, 1.0),
+                Vector::new(0.0, 1.0, 0.0),
+                0.0,
+                Vector::new(x, 0.0, 0.0),
+            )
+            .unwrap()
+        };
+        let world = |geometries| {
+            let mut prototypes = HashMap::new();
+            prototypes.insert(
+                "ball".to_string(),
+                sphere(Vector::new(0.0, 0.0, 0.0), lambertian()),
+            );
+            WorldSave {
+                prototypes,
+                ..WorldSave::new(Background::new(black, black), geometries)
+            }
+        };
+
+        let mut instanced = world(vec![instance("ball", -5.0), instance("ball", 5.0)]);
+        assert!(instanced.validate(&assets).is_ok());
+        for (geometry, x) in instanced.drain_geometries().iter().zip(&[-5.0, 5.0]) {
+            let ray = Ray::new(Vector::new(*x, 0.0, 5.0), Vector::new(0.0, 0.0, -1.0), 0.0);
+            assert!(geometry.hit(&ray, 0.001, Float::MAX).is_some());
+        }
+
+        let unknown = instance("tree", 0.0);
+        assert!(world(vec![unknown]).validate(&assets).is_err());
+        let nested = instance("ball", 0.0).translate(Vector::new(0.0, 1.0, 0.0));
+        assert!(world(vec![nested]).validate(&assets).is_err());
+    }
+
+    #[test]
+    fn test_background_defaults_to_the_classic_sky() {
+        let world: WorldSave = serde_yaml::from_str("geometries: []").unwrap();
+        let ray = Ray::new(Vector::new(0.0, 0.0, 0.0), Vector::new(0.0, 1.0, 0.0), 0.0);
+        assert_eq!(
+            world.background().colour(&ray, &Assets::new(&[]).unwrap()),
+            Colour::new(0.5, 0.7, 1.0)
+        );
+    }
 }
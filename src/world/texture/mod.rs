@@ -1,37 +1,168 @@
 pub mod perlin;
 
 use crate::data::assets::Assets;
-use crate::data::colour::Colour;
+use crate::data::colour::{Colour, ColourSpace};
 use crate::data::image::Image;
 use crate::data::vector::Vector;
-use crate::world::texture::perlin::{perlin_turbulence, NoiseConfig};
+use crate::float::consts::PI;
+use crate::float::Float;
+use crate::world::texture::perlin::{perlin_turbulence, NoiseConfig, SeededNoise};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TextureError {
+    #[error("brick size must be positive, got {brick_size:?}")]
+    InvalidBrickSize { brick_size: (Float, Float) },
+    #[error(
+        "mortar thickness must not be negative or as thick as a brick, got {mortar_thickness}"
+    )]
+    InvalidMortarThickness { mortar_thickness: Float },
+}
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Texture {
     Constant {
         colour: Colour,
     },
+    // A constant colour given in sRGB, the way colour pickers show it, decoded to linear
+    Srgb {
+        colour: Colour,
+    },
     Checker {
         even: Box<Texture>,
         odd: Box<Texture>,
+        // Number of checker squares per 2π units along each axis
+        #[serde(default = "default_checker_scale")]
+        scale: Float,
     },
     Noise {
         base_colour: Colour,
-        scale: f64,
-        noisiness: f64,
+        scale: Float,
+        noisiness: Float,
         noise_config: NoiseConfig,
     },
+    // Thin veins of `vein` running through `base`, bent by turbulence. There are `scale` bands
+    // per 2π units along the x axis
+    Marble {
+        #[serde(default = "default_marble_scale")]
+        scale: Float,
+        #[serde(default = "default_marble_base")]
+        base: Colour,
+        #[serde(default = "default_marble_vein")]
+        vein: Colour,
+        #[serde(default)]
+        seed: SeededNoise,
+    },
+    // Growth rings centred on the z axis blending from `light` to `dark`, with `scale` rings per
+    // unit out from the centre. A plank lying in the x-z plane shows grain running along z
+    Wood {
+        #[serde(default = "default_wood_scale")]
+        scale: Float,
+        #[serde(default = "default_wood_light")]
+        light: Colour,
+        #[serde(default = "default_wood_dark")]
+        dark: Colour,
+        #[serde(default)]
+        seed: SeededNoise,
+    },
     Image {
         asset_name: String,
+        #[serde(default)]
+        filter: Filter,
+        #[serde(default)]
+        wrap_mode: WrapMode,
+        // Images painted or photographed for display are usually sRGB, data such as normal maps
+        // or alpha is linear
+        #[serde(default)]
+        colour_space: ColourSpace,
+    },
+    // Blends from `start` at the bottom or left of the texture to `end` at the top or right
+    Gradient {
+        start: Colour,
+        end: Colour,
+        #[serde(default)]
+        axis: Axis,
+    },
+    // Remaps the texture coords before looking up the inner texture, to tile or turn it
+    Transform {
+        #[serde(default = "default_transform_scale")]
+        scale: (Float, Float),
+        #[serde(default)]
+        offset: (Float, Float),
+        #[serde(default)]
+        rotation: Float,
+        inner: Box<Texture>,
     },
+    // Courses of bricks laid in a running bond, each course shifted by half a brick from the one
+    // below. Bricks are `brick_size` wide along the first texture coord, which runs across walls
+    // made of rectangles, and high along the second. Both include half of the mortar joint around
+    // the brick, all in texture coords
+    Brick {
+        #[serde(default = "default_brick_colour")]
+        brick_colour: Colour,
+        #[serde(default = "default_mortar_colour")]
+        mortar_colour: Colour,
+        brick_size: (Float, Float),
+        mortar_thickness: Float,
+    },
+}
+
+/// How the texels around the texture coords are combined into a single colour
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Filter {
+    Nearest,
+    // Blends the four texels nearest to the texture coords
+    #[default]
+    Bilinear,
+}
+
+/// What is seen outside the edges of an image, where texture coords are below 0 or above 1
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum WrapMode {
+    // The edge texels stretch out forever
+    #[default]
+    Clamp,
+    // The image tiles
+    Repeat,
+    // The image tiles, with every other tile flipped so the edges of neighbouring tiles match
+    Mirror,
+}
+
+impl WrapMode {
+    /// Brings a texel index that may be outside of the image back inside of it
+    fn wrap(self, index: i64, size: u32) -> u32 {
+        let size = i64::from(size);
+        let index = match self {
+            WrapMode::Clamp => index.clamp(0, size - 1),
+            WrapMode::Repeat => index.rem_euclid(size),
+            WrapMode::Mirror => {
+                let index = index.rem_euclid(2 * size);
+                if index < size {
+                    index
+                } else {
+                    2 * size - 1 - index
+                }
+            }
+        };
+        index as u32
+    }
+}
+
+/// Which of the texture coords a gradient follows
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Axis {
+    #[default]
+    Row,
+    Column,
 }
 
 impl Texture {
-    pub fn value(&self, texture_coords: (f64, f64), point: &Vector, assets: &Assets) -> Colour {
+    pub fn value(&self, texture_coords: (Float, Float), point: &Vector, assets: &Assets) -> Colour {
         match self {
             Texture::Constant { colour } => *colour,
-            Texture::Checker { odd, even } => {
-                checker_texture(&odd, &even, texture_coords, &point, &assets)
+            Texture::Srgb { colour } => ColourSpace::Srgb.to_linear(colour),
+            Texture::Checker { odd, even, scale } => {
+                checker_texture(&odd, &even, *scale, texture_coords, &point, &assets)
             }
             Texture::Noise {
                 base_colour,
@@ -39,33 +170,181 @@ impl Texture {
                 noisiness,
                 noise_config,
             } => noise_texture(&base_colour, *scale, *noisiness, &noise_config, &point),
-            Texture::Image { asset_name } => {
+            Texture::Marble {
+                scale,
+                base,
+                vein,
+                seed,
+            } => marble_texture(base, vein, *scale, seed.config(), point),
+            Texture::Wood {
+                scale,
+                light,
+                dark,
+                seed,
+            } => wood_texture(light, dark, *scale, seed.config(), point),
+            Texture::Image {
+                asset_name,
+                filter,
+                wrap_mode,
+                colour_space,
+            } => {
                 let image = assets.get_asset(asset_name);
-                image_texture(image, texture_coords)
+                image_texture(image, texture_coords, *filter, *wrap_mode, *colour_space)
+            }
+            Texture::Gradient { start, end, axis } => {
+                gradient_texture(start, end, *axis, texture_coords)
+            }
+            Texture::Transform {
+                scale,
+                offset,
+                rotation,
+                inner,
+            } => {
+                let texture_coords = transform_coords(texture_coords, *scale, *offset, *rotation);
+                inner.value(texture_coords, point, assets)
+            }
+            Texture::Brick {
+                brick_colour,
+                mortar_colour,
+                brick_size,
+                mortar_thickness,
+            } => {
+                if is_mortar(*brick_size, *mortar_thickness, texture_coords) {
+                    *mortar_colour
+                } else {
+                    *brick_colour
+                }
             }
         }
     }
 
     pub fn validate(&self, assets: &Assets) -> Result<(), anyhow::Error> {
         match self {
-            Texture::Image { asset_name } => {
+            Texture::Checker { odd, even, .. } => {
+                odd.validate(assets)?;
+                even.validate(assets)
+            }
+            Texture::Image { asset_name, .. } => {
                 assets.validate(&asset_name)?;
                 Ok(())
             }
+            Texture::Transform { inner, .. } => inner.validate(assets),
+            Texture::Brick {
+                brick_size,
+                mortar_thickness,
+                ..
+            } => {
+                let (width, height) = *brick_size;
+                if !(width > 0.0 && height > 0.0) {
+                    return Err(TextureError::InvalidBrickSize {
+                        brick_size: *brick_size,
+                    }
+                    .into());
+                }
+                if !(0.0..width.min(height)).contains(mortar_thickness) {
+                    return Err(TextureError::InvalidMortarThickness {
+                        mortar_thickness: *mortar_thickness,
+                    }
+                    .into());
+                }
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
 }
 
+fn default_checker_scale() -> Float {
+    10.0
+}
+
+fn default_marble_scale() -> Float {
+    4.0
+}
+
+fn default_marble_base() -> Colour {
+    Colour::new(0.9, 0.9, 0.88)
+}
+
+fn default_marble_vein() -> Colour {
+    Colour::new(0.3, 0.3, 0.35)
+}
+
+fn default_wood_scale() -> Float {
+    8.0
+}
+
+fn default_wood_light() -> Colour {
+    Colour::new(0.75, 0.55, 0.33)
+}
+
+fn default_wood_dark() -> Colour {
+    Colour::new(0.45, 0.28, 0.14)
+}
+
+fn default_transform_scale() -> (Float, Float) {
+    (1.0, 1.0)
+}
+
+fn default_brick_colour() -> Colour {
+    Colour::new(0.5, 0.18, 0.1)
+}
+
+fn default_mortar_colour() -> Colour {
+    Colour::new(0.7, 0.68, 0.63)
+}
+
+/// Turns the coords `rotation` degrees around the centre of the texture, then scales them so the
+/// texture repeats `scale` times in each direction and finally shifts them by `offset`
+fn transform_coords(
+    texture_coords: (Float, Float),
+    scale: (Float, Float),
+    offset: (Float, Float),
+    rotation: Float,
+) -> (Float, Float) {
+    let radians = rotation.to_radians();
+    let (sin_theta, cos_theta) = radians.sin_cos();
+
+    let row = texture_coords.0 - 0.5;
+    let col = texture_coords.1 - 0.5;
+    let rotated_row = cos_theta * row + sin_theta * col + 0.5;
+    let rotated_col = -sin_theta * row + cos_theta * col + 0.5;
+
+    (
+        rotated_row * scale.0 + offset.0,
+        rotated_col * scale.1 + offset.1,
+    )
+}
+
+/// Whether the texture coords are in the mortar between the bricks rather than on one. Joints
+/// are centred on the edges of the bricks so half of the mortar is taken from each side
+fn is_mortar(
+    (width, height): (Float, Float),
+    mortar_thickness: Float,
+    texture_coords: (Float, Float),
+) -> bool {
+    let courses = texture_coords.1 / height;
+    let course = courses.floor();
+    // Every other course is shifted by half a brick so the joints do not line up
+    let bricks = texture_coords.0 / width + 0.5 * course.rem_euclid(2.0);
+    let in_joint = |along: Float, size: Float| {
+        let offset = (along - along.floor()) * size;
+        offset < 0.5 * mortar_thickness || offset > size - 0.5 * mortar_thickness
+    };
+    in_joint(courses, height) || in_joint(bricks, width)
+}
+
 fn checker_texture(
     odd: &Texture,
     even: &Texture,
-    texture_coords: (f64, f64),
+    scale: Float,
+    texture_coords: (Float, Float),
     point: &Vector,
     assets: &Assets,
 ) -> Colour {
-    let sines =
-        f64::sin(10.0 * point.x()) * f64::sin(10.0 * point.y()) * f64::sin(10.0 * point.z());
+    let sines = Float::sin(scale * point.x())
+        * Float::sin(scale * point.y())
+        * Float::sin(scale * point.z());
     if sines < 0.0 {
         odd.value(texture_coords, &point, &assets)
     } else {
@@ -75,29 +354,530 @@ fn checker_texture(
 
 fn noise_texture(
     base_colour: &Colour,
-    scale: f64,
-    noisiness: f64,
+    scale: Float,
+    noisiness: Float,
     noise_config: &NoiseConfig,
     point: &Vector,
 ) -> Colour {
     let noise = perlin_turbulence(&noise_config, &point, 7);
-    let mult = 0.5 * (1.0 + f64::sin(scale * point.z() + noisiness * noise));
+    let mult = 0.5 * (1.0 + Float::sin(scale * point.z() + noisiness * noise));
 
     mult * base_colour
 }
 
-fn image_texture(image: &Image, texture_coords: (f64, f64)) -> Colour {
-    let height = image.height() as f64;
-    let width = image.width() as f64;
+fn marble_texture(
+    base: &Colour,
+    vein: &Colour,
+    scale: Float,
+    noise_config: &NoiseConfig,
+    point: &Vector,
+) -> Colour {
+    let noise = perlin_turbulence(noise_config, point, 7);
+    let bands = Float::sin(scale * point.x() + 8.0 * noise);
+    // Veins only where the bands cross zero, sharpened so most of the surface is the base
+    let t = (1.0 - bands.abs()).powi(4);
+
+    (1.0 - t) * base + t * vein
+}
+
+fn wood_texture(
+    light: &Colour,
+    dark: &Colour,
+    scale: Float,
+    noise_config: &NoiseConfig,
+    point: &Vector,
+) -> Colour {
+    let radius = Float::sqrt(point.x() * point.x() + point.y() * point.y());
+    // Turbulence wobbles the rings so they are not perfect circles
+    let rings = scale * radius + 0.5 * perlin_turbulence(noise_config, point, 4);
+    let t = 0.5 * (1.0 + Float::sin(2.0 * PI * rings));
+
+    (1.0 - t) * light + t * dark
+}
+
+fn gradient_texture(
+    start: &Colour,
+    end: &Colour,
+    axis: Axis,
+    texture_coords: (Float, Float),
+) -> Colour {
+    let t = match axis {
+        Axis::Row => texture_coords.0,
+        Axis::Column => texture_coords.1,
+    };
+    let t = t.clamp(0.0, 1.0);
+
+    (1.0 - t) * start + t * end
+}
+
+/// Each texel covers an equal part of the texture coords, with texel centres half a texel in
+/// from the edges. Rows count up from the bottom of the image. Texels are decoded to linear before
+/// they are blended
+fn image_texture(
+    image: &Image,
+    texture_coords: (Float, Float),
+    filter: Filter,
+    wrap_mode: WrapMode,
+    colour_space: ColourSpace,
+) -> Colour {
+    let height = image.height();
+    let width = image.width();
+
+    let row = texture_coords.0 * height as Float;
+    let col = texture_coords.1 * width as Float;
+
+    let texel = |row: i64, col: i64| {
+        colour_space
+            .to_linear(image.get_pixel(wrap_mode.wrap(row, height), wrap_mode.wrap(col, width)))
+    };
+
+    match filter {
+        Filter::Nearest => texel(row.floor() as i64, col.floor() as i64),
+        Filter::Bilinear => {
+            let row = row - 0.5;
+            let col = col - 0.5;
+            let row_0 = row.floor();
+            let col_0 = col.floor();
+            let row_t = row - row_0;
+            let col_t = col - col_0;
+            let (row_0, col_0) = (row_0 as i64, col_0 as i64);
+
+            let bottom = (1.0 - col_t) * texel(row_0, col_0) + col_t * texel(row_0, col_0 + 1);
+            let top = (1.0 - col_t) * texel(row_0 + 1, col_0) + col_t * texel(row_0 + 1, col_0 + 1);
+            (1.0 - row_t) * bottom + row_t * top
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::image::Pixel;
+    use crate::world::texture::perlin::build_seeded_noise_config;
+    use assert_approx_eq::assert_approx_eq;
+
+    fn constant(r: Float, g: Float, b: Float) -> Box<Texture> {
+        Box::from(Texture::Constant {
+            colour: Colour::new(r, g, b),
+        })
+    }
 
-    let row = texture_coords.0 * (height - 1.0);
-    let col = texture_coords.1 * (width - 1.0);
+    #[test]
+    fn test_checker_texture() {
+        let assets = Assets::new(&[]).unwrap();
+        let texture = Texture::Checker {
+            even: constant(1.0, 1.0, 1.0),
+            odd: constant(0.0, 0.0, 0.0),
+            scale: 1.0,
+        };
 
-    let row = f64::max(0.0, row);
-    let col = f64::max(0.0, col);
+        let even_point = Vector::new(1.0, 1.0, 1.0);
+        assert_eq!(
+            texture.value((0.0, 0.0), &even_point, &assets),
+            Colour::new(1.0, 1.0, 1.0)
+        );
 
-    let row = f64::min(height - 1.0, row);
-    let col = f64::min(width - 1.0, col);
+        let odd_point = Vector::new(-1.0, 1.0, 1.0);
+        assert_eq!(
+            texture.value((0.0, 0.0), &odd_point, &assets),
+            Colour::new(0.0, 0.0, 0.0)
+        );
+
+        // Doubling the scale moves the point into the next square along x
+        let texture = Texture::Checker {
+            even: constant(1.0, 1.0, 1.0),
+            odd: constant(0.0, 0.0, 0.0),
+            scale: 2.0,
+        };
+        let point = Vector::new(2.0, 0.5, 0.5);
+        assert_eq!(
+            texture.value((0.0, 0.0), &point, &assets),
+            Colour::new(0.0, 0.0, 0.0)
+        );
+    }
 
-    *image.get_pixel(row as u32, col as u32)
+    #[test]
+    fn test_checker_texture_validate() {
+        let assets = Assets::new(&[]).unwrap();
+        let nested = Texture::Checker {
+            even: constant(1.0, 1.0, 1.0),
+            odd: Box::from(Texture::Checker {
+                even: constant(1.0, 1.0, 1.0),
+                odd: Box::from(Texture::Image {
+                    asset_name: String::from("missing.png"),
+                    filter: Filter::Bilinear,
+                    wrap_mode: WrapMode::Clamp,
+                    colour_space: ColourSpace::Linear,
+                }),
+                scale: 10.0,
+            }),
+            scale: 10.0,
+        };
+
+        assert!(nested.validate(&assets).is_err());
+    }
+
+    #[test]
+    fn test_checker_texture_default_scale() {
+        let serialised = "Checker:
+  even:
+    Constant:
+      colour: {r: 1.0, g: 1.0, b: 1.0}
+  odd:
+    Constant:
+      colour: {r: 0.0, g: 0.0, b: 0.0}
+";
+        match serde_yaml::from_str::<Texture>(serialised).unwrap() {
+            Texture::Checker { scale, .. } => assert_eq!(scale, 10.0),
+            _ => panic!("expected a checker texture"),
+        }
+    }
+
+    #[test]
+    fn test_noise_texture_range() {
+        let assets = Assets::new(&[]).unwrap();
+        let texture = Texture::Noise {
+            base_colour: Colour::new(1.0, 1.0, 1.0),
+            scale: 4.0,
+            noisiness: 10.0,
+            noise_config: build_seeded_noise_config(3),
+        };
+
+        for i in 0..1000 {
+            let point = Vector::new(0.013 * i as Float, -0.7 * i as Float, 0.29 * i as Float);
+            let colour = texture.value((0.0, 0.0), &point, &assets);
+            assert!(colour.r() >= 0.0 && colour.r() <= 1.0);
+            assert_eq!(colour, texture.value((0.0, 0.0), &point, &assets));
+        }
+    }
+
+    #[test]
+    fn test_marble_and_wood_deterministic() {
+        let assets = Assets::new(&[]).unwrap();
+        let marble = |seed: u64| Texture::Marble {
+            scale: 4.0,
+            base: Colour::new(1.0, 1.0, 1.0),
+            vein: Colour::new(0.0, 0.0, 0.0),
+            seed: SeededNoise::from(seed),
+        };
+        let wood = |seed: u64| Texture::Wood {
+            scale: 8.0,
+            light: Colour::new(1.0, 1.0, 1.0),
+            dark: Colour::new(0.0, 0.0, 0.0),
+            seed: SeededNoise::from(seed),
+        };
+
+        for texture in &[marble, wood] {
+            let mut differs = false;
+            for i in 0..500 {
+                let point = Vector::new(0.031 * i as Float, 0.17 * i as Float, -0.07 * i as Float);
+                let colour = texture(5).value((0.0, 0.0), &point, &assets);
+                assert!(colour.r() >= 0.0 && colour.r() <= 1.0);
+                assert_eq!(colour, texture(5).value((0.0, 0.0), &point, &assets));
+                differs |= colour != texture(6).value((0.0, 0.0), &point, &assets);
+            }
+            assert!(differs);
+        }
+    }
+
+    #[test]
+    fn test_wood_rings() {
+        let assets = Assets::new(&[]).unwrap();
+        let texture = Texture::Wood {
+            scale: 2.0,
+            light: Colour::new(1.0, 1.0, 1.0),
+            dark: Colour::new(0.0, 0.0, 0.0),
+            seed: SeededNoise::from(1),
+        };
+
+        // Going out from the centre passes from light to dark and back every half unit
+        let brightness: Vec<Float> = (0..200)
+            .map(|i| {
+                let point = Vector::new(0.01 * i as Float, 0.0, 0.3);
+                texture.value((0.0, 0.0), &point, &assets).r()
+            })
+            .collect();
+        let crossings = brightness
+            .windows(2)
+            .filter(|pair| (pair[0] < 0.5) != (pair[1] < 0.5))
+            .count();
+        assert!((6..=10).contains(&crossings), "{} crossings", crossings);
+    }
+
+    #[test]
+    fn test_marble_defaults() {
+        let texture: Texture = serde_yaml::from_str("Marble: {seed: 3}").unwrap();
+        match texture {
+            Texture::Marble { scale, seed, .. } => {
+                assert_eq!(scale, 4.0);
+                assert_eq!(seed, SeededNoise::from(3));
+            }
+            _ => panic!("expected a marble texture"),
+        }
+    }
+
+    #[test]
+    fn test_transform_coords() {
+        let (row, col) = transform_coords((0.25, 0.5), (10.0, 2.0), (0.0, 0.0), 0.0);
+        assert_approx_eq!(row, 2.5);
+        assert_approx_eq!(col, 1.0);
+
+        let (row, col) = transform_coords((0.25, 0.5), (1.0, 1.0), (0.5, -0.5), 0.0);
+        assert_approx_eq!(row, 0.75);
+        assert_approx_eq!(col, 0.0);
+
+        // A quarter turn looks up the middle of the right edge from the middle of the bottom edge
+        let (row, col) = transform_coords((0.0, 0.5), (1.0, 1.0), (0.0, 0.0), 90.0);
+        assert_approx_eq!(row, 0.5);
+        assert_approx_eq!(col, 1.0);
+    }
+
+    #[test]
+    fn test_transform_texture_validate() {
+        let assets = Assets::new(&[]).unwrap();
+        let serialised = "Transform:
+  scale: [10.0, 10.0]
+  inner:
+    Image:
+      asset_name: missing.png
+";
+        let texture = serde_yaml::from_str::<Texture>(serialised).unwrap();
+        match &texture {
+            Texture::Transform {
+                offset, rotation, ..
+            } => {
+                assert_eq!(*offset, (0.0, 0.0));
+                assert_eq!(*rotation, 0.0);
+            }
+            _ => panic!("expected a transform texture"),
+        }
+        assert!(texture.validate(&assets).is_err());
+    }
+
+    #[test]
+    fn test_brick_texture() {
+        let assets = Assets::new(&[]).unwrap();
+        let point = Vector::new(0.0, 0.0, 0.0);
+        let brick = Colour::new(1.0, 0.0, 0.0);
+        let mortar = Colour::new(1.0, 1.0, 1.0);
+        let texture = Texture::Brick {
+            brick_colour: brick,
+            mortar_colour: mortar,
+            brick_size: (0.2, 0.1),
+            mortar_thickness: 0.02,
+        };
+        let value = |up: Float, across: Float| texture.value((across, up), &point, &assets);
+
+        // The middle of the first brick, then the joints beside and above it
+        assert_eq!(value(0.05, 0.1), brick);
+        assert_eq!(value(0.05, 0.195), mortar);
+        assert_eq!(value(0.095, 0.1), mortar);
+        // The course above is shifted by half a brick, putting a joint over the middle of it
+        assert_eq!(value(0.15, 0.1), mortar);
+        assert_eq!(value(0.15, 0.2), brick);
+        // Every other course lines up with the first, below zero too
+        assert_eq!(value(0.25, 0.1), brick);
+        assert_eq!(value(-0.05, 0.1), mortar);
+        assert_eq!(value(-0.15, 0.1), brick);
+
+        assert!(texture.validate(&assets).is_ok());
+        let unmortared = Texture::Brick {
+            brick_colour: brick,
+            mortar_colour: mortar,
+            brick_size: (0.2, 0.1),
+            mortar_thickness: 0.0,
+        };
+        assert_eq!(unmortared.value((0.1999, 0.05), &point, &assets), brick);
+    }
+
+    #[test]
+    fn test_brick_texture_validate() {
+        let assets = Assets::new(&[]).unwrap();
+        let brick = |serialised: &str| {
+            serde_yaml::from_str::<Texture>(serialised)
+                .unwrap()
+                .validate(&assets)
+        };
+        assert!(brick("Brick: {brick_size: [0.25, 0.08], mortar_thickness: 0.01}").is_ok());
+        assert!(brick("Brick: {brick_size: [0.25, 0.0], mortar_thickness: 0.01}").is_err());
+        assert!(brick("Brick: {brick_size: [0.25, 0.08], mortar_thickness: 0.1}").is_err());
+        assert!(brick("Brick: {brick_size: [0.25, 0.08], mortar_thickness: -0.01}").is_err());
+    }
+
+    #[test]
+    fn test_gradient_texture() {
+        let assets = Assets::new(&[]).unwrap();
+        let point = Vector::new(0.0, 0.0, 0.0);
+        let texture = Texture::Gradient {
+            start: Colour::new(1.0, 0.0, 0.0),
+            end: Colour::new(0.0, 0.0, 1.0),
+            axis: Axis::Row,
+        };
+
+        assert_eq!(
+            texture.value((0.0, 0.7), &point, &assets),
+            Colour::new(1.0, 0.0, 0.0)
+        );
+        let middle = texture.value((0.5, 0.7), &point, &assets);
+        assert_approx_eq!(middle.r(), 0.5);
+        assert_approx_eq!(middle.b(), 0.5);
+        assert_eq!(
+            texture.value((1.5, 0.7), &point, &assets),
+            Colour::new(0.0, 0.0, 1.0)
+        );
+
+        let texture = Texture::Gradient {
+            start: Colour::new(0.0, 0.0, 0.0),
+            end: Colour::new(1.0, 1.0, 1.0),
+            axis: Axis::Column,
+        };
+        assert_approx_eq!(texture.value((0.9, 0.25), &point, &assets).g(), 0.25);
+    }
+
+    // A 2 by 2 image, black at the bottom left and brightening to the right and the top
+    fn image() -> Image {
+        Image::from(&vec![
+            Pixel::new(0, 0, Colour::new(0.0, 0.0, 0.0)),
+            Pixel::new(0, 1, Colour::new(1.0, 0.0, 0.0)),
+            Pixel::new(1, 0, Colour::new(0.0, 1.0, 0.0)),
+            Pixel::new(1, 1, Colour::new(1.0, 1.0, 0.0)),
+        ])
+    }
+
+    #[test]
+    fn test_image_texture_nearest() {
+        let image = image();
+        let colour = image_texture(
+            &image,
+            (0.2, 0.7),
+            Filter::Nearest,
+            WrapMode::Clamp,
+            ColourSpace::Linear,
+        );
+        assert_eq!(colour, Colour::new(1.0, 0.0, 0.0));
+
+        let colour = image_texture(
+            &image,
+            (1.0, 1.0),
+            Filter::Nearest,
+            WrapMode::Clamp,
+            ColourSpace::Linear,
+        );
+        assert_eq!(colour, Colour::new(1.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_image_texture_srgb() {
+        let grey = Image::from(&vec![Pixel::new(0, 0, Colour::new(0.5, 0.5, 0.5))]);
+        for &filter in &[Filter::Nearest, Filter::Bilinear] {
+            let linear = image_texture(
+                &grey,
+                (0.5, 0.5),
+                filter,
+                WrapMode::Clamp,
+                ColourSpace::Linear,
+            );
+            assert_approx_eq!(linear.g(), 0.5);
+            let srgb = image_texture(
+                &grey,
+                (0.5, 0.5),
+                filter,
+                WrapMode::Clamp,
+                ColourSpace::Srgb,
+            );
+            assert_approx_eq!(srgb.g(), 0.214, 1e-3);
+        }
+
+        let assets = Assets::new(&[]).unwrap();
+        let point = Vector::new(0.0, 0.0, 0.0);
+        let srgb = Texture::Srgb {
+            colour: Colour::new(0.5, 0.5, 0.5),
+        };
+        assert_approx_eq!(srgb.value((0.0, 0.0), &point, &assets).g(), 0.214, 1e-3);
+    }
+
+    #[test]
+    fn test_image_texture_bilinear() {
+        let image = image();
+
+        // Texel centres are a quarter of the way in, so the middle is an even blend of all four
+        let colour = image_texture(
+            &image,
+            (0.5, 0.5),
+            Filter::Bilinear,
+            WrapMode::Clamp,
+            ColourSpace::Linear,
+        );
+        assert_approx_eq!(colour.r(), 0.5);
+        assert_approx_eq!(colour.g(), 0.5);
+
+        let colour = image_texture(
+            &image,
+            (0.25, 0.375),
+            Filter::Bilinear,
+            WrapMode::Clamp,
+            ColourSpace::Linear,
+        );
+        assert_approx_eq!(colour.r(), 0.25);
+        assert_approx_eq!(colour.g(), 0.0);
+
+        // Past the edge the same texels are blended when clamped, and the far side when repeated
+        let colour = image_texture(
+            &image,
+            (0.25, 1.0),
+            Filter::Bilinear,
+            WrapMode::Clamp,
+            ColourSpace::Linear,
+        );
+        assert_approx_eq!(colour.r(), 1.0);
+        let colour = image_texture(
+            &image,
+            (0.25, 1.0),
+            Filter::Bilinear,
+            WrapMode::Repeat,
+            ColourSpace::Linear,
+        );
+        assert_approx_eq!(colour.r(), 0.5);
+    }
+
+    // A single row of 4 texels getting brighter to the right
+    fn ramp() -> Image {
+        Image::from(&vec![
+            Pixel::new(0, 0, Colour::new(0.0, 0.0, 0.0)),
+            Pixel::new(0, 1, Colour::new(1.0, 0.0, 0.0)),
+            Pixel::new(0, 2, Colour::new(2.0, 0.0, 0.0)),
+            Pixel::new(0, 3, Colour::new(3.0, 0.0, 0.0)),
+        ])
+    }
+
+    fn ramp_texel(u: Float, wrap_mode: WrapMode) -> Float {
+        image_texture(
+            &ramp(),
+            (0.5, u),
+            Filter::Nearest,
+            wrap_mode,
+            ColourSpace::Linear,
+        )
+        .r()
+    }
+
+    #[test]
+    fn test_wrap_mode_clamp() {
+        assert_eq!(ramp_texel(1.5, WrapMode::Clamp), 3.0);
+        assert_eq!(ramp_texel(-0.25, WrapMode::Clamp), 0.0);
+    }
+
+    #[test]
+    fn test_wrap_mode_repeat() {
+        assert_eq!(ramp_texel(1.5, WrapMode::Repeat), 2.0);
+        assert_eq!(ramp_texel(-0.25, WrapMode::Repeat), 3.0);
+    }
+
+    #[test]
+    fn test_wrap_mode_mirror() {
+        // The second tile runs backwards and so does the one before the first
+        assert_eq!(ramp_texel(1.5, WrapMode::Mirror), 1.0);
+        assert_eq!(ramp_texel(-0.25, WrapMode::Mirror), 0.0);
+        assert_eq!(ramp_texel(-0.9, WrapMode::Mirror), 3.0);
+    }
 }
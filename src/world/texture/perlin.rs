@@ -1,6 +1,8 @@
 use crate::data::colour::Colour;
 use crate::data::vector::Vector;
-use crate::pdf::uniform;
+use crate::float::Float;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 const RAN_SIZE: usize = 256;
 
@@ -12,7 +14,43 @@ pub struct NoiseConfig {
     perm_z: Vec<usize>,
 }
 
-pub fn perlin_turbulence(config: &NoiseConfig, point: &Vector, depth: u8) -> f64 {
+/// Noise that is saved as just the seed it is built from, so configs stay small and the same
+/// seed always gives the same pattern
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "u64", into = "u64")]
+pub struct SeededNoise {
+    seed: u64,
+    config: NoiseConfig,
+}
+
+impl SeededNoise {
+    pub fn config(&self) -> &NoiseConfig {
+        &self.config
+    }
+}
+
+impl Default for SeededNoise {
+    fn default() -> SeededNoise {
+        SeededNoise::from(0)
+    }
+}
+
+impl From<u64> for SeededNoise {
+    fn from(seed: u64) -> SeededNoise {
+        SeededNoise {
+            seed,
+            config: build_seeded_noise_config(seed),
+        }
+    }
+}
+
+impl From<SeededNoise> for u64 {
+    fn from(noise: SeededNoise) -> u64 {
+        noise.seed
+    }
+}
+
+pub fn perlin_turbulence(config: &NoiseConfig, point: &Vector, depth: u8) -> Float {
     let mut accum = 0.0;
     let mut weight = 1.0;
     let mut point = *point;
@@ -26,7 +64,7 @@ pub fn perlin_turbulence(config: &NoiseConfig, point: &Vector, depth: u8) -> f64
     accum.abs()
 }
 
-fn perlin_noise(config: &NoiseConfig, point: &Vector) -> f64 {
+fn perlin_noise(config: &NoiseConfig, point: &Vector) -> Float {
     let intra_pixel_distance_u = point.x() - point.x().floor();
     let intra_pixel_distance_v = point.y() - point.y().floor();
     let intra_pixel_distance_w = point.z() - point.z().floor();
@@ -62,10 +100,10 @@ fn perlin_noise(config: &NoiseConfig, point: &Vector) -> f64 {
 
 fn perlin_interpolation(
     ran_matrix: [[[Colour; 2]; 2]; 2],
-    intra_pixel_distance_u: f64,
-    intra_pixel_distance_v: f64,
-    intra_pixel_distance_w: f64,
-) -> f64 {
+    intra_pixel_distance_u: Float,
+    intra_pixel_distance_v: Float,
+    intra_pixel_distance_w: Float,
+) -> Float {
     // Compute hermite cubic to eliminate Mach bands
     let uu = intra_pixel_distance_u * intra_pixel_distance_u * (3.0 - 2.0 * intra_pixel_distance_u);
     let vv = intra_pixel_distance_v * intra_pixel_distance_v * (3.0 - 2.0 * intra_pixel_distance_v);
@@ -76,9 +114,9 @@ fn perlin_interpolation(
     for (i, i_axis) in ran_matrix.iter().enumerate() {
         for (j, j_axis) in i_axis.iter().enumerate() {
             for (k, ran) in j_axis.iter().enumerate() {
-                let i = i as f64;
-                let j = j as f64;
-                let k = k as f64;
+                let i = i as Float;
+                let j = j as Float;
+                let k = k as Float;
 
                 let weight = Colour::new(
                     intra_pixel_distance_u - i,
@@ -98,21 +136,30 @@ fn perlin_interpolation(
 }
 
 pub fn build_noise_config() -> NoiseConfig {
+    build_noise_config_from_rng(&mut rand::thread_rng())
+}
+
+/// Builds the same noise every time for a given seed
+pub fn build_seeded_noise_config(seed: u64) -> NoiseConfig {
+    build_noise_config_from_rng(&mut StdRng::seed_from_u64(seed))
+}
+
+fn build_noise_config_from_rng<R: Rng>(rng: &mut R) -> NoiseConfig {
     NoiseConfig {
-        ran: perlin_generate_ran().to_vec(),
-        perm_x: perlin_generate_perm().to_vec(),
-        perm_y: perlin_generate_perm().to_vec(),
-        perm_z: perlin_generate_perm().to_vec(),
+        ran: perlin_generate_ran(rng).to_vec(),
+        perm_x: perlin_generate_perm(rng).to_vec(),
+        perm_y: perlin_generate_perm(rng).to_vec(),
+        perm_z: perlin_generate_perm(rng).to_vec(),
     }
 }
 
-fn perlin_generate_ran() -> [Colour; RAN_SIZE] {
+fn perlin_generate_ran<R: Rng>(rng: &mut R) -> [Colour; RAN_SIZE] {
     let mut ran = [Colour::new(0.0, 0.0, 0.0); RAN_SIZE];
     for item in ran.iter_mut() {
         *item = Colour::new(
-            -1.0 + 2.0 * uniform::<f64>(),
-            -1.0 + 2.0 * uniform::<f64>(),
-            -1.0 + 2.0 * uniform::<f64>(),
+            -1.0 + 2.0 * rng.gen::<Float>(),
+            -1.0 + 2.0 * rng.gen::<Float>(),
+            -1.0 + 2.0 * rng.gen::<Float>(),
         )
         .unit_vector();
     }
@@ -120,18 +167,57 @@ fn perlin_generate_ran() -> [Colour; RAN_SIZE] {
     ran
 }
 
-fn permute(perm: &mut [usize; RAN_SIZE]) {
+fn permute<R: Rng>(perm: &mut [usize; RAN_SIZE], rng: &mut R) {
     for i in (0..RAN_SIZE).rev() {
-        let target = uniform::<usize>() % (i + 1);
+        let target = rng.gen::<usize>() % (i + 1);
         perm.swap(i, target);
     }
 }
 
-fn perlin_generate_perm() -> [usize; RAN_SIZE] {
+fn perlin_generate_perm<R: Rng>(rng: &mut R) -> [usize; RAN_SIZE] {
     let mut perm = [0; RAN_SIZE];
     for (i, item) in perm.iter_mut().enumerate() {
         *item = i;
     }
-    permute(&mut perm);
+    permute(&mut perm, rng);
     perm
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_noise_config() {
+        assert_eq!(build_seeded_noise_config(7), build_seeded_noise_config(7));
+        assert_ne!(build_seeded_noise_config(7), build_seeded_noise_config(8));
+    }
+
+    #[test]
+    fn test_seeded_noise_saves_seed() {
+        let noise = SeededNoise::from(7);
+        assert_eq!(noise.config(), &build_seeded_noise_config(7));
+
+        let yaml = serde_yaml::to_string(&noise).unwrap();
+        let loaded: SeededNoise = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(loaded, noise);
+    }
+
+    #[test]
+    fn test_perlin_turbulence_deterministic() {
+        let config = build_seeded_noise_config(7);
+        let other_config = build_seeded_noise_config(7);
+
+        for i in 0..100 {
+            let point = Vector::new(
+                0.37 * i as Float,
+                1.3 - 0.11 * i as Float,
+                0.05 * i as Float,
+            );
+            assert_eq!(
+                perlin_turbulence(&config, &point, 7),
+                perlin_turbulence(&other_config, &point, 7)
+            );
+        }
+    }
+}